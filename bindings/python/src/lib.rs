@@ -0,0 +1,203 @@
+//! The synth as a Python module, for scripting it, e.g. for algorithmic composition, or rendering
+//! batches of sounds from a notebook.
+//!
+//! Build and install it into the current virtualenv from this directory with `maturin develop
+//! --release`, then:
+//!
+//! ```python
+//! import numpy as np
+//! from basic_synth import Synth
+//!
+//! synth = Synth(sample_rate=48000)
+//! synth.load_preset("pad/warm")
+//! synth.note_on(60, 100)
+//! audio = np.frombuffer(synth.render(48000), dtype=np.float32).reshape(-1, synth.channels)
+//! ```
+//!
+//! `render` hands back interleaved 32-bit float samples as `bytes`, and `render_into` fills any
+//! writable buffer of them in place, such as a float32 numpy array, without copying through
+//! Python.
+
+use std::{fs::File, path::PathBuf};
+
+use basic_synth::{
+    param::ParamId, patch::Patch, preset::PresetBank, Synth, SynthCommand, SynthError, CHANNELS,
+};
+use midi_msg::MidiMsg;
+use pyo3::{
+    buffer::PyBuffer,
+    exceptions::{PyIOError, PyValueError},
+    prelude::*,
+    types::PyBytes,
+};
+
+fn synth_error(e: SynthError) -> PyErr {
+    PyValueError::new_err(e.to_string())
+}
+
+fn param_id(name: &str) -> PyResult<ParamId> {
+    name.parse().map_err(PyValueError::new_err)
+}
+
+/// A polyphonic synth, rendering on the calling thread as fast as it's asked to.
+#[pyclass(name = "Synth")]
+struct PySynth {
+    synth: Synth,
+}
+
+#[pymethods]
+impl PySynth {
+    #[new]
+    #[pyo3(signature = (sample_rate = 48000, voices = 16, seed = None))]
+    fn new(sample_rate: u32, voices: usize, seed: Option<u32>) -> PyResult<Self> {
+        let mut builder = Synth::builder().voices(voices).sample_rate(sample_rate);
+        if let Some(seed) = seed {
+            builder = builder.seed(seed);
+        }
+        let mut synth = builder.build().map_err(synth_error)?;
+        // scripts can keep their own copies of patches, and the history would only grow
+        synth.set_undo_enabled(false);
+        Ok(Self { synth })
+    }
+
+    #[getter]
+    fn sample_rate(&self) -> u32 {
+        self.synth.sample_rate()
+    }
+
+    /// How many samples make up each frame of the rendered audio.
+    #[getter]
+    fn channels(&self) -> usize {
+        CHANNELS
+    }
+
+    /// Switch to one of the factory presets, by name or as `category/name`.
+    fn load_preset(&mut self, name: &str) -> PyResult<()> {
+        let patch = PresetBank::factory()
+            .load_by_name(name)
+            .map_err(|e| PyValueError::new_err(e.to_string()))?;
+        self.synth.load_patch(&patch);
+        Ok(())
+    }
+
+    /// Switch to the patch in a file, as saved by the CLI or written by hand.
+    fn load_patch(&mut self, path: PathBuf) -> PyResult<()> {
+        let patch = File::open(&path)
+            .and_then(Patch::read)
+            .map_err(|e| PyIOError::new_err(format!("{}: {}", path.display(), e)))?;
+        self.synth.load_patch(&patch);
+        Ok(())
+    }
+
+    /// The current settings, in the patch file format.
+    fn save_patch(&self) -> PyResult<String> {
+        let mut text = Vec::new();
+        self.synth
+            .save_patch()
+            .write(&mut text)
+            .map_err(|e| PyIOError::new_err(e.to_string()))?;
+        Ok(String::from_utf8_lossy(&text).into_owned())
+    }
+
+    #[pyo3(signature = (note, velocity = 100))]
+    fn note_on(&mut self, note: u8, velocity: u8) -> PyResult<()> {
+        self.synth
+            .apply(SynthCommand::NoteOn { note, velocity })
+            .map_err(synth_error)
+    }
+
+    fn note_off(&mut self, note: u8) -> PyResult<()> {
+        self.synth
+            .apply(SynthCommand::NoteOff { note })
+            .map_err(synth_error)
+    }
+
+    /// A MIDI control change, as from a controller on channel 1. See `midi` for which are
+    /// understood.
+    fn control_change(&mut self, control: u8, value: u8) -> PyResult<()> {
+        self.midi(&[0xb0, control, value])
+    }
+
+    /// Bend all notes by a number of semitones.
+    fn pitch_bend(&mut self, semitones: f32) -> PyResult<()> {
+        self.synth
+            .apply(SynthCommand::PitchBend(semitones))
+            .map_err(synth_error)
+    }
+
+    /// Release every note.
+    fn all_notes_off(&mut self) -> PyResult<()> {
+        self.synth
+            .apply(SynthCommand::AllNotesOff)
+            .map_err(synth_error)
+    }
+
+    /// One raw MIDI message, on any channel: notes, pitch bend, brightness (CC 74), volume (CC 7)
+    /// and general purpose controller 1 (CC 16, the morph) are understood, and anything else is
+    /// ignored.
+    fn midi(&mut self, data: &[u8]) -> PyResult<()> {
+        let (msg, _) = MidiMsg::from_midi(data)
+            .map_err(|e| PyValueError::new_err(format!("Invalid MIDI message: {:?}", e)))?;
+        self.synth.handle_midi(&msg).map_err(synth_error)
+    }
+
+    /// A command in its text form, e.g. `note-on 60 100` or `set cutoff = 800`.
+    fn command(&mut self, text: &str) -> PyResult<()> {
+        let command: SynthCommand = text.parse().map_err(PyValueError::new_err)?;
+        self.synth.apply(command).map_err(synth_error)
+    }
+
+    /// Set a parameter, named as in the text form of commands, e.g. `cutoff` or `send 1 level`.
+    fn set_param(&mut self, name: &str, value: f32) -> PyResult<()> {
+        self.synth
+            .set_param(param_id(name)?, value)
+            .map_err(synth_error)
+    }
+
+    fn get_param(&self, name: &str) -> PyResult<f32> {
+        self.synth
+            .get_param(param_id(name)?)
+            .ok_or_else(|| PyValueError::new_err(format!("No such parameter: {}", name)))
+    }
+
+    /// The names of the parameters every synth has.
+    fn params(&self) -> Vec<String> {
+        ParamId::fixed().map(|param| param.to_string()).collect()
+    }
+
+    /// Render the next `frames` frames, as interleaved native-endian 32-bit floats.
+    fn render<'py>(&mut self, py: Python<'py>, frames: usize) -> Bound<'py, PyBytes> {
+        let mut samples = vec![0.0f32; frames * CHANNELS];
+        py.allow_threads(|| self.synth.process(&mut samples));
+        let bytes: Vec<u8> = samples.iter().flat_map(|s| s.to_ne_bytes()).collect();
+        PyBytes::new_bound(py, &bytes)
+    }
+
+    /// Render into a writable, contiguous buffer of 32-bit floats, interleaved, filling the whole
+    /// of it, which must hold a whole number of frames.
+    fn render_into(&mut self, py: Python<'_>, buffer: PyBuffer<f32>) -> PyResult<()> {
+        if buffer.item_count() % CHANNELS != 0 {
+            return Err(PyValueError::new_err(format!(
+                "The buffer must hold a multiple of {} samples",
+                CHANNELS
+            )));
+        }
+        let cells = buffer
+            .as_mut_slice(py)
+            .ok_or_else(|| PyValueError::new_err("The buffer must be writable and C-contiguous"))?;
+        let mut samples = vec![0.0f32; cells.len()];
+        py.allow_threads(|| self.synth.process(&mut samples));
+        for (cell, sample) in cells.iter().zip(samples) {
+            cell.set(sample);
+        }
+        Ok(())
+    }
+}
+
+#[pymodule]
+#[pyo3(name = "basic_synth")]
+fn module(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PySynth>()?;
+    m.add("CHANNELS", CHANNELS)?;
+    Ok(())
+}
@@ -0,0 +1,290 @@
+//! A graphical editor for the synth, built with egui: sliders for each of its fixed parameters, a
+//! drawing of the amplitude envelope, the presets to browse, and an octave of keys to try the
+//! sound out on.
+//!
+//! Build and run it from this directory with `cargo run --release`. It plays through the default
+//! audio output device, and finds presets in `presets`, as the CLI does.
+//!
+//! The window runs on the main thread and the synth on the audio device's, and neither waits for
+//! the other: parameters are set through a `SynthController`, changes made by the synth itself
+//! (e.g. loading a preset) come back through its parameter observer and a ring buffer, and notes
+//! and patches go to it through another ring buffer.
+
+use std::{error::Error, time::Duration};
+
+use basic_synth::{
+    backend::{AudioBackend, CpalBackend},
+    controller::SynthController,
+    param::{Curve, ParamId, ParamInfo},
+    preset::PresetBank,
+    ring::{ring_buffer, Consumer, Producer},
+    Synth, SynthCommand, Waveform,
+};
+use eframe::egui;
+
+const VOICES: usize = 16;
+
+/// Where more presets are looked for, besides the factory ones.
+const PRESET_DIR: &str = "presets";
+
+/// How many notes and patches can be waiting for the synth at once.
+const COMMAND_QUEUE_LEN: usize = 64;
+
+/// How many parameter changes can be waiting for the window at once. Any more are dropped, so
+/// the window may show a stale value until the parameter next changes.
+const CHANGE_QUEUE_LEN: usize = 1024;
+
+/// How often the window looks for changes from the synth when nothing else wakes it.
+const REFRESH_INTERVAL: Duration = Duration::from_millis(50);
+
+/// The lowest note of the on-screen keys, middle C.
+const FIRST_KEY: u8 = 60;
+
+const KEY_NAMES: [&str; 13] = [
+    "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B", "C",
+];
+
+/// How hard the on-screen keys are played.
+const KEY_VELOCITY: u8 = 100;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let mut backend = CpalBackend::new(None)?;
+    let mut synth = Synth::builder()
+        .voices(VOICES)
+        .sample_rate(backend.sample_rate())
+        .build()?;
+
+    let params: Vec<_> = ParamId::fixed()
+        .filter(|param| *param != ParamId::Tempo)
+        .filter_map(|param| Some((param, param.info()?)))
+        .collect();
+    let values = params
+        .iter()
+        .map(|(param, _)| synth.get_param(*param).unwrap_or_default())
+        .collect();
+
+    let controller = synth.controller();
+    let (commands, mut command_queue) = ring_buffer(COMMAND_QUEUE_LEN);
+    let (mut change_queue, changes) = ring_buffer(CHANGE_QUEUE_LEN);
+    synth.set_param_observer(Some(Box::new(move |param, value| {
+        let _ = change_queue.push((param, value));
+    })));
+    backend.start(Box::new(move |buffer| {
+        synth.apply_queued(&mut command_queue);
+        synth.process(buffer);
+    }))?;
+
+    let mut presets = PresetBank::factory();
+    if let Ok(more) = PresetBank::scan(PRESET_DIR) {
+        presets.extend(more);
+    }
+
+    let editor = Editor {
+        controller,
+        commands,
+        changes,
+        params,
+        values,
+        presets,
+        current: None,
+        held: None,
+    };
+    let options = eframe::NativeOptions {
+        viewport: egui::ViewportBuilder::default().with_inner_size([760.0, 600.0]),
+        ..Default::default()
+    };
+    eframe::run_native("basic-synth", options, Box::new(move |_| Box::new(editor)))?;
+    backend.stop();
+    Ok(())
+}
+
+struct Editor {
+    controller: SynthController,
+    commands: Producer<SynthCommand>,
+    changes: Consumer<(ParamId, f32)>,
+    /// The parameters shown, with what their values mean.
+    params: Vec<(ParamId, ParamInfo)>,
+    /// Their values, as last set here or reported by the synth.
+    values: Vec<f32>,
+    presets: PresetBank,
+    /// The index of the preset last loaded.
+    current: Option<usize>,
+    /// The note being played with the on-screen keys.
+    held: Option<u8>,
+}
+
+impl Editor {
+    fn send(&mut self, command: SynthCommand) {
+        if self.commands.push(command).is_err() {
+            eprintln!("The synth is not keeping up, dropped a command");
+        }
+    }
+
+    /// Catch up with the parameters the synth has changed itself.
+    fn take_changes(&mut self) {
+        while let Some((param, value)) = self.changes.pop() {
+            if let Some(index) = self.params.iter().position(|(p, _)| *p == param) {
+                self.values[index] = value;
+            }
+        }
+    }
+
+    fn value(&self, param: ParamId) -> f32 {
+        self.params
+            .iter()
+            .position(|(p, _)| *p == param)
+            .map_or(0.0, |index| self.values[index])
+    }
+
+    fn load_preset(&mut self, index: usize) {
+        match self.presets.load(index) {
+            Ok(patch) => {
+                self.send(SynthCommand::LoadPatch(Box::new(patch)));
+                self.current = Some(index);
+            }
+            Err(e) => eprintln!("Failed to load preset {}: {}", index, e),
+        }
+    }
+
+    fn preset_list(&mut self, ui: &mut egui::Ui) {
+        ui.heading("Presets");
+        let mut load = None;
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            let mut category = None;
+            for (index, preset) in self.presets.presets().iter().enumerate() {
+                if preset.category != category {
+                    category = preset.category.clone();
+                    ui.separator();
+                    if let Some(name) = &category {
+                        ui.strong(name);
+                    }
+                }
+                if ui
+                    .selectable_label(self.current == Some(index), &preset.name)
+                    .clicked()
+                {
+                    load = Some(index);
+                }
+            }
+        });
+        if let Some(index) = load {
+            self.load_preset(index);
+        }
+    }
+
+    fn param_controls(&mut self, ui: &mut egui::Ui) {
+        egui::Grid::new("params")
+            .num_columns(2)
+            .striped(true)
+            .show(ui, |ui| {
+                for ((param, info), value) in self.params.iter().zip(&mut self.values) {
+                    ui.label(param.to_string());
+                    let changed = if *param == ParamId::Waveform {
+                        waveform_picker(ui, value)
+                    } else {
+                        let slider = egui::Slider::new(value, info.min..=info.max)
+                            .logarithmic(info.curve == Curve::Logarithmic)
+                            .suffix(format!(" {}", info.unit.symbol()).trim_end().to_string());
+                        ui.add(slider).changed()
+                    };
+                    if changed {
+                        self.controller.set_param(*param, *value);
+                    }
+                    ui.end_row();
+                }
+            });
+    }
+
+    /// The shape of the amplitude envelope, for a note held for as long as the attack and decay
+    /// together take.
+    fn envelope(&self, ui: &mut egui::Ui) {
+        let attack = self.value(ParamId::Attack);
+        let decay = self.value(ParamId::Decay);
+        let sustain = self.value(ParamId::Sustain);
+        let release = self.value(ParamId::Release);
+        let hold = attack + decay;
+        let total = (attack + decay + hold + release).max(f32::EPSILON);
+
+        let size = egui::vec2(ui.available_width(), 120.0);
+        let (response, painter) = ui.allocate_painter(size, egui::Sense::hover());
+        let rect = response.rect.shrink(6.0);
+        painter.rect_filled(response.rect, 4.0, ui.visuals().extreme_bg_color);
+        let point = |time: f32, level: f32| {
+            egui::pos2(
+                rect.left() + rect.width() * time / total,
+                rect.bottom() - rect.height() * level,
+            )
+        };
+        let points = vec![
+            point(0.0, 0.0),
+            point(attack, 1.0),
+            point(attack + decay, sustain),
+            point(attack + decay + hold, sustain),
+            point(total, 0.0),
+        ];
+        let stroke = egui::Stroke::new(2.0, ui.visuals().selection.bg_fill);
+        painter.add(egui::Shape::line(points, stroke));
+        ui.label(format!(
+            "attack {:.3} s, decay {:.3} s, sustain {:.2}, release {:.3} s",
+            attack, decay, sustain, release
+        ));
+    }
+
+    /// An octave of keys, playing a note while one is held down.
+    fn keys(&mut self, ui: &mut egui::Ui) {
+        let mut pressed = None;
+        ui.horizontal(|ui| {
+            for (offset, name) in KEY_NAMES.iter().enumerate() {
+                let button = egui::Button::new(*name).min_size(egui::vec2(32.0, 64.0));
+                if ui.add(button).is_pointer_button_down_on() {
+                    pressed = Some(FIRST_KEY + offset as u8);
+                }
+            }
+        });
+        if pressed != self.held {
+            if let Some(note) = self.held {
+                self.send(SynthCommand::NoteOff { note });
+            }
+            if let Some(note) = pressed {
+                self.send(SynthCommand::NoteOn {
+                    note,
+                    velocity: KEY_VELOCITY,
+                });
+            }
+            self.held = pressed;
+        }
+    }
+}
+
+/// A drop-down of the waveforms, returning whether another was picked.
+fn waveform_picker(ui: &mut egui::Ui, value: &mut f32) -> bool {
+    let mut selected = (*value as usize).min(Waveform::ALL.len() - 1);
+    let before = selected;
+    egui::ComboBox::from_id_source("waveform")
+        .selected_text(Waveform::ALL[selected].name())
+        .show_ui(ui, |ui| {
+            for (index, waveform) in Waveform::ALL.iter().enumerate() {
+                ui.selectable_value(&mut selected, index, waveform.name());
+            }
+        });
+    *value = selected as f32;
+    selected != before
+}
+
+impl eframe::App for Editor {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        self.take_changes();
+        egui::SidePanel::left("presets")
+            .default_width(180.0)
+            .show(ctx, |ui| self.preset_list(ui));
+        egui::TopBottomPanel::bottom("keys").show(ctx, |ui| self.keys(ui));
+        egui::CentralPanel::default().show(ctx, |ui| {
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                self.envelope(ui);
+                ui.separator();
+                self.param_controls(ui);
+            });
+        });
+        ctx.request_repaint_after(REFRESH_INTERVAL);
+    }
+}
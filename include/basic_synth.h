@@ -0,0 +1,239 @@
+#ifndef BASIC_SYNTH_H
+#define BASIC_SYNTH_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+#define BASIC_SYNTH_OK 0
+
+/**
+ * A pointer which shouldn't have been null was.
+ */
+#define BASIC_SYNTH_ERROR_NULL -1
+
+/**
+ * An argument was out of range, or couldn't be understood.
+ */
+#define BASIC_SYNTH_ERROR_INVALID_ARGUMENT -2
+
+/**
+ * Every voice was busy, so a note couldn't be started.
+ */
+#define BASIC_SYNTH_ERROR_OUT_OF_VOICES -3
+
+/**
+ * No voice was playing the note, so it couldn't be ended.
+ */
+#define BASIC_SYNTH_ERROR_NO_SUCH_NOTE -4
+
+/**
+ * The parameter can't take the value.
+ */
+#define BASIC_SYNTH_ERROR_INVALID_PARAMETER -5
+
+/**
+ * Too many events are waiting for later in the buffer.
+ */
+#define BASIC_SYNTH_ERROR_SCHEDULE_FULL -6
+
+/**
+ * Start a note, at `note` and `velocity`.
+ */
+#define BASIC_SYNTH_EVENT_NOTE_ON 0
+
+/**
+ * Release a note, at `note`.
+ */
+#define BASIC_SYNTH_EVENT_NOTE_OFF 1
+
+/**
+ * Bend all notes by `value` semitones.
+ */
+#define BASIC_SYNTH_EVENT_PITCH_BEND 2
+
+/**
+ * Release every note.
+ */
+#define BASIC_SYNTH_EVENT_ALL_NOTES_OFF 3
+
+/**
+ * Silence every voice at once.
+ */
+#define BASIC_SYNTH_EVENT_ALL_SOUND_OFF 4
+
+#define BASIC_SYNTH_CURVE_LINEAR 0
+
+/**
+ * Evenly in ratio rather than difference, as suits frequencies and times.
+ */
+#define BASIC_SYNTH_CURVE_LOGARITHMIC 1
+
+/**
+ * Whole numbers only, each picking a different setting.
+ */
+#define BASIC_SYNTH_CURVE_STEPPED 2
+
+/**
+ * Samples in each frame of audio, which is interleaved.
+ */
+#define BASIC_SYNTH_CHANNELS 2
+
+/**
+ * A synth, made by `basic_synth_new` and freed by `basic_synth_free`.
+ */
+typedef struct BasicSynth BasicSynth;
+
+/**
+ * Something for the synth to do, as pushed with `basic_synth_push_event`.
+ */
+typedef struct BasicSynthEvent {
+  /**
+   * One of the `BASIC_SYNTH_EVENT_*` kinds.
+   */
+  uint32_t kind;
+  /**
+   * How many frames into the next call to `basic_synth_process` it happens. Zero has it
+   * happen straight away.
+   */
+  uint32_t offset;
+  uint8_t note;
+  uint8_t velocity;
+  float value;
+} BasicSynthEvent;
+
+/**
+ * What a parameter's values mean, as filled in by `basic_synth_param_info`.
+ */
+typedef struct BasicSynthParamInfo {
+  float min;
+  float max;
+  float default_value;
+  /**
+   * One of the `BASIC_SYNTH_CURVE_*` curves.
+   */
+  uint32_t curve;
+} BasicSynthParamInfo;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Make a synth with `voices` voices, rendering at `sample_rate`. Returns null if the sample rate
+ * isn't supported, or `voices` is zero.
+ */
+BasicSynth *basic_synth_new(uint32_t sample_rate, size_t voices);
+
+/**
+ * Free a synth made by `basic_synth_new`. Does nothing if `synth` is null.
+ *
+ * # Safety
+ *
+ * `synth` must have come from `basic_synth_new`, and not have been freed already.
+ */
+void basic_synth_free(BasicSynth *synth);
+
+/**
+ * Render `frames` frames of interleaved audio into `out`, which holds `BASIC_SYNTH_CHANNELS`
+ * samples for each. This doesn't allocate, so is fine to call from an audio callback.
+ *
+ * # Safety
+ *
+ * `synth` must be a live synth, and `out` must point to at least `frames *
+ * BASIC_SYNTH_CHANNELS` floats.
+ */
+int32_t basic_synth_process(BasicSynth *synth, float *out, size_t frames);
+
+/**
+ * Have the synth do something, now or partway through the next buffer.
+ *
+ * # Safety
+ *
+ * `synth` must be a live synth, and `event` must point to an event.
+ */
+int32_t basic_synth_push_event(BasicSynth *synth, const BasicSynthEvent *event);
+
+#if defined(BASIC_SYNTH_MIDI)
+/**
+ * Have the synth respond to a MIDI message, `len` bytes long, straight away.
+ *
+ * # Safety
+ *
+ * `synth` must be a live synth, and `data` must point to `len` bytes.
+ */
+int32_t basic_synth_push_midi(BasicSynth *synth, const uint8_t *data, size_t len);
+#endif
+
+#if defined(BASIC_SYNTH_MIDI)
+/**
+ * Have the synth respond to Universal MIDI Packets, as MIDI 2.0 sends, `len` 32-bit words of
+ * them, straight away. Stops at the first packet which fails, returning why.
+ *
+ * # Safety
+ *
+ * `synth` must be a live synth, and `words` must point to `len` words.
+ */
+int32_t basic_synth_push_ump(BasicSynth *synth, const uint32_t *words, size_t len);
+#endif
+
+/**
+ * How many parameters there are, numbered from zero.
+ */
+size_t basic_synth_param_count(void);
+
+/**
+ * Fill in what parameter `index`'s values mean.
+ *
+ * # Safety
+ *
+ * `info` must point to somewhere to write the info.
+ */
+int32_t basic_synth_param_info(size_t index, BasicSynthParamInfo *info);
+
+/**
+ * Write parameter `index`'s name into `buffer`, as a null-terminated string cut short to fit in
+ * `len` bytes, as `snprintf` does. Returns the length of the whole name, not counting the null,
+ * or a negative status if there's no such parameter.
+ *
+ * # Safety
+ *
+ * `buffer` must point to `len` bytes, or may be null if `len` is zero.
+ */
+int32_t basic_synth_param_name(size_t index, char *buffer, size_t len);
+
+/**
+ * Set parameter `index` to `value`.
+ *
+ * # Safety
+ *
+ * `synth` must be a live synth.
+ */
+int32_t basic_synth_set_param(BasicSynth *synth, size_t index, float value);
+
+/**
+ * Read parameter `index`'s value into `value`.
+ *
+ * # Safety
+ *
+ * `synth` must be a live synth, and `value` must point to somewhere to write the value.
+ */
+int32_t basic_synth_get_param(const BasicSynth *synth, size_t index, float *value);
+
+/**
+ * Switch to a patch, given in the text format of preset files. This allocates, so is best not
+ * done from an audio callback.
+ *
+ * # Safety
+ *
+ * `synth` must be a live synth, and `text` a null-terminated string.
+ */
+int32_t basic_synth_load_patch(BasicSynth *synth, const char *text);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* BASIC_SYNTH_H */
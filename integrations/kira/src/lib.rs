@@ -0,0 +1,156 @@
+//! The synth as a sound in kira, the audio engine behind `bevy_kira_audio` and many other games,
+//! for procedural music and sound effects.
+//!
+//! Build a synth, wrap it in a `SynthSoundData` and play that through an `AudioManager` like any
+//! other sound, e.g. `manager.play(SynthSoundData::new(synth))?`. Keep the `SynthHandle` that gives
+//! back, to play notes and change parameters from any thread; the synth keeps playing (silence,
+//! between notes) until it's stopped through the handle.
+//!
+//! The synth is rendered at its own sample rate, so build it at the rate the audio device runs at
+//! (usually 48000 or 44100 Hz), or it'll play out of tune.
+
+use std::{
+    convert::Infallible,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use basic_synth::{
+    controller::SynthController,
+    param::ParamId,
+    ring::{ring_buffer, Consumer, Producer},
+    Synth, SynthCommand, CHANNELS,
+};
+use kira::{
+    clock::clock_info::ClockInfoProvider,
+    dsp::Frame,
+    modulator::value_provider::ModulatorValueProvider,
+    sound::{Sound, SoundData},
+    OutputDestination,
+};
+
+/// How many commands can be waiting for the audio thread at once.
+const COMMAND_QUEUE_LEN: usize = 256;
+
+/// A synth, ready to be played by an `AudioManager`.
+pub struct SynthSoundData {
+    synth: Synth,
+    output_destination: OutputDestination,
+}
+
+impl SynthSoundData {
+    pub fn new(synth: Synth) -> Self {
+        Self {
+            synth,
+            output_destination: OutputDestination::MAIN_TRACK,
+        }
+    }
+
+    /// Play through a mixer track other than the main one, e.g. to put effects on it, or from an
+    /// emitter in a spatial scene.
+    pub fn output_destination(mut self, destination: impl Into<OutputDestination>) -> Self {
+        self.output_destination = destination.into();
+        self
+    }
+}
+
+impl SoundData for SynthSoundData {
+    type Error = Infallible;
+    type Handle = SynthHandle;
+
+    fn into_sound(mut self) -> Result<(Box<dyn Sound>, Self::Handle), Self::Error> {
+        let (producer, consumer) = ring_buffer(COMMAND_QUEUE_LEN);
+        let handle = SynthHandle {
+            commands: Arc::new(Mutex::new(producer)),
+            controller: self.synth.controller(),
+        };
+        let block_len = self.synth.block_size() * CHANNELS;
+        let sound = SynthSound {
+            synth: self.synth,
+            output_destination: self.output_destination,
+            commands: consumer,
+            buffer: vec![0.0; block_len],
+            position: block_len,
+        };
+        Ok((Box::new(sound), handle))
+    }
+}
+
+/// Plays notes on a synth which is playing in kira, and changes its parameters, from any thread.
+/// Clones all control the same synth. The audio thread never waits on the handle.
+#[derive(Clone)]
+pub struct SynthHandle {
+    /// Only ever locked by handles, to share the sending end between them.
+    commands: Arc<Mutex<Producer<SynthCommand>>>,
+    controller: SynthController,
+}
+
+impl SynthHandle {
+    /// Queue up a command for the synth, which carries it out at the start of its next block.
+    /// Returns false if too many are waiting already, in which case it's dropped.
+    pub fn send(&self, command: SynthCommand) -> bool {
+        self.commands.lock().unwrap().push(command).is_ok()
+    }
+
+    pub fn note_on(&self, note: u8, velocity: u8) -> bool {
+        self.send(SynthCommand::NoteOn { note, velocity })
+    }
+
+    pub fn note_off(&self, note: u8) -> bool {
+        self.send(SynthCommand::NoteOff { note })
+    }
+
+    pub fn all_notes_off(&self) -> bool {
+        self.send(SynthCommand::AllNotesOff)
+    }
+
+    /// Set a parameter, as `SynthController::set_param` does.
+    pub fn set_param(&self, param: ParamId, value: f32) -> bool {
+        self.controller.set_param(param, value)
+    }
+
+    /// Fade the synth out over `time`, after which the sound finishes and kira lets go of it.
+    pub fn stop(&self, time: Duration) -> bool {
+        self.send(SynthCommand::FadeOut(time))
+    }
+}
+
+/// The synth, playing on kira's audio thread, a block at a time.
+struct SynthSound {
+    synth: Synth,
+    output_destination: OutputDestination,
+    commands: Consumer<SynthCommand>,
+    /// The block being played, interleaved.
+    buffer: Vec<f32>,
+    /// How far into `buffer` playback has got.
+    position: usize,
+}
+
+impl Sound for SynthSound {
+    fn output_destination(&mut self) -> OutputDestination {
+        self.output_destination
+    }
+
+    fn process(
+        &mut self,
+        _dt: f64,
+        _clock_info_provider: &ClockInfoProvider,
+        _modulator_value_provider: &ModulatorValueProvider,
+    ) -> Frame {
+        if self.position == self.buffer.len() {
+            self.synth.apply_queued(&mut self.commands);
+            self.synth.process(&mut self.buffer);
+            self.position = 0;
+        }
+        let frame = Frame {
+            left: self.buffer[self.position],
+            right: self.buffer[self.position + 1],
+        };
+        self.position += CHANNELS;
+        frame
+    }
+
+    fn finished(&self) -> bool {
+        self.synth.is_faded_out()
+    }
+}
@@ -0,0 +1,302 @@
+//! The synth as a VST3 plugin, built with nih-plug, for hosts which don't load CLAP plugins (for
+//! which the synth crate's own `clap` feature is the lighter option).
+//!
+//! Build it from this directory with `cargo build --release`, then put the library in a bundle
+//! where the host looks for plugins, e.g. on Linux, copy `target/release/libbasic_synth_vst3.so`
+//! to `~/.vst3/basic-synth.vst3/Contents/x86_64-linux/basic-synth.so`.
+//!
+//! Each of the synth's fixed parameters (see `ParamId::fixed`) is a plugin parameter, apart from
+//! the tempo, which follows the host's transport. Notes, and their per-note volume, pan and tuning
+//! expressions, come from the host's note events. The host's saved state holds the patch, in the
+//! preset text format, so effects chains are kept along with the parameters.
+
+use std::{
+    collections::BTreeMap,
+    num::NonZeroU32,
+    sync::{Arc, Mutex},
+};
+
+use basic_synth::{
+    param::{Curve, ParamId},
+    patch::Patch,
+    NoteExpression, Synth, SynthCommand, Waveform,
+};
+use nih_plug::{params::internals::ParamPtr, prelude::*};
+
+/// Voices in each instance of the plugin.
+const VOICES: usize = 16;
+
+/// The key the patch is saved under in the host's state.
+const PATCH_KEY: &str = "patch";
+
+/// The parameters the host sees, in order.
+fn synth_params() -> impl Iterator<Item = ParamId> {
+    ParamId::fixed().filter(|param| *param != ParamId::Tempo)
+}
+
+/// A plugin parameter for one of the synth's, with the same range, curve, default and unit.
+fn float_param(param: ParamId) -> Option<FloatParam> {
+    let info = param.info()?;
+    let range = match info.curve {
+        Curve::Linear | Curve::Stepped => FloatRange::Linear {
+            min: info.min,
+            max: info.max,
+        },
+        // nih-plug has no logarithmic range, but a skewed one comes close
+        Curve::Logarithmic => FloatRange::Skewed {
+            min: info.min,
+            max: info.max,
+            factor: FloatRange::skew_factor(-2.0),
+        },
+    };
+    let symbol = info.unit.symbol();
+    let mut float_param = FloatParam::new(param.to_string(), info.default, range)
+        .with_value_to_string(Arc::new(move |value| {
+            if param == ParamId::Waveform {
+                let waveform = Waveform::ALL.get(value.round() as usize);
+                return waveform.map_or_else(String::new, |w| w.name().to_string());
+            }
+            format!("{:.2} {}", value, symbol).trim_end().to_string()
+        }))
+        .with_string_to_value(Arc::new(move |text| {
+            let text = text.trim();
+            if param == ParamId::Waveform {
+                if let Some(waveform) = Waveform::from_name(text) {
+                    return Waveform::ALL
+                        .iter()
+                        .position(|w| *w == waveform)
+                        .map(|i| i as f32);
+                }
+            }
+            // anything after the number is taken to be the unit
+            text.split_whitespace().next()?.parse().ok()
+        }));
+    if info.curve == Curve::Stepped {
+        float_param = float_param.with_step_size(1.0);
+    }
+    Some(float_param)
+}
+
+/// The plugin's parameters, made from the synth's, along with the patch they go on top of.
+struct SynthParams {
+    /// Each parameter with the synth's parameter it controls. Never changed once made, as the
+    /// host keeps pointers to them.
+    params: Vec<(ParamId, FloatParam)>,
+    /// The patch loaded from the host's state, or the one the synth started with, in the preset
+    /// text format.
+    patch: Mutex<String>,
+}
+
+impl Default for SynthParams {
+    fn default() -> Self {
+        Self {
+            params: synth_params()
+                .filter_map(|param| Some((param, float_param(param)?)))
+                .collect(),
+            patch: Mutex::new(String::new()),
+        }
+    }
+}
+
+// the parameters live as long as `SynthParams`, which the plugin keeps in an `Arc`, and are never
+// moved or replaced
+unsafe impl Params for SynthParams {
+    fn param_map(&self) -> Vec<(String, ParamPtr, String)> {
+        self.params
+            .iter()
+            .map(|(_, param)| {
+                (
+                    param.name().replace(' ', "_"),
+                    param.as_ptr(),
+                    String::new(),
+                )
+            })
+            .collect()
+    }
+
+    fn serialize_fields(&self) -> BTreeMap<String, String> {
+        let patch = self.patch.lock().unwrap().clone();
+        BTreeMap::from([(PATCH_KEY.to_string(), patch)])
+    }
+
+    fn deserialize_fields(&self, serialized: &BTreeMap<String, String>) {
+        // the host initializes the plugin again after restoring its state, which loads this
+        if let Some(patch) = serialized.get(PATCH_KEY) {
+            *self.patch.lock().unwrap() = patch.clone();
+        }
+    }
+}
+
+struct BasicSynth {
+    params: Arc<SynthParams>,
+    /// Made when the host first tells us the sample rate.
+    synth: Option<Synth>,
+    /// The value of each parameter last passed on to the synth, in the same order.
+    applied: Vec<f32>,
+}
+
+impl Default for BasicSynth {
+    fn default() -> Self {
+        Self {
+            params: Arc::new(SynthParams::default()),
+            synth: None,
+            applied: Vec::new(),
+        }
+    }
+}
+
+impl BasicSynth {
+    /// Pass on each parameter the host has changed since the last time, or all of them.
+    fn apply_params(&mut self, all: bool) {
+        let synth = match &mut self.synth {
+            Some(synth) => synth,
+            None => return,
+        };
+        self.applied.resize(self.params.params.len(), f32::NAN);
+        for ((param, float_param), applied) in self.params.params.iter().zip(&mut self.applied) {
+            let value = float_param.value();
+            if all || value != *applied {
+                let _ = synth.set_param(*param, value);
+                *applied = value;
+            }
+        }
+    }
+}
+
+/// The command for a note event, if it's one the synth understands.
+fn translate_event(event: &NoteEvent<()>) -> Option<SynthCommand> {
+    Some(match *event {
+        NoteEvent::NoteOn { note, velocity, .. } => SynthCommand::NoteOn {
+            note,
+            velocity: (velocity * 127.0).round().clamp(1.0, 127.0) as u8,
+        },
+        // the synth can't cut one note short, so a choked note is released like any other
+        NoteEvent::NoteOff { note, .. } | NoteEvent::Choke { note, .. } => {
+            SynthCommand::NoteOff { note }
+        }
+        NoteEvent::PolyVolume { note, gain, .. } => SynthCommand::NoteExpression {
+            note,
+            expression: NoteExpression::Volume(gain),
+        },
+        NoteEvent::PolyPan { note, pan, .. } => SynthCommand::NoteExpression {
+            note,
+            expression: NoteExpression::Pan(pan),
+        },
+        NoteEvent::PolyTuning { note, tuning, .. } => SynthCommand::NoteExpression {
+            note,
+            expression: NoteExpression::Tuning(tuning),
+        },
+        _ => return None,
+    })
+}
+
+impl Plugin for BasicSynth {
+    const NAME: &'static str = "basic-synth";
+    const VENDOR: &'static str = "George Kaplan";
+    const URL: &'static str = "";
+    const EMAIL: &'static str = "gkaplan@fearless.tech";
+    const VERSION: &'static str = env!("CARGO_PKG_VERSION");
+
+    const AUDIO_IO_LAYOUTS: &'static [AudioIOLayout] = &[AudioIOLayout {
+        main_input_channels: None,
+        main_output_channels: NonZeroU32::new(2),
+        ..AudioIOLayout::const_default()
+    }];
+    const MIDI_INPUT: MidiConfig = MidiConfig::Basic;
+
+    type SysExMessage = ();
+    type BackgroundTask = ();
+
+    fn params(&self) -> Arc<dyn Params> {
+        self.params.clone()
+    }
+
+    fn initialize(
+        &mut self,
+        _audio_io_layout: &AudioIOLayout,
+        buffer_config: &BufferConfig,
+        _context: &mut impl InitContext<Self>,
+    ) -> bool {
+        let mut synth = match Synth::builder()
+            .voices(VOICES)
+            .sample_rate(buffer_config.sample_rate.round() as u32)
+            .build()
+        {
+            Ok(synth) => synth,
+            Err(e) => {
+                nih_error!("Could not create the synth: {}", e);
+                return false;
+            }
+        };
+        // the host keeps the undo history, and saving settings for it would allocate while
+        // rendering
+        synth.set_undo_enabled(false);
+
+        let mut patch = self.params.patch.lock().unwrap();
+        if patch.is_empty() {
+            let mut text = Vec::new();
+            if synth.save_patch().write(&mut text).is_ok() {
+                *patch = String::from_utf8(text).unwrap_or_default();
+            }
+        } else {
+            match Patch::read(patch.as_bytes()) {
+                Ok(saved) => synth.load_patch(&saved),
+                Err(e) => nih_error!("Could not load the saved patch: {}", e),
+            }
+        }
+        drop(patch);
+
+        self.synth = Some(synth);
+        // the parameters were saved along with the patch, and win where they differ
+        self.apply_params(true);
+        true
+    }
+
+    fn reset(&mut self) {
+        if let Some(synth) = &mut self.synth {
+            synth.reset();
+        }
+    }
+
+    fn process(
+        &mut self,
+        buffer: &mut Buffer,
+        _aux: &mut AuxiliaryBuffers,
+        context: &mut impl ProcessContext<Self>,
+    ) -> ProcessStatus {
+        self.apply_params(false);
+        let synth = match &mut self.synth {
+            Some(synth) => synth,
+            None => return ProcessStatus::Error("The synth hasn't been initialized"),
+        };
+        if let Some(tempo) = context.transport().tempo {
+            if tempo as f32 != synth.tempo() {
+                synth.set_tempo(tempo as f32);
+            }
+        }
+        // events land on the frame they're for
+        let start = synth.clock();
+        while let Some(event) = context.next_event() {
+            if let Some(command) = translate_event(&event) {
+                // there's nobody to tell about dropped commands in here
+                let _ = synth.schedule(start + event.timing() as u64, command);
+            }
+        }
+        if let [left, right] = buffer.as_slice() {
+            synth.process_stereo(left, right);
+        }
+        // notes ring on after they're released, and effects after that
+        ProcessStatus::KeepAlive
+    }
+}
+
+impl Vst3Plugin for BasicSynth {
+    const VST3_CLASS_ID: [u8; 16] = *b"BasicSynthGSKvst";
+    const VST3_SUBCATEGORIES: &'static [Vst3SubCategory] = &[
+        Vst3SubCategory::Instrument,
+        Vst3SubCategory::Synth,
+        Vst3SubCategory::Stereo,
+    ];
+}
+
+nih_export_vst3!(BasicSynth);
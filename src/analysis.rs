@@ -0,0 +1,155 @@
+//! A spectrum analyzer, reading the audio from a tap (see `tap`) and finding how much of each
+//! frequency it holds, e.g. for drawing in a user interface, or for checking how much aliasing
+//! makes it into the output.
+//!
+//! The analysis is done on demand, on whichever thread reads the tap, so the audio thread only
+//! ever copies samples.
+
+use alloc::{vec, vec::Vec};
+use core::f32::consts::TAU;
+
+use crate::{ring::Consumer, tap::TapPoint, Frame, Synth};
+
+#[cfg(not(any(feature = "std", test)))]
+use crate::math::Float;
+
+/// How many windows' worth of frames the tap made by `SpectrumAnalyzer::new` can hold.
+const TAP_WINDOWS: usize = 4;
+
+/// The magnitude spectrum of the latest frames read from a tap, through a Hann window, mixed down
+/// to mono.
+pub struct SpectrumAnalyzer {
+    source: Consumer<Frame>,
+    sample_rate: f32,
+    window: Vec<f32>,
+    /// The latest samples, oldest first from `write`.
+    history: Vec<f32>,
+    write: usize,
+    /// `cos` and `-sin` of each step around the circle the transform takes, a fraction of `size`.
+    twiddles: Vec<(f32, f32)>,
+    real: Vec<f32>,
+    imag: Vec<f32>,
+    magnitudes: Vec<f32>,
+}
+
+impl SpectrumAnalyzer {
+    /// Analyze the output of `synth`, as `process` writes it, `size` frames at a time: a power of
+    /// two, at least 2. Larger sizes tell frequencies apart more finely, but take longer to see
+    /// changes.
+    pub fn new(synth: &mut Synth, size: usize) -> Self {
+        let source = synth
+            .add_tap(TapPoint::Output, 1, size * TAP_WINDOWS)
+            .expect("The output can always be tapped");
+        Self::from_tap(source, synth.sample_rate() as f32, size)
+    }
+
+    /// Analyze what a tap made with `Synth::add_tap` reads, at `sample_rate`: the synth's own,
+    /// divided by the tap's decimation. Decimating doesn't filter, so anything above the new
+    /// half sample rate folds back down into what's shown.
+    pub fn from_tap(source: Consumer<Frame>, sample_rate: f32, size: usize) -> Self {
+        assert!(
+            size >= 2 && size.is_power_of_two(),
+            "Spectrum size must be a power of two, at least 2"
+        );
+        let window = (0..size)
+            .map(|i| 0.5 - 0.5 * (TAU * i as f32 / size as f32).cos())
+            .collect();
+        let twiddles = (0..size / 2)
+            .map(|i| {
+                let angle = TAU * i as f32 / size as f32;
+                (angle.cos(), -angle.sin())
+            })
+            .collect();
+        Self {
+            source,
+            sample_rate,
+            window,
+            history: vec![0.0; size],
+            write: 0,
+            twiddles,
+            real: vec![0.0; size],
+            imag: vec![0.0; size],
+            magnitudes: vec![0.0; size / 2 + 1],
+        }
+    }
+
+    /// How many frames are analyzed at a time.
+    pub fn size(&self) -> usize {
+        self.history.len()
+    }
+
+    /// The frequency in the middle of a bin of `magnitudes`, in Hz.
+    pub fn bin_frequency(&self, bin: usize) -> f32 {
+        bin as f32 * self.sample_rate / self.size() as f32
+    }
+
+    /// Read whatever the tap has waiting, and analyze the latest `size` frames, returning their
+    /// magnitudes (see `magnitudes`).
+    pub fn update(&mut self) -> &[f32] {
+        while let Some(frame) = self.source.pop() {
+            self.history[self.write] = frame.to_mono();
+            self.write = (self.write + 1) % self.history.len();
+        }
+        self.analyze();
+        &self.magnitudes
+    }
+
+    /// The magnitudes found by the last `update`, from 0 Hz up to half the sample rate, in bins
+    /// `bin_frequency` apart. A sine wave at full scale measures about 1 in the bin nearest its
+    /// frequency, spilling into its neighbours.
+    pub fn magnitudes(&self) -> &[f32] {
+        &self.magnitudes
+    }
+
+    fn analyze(&mut self) {
+        let size = self.size();
+        let oldest = self.history[self.write..]
+            .iter()
+            .chain(&self.history[..self.write]);
+        for ((real, sample), gain) in self.real.iter_mut().zip(oldest).zip(&self.window) {
+            *real = sample * gain;
+        }
+        self.imag.fill(0.0);
+        self.transform();
+
+        // the window takes away half of the level, and the other half of each frequency but the
+        // lowest and highest is in the mirror image above half the sample rate
+        let scale = 4.0 / size as f32;
+        for (bin, magnitude) in self.magnitudes.iter_mut().enumerate() {
+            let edge = bin == 0 || bin == size / 2;
+            let length = (self.real[bin] * self.real[bin] + self.imag[bin] * self.imag[bin]).sqrt();
+            *magnitude = length * if edge { scale * 0.5 } else { scale };
+        }
+    }
+
+    /// An in-place radix 2 fast Fourier transform of `real` and `imag`.
+    fn transform(&mut self) {
+        let size = self.size();
+        let bits = size.trailing_zeros();
+        for i in 0..size {
+            let j = i.reverse_bits() >> (usize::BITS - bits);
+            if i < j {
+                self.real.swap(i, j);
+                self.imag.swap(i, j);
+            }
+        }
+
+        let mut half = 1;
+        while half < size {
+            let stride = size / (half * 2);
+            for start in (0..size).step_by(half * 2) {
+                for k in 0..half {
+                    let (cos, sin) = self.twiddles[k * stride];
+                    let (a, b) = (start + k, start + k + half);
+                    let real = self.real[b] * cos - self.imag[b] * sin;
+                    let imag = self.real[b] * sin + self.imag[b] * cos;
+                    self.real[b] = self.real[a] - real;
+                    self.imag[b] = self.imag[a] - imag;
+                    self.real[a] += real;
+                    self.imag[a] += imag;
+                }
+            }
+            half *= 2;
+        }
+    }
+}
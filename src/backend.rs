@@ -0,0 +1,307 @@
+//! Playing audio through the system's output devices, behind a common interface so that
+//! embedders can supply their own (e.g. a game engine's mixer, or a DAC on a microcontroller).
+
+use std::{io, time::Duration};
+
+use rodio::{
+    cpal::{
+        self,
+        traits::{HostTrait, StreamTrait},
+        Sample, SampleFormat, StreamConfig, SupportedStreamConfig,
+    },
+    Device, DeviceTrait, OutputStream, OutputStreamHandle, Sink, Source,
+};
+
+use crate::{dither::Dither, Frame, CHANNELS, DEFAULT_BLOCK_SIZE};
+
+/// Fills a buffer of interleaved samples, `CHANNELS` per frame, with the next of the audio.
+///
+/// This is called on the backend's own thread, which may well be a real-time one, so it should
+/// avoid blocking.
+pub type AudioCallback = Box<dyn FnMut(&mut [f32]) + Send>;
+
+/// Receives a buffer of (mono) samples from an input device. Like `AudioCallback`, this runs on
+/// the device's own thread.
+pub type InputCallback = Box<dyn FnMut(&[f32]) + Send>;
+
+/// Something which plays audio, asking for it a buffer at a time.
+pub trait AudioBackend {
+    /// The rate the callback should produce audio at, in Hz.
+    fn sample_rate(&self) -> u32;
+
+    /// Start asking `callback` for audio, until `stop` is called (or the backend is dropped).
+    fn start(&mut self, callback: AudioCallback) -> io::Result<()>;
+
+    /// Stop asking for audio.
+    fn stop(&mut self);
+}
+
+/// The names of all available audio output devices.
+pub fn output_device_names() -> Vec<String> {
+    cpal::default_host()
+        .output_devices()
+        .map(|devices| devices.filter_map(|d| d.name().ok()).collect())
+        .unwrap_or_default()
+}
+
+/// Look up an output device by name, or get the default one if no name is given.
+fn output_device(name: Option<&str>) -> io::Result<Device> {
+    let host = cpal::default_host();
+    let device = match name {
+        None => host.default_output_device(),
+        Some(name) => host
+            .output_devices()
+            .map_err(io::Error::other)?
+            .find(|d| d.name().is_ok_and(|n| n == name)),
+    };
+    device.ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "No such audio output device"))
+}
+
+/// Plays audio straight from the device's own callback, using `cpal`.
+pub struct CpalBackend {
+    device: Device,
+    config: SupportedStreamConfig,
+    stream: Option<cpal::Stream>,
+}
+
+impl CpalBackend {
+    /// Use the output device called `device_name`, or the default one if that is `None`, in its
+    /// preferred configuration.
+    pub fn new(device_name: Option<&str>) -> io::Result<Self> {
+        let device = output_device(device_name)?;
+        let config = device.default_output_config().map_err(io::Error::other)?;
+        Ok(Self {
+            device,
+            config,
+            stream: None,
+        })
+    }
+}
+
+impl AudioBackend for CpalBackend {
+    fn sample_rate(&self) -> u32 {
+        self.config.sample_rate().0
+    }
+
+    fn start(&mut self, callback: AudioCallback) -> io::Result<()> {
+        let config = self.config.config();
+        let stream = match self.config.sample_format() {
+            SampleFormat::F32 => build_stream::<f32>(&self.device, &config, callback),
+            SampleFormat::I16 => build_stream::<i16>(&self.device, &config, callback),
+            SampleFormat::U16 => build_stream::<u16>(&self.device, &config, callback),
+        }?;
+        stream.play().map_err(io::Error::other)?;
+        self.stream = Some(stream);
+        Ok(())
+    }
+
+    fn stop(&mut self) {
+        self.stream = None;
+    }
+}
+
+/// Open a stream which gets its audio from `callback`, mapping it onto however many channels
+/// the device has.
+fn build_stream<T: Sample>(
+    device: &Device,
+    config: &StreamConfig,
+    mut callback: AudioCallback,
+) -> io::Result<cpal::Stream> {
+    let channels = config.channels as usize;
+    let mut buffer = Vec::new();
+    let mut dither = Dither::new();
+    // dither down to 16 bits ourselves, rather than letting cpal truncate
+    let mut convert = move |sample: f32| match T::FORMAT {
+        SampleFormat::F32 => T::from(&sample),
+        SampleFormat::I16 | SampleFormat::U16 => T::from(&dither.to_i16(sample)),
+    };
+    device
+        .build_output_stream(
+            config,
+            move |data: &mut [T], _| {
+                // only allocates if the device asks for more than it has before
+                buffer.resize(data.len() / channels * CHANNELS, 0.0);
+                callback(&mut buffer);
+
+                for (out, frame) in data
+                    .chunks_mut(channels)
+                    .zip(Frame::from_interleaved(&buffer))
+                {
+                    if channels == 1 {
+                        out[0] = convert(frame.to_mono());
+                    } else {
+                        for (i, sample) in out.iter_mut().enumerate() {
+                            *sample = convert(*frame.get(i).unwrap_or(&0.0));
+                        }
+                    }
+                }
+            },
+            |err| log::error!("Audio output error: {}", err),
+        )
+        .map_err(io::Error::other)
+}
+
+/// Plays audio through a `rodio` sink, which the audio is streamed into as a never-ending
+/// `Source`.
+pub struct RodioBackend {
+    _stream: OutputStream,
+    handle: OutputStreamHandle,
+    sample_rate: u32,
+    sink: Option<Sink>,
+}
+
+impl RodioBackend {
+    /// Use the output device called `device_name`, or the default one if that is `None`.
+    pub fn new(device_name: Option<&str>) -> io::Result<Self> {
+        let device = output_device(device_name)?;
+        let sample_rate = device
+            .default_output_config()
+            .map_err(io::Error::other)?
+            .sample_rate()
+            .0;
+        let (stream, handle) = OutputStream::try_from_device(&device).map_err(io::Error::other)?;
+        Ok(Self {
+            _stream: stream,
+            handle,
+            sample_rate,
+            sink: None,
+        })
+    }
+}
+
+impl AudioBackend for RodioBackend {
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn start(&mut self, callback: AudioCallback) -> io::Result<()> {
+        let sink = Sink::try_new(&self.handle).map_err(io::Error::other)?;
+        sink.append(CallbackSource {
+            callback,
+            sample_rate: self.sample_rate,
+            buffer: vec![0.0; DEFAULT_BLOCK_SIZE * CHANNELS],
+            position: DEFAULT_BLOCK_SIZE * CHANNELS,
+        });
+        self.sink = Some(sink);
+        Ok(())
+    }
+
+    fn stop(&mut self) {
+        if let Some(sink) = self.sink.take() {
+            sink.stop();
+        }
+    }
+}
+
+/// Pulls audio from a callback a block at a time, for `rodio` to take one sample at a time.
+struct CallbackSource {
+    callback: AudioCallback,
+    sample_rate: u32,
+    buffer: Vec<f32>,
+    position: usize,
+}
+
+impl Iterator for CallbackSource {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.position == self.buffer.len() {
+            (self.callback)(&mut self.buffer);
+            self.position = 0;
+        }
+
+        self.position += 1;
+        Some(self.buffer[self.position - 1])
+    }
+}
+
+impl Source for CallbackSource {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        CHANNELS as u16
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
+
+/// The names of all available audio input devices.
+pub fn input_device_names() -> Vec<String> {
+    cpal::default_host()
+        .input_devices()
+        .map(|devices| devices.filter_map(|d| d.name().ok()).collect())
+        .unwrap_or_default()
+}
+
+/// Receives audio from an input device through `cpal`, mixed down to mono.
+pub struct CpalInput {
+    _stream: cpal::Stream,
+    sample_rate: u32,
+}
+
+impl CpalInput {
+    /// Start recording from the input device called `device_name`, or the default one if that is
+    /// `None`, passing each buffer that comes in to `callback`.
+    pub fn start(device_name: Option<&str>, callback: InputCallback) -> io::Result<Self> {
+        let host = cpal::default_host();
+        let device = match device_name {
+            None => host.default_input_device(),
+            Some(name) => host
+                .input_devices()
+                .map_err(io::Error::other)?
+                .find(|d| d.name().is_ok_and(|n| n == name)),
+        }
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "No such audio input device"))?;
+
+        let supported = device.default_input_config().map_err(io::Error::other)?;
+        let config = supported.config();
+        let stream = match supported.sample_format() {
+            SampleFormat::F32 => build_input_stream::<f32>(&device, &config, callback),
+            SampleFormat::I16 => build_input_stream::<i16>(&device, &config, callback),
+            SampleFormat::U16 => build_input_stream::<u16>(&device, &config, callback),
+        }?;
+        stream.play().map_err(io::Error::other)?;
+
+        Ok(Self {
+            _stream: stream,
+            sample_rate: config.sample_rate.0,
+        })
+    }
+
+    /// The rate input arrives at, in Hz.
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+}
+
+fn build_input_stream<T: Sample>(
+    device: &Device,
+    config: &StreamConfig,
+    mut callback: InputCallback,
+) -> io::Result<cpal::Stream> {
+    let channels = config.channels as usize;
+    let mut buffer = Vec::new();
+    device
+        .build_input_stream(
+            config,
+            move |data: &[T], _| {
+                buffer.clear();
+                buffer.extend(
+                    data.chunks(channels).map(|frame| {
+                        frame.iter().map(|s| s.to_f32()).sum::<f32>() / channels as f32
+                    }),
+                );
+                callback(&buffer);
+            },
+            |err| log::error!("Audio input error: {}", err),
+        )
+        .map_err(io::Error::other)
+}
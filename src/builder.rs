@@ -0,0 +1,165 @@
+//! Setting up a synth with more options than `Synth::new` takes, each with a sensible default so
+//! that only the ones that matter need mentioning, e.g.
+//! `Synth::builder().sample_rate(48000).voices(16).build()?`.
+
+use core::time::Duration;
+
+use crate::{
+    patch::Patch, Oversampling, Synth, SynthError, VoiceStealing, DEFAULT_BLOCK_SIZE,
+    DEFAULT_OSCILLATORS, MAX_SAMPLE_RATE, MIN_SAMPLE_RATE,
+};
+
+/// Number of voices, unless changed with `SynthBuilder::voices`.
+const DEFAULT_VOICES: usize = 8;
+
+/// Sample rate, in Hz, unless changed with `SynthBuilder::sample_rate`.
+const DEFAULT_SAMPLE_RATE: u32 = 44100;
+
+/// The options for a new synth, as made by `Synth::builder`.
+#[derive(Debug, Clone)]
+pub struct SynthBuilder {
+    voices: usize,
+    oscillators: usize,
+    sample_rate: u32,
+    oversampling: Oversampling,
+    block_size: usize,
+    note_timeout: Option<Duration>,
+    voice_stealing: VoiceStealing,
+    seed: Option<u32>,
+    patch: Option<Patch>,
+}
+
+impl Default for SynthBuilder {
+    fn default() -> Self {
+        Self {
+            voices: DEFAULT_VOICES,
+            oscillators: DEFAULT_OSCILLATORS,
+            sample_rate: DEFAULT_SAMPLE_RATE,
+            oversampling: Oversampling::default(),
+            block_size: DEFAULT_BLOCK_SIZE,
+            note_timeout: None,
+            voice_stealing: VoiceStealing::default(),
+            seed: None,
+            patch: None,
+        }
+    }
+}
+
+impl SynthBuilder {
+    /// How many notes can play at once, at least one. Defaults to 8.
+    pub fn voices(mut self, voices: usize) -> Self {
+        self.voices = voices;
+        self
+    }
+
+    /// How many oscillators each voice has, detuned from each other (see `Synth::set_detune`).
+    /// Defaults to 3; fewer costs less CPU time, for a thinner sound. At least one is used.
+    pub fn oscillators(mut self, oscillators: usize) -> Self {
+        self.oscillators = oscillators.max(1);
+        self
+    }
+
+    /// The sample rate audio is produced at, in Hz, from `MIN_SAMPLE_RATE` to `MAX_SAMPLE_RATE`.
+    /// Defaults to 44100.
+    pub fn sample_rate(mut self, sample_rate: u32) -> Self {
+        self.sample_rate = sample_rate;
+        self
+    }
+
+    /// How much higher a rate the voices run at internally. Defaults to `Oversampling::X4`.
+    pub fn oversampling(mut self, oversampling: Oversampling) -> Self {
+        self.oversampling = oversampling;
+        self
+    }
+
+    /// See `Synth::set_block_size`; it must be at least one frame. Defaults to
+    /// `DEFAULT_BLOCK_SIZE`.
+    pub fn block_size(mut self, block_size: usize) -> Self {
+        self.block_size = block_size;
+        self
+    }
+
+    /// See `Synth::set_note_timeout`. Defaults to none.
+    pub fn note_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.note_timeout = timeout;
+        self
+    }
+
+    /// See `Synth::set_voice_stealing`. Defaults to `VoiceStealing::Off`.
+    pub fn voice_stealing(mut self, stealing: VoiceStealing) -> Self {
+        self.voice_stealing = stealing;
+        self
+    }
+
+    /// See `Synth::set_seed`. Defaults to none, so that each synth sounds slightly different.
+    pub fn seed(mut self, seed: u32) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// A patch to start with, rather than the default sound.
+    pub fn patch(mut self, patch: Patch) -> Self {
+        self.patch = Some(patch);
+        self
+    }
+
+    /// Make the synth, if the options are all valid.
+    pub fn build(self) -> Result<Synth, SynthError> {
+        if !(MIN_SAMPLE_RATE..=MAX_SAMPLE_RATE).contains(&self.sample_rate) {
+            return Err(SynthError::UnsupportedSampleRate(self.sample_rate));
+        }
+        if self.block_size == 0 {
+            return Err(SynthError::InvalidBlockSize);
+        }
+        if self.voices == 0 {
+            return Err(SynthError::NoVoices);
+        }
+        let mut synth = Synth::with_oscillators(
+            self.voices,
+            self.oscillators,
+            self.sample_rate,
+            self.oversampling,
+        );
+        synth.set_block_size(self.block_size);
+        synth.set_note_timeout(self.note_timeout);
+        synth.set_voice_stealing(self.voice_stealing);
+        if let Some(seed) = self.seed {
+            synth.set_seed(seed);
+        }
+        if let Some(patch) = &self.patch {
+            synth.load_patch(patch);
+        }
+        Ok(synth)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::param::ParamId;
+
+    #[test]
+    fn rejects_invalid_options() {
+        let build = |builder: SynthBuilder| builder.build().err();
+        assert_eq!(
+            build(Synth::builder().voices(0)),
+            Some(SynthError::NoVoices)
+        );
+        assert_eq!(
+            build(Synth::builder().block_size(0)),
+            Some(SynthError::InvalidBlockSize)
+        );
+        assert_eq!(
+            build(Synth::builder().sample_rate(100)),
+            Some(SynthError::UnsupportedSampleRate(100))
+        );
+        assert!(Synth::builder().voices(1).build().is_ok());
+    }
+
+    #[test]
+    fn new_makes_at_least_one_voice() {
+        let synth = Synth::new(0, 48000, Oversampling::default());
+        assert!(synth.voice_effects().is_empty());
+        assert!(synth.get_param(ParamId::SendLevel(0)).is_some());
+    }
+}
@@ -0,0 +1,648 @@
+//! The synth as a CLAP plugin, for playing it inside hosts such as Bitwig Studio and Reaper.
+//!
+//! Build the library with the `clap` feature and give it the `.clap` extension where the host
+//! looks for plugins, e.g. on Linux, `cargo build --release --features clap` and copy
+//! `target/release/libbasic_synth.so` to `~/.clap/basic-synth.clap`.
+//!
+//! The plugin has one stereo output and one note input, which takes CLAP notes (with per-note
+//! volume, pan and tuning expressions) or MIDI. The fixed parameters (see `ParamId::fixed`) can be
+//! automated, apart from the tempo, which follows the host's transport. The whole patch, effects
+//! included, is saved with the host's project.
+
+mod sys;
+
+use {
+    core::ffi::{c_char, c_void},
+    midi_msg::MidiMsg,
+    std::{
+        ffi::CStr,
+        ptr, slice,
+        sync::{
+            atomic::{AtomicU32, Ordering},
+            Arc, Mutex,
+        },
+    },
+};
+
+use crate::{
+    param::{Curve, ParamId},
+    patch::Patch,
+    NoteExpression, Synth, SynthCommand, SynthError, Waveform,
+};
+
+/// Voices in each instance of the plugin.
+const VOICES: usize = 16;
+
+/// The sample rate a new instance runs at, until the host activates it at its own.
+const INITIAL_SAMPLE_RATE: u32 = 44100;
+
+const PLUGIN_ID: &[u8] = b"com.github.g-s-k.basic-synth\0";
+
+/// A string literal, nul-terminated for C.
+macro_rules! c_str {
+    ($s:expr) => {
+        concat!($s, "\0").as_ptr() as *const c_char
+    };
+}
+
+/// Something the host is handed pointers into, which never changes, so it can be shared between
+/// threads despite the raw pointers in it.
+struct Constant<T>(T);
+
+unsafe impl<T> Sync for Constant<T> {}
+
+static FEATURES: Constant<[*const c_char; 4]> = Constant([
+    c_str!("instrument"),
+    c_str!("synthesizer"),
+    c_str!("stereo"),
+    ptr::null(),
+]);
+
+static DESCRIPTOR: Constant<sys::PluginDescriptor> = Constant(sys::PluginDescriptor {
+    clap_version: sys::VERSION,
+    id: PLUGIN_ID.as_ptr() as *const c_char,
+    name: c_str!("basic-synth"),
+    vendor: c_str!("George Kaplan"),
+    url: c_str!(""),
+    manual_url: c_str!(""),
+    support_url: c_str!(""),
+    version: c_str!(env!("CARGO_PKG_VERSION")),
+    description: c_str!("A polyphonic subtractive synthesizer"),
+    features: &FEATURES.0 as *const [*const c_char; 4] as *const *const c_char,
+});
+
+/// What the host looks up when it loads the library.
+#[allow(non_upper_case_globals)]
+#[no_mangle]
+pub static clap_entry: sys::PluginEntry = sys::PluginEntry {
+    clap_version: sys::VERSION,
+    init: entry_init,
+    deinit: entry_deinit,
+    get_factory: entry_get_factory,
+};
+
+static FACTORY: sys::PluginFactory = sys::PluginFactory {
+    get_plugin_count: factory_plugin_count,
+    get_plugin_descriptor: factory_plugin_descriptor,
+    create_plugin: factory_create_plugin,
+};
+
+static AUDIO_PORTS: sys::PluginAudioPorts = sys::PluginAudioPorts {
+    count: audio_ports_count,
+    get: audio_ports_get,
+};
+
+static NOTE_PORTS: sys::PluginNotePorts = sys::PluginNotePorts {
+    count: note_ports_count,
+    get: note_ports_get,
+};
+
+static PARAMS: sys::PluginParams = sys::PluginParams {
+    count: params_count,
+    get_info: params_get_info,
+    get_value: params_get_value,
+    value_to_text: params_value_to_text,
+    text_to_value: params_text_to_value,
+    flush: params_flush,
+};
+
+static STATE: sys::PluginState = sys::PluginState {
+    save: state_save,
+    load: state_load,
+};
+
+/// The parameters the host sees, in order: their index is their CLAP id.
+fn params() -> impl Iterator<Item = ParamId> {
+    ParamId::fixed().filter(|param| *param != ParamId::Tempo)
+}
+
+fn param_index(param: ParamId) -> Option<usize> {
+    params().position(|p| p == param)
+}
+
+/// Make a synth for the plugin, reporting each change to a parameter in `values`.
+fn make_synth(sample_rate: u32, values: &Arc<Vec<AtomicU32>>) -> Result<Synth, SynthError> {
+    let mut synth = Synth::builder()
+        .voices(VOICES)
+        .sample_rate(sample_rate)
+        .build()?;
+    // the host keeps the undo history, and saving settings for it would allocate while rendering
+    synth.set_undo_enabled(false);
+    for (param, value) in params().zip(values.iter()) {
+        let current = synth.get_param(param).unwrap_or_default();
+        value.store(current.to_bits(), Ordering::Relaxed);
+    }
+    let values = values.clone();
+    synth.set_param_observer(Some(Box::new(move |param, value| {
+        if let Some(index) = param_index(param) {
+            values[index].store(value.to_bits(), Ordering::Relaxed);
+        }
+    })));
+    Ok(synth)
+}
+
+/// One instance of the plugin, behind the `clap_plugin` the host holds.
+struct Instance {
+    /// What the host calls into, whose `plugin_data` points back here.
+    raw: sys::Plugin,
+    host: *const sys::Host,
+    /// The host's side of the parameters extension, if it has one.
+    host_params: *const sys::HostParams,
+    /// Taken by the audio thread to render, and by the main thread to switch patches or sample
+    /// rates. The audio thread never waits for it.
+    synth: Mutex<Synth>,
+    /// Each parameter's latest value, as `f32` bits, for the host to read without the lock.
+    values: Arc<Vec<AtomicU32>>,
+}
+
+impl Instance {
+    unsafe fn from_raw<'a>(plugin: *const sys::Plugin) -> &'a Self {
+        &*((*plugin).plugin_data as *const Self)
+    }
+
+    /// Tell the host that the parameters have changed, e.g. after loading a patch.
+    unsafe fn rescan_values(&self) {
+        if let Some(host_params) = self.host_params.as_ref() {
+            (host_params.rescan)(self.host, sys::PARAM_RESCAN_VALUES);
+        }
+    }
+}
+
+/// Whether a C string from the host is `expected`, which includes the nul.
+unsafe fn c_str_is(s: *const c_char, expected: &[u8]) -> bool {
+    !s.is_null() && CStr::from_ptr(s).to_bytes_with_nul() == expected
+}
+
+/// Copy `s` into a fixed-size C string, cutting it short if need be.
+fn write_c_str(out: &mut [c_char], s: &str) {
+    let len = s.len().min(out.len().saturating_sub(1));
+    for (out, byte) in out.iter_mut().zip(&s.as_bytes()[..len]) {
+        *out = *byte as c_char;
+    }
+    if let Some(end) = out.get_mut(len) {
+        *end = 0;
+    }
+}
+
+unsafe extern "C" fn entry_init(_plugin_path: *const c_char) -> bool {
+    true
+}
+
+unsafe extern "C" fn entry_deinit() {}
+
+unsafe extern "C" fn entry_get_factory(factory_id: *const c_char) -> *const c_void {
+    if c_str_is(factory_id, sys::PLUGIN_FACTORY_ID) {
+        &FACTORY as *const sys::PluginFactory as *const c_void
+    } else {
+        ptr::null()
+    }
+}
+
+unsafe extern "C" fn factory_plugin_count(_factory: *const sys::PluginFactory) -> u32 {
+    1
+}
+
+unsafe extern "C" fn factory_plugin_descriptor(
+    _factory: *const sys::PluginFactory,
+    index: u32,
+) -> *const sys::PluginDescriptor {
+    if index == 0 {
+        &DESCRIPTOR.0
+    } else {
+        ptr::null()
+    }
+}
+
+unsafe extern "C" fn factory_create_plugin(
+    _factory: *const sys::PluginFactory,
+    host: *const sys::Host,
+    plugin_id: *const c_char,
+) -> *const sys::Plugin {
+    if !c_str_is(plugin_id, PLUGIN_ID) {
+        return ptr::null();
+    }
+    let values = Arc::new(params().map(|_| AtomicU32::new(0)).collect());
+    let synth = match make_synth(INITIAL_SAMPLE_RATE, &values) {
+        Ok(synth) => synth,
+        Err(e) => {
+            log::error!("Could not create the synth: {}", e);
+            return ptr::null();
+        }
+    };
+    let instance = Box::into_raw(Box::new(Instance {
+        raw: sys::Plugin {
+            desc: &DESCRIPTOR.0,
+            plugin_data: ptr::null_mut(),
+            init: plugin_init,
+            destroy: plugin_destroy,
+            activate: plugin_activate,
+            deactivate: plugin_deactivate,
+            start_processing: plugin_start_processing,
+            stop_processing: plugin_stop_processing,
+            reset: plugin_reset,
+            process: plugin_process,
+            get_extension: plugin_get_extension,
+            on_main_thread: plugin_on_main_thread,
+        },
+        host,
+        host_params: ptr::null(),
+        synth: Mutex::new(synth),
+        values,
+    }));
+    (*instance).raw.plugin_data = instance as *mut c_void;
+    &(*instance).raw
+}
+
+unsafe extern "C" fn plugin_init(plugin: *const sys::Plugin) -> bool {
+    let instance = (*plugin).plugin_data as *mut Instance;
+    let host = (*instance).host;
+    (*instance).host_params =
+        ((*host).get_extension)(host, sys::EXT_PARAMS.as_ptr() as *const c_char)
+            as *const sys::HostParams;
+    true
+}
+
+unsafe extern "C" fn plugin_destroy(plugin: *const sys::Plugin) {
+    drop(Box::from_raw((*plugin).plugin_data as *mut Instance));
+}
+
+unsafe extern "C" fn plugin_activate(
+    plugin: *const sys::Plugin,
+    sample_rate: f64,
+    _min_frames_count: u32,
+    _max_frames_count: u32,
+) -> bool {
+    let instance = Instance::from_raw(plugin);
+    let sample_rate = sample_rate.round() as u32;
+    let mut synth = instance.synth.lock().unwrap();
+    if synth.sample_rate() == sample_rate {
+        return true;
+    }
+    // the synth is made again at the host's rate, keeping its sound
+    match make_synth(sample_rate, &instance.values) {
+        Ok(mut new) => {
+            new.load_patch(&synth.save_patch());
+            *synth = new;
+            true
+        }
+        Err(e) => {
+            log::error!("Could not run at {} Hz: {}", sample_rate, e);
+            false
+        }
+    }
+}
+
+unsafe extern "C" fn plugin_deactivate(_plugin: *const sys::Plugin) {}
+
+unsafe extern "C" fn plugin_start_processing(_plugin: *const sys::Plugin) -> bool {
+    true
+}
+
+unsafe extern "C" fn plugin_stop_processing(_plugin: *const sys::Plugin) {}
+
+unsafe extern "C" fn plugin_reset(plugin: *const sys::Plugin) {
+    Instance::from_raw(plugin).synth.lock().unwrap().reset();
+}
+
+unsafe extern "C" fn plugin_process(
+    plugin: *const sys::Plugin,
+    process: *const sys::Process,
+) -> i32 {
+    let instance = Instance::from_raw(plugin);
+    let process = &*process;
+    let output = match process.audio_outputs.as_ref() {
+        Some(output) if process.audio_outputs_count > 0 => output,
+        _ => return sys::PROCESS_ERROR,
+    };
+    if output.channel_count < 2 || output.data32.is_null() {
+        return sys::PROCESS_ERROR;
+    }
+    let frames = process.frames_count as usize;
+    let channels = slice::from_raw_parts(output.data32, 2);
+    let left = slice::from_raw_parts_mut(channels[0], frames);
+    let right = slice::from_raw_parts_mut(channels[1], frames);
+
+    let mut synth = match instance.synth.try_lock() {
+        Ok(synth) => synth,
+        Err(_) => {
+            // the main thread is switching patches; rather than wait, this block is silent
+            left.fill(0.0);
+            right.fill(0.0);
+            return sys::PROCESS_CONTINUE;
+        }
+    };
+    if let Some(transport) = process.transport.as_ref() {
+        let tempo = transport.tempo as f32;
+        if transport.flags & sys::TRANSPORT_HAS_TEMPO != 0 && tempo != synth.tempo() {
+            synth.set_tempo(tempo);
+        }
+    }
+    // events land on the frame they're for
+    let start = synth.clock();
+    read_events(process.in_events, |time, command| {
+        // there's nobody to tell about dropped commands in here
+        let _ = synth.schedule(start + time as u64, command);
+    });
+    synth.process_stereo(left, right);
+    sys::PROCESS_CONTINUE
+}
+
+unsafe extern "C" fn plugin_get_extension(
+    _plugin: *const sys::Plugin,
+    id: *const c_char,
+) -> *const c_void {
+    if c_str_is(id, sys::EXT_AUDIO_PORTS) {
+        &AUDIO_PORTS as *const sys::PluginAudioPorts as *const c_void
+    } else if c_str_is(id, sys::EXT_NOTE_PORTS) {
+        &NOTE_PORTS as *const sys::PluginNotePorts as *const c_void
+    } else if c_str_is(id, sys::EXT_PARAMS) {
+        &PARAMS as *const sys::PluginParams as *const c_void
+    } else if c_str_is(id, sys::EXT_STATE) {
+        &STATE as *const sys::PluginState as *const c_void
+    } else {
+        ptr::null()
+    }
+}
+
+unsafe extern "C" fn plugin_on_main_thread(_plugin: *const sys::Plugin) {}
+
+/// Translate the host's events into commands, along with the frame of the block each is for.
+unsafe fn read_events(events: *const sys::InputEvents, mut f: impl FnMut(u32, SynthCommand)) {
+    let events = match events.as_ref() {
+        Some(events) => events,
+        None => return,
+    };
+    for index in 0..(events.size)(events) {
+        if let Some(header) = (events.get)(events, index).as_ref() {
+            if let Some(command) = translate_event(header) {
+                f(header.time, command);
+            }
+        }
+    }
+}
+
+/// The command for an event, if it's one the synth understands.
+unsafe fn translate_event(header: &sys::EventHeader) -> Option<SynthCommand> {
+    if header.space_id != sys::CORE_EVENT_SPACE_ID {
+        return None;
+    }
+    // a negative key means every key, which the synth has no way to address
+    let key = |key: i16| (0..128).contains(&key).then_some(key as u8);
+    let event = header as *const sys::EventHeader;
+    match header.type_ {
+        sys::EVENT_NOTE_ON => {
+            let event = &*(event as *const sys::EventNote);
+            let velocity = (event.velocity * 127.0).round().clamp(1.0, 127.0) as u8;
+            Some(SynthCommand::NoteOn {
+                note: key(event.key)?,
+                velocity,
+            })
+        }
+        // the synth can't cut one note short, so a choked note is released like any other
+        sys::EVENT_NOTE_OFF | sys::EVENT_NOTE_CHOKE => {
+            let event = &*(event as *const sys::EventNote);
+            Some(SynthCommand::NoteOff {
+                note: key(event.key)?,
+            })
+        }
+        sys::EVENT_NOTE_EXPRESSION => {
+            let event = &*(event as *const sys::EventNoteExpression);
+            let value = event.value as f32;
+            let expression = match event.expression_id {
+                sys::NOTE_EXPRESSION_VOLUME => NoteExpression::Volume(value),
+                // from 0 (left) to 1 (right)
+                sys::NOTE_EXPRESSION_PAN => NoteExpression::Pan(value * 2.0 - 1.0),
+                sys::NOTE_EXPRESSION_TUNING => NoteExpression::Tuning(value),
+                _ => return None,
+            };
+            Some(SynthCommand::NoteExpression {
+                note: key(event.key)?,
+                expression,
+            })
+        }
+        sys::EVENT_PARAM_VALUE => {
+            let event = &*(event as *const sys::EventParamValue);
+            Some(SynthCommand::SetParam {
+                param: params().nth(event.param_id as usize)?,
+                value: event.value as f32,
+            })
+        }
+        sys::EVENT_MIDI => {
+            let event = &*(event as *const sys::EventMidi);
+            let (msg, _) = MidiMsg::from_midi(&event.data).ok()?;
+            SynthCommand::from_midi(&msg)
+        }
+        _ => None,
+    }
+}
+
+unsafe extern "C" fn audio_ports_count(_plugin: *const sys::Plugin, is_input: bool) -> u32 {
+    if is_input {
+        0
+    } else {
+        1
+    }
+}
+
+unsafe extern "C" fn audio_ports_get(
+    _plugin: *const sys::Plugin,
+    index: u32,
+    is_input: bool,
+    info: *mut sys::AudioPortInfo,
+) -> bool {
+    if is_input || index != 0 {
+        return false;
+    }
+    let info = &mut *info;
+    info.id = 0;
+    write_c_str(&mut info.name, "Output");
+    info.flags = sys::AUDIO_PORT_IS_MAIN;
+    info.channel_count = 2;
+    info.port_type = sys::PORT_STEREO.as_ptr() as *const c_char;
+    info.in_place_pair = sys::INVALID_ID;
+    true
+}
+
+unsafe extern "C" fn note_ports_count(_plugin: *const sys::Plugin, is_input: bool) -> u32 {
+    if is_input {
+        1
+    } else {
+        0
+    }
+}
+
+unsafe extern "C" fn note_ports_get(
+    _plugin: *const sys::Plugin,
+    index: u32,
+    is_input: bool,
+    info: *mut sys::NotePortInfo,
+) -> bool {
+    if !is_input || index != 0 {
+        return false;
+    }
+    let info = &mut *info;
+    info.id = 0;
+    info.supported_dialects = sys::NOTE_DIALECT_CLAP | sys::NOTE_DIALECT_MIDI;
+    info.preferred_dialect = sys::NOTE_DIALECT_CLAP;
+    write_c_str(&mut info.name, "Notes");
+    true
+}
+
+unsafe extern "C" fn params_count(_plugin: *const sys::Plugin) -> u32 {
+    params().count() as u32
+}
+
+unsafe extern "C" fn params_get_info(
+    _plugin: *const sys::Plugin,
+    param_index: u32,
+    info: *mut sys::ParamInfo,
+) -> bool {
+    let (param, param_info) = match params().nth(param_index as usize) {
+        Some(param) => (param, param.info()),
+        None => return false,
+    };
+    let param_info = match param_info {
+        Some(param_info) => param_info,
+        None => return false,
+    };
+    let info = &mut *info;
+    info.id = param_index;
+    info.flags = sys::PARAM_IS_AUTOMATABLE;
+    if param_info.curve == Curve::Stepped {
+        info.flags |= sys::PARAM_IS_STEPPED;
+    }
+    info.cookie = ptr::null_mut();
+    // as in the text form of commands, which tells the sends apart
+    write_c_str(&mut info.name, &param.to_string());
+    write_c_str(&mut info.module, "");
+    info.min_value = param_info.min as f64;
+    info.max_value = param_info.max as f64;
+    info.default_value = param_info.default as f64;
+    true
+}
+
+unsafe extern "C" fn params_get_value(
+    plugin: *const sys::Plugin,
+    param_id: u32,
+    out_value: *mut f64,
+) -> bool {
+    match Instance::from_raw(plugin).values.get(param_id as usize) {
+        Some(value) => {
+            *out_value = f32::from_bits(value.load(Ordering::Relaxed)) as f64;
+            true
+        }
+        None => false,
+    }
+}
+
+unsafe extern "C" fn params_value_to_text(
+    _plugin: *const sys::Plugin,
+    param_id: u32,
+    value: f64,
+    out_buffer: *mut c_char,
+    out_buffer_capacity: u32,
+) -> bool {
+    let param = match params().nth(param_id as usize) {
+        Some(param) => param,
+        None => return false,
+    };
+    let text = match (param, param.info()) {
+        (ParamId::Waveform, _) => match Waveform::ALL.get(value.round() as usize) {
+            Some(waveform) => waveform.name().to_string(),
+            None => return false,
+        },
+        (_, Some(info)) => format!("{:.2} {}", value, info.unit.symbol()),
+        (_, None) => return false,
+    };
+    let out = slice::from_raw_parts_mut(out_buffer, out_buffer_capacity as usize);
+    write_c_str(out, text.trim_end());
+    true
+}
+
+unsafe extern "C" fn params_text_to_value(
+    _plugin: *const sys::Plugin,
+    param_id: u32,
+    param_value_text: *const c_char,
+    out_value: *mut f64,
+) -> bool {
+    let param = params().nth(param_id as usize);
+    let text = match CStr::from_ptr(param_value_text).to_str() {
+        Ok(text) => text.trim(),
+        Err(_) => return false,
+    };
+    let waveform = Waveform::from_name(text)
+        .filter(|_| param == Some(ParamId::Waveform))
+        .and_then(|waveform| Waveform::ALL.iter().position(|w| *w == waveform));
+    // anything after the number is taken to be the unit
+    let number = text.split_whitespace().next().and_then(|n| n.parse().ok());
+    match waveform.map(|index| index as f64).or(number) {
+        Some(value) if param.is_some() => {
+            *out_value = value;
+            true
+        }
+        _ => false,
+    }
+}
+
+unsafe extern "C" fn params_flush(
+    plugin: *const sys::Plugin,
+    in_events: *const sys::InputEvents,
+    _out_events: *const sys::OutputEvents,
+) {
+    let mut synth = Instance::from_raw(plugin).synth.lock().unwrap();
+    read_events(in_events, |_, command| {
+        let _ = synth.apply(command);
+    });
+}
+
+unsafe extern "C" fn state_save(plugin: *const sys::Plugin, stream: *const sys::OStream) -> bool {
+    let patch = Instance::from_raw(plugin)
+        .synth
+        .lock()
+        .unwrap()
+        .save_patch();
+    let mut text = Vec::new();
+    if patch.write(&mut text).is_err() {
+        return false;
+    }
+    let stream = &*stream;
+    let mut written = 0;
+    while written < text.len() {
+        let rest = &text[written..];
+        let count = (stream.write)(stream, rest.as_ptr() as *const c_void, rest.len() as u64);
+        if count <= 0 {
+            return false;
+        }
+        written += count as usize;
+    }
+    true
+}
+
+unsafe extern "C" fn state_load(plugin: *const sys::Plugin, stream: *const sys::IStream) -> bool {
+    let instance = Instance::from_raw(plugin);
+    let stream = &*stream;
+    let mut text = Vec::new();
+    let mut buffer = [0u8; 4096];
+    loop {
+        let count = (stream.read)(
+            stream,
+            buffer.as_mut_ptr() as *mut c_void,
+            buffer.len() as u64,
+        );
+        match count {
+            0 => break,
+            count if count < 0 => return false,
+            count => text.extend_from_slice(&buffer[..count as usize]),
+        }
+    }
+    let patch = match Patch::read(&text[..]) {
+        Ok(patch) => patch,
+        Err(e) => {
+            log::error!("Could not load the saved patch: {}", e);
+            return false;
+        }
+    };
+    instance.synth.lock().unwrap().load_patch(&patch);
+    instance.rescan_values();
+    true
+}
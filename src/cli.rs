@@ -0,0 +1,469 @@
+use std::{env, path::PathBuf, process, time::Duration};
+
+use log::LevelFilter;
+
+use basic_synth::{
+    effects::{self, Placement},
+    generate::GeneratorSettings,
+    random::Category,
+    send::SENDS,
+    sequencer::SequencerClock,
+    wav::SampleFormat,
+    Oversampling,
+};
+
+pub mod alloc_guard;
+pub mod audio;
+pub mod bench;
+pub mod logger;
+pub mod monitor;
+pub mod priority;
+pub mod record;
+pub mod remote;
+#[cfg(feature = "rtp-midi")]
+pub mod rtp_midi;
+pub mod stdin;
+#[cfg(feature = "tui")]
+pub mod tui;
+pub mod watch;
+
+/// Where presets are looked for, unless told otherwise.
+const DEFAULT_PRESET_DIR: &str = "presets";
+
+const USAGE: &str = "\
+Usage: basic-synth-cli [OPTIONS]
+
+Options:
+    --monitor        Print incoming MIDI messages as they arrive
+    --record <FILE>  Save all incoming MIDI to a Standard MIDI File on exit
+    --replay <FILE>  Play back a Standard MIDI File instead of listening for MIDI input
+    --render <FILE>  Render the file given with --replay to a WAV file, faster than real time
+    --bit-depth <BITS>
+                     Sample format to render in: 16 or 24 (dithered integers) or 32 (float,
+                     the default)
+    --sample-rate <HZ>
+                     Sample rate to run the synth at, resampling to suit the audio device if
+                     need be (default: the device's own, or 48000 when rendering)
+    --stdin <FORMAT> Read MIDI from stdin instead of a MIDI port, as `raw` bytes or `text`
+                     commands (one per line: on NOTE [VEL], off NOTE, cc NUM VAL, bend AMT,
+                     program NUM, wait MILLISECONDS)
+    --rtp-midi <ADDR>
+                     Take MIDI from the network instead of a MIDI port, as an AppleMIDI
+                     (RTP-MIDI) session at this address and the port after it (e.g.
+                     0.0.0.0:5004), for senders to add by address (if enabled)
+    --backend <NAME> Audio output library: `cpal` (default), `rodio` or `jack` (if enabled)
+    --audio-device <NAME>
+                     Audio output device to play through, or `ask` to choose from a list
+                     (default: the system default; ignored by the JACK backend)
+    --input <DEVICE> Play audio from an input device (or `default`) through the voices instead
+                     of their oscillators, gated and shaped by the notes played (not with JACK)
+    --vocoder        Use the audio from --input to modulate the voices through a vocoder,
+                     instead of playing it through them
+    --gate <DB>      Silence the audio from --input whenever it's quieter than this, so it
+                     doesn't hiss between phrases (e.g. -50)
+    --preset <NAME>  Start with a preset, built in or from the preset directory, by name (or
+                     CATEGORY/NAME)
+    --random <CATEGORY>
+                     Start with a random patch, printed out so it can be saved: `bass`, `fx`,
+                     `keys`, `lead`, `pad` or `any`
+    --morph <NAME>   Morph from the --preset (or the default sound) to this preset with the
+                     general purpose controller 1 (CC 16)
+    --preset-dir <DIR>
+                     Where to find more presets, as .patch files or in subdirectories by
+                     category (default: presets), and to save them: type a name while playing,
+                     or press a button sending general purpose controller 5 (CC 80)
+    --tui            Show a terminal UI while playing, with the voices playing, the output
+                     levels and the preset, and the parameters to change from the keyboard
+                     (if enabled)
+    --pattern <FILE> Play a pattern on the step sequencer, one step per line: NOTE [VEL [GATE]]
+                     or `rest`, then `tie` to hold it into the next step and `cutoff=HZ` to
+                     lock the filter (e.g. `60 100 0.5 cutoff=800`); Space in the terminal UI
+                     starts and stops it
+    --pattern-clock <SOURCE>
+                     What times the sequencer's steps: `internal` (the default, at the tempo)
+                     or `midi` clock, waiting for MIDI Start
+    --generate <SETTINGS>
+                     Play random notes by itself, with nothing attached: `on` for the
+                     defaults, or any of density=0-1, scale=NAME, root=NOTE, octaves=1-4,
+                     grid=STEPS-PER-BEAT and seed=N, separated by commas (e.g.
+                     `scale=dorian,density=0.5`); scales are `chromatic`, `major`, `minor`,
+                     `dorian`, `pentatonic` and `minor-pentatonic` (the default)
+    --latch          Keep notes sounding after their keys are let go, until the next chord;
+                     `l` in the terminal UI turns it on and off
+    --watch          Reload the preset whenever its file is saved, to hear edits straight away
+    --benchmark      Find how many voices can play at once with the other options given (e.g.
+                     --oversampling, --effect), by rendering more and more of them, and exit
+    --list-presets   Print the available presets, numbered for Program Change, and exit
+    --lv2-bundle <DIR>
+                     Write the files describing the LV2 plugin into a bundle directory, for
+                     the library to be copied into, and exit (if enabled)
+    --effect <NAME>  Add an effect to the master chain, in the order given (may be repeated):
+                     `autopan`, `bitcrusher`, `chorus`, `compressor`, `delay`, `distortion`,
+                     `eq`, `gate`, `phaser`, `pitch`, `reverb`, `rotary`, `tape`,
+                     `tremolo`, `wavefolder` or `widener`
+    --voice-effect <NAME>
+                     Add an effect (as for --effect) to every voice, before they're mixed
+    --send <NAME>    Put an effect (as for --effect) on a send bus, fully wet, shared by all
+                     the voices (may be given twice, for two buses)
+    --oversampling <RATIO>
+                     Internal oversampling: 1 (off), 2, 4 (default) or 8
+    --block-size <FRAMES>
+                     Samples rendered at a time (default: 1/100th of a second)
+    --queue-blocks <COUNT>
+                     Blocks rendered ahead of the audio device (except with JACK). Fewer
+                     means lower latency, but more risk of underruns (default: 4)
+    --note-timeout <SECONDS>
+                     Release notes held for longer than this, or when MIDI input goes away
+    --seed <NUMBER>  Start the oscillators (and pick the --random patch) from this seed, so that
+                     the same input always sounds exactly the same, e.g. when rendering
+    --remote <ADDR>  Listen for remote control over WebSocket at this address (e.g.
+                     127.0.0.1:9000), with JSON messages to list and set parameters, load
+                     presets, play notes and follow the levels. Browsers can only connect
+                     from pages served from this machine, unless allowed with --allow-origin
+    --allow-origin <ORIGIN>
+                     Let pages from this origin (e.g. https://example.com) use --remote too (may
+                     be given more than once)
+    --profile        Report how much CPU time the oscillators, filters, envelopes and effects
+                     take, every few seconds or after rendering (if enabled; not with JACK)
+    --log-level <LEVEL>
+                     Which messages to print: `off`, `error`, `warn`, `info` (default),
+                     `debug` or `trace` (the last two also from the audio thread, which can
+                     cause dropouts)
+    -h, --help       Show this message and exit";
+
+/// Options for the command line frontend.
+#[derive(Debug, Clone)]
+pub struct Options {
+    pub monitor: bool,
+    pub record: Option<PathBuf>,
+    pub replay: Option<PathBuf>,
+    pub render: Option<PathBuf>,
+    pub bit_depth: SampleFormat,
+    pub sample_rate: Option<u32>,
+    pub stdin: Option<stdin::Format>,
+    #[cfg(feature = "rtp-midi")]
+    pub rtp_midi: Option<String>,
+    pub backend: audio::Backend,
+    pub audio_device: Option<String>,
+    pub input: Option<String>,
+    pub vocoder: bool,
+    pub gate: Option<f32>,
+    pub preset: Option<String>,
+    pub morph: Option<String>,
+    pub random: Option<Option<Category>>,
+    pub preset_dir: PathBuf,
+    pub list_presets: bool,
+    #[cfg(feature = "lv2")]
+    pub lv2_bundle: Option<PathBuf>,
+    pub benchmark: bool,
+    pub tui: bool,
+    pub watch: bool,
+    pub pattern: Option<PathBuf>,
+    pub pattern_clock: SequencerClock,
+    pub latch: bool,
+    pub generate: Option<GeneratorSettings>,
+    pub effects: Vec<(String, Placement)>,
+    pub sends: Vec<String>,
+    pub oversampling: Oversampling,
+    pub block_size: Option<usize>,
+    pub queue_blocks: usize,
+    pub note_timeout: Option<Duration>,
+    pub seed: Option<u32>,
+    pub remote: Option<String>,
+    pub allowed_origins: Vec<String>,
+    #[cfg(feature = "profile")]
+    pub profile: bool,
+    pub log_level: LevelFilter,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            monitor: false,
+            record: None,
+            replay: None,
+            render: None,
+            bit_depth: Default::default(),
+            sample_rate: None,
+            stdin: None,
+            #[cfg(feature = "rtp-midi")]
+            rtp_midi: None,
+            backend: Default::default(),
+            audio_device: None,
+            input: None,
+            vocoder: false,
+            gate: None,
+            preset: None,
+            morph: None,
+            random: None,
+            preset_dir: DEFAULT_PRESET_DIR.into(),
+            list_presets: false,
+            #[cfg(feature = "lv2")]
+            lv2_bundle: None,
+            benchmark: false,
+            tui: false,
+            watch: false,
+            pattern: None,
+            pattern_clock: SequencerClock::Internal,
+            latch: false,
+            generate: None,
+            effects: Vec::new(),
+            sends: Vec::new(),
+            oversampling: Default::default(),
+            block_size: None,
+            queue_blocks: audio::DEFAULT_QUEUE_BLOCKS,
+            note_timeout: None,
+            seed: None,
+            remote: None,
+            allowed_origins: Vec::new(),
+            #[cfg(feature = "profile")]
+            profile: false,
+            log_level: LevelFilter::Info,
+        }
+    }
+}
+
+impl Options {
+    /// Read options from the process arguments, exiting with a usage message if they are invalid.
+    pub fn from_env() -> Self {
+        match Self::parse(env::args().skip(1)) {
+            Ok(opts) => opts,
+            Err(None) => {
+                println!("{}", USAGE);
+                process::exit(0);
+            }
+            Err(Some(msg)) => {
+                eprintln!("{}\n\n{}", msg, USAGE);
+                process::exit(2);
+            }
+        }
+    }
+
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, Option<String>> {
+        let mut opts = Self::default();
+
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| Some(format!("Missing value for argument: {}", arg)))
+            };
+
+            match arg.as_str() {
+                "--monitor" => opts.monitor = true,
+                "--record" => opts.record = Some(value()?.into()),
+                "--replay" => opts.replay = Some(value()?.into()),
+                "--render" => opts.render = Some(value()?.into()),
+                "--bit-depth" => {
+                    let bits = value()?;
+                    opts.bit_depth = bits
+                        .parse()
+                        .ok()
+                        .and_then(SampleFormat::from_bits)
+                        .ok_or_else(|| Some(format!("Invalid bit depth: {}", bits)))?;
+                }
+                "--sample-rate" => {
+                    let rate = value()?;
+                    opts.sample_rate = Some(
+                        rate.parse()
+                            .ok()
+                            .filter(|r| *r > 0)
+                            .ok_or_else(|| Some(format!("Invalid sample rate: {}", rate)))?,
+                    );
+                }
+                "--stdin" => opts.stdin = Some(value()?.parse()?),
+                #[cfg(feature = "rtp-midi")]
+                "--rtp-midi" => opts.rtp_midi = Some(value()?),
+                #[cfg(not(feature = "rtp-midi"))]
+                "--rtp-midi" => {
+                    return Err(Some(
+                        "Network MIDI was not enabled at build time".to_string(),
+                    ))
+                }
+                "--backend" => opts.backend = value()?.parse()?,
+                "--audio-device" => opts.audio_device = Some(value()?),
+                "--input" => opts.input = Some(value()?),
+                "--vocoder" => opts.vocoder = true,
+                "--gate" => {
+                    let threshold = value()?;
+                    opts.gate = Some(
+                        threshold
+                            .parse()
+                            .ok()
+                            .filter(|t: &f32| *t <= 0.0)
+                            .ok_or_else(|| {
+                                Some(format!("Invalid gate threshold: {}", threshold))
+                            })?,
+                    );
+                }
+                "--preset" => opts.preset = Some(value()?),
+                "--morph" => opts.morph = Some(value()?),
+                "--random" => {
+                    let name = value()?;
+                    opts.random = match name.as_str() {
+                        "any" => Some(None),
+                        _ => {
+                            Some(Some(Category::from_name(&name).ok_or_else(|| {
+                                Some(format!("Unknown category: {}", name))
+                            })?))
+                        }
+                    };
+                }
+                "--preset-dir" => opts.preset_dir = value()?.into(),
+                "--list-presets" => opts.list_presets = true,
+                #[cfg(feature = "lv2")]
+                "--lv2-bundle" => opts.lv2_bundle = Some(value()?.into()),
+                #[cfg(not(feature = "lv2"))]
+                "--lv2-bundle" => {
+                    return Err(Some("LV2 was not enabled at build time".to_string()))
+                }
+                "--benchmark" => opts.benchmark = true,
+                #[cfg(feature = "tui")]
+                "--tui" => opts.tui = true,
+                #[cfg(not(feature = "tui"))]
+                "--tui" => {
+                    return Err(Some(
+                        "The terminal UI was not enabled at build time".to_string(),
+                    ))
+                }
+                "--watch" => opts.watch = true,
+                "--latch" => opts.latch = true,
+                "--generate" => {
+                    let settings = value()?;
+                    opts.generate = Some(match settings.as_str() {
+                        "on" => GeneratorSettings::default(),
+                        _ => settings.parse()?,
+                    });
+                }
+                "--pattern" => opts.pattern = Some(value()?.into()),
+                "--pattern-clock" => {
+                    let source = value()?;
+                    opts.pattern_clock = match source.as_str() {
+                        "internal" => SequencerClock::Internal,
+                        "midi" => SequencerClock::Midi,
+                        _ => return Err(Some(format!("Unknown clock source: {}", source))),
+                    };
+                }
+                "--effect" | "--voice-effect" => {
+                    let name = value()?;
+                    if !effects::NAMES.contains(&name.as_str()) {
+                        return Err(Some(format!("Unknown effect: {}", name)));
+                    }
+                    let placement = if arg == "--effect" {
+                        Placement::Master
+                    } else {
+                        Placement::Voice
+                    };
+                    opts.effects.push((name, placement));
+                }
+                "--send" => {
+                    let name = value()?;
+                    if !effects::NAMES.contains(&name.as_str()) {
+                        return Err(Some(format!("Unknown effect: {}", name)));
+                    }
+                    if opts.sends.len() == SENDS {
+                        return Err(Some(format!("At most {} send effects are allowed", SENDS)));
+                    }
+                    opts.sends.push(name);
+                }
+                "--oversampling" => {
+                    let ratio = value()?;
+                    opts.oversampling = ratio
+                        .parse()
+                        .ok()
+                        .and_then(Oversampling::from_ratio)
+                        .ok_or_else(|| Some(format!("Invalid oversampling ratio: {}", ratio)))?;
+                }
+                "--block-size" => {
+                    let size = value()?;
+                    opts.block_size = Some(
+                        size.parse()
+                            .ok()
+                            .filter(|s| *s > 0)
+                            .ok_or_else(|| Some(format!("Invalid block size: {}", size)))?,
+                    );
+                }
+                "--queue-blocks" => {
+                    let count = value()?;
+                    opts.queue_blocks = count
+                        .parse()
+                        .ok()
+                        .filter(|c| *c > 0)
+                        .ok_or_else(|| Some(format!("Invalid queue length: {}", count)))?;
+                }
+                "--note-timeout" => {
+                    let secs = value()?;
+                    let secs = secs
+                        .parse::<f32>()
+                        .ok()
+                        .filter(|s| *s > 0.0)
+                        .ok_or_else(|| Some(format!("Invalid note timeout: {}", secs)))?;
+                    opts.note_timeout = Some(Duration::from_secs_f32(secs));
+                }
+                "--seed" => {
+                    let seed = value()?;
+                    opts.seed = Some(
+                        seed.parse()
+                            .map_err(|_| Some(format!("Invalid seed: {}", seed)))?,
+                    );
+                }
+                "--remote" => opts.remote = Some(value()?),
+                "--allow-origin" => opts.allowed_origins.push(value()?),
+                #[cfg(feature = "profile")]
+                "--profile" => opts.profile = true,
+                #[cfg(not(feature = "profile"))]
+                "--profile" => {
+                    return Err(Some("Profiling was not enabled at build time".to_string()))
+                }
+                "--log-level" => {
+                    let level = value()?;
+                    opts.log_level = level
+                        .parse()
+                        .map_err(|_| Some(format!("Invalid log level: {}", level)))?;
+                }
+                "-h" | "--help" => return Err(None),
+                other => return Err(Some(format!("Unrecognized argument: {}", other))),
+            }
+        }
+
+        if opts.vocoder && opts.input.is_none() {
+            return Err(Some(
+                "The vocoder needs an input device, given with --input".to_string(),
+            ));
+        }
+        if !opts.allowed_origins.is_empty() && opts.remote.is_none() {
+            return Err(Some(
+                "Allowed origins are for remote control, given with --remote".to_string(),
+            ));
+        }
+        if opts.gate.is_some() && opts.input.is_none() {
+            return Err(Some(
+                "The gate needs an input device, given with --input".to_string(),
+            ));
+        }
+
+        if opts.tui && (opts.stdin.is_some() || opts.monitor) {
+            return Err(Some(
+                "The terminal UI can't be used with --stdin or --monitor, which need the \
+                 terminal for themselves"
+                    .to_string(),
+            ));
+        }
+
+        #[cfg(feature = "rtp-midi")]
+        if opts.rtp_midi.is_some() && (opts.stdin.is_some() || opts.replay.is_some()) {
+            return Err(Some(
+                "Network MIDI can't be used with --stdin or --replay, which take the place of \
+                 MIDI input"
+                    .to_string(),
+            ));
+        }
+
+        if opts.preset.is_some() && opts.random.is_some() {
+            return Err(Some(
+                "Only one of --preset and --random can be given".to_string(),
+            ));
+        }
+
+        Ok(opts)
+    }
+}
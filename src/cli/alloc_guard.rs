@@ -0,0 +1,77 @@
+//! Catching allocations where there mustn't be any: on the audio thread, where waiting on the
+//! allocator can make the output drop out.
+//!
+//! In debug builds, every allocation goes through a wrapper around the system allocator, which
+//! aborts with a message if the current thread is inside `real_time`. Release builds use the system
+//! allocator directly, and `real_time` does nothing.
+
+#[cfg(debug_assertions)]
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    cell::Cell,
+    io::{stderr, Write},
+    process,
+};
+
+/// Run `f`, which must not allocate or free any memory (checked in debug builds).
+#[cfg(debug_assertions)]
+pub fn real_time<R>(f: impl FnOnce() -> R) -> R {
+    let outer = REAL_TIME.with(|r| r.replace(true));
+    let result = f();
+    REAL_TIME.with(|r| r.set(outer));
+    result
+}
+
+#[cfg(not(debug_assertions))]
+pub fn real_time<R>(f: impl FnOnce() -> R) -> R {
+    f()
+}
+
+#[cfg(debug_assertions)]
+thread_local! {
+    /// Whether the thread is somewhere allocating isn't allowed.
+    static REAL_TIME: Cell<bool> = const { Cell::new(false) };
+}
+
+#[cfg(debug_assertions)]
+#[global_allocator]
+static ALLOCATOR: Guard = Guard;
+
+#[cfg(debug_assertions)]
+struct Guard;
+
+#[cfg(debug_assertions)]
+impl Guard {
+    fn check(&self, what: &str) {
+        // `try_with`, as the thread local may already be gone while a thread shuts down
+        if REAL_TIME.try_with(|r| r.replace(false)) == Ok(true) {
+            // unwinding out of the allocator isn't allowed, so no panicking
+            let _ = writeln!(stderr(), "Memory was {} on the audio thread", what);
+            process::abort();
+        }
+    }
+}
+
+// SAFETY: everything is passed straight on to the system allocator
+#[cfg(debug_assertions)]
+unsafe impl GlobalAlloc for Guard {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        self.check("allocated");
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        self.check("freed");
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        self.check("allocated");
+        System.alloc_zeroed(layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        self.check("reallocated");
+        System.realloc(ptr, layout, new_size)
+    }
+}
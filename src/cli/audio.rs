@@ -0,0 +1,669 @@
+mod input;
+#[cfg(feature = "jack")]
+mod jack;
+
+use std::{
+    array, fmt, mem,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering},
+        Arc, Condvar, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use {
+    super::{
+        alloc_guard::real_time,
+        priority::{self, Priority},
+        Options,
+    },
+    basic_synth::{
+        backend::{AudioBackend, CpalBackend, RodioBackend},
+        frame::Frame,
+        param::ParamId,
+        patch::Patch,
+        prepare::{PatchPreparer, PreparedPatch},
+        resample::Resampler,
+        ring::{ring_buffer, Consumer, Producer},
+        sequencer::{Pattern, SequencerCommand},
+        Levels, Synth, SynthCommand, VoiceSource, CHANNELS,
+    },
+    midi_msg::MidiMsg,
+};
+
+/// How many blocks to keep queued up ahead of the audio device, unless told otherwise.
+pub const DEFAULT_QUEUE_BLOCKS: usize = 4;
+
+/// How many commands can be waiting for the synth thread at once.
+const COMMAND_QUEUE_LEN: usize = 1024;
+
+/// How long to keep going after a fade out finishes, for the silence to push the end of the fade
+/// out of the device's own buffer.
+const FLUSH_TIME: Duration = Duration::from_millis(50);
+
+/// How often to check whether the synth thread has finished shutting down, or answered a request.
+const SHUTDOWN_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// How long to wait for the synth to be made, before anything can be got ready for it.
+const SETUP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long to wait for the synth's current settings, to glide from them.
+const SNAPSHOT_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// How many of the forms the synth's settings were last in to keep, for it to save them into.
+const SAVED_FORMS: usize = 4;
+
+/// How often to report new underruns.
+const UNDERRUN_REPORT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How often to report where the CPU time went, with `--profile`.
+#[cfg(feature = "profile")]
+const PROFILE_REPORT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Which library is used to talk to the audio device.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Backend {
+    /// Talks to the device directly through `cpal`.
+    #[default]
+    Cpal,
+    /// Goes through a `rodio` sink.
+    Rodio,
+    /// Runs as a JACK client, with its own MIDI input port.
+    #[cfg(feature = "jack")]
+    Jack,
+}
+
+impl FromStr for Backend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "cpal" => Ok(Self::Cpal),
+            "rodio" => Ok(Self::Rodio),
+            #[cfg(feature = "jack")]
+            "jack" => Ok(Self::Jack),
+            #[cfg(not(feature = "jack"))]
+            "jack" => Err("JACK support was not enabled at build time".to_string()),
+            other => Err(format!("Unknown audio backend: {}", other)),
+        }
+    }
+}
+
+/// The sending end of the queue of commands for the synth thread. The synth thread itself never
+/// waits on the lock, which is only there so that several other threads can share this end.
+///
+/// Anything which would allocate on the synth thread, such as making a patch's effects, is done
+/// here instead, by whichever thread is sending.
+#[derive(Clone)]
+pub struct CommandSender {
+    queue: Arc<Mutex<Queue>>,
+    setup: Arc<(Mutex<Option<Setup>>, Condvar)>,
+    snapshot: Arc<Snapshot>,
+    status: Arc<Status>,
+    finished: Arc<AtomicBool>,
+}
+
+/// What goes to the synth thread: commands, and things got ready for it to take without
+/// allocating. It sends the things back once it's done with them, to be freed at this end.
+enum Queued {
+    Command(SynthCommand),
+    Patch(Box<PreparedPatch>),
+    Pattern(Box<Pattern>),
+}
+
+impl fmt::Display for Queued {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Queued::Command(command) => command.fmt(f),
+            Queued::Patch(_) => f.write_str("patch"),
+            Queued::Pattern(_) => f.write_str("pattern"),
+        }
+    }
+}
+
+struct Queue {
+    /// Everything sent, with when it was sent.
+    producer: Producer<(Instant, Queued)>,
+    /// Whatever the synth thread has finished with, to free.
+    returned: Consumer<Queued>,
+}
+
+/// What's needed from the synth to get things ready for it, once it's been made.
+struct Setup {
+    preparer: PatchPreparer,
+    /// Patches with the effects the synth has most recently had, every parameter given, for the
+    /// synth thread to save its settings into without allocating. Newest first.
+    forms: Vec<Patch>,
+}
+
+/// A copy of the synth's current settings, which the synth thread takes when asked.
+#[derive(Default)]
+struct Snapshot {
+    wanted: AtomicBool,
+    /// Patches for the synth thread to save into, and which of them it managed to.
+    slot: Mutex<(Vec<Patch>, Option<usize>)>,
+}
+
+/// What the synth thread last reported about the synth, for other threads to show: its output
+/// levels, how many voices are playing and the values of its fixed parameters, updated at the
+/// start of every block.
+pub struct Status {
+    active_voices: AtomicUsize,
+    /// Whether the sequencer is playing, and its step and pattern length.
+    sequencer_running: AtomicBool,
+    sequencer_position: AtomicUsize,
+    sequencer_len: AtomicUsize,
+    latch: AtomicBool,
+    peak: [AtomicU32; CHANNELS],
+    rms: [AtomicU32; CHANNELS],
+    clipped: AtomicBool,
+    /// How many blocks have clipped so far, so that a clip isn't missed between looks.
+    clipped_blocks: AtomicUsize,
+    /// The values of `ParamId::fixed`, in order, as `f32` bits.
+    params: Vec<AtomicU32>,
+}
+
+impl Default for Status {
+    fn default() -> Self {
+        Self {
+            active_voices: AtomicUsize::new(0),
+            sequencer_running: AtomicBool::new(false),
+            sequencer_position: AtomicUsize::new(0),
+            sequencer_len: AtomicUsize::new(0),
+            latch: AtomicBool::new(false),
+            peak: Default::default(),
+            rms: Default::default(),
+            clipped: AtomicBool::new(false),
+            clipped_blocks: AtomicUsize::new(0),
+            params: ParamId::fixed().map(|_| AtomicU32::new(0)).collect(),
+        }
+    }
+}
+
+impl Status {
+    fn publish(&self, synth: &Synth) {
+        self.active_voices
+            .store(synth.active_voice_count(), Ordering::Relaxed);
+        let sequencer = synth.sequencer();
+        self.sequencer_running
+            .store(sequencer.is_running(), Ordering::Relaxed);
+        self.sequencer_position
+            .store(sequencer.position(), Ordering::Relaxed);
+        self.sequencer_len
+            .store(sequencer.pattern().len(), Ordering::Relaxed);
+        self.latch.store(synth.latch(), Ordering::Relaxed);
+        let levels = synth.levels();
+        for channel in 0..CHANNELS {
+            self.peak[channel].store(levels.peak[channel].to_bits(), Ordering::Relaxed);
+            self.rms[channel].store(levels.rms[channel].to_bits(), Ordering::Relaxed);
+        }
+        self.clipped.store(levels.clipped, Ordering::Relaxed);
+        if levels.clipped {
+            self.clipped_blocks.fetch_add(1, Ordering::Relaxed);
+        }
+        for (param, value) in ParamId::fixed().zip(&self.params) {
+            let current = synth.get_param(param).unwrap_or_default();
+            value.store(current.to_bits(), Ordering::Relaxed);
+        }
+    }
+
+    /// The output levels over the last block rendered.
+    pub fn levels(&self) -> Levels {
+        let load = |values: &[AtomicU32; CHANNELS]| {
+            Frame(array::from_fn(|channel| {
+                f32::from_bits(values[channel].load(Ordering::Relaxed))
+            }))
+        };
+        Levels {
+            peak: load(&self.peak),
+            rms: load(&self.rms),
+            clipped: self.clipped.load(Ordering::Relaxed),
+        }
+    }
+
+    /// How many voices were making sound.
+    #[cfg(feature = "tui")]
+    pub fn active_voices(&self) -> usize {
+        self.active_voices.load(Ordering::Relaxed)
+    }
+
+    /// Whether notes are being latched.
+    #[cfg(feature = "tui")]
+    pub fn latch(&self) -> bool {
+        self.latch.load(Ordering::Relaxed)
+    }
+
+    /// Whether the sequencer is playing, which step it's at, and how many steps it has.
+    #[cfg(feature = "tui")]
+    pub fn sequencer(&self) -> (bool, usize, usize) {
+        (
+            self.sequencer_running.load(Ordering::Relaxed),
+            self.sequencer_position.load(Ordering::Relaxed),
+            self.sequencer_len.load(Ordering::Relaxed),
+        )
+    }
+
+    /// How many blocks have clipped since the synth started.
+    pub fn clipped_blocks(&self) -> usize {
+        self.clipped_blocks.load(Ordering::Relaxed)
+    }
+
+    /// The value of one of the fixed parameters, or `None` for any other.
+    pub fn param(&self, param: ParamId) -> Option<f32> {
+        let index = ParamId::fixed().position(|p| p == param)?;
+        Some(f32::from_bits(self.params[index].load(Ordering::Relaxed)))
+    }
+}
+
+impl CommandSender {
+    /// Queue up whatever the synth should do in response to `msg`, if anything.
+    pub fn send_midi(&self, msg: &MidiMsg) {
+        if let Some(command) = SynthCommand::from_midi(msg) {
+            self.send(command);
+        }
+    }
+
+    /// Queue up a command for the synth.
+    pub fn send(&self, command: SynthCommand) {
+        match command {
+            SynthCommand::LoadPatch(patch) => self.send_patch(*patch),
+            SynthCommand::GlideToPatch { patch, time } => self.glide_patch(*patch, time),
+            SynthCommand::Sequencer(SequencerCommand::LoadPattern(pattern)) => {
+                self.push(Queued::Pattern(pattern))
+            }
+            command => self.push(Queued::Command(command)),
+        }
+    }
+
+    fn push(&self, queued: Queued) {
+        let mut queue = self.queue.lock().unwrap();
+        while queue.returned.pop().is_some() {}
+        if let Err((_, queued)) = queue.producer.push((Instant::now(), queued)) {
+            log::warn!("Synth thread is not keeping up, dropped {}", queued);
+        }
+    }
+
+    /// Have the synth switch to `patch` at the start of its next block.
+    pub fn send_patch(&self, patch: Patch) {
+        if let Some(preparer) = self.prepare(&patch) {
+            self.push(Queued::Patch(Box::new(preparer.load(patch))));
+        }
+    }
+
+    /// Have the synth move gradually to `patch` over `time`, starting at its next block.
+    pub fn glide_patch(&self, patch: Patch, time: Duration) {
+        if time.is_zero() {
+            return self.send_patch(patch);
+        }
+        let from = match self.request_patch(SNAPSHOT_TIMEOUT) {
+            Some(from) => from,
+            None => {
+                log::warn!("Could not glide from the synth's settings, switching instead");
+                return self.send_patch(patch);
+            }
+        };
+        if let Some(preparer) = self.prepare(&patch) {
+            self.push(Queued::Patch(Box::new(preparer.glide(from, patch, time))));
+        }
+    }
+
+    /// Something to get `patch` ready for the synth with, noting the form its settings will be
+    /// in, or `None` if the synth never got made.
+    fn prepare(&self, patch: &Patch) -> Option<PatchPreparer> {
+        let preparer = self.with_setup(|setup| setup.preparer)?;
+        let form = preparer.saved(patch);
+        self.with_setup(|setup| {
+            setup.forms.retain(|f| !same_effects(f, &form));
+            setup.forms.insert(0, form);
+            setup.forms.truncate(SAVED_FORMS);
+        });
+        Some(preparer)
+    }
+
+    /// Wait for the synth to be made, then use what's needed to get things ready for it.
+    fn with_setup<R>(&self, f: impl FnOnce(&mut Setup) -> R) -> Option<R> {
+        let (setup, made) = &*self.setup;
+        let (mut setup, _) = made
+            .wait_timeout_while(setup.lock().unwrap(), SETUP_TIMEOUT, |s| s.is_none())
+            .unwrap();
+        if setup.is_none() {
+            log::warn!("The synth was never made, so there's nothing to send to");
+        }
+        setup.as_mut().map(f)
+    }
+
+    /// What the synth thread last reported about the synth.
+    pub fn status(&self) -> &Status {
+        &self.status
+    }
+
+    /// Ask the synth thread for its current settings, waiting up to `timeout` for them.
+    pub fn request_patch(&self, timeout: Duration) -> Option<Patch> {
+        let start = Instant::now();
+        while start.elapsed() < timeout {
+            let forms = self.with_setup(|setup| setup.forms.clone())?;
+            *self.snapshot.slot.lock().unwrap() = (forms, None);
+            self.snapshot.wanted.store(true, Ordering::Release);
+            while self.snapshot.wanted.load(Ordering::Acquire) && start.elapsed() < timeout {
+                thread::sleep(SHUTDOWN_POLL_INTERVAL);
+            }
+            // whatever the synth thread didn't save into is freed here
+            let (mut forms, saved) = mem::take(&mut *self.snapshot.slot.lock().unwrap());
+            if let Some(index) = saved {
+                return Some(forms.swap_remove(index));
+            }
+            // none fit, e.g. halfway through a glide, so try again once it's moved on
+            thread::sleep(SHUTDOWN_POLL_INTERVAL);
+        }
+        self.snapshot.wanted.store(false, Ordering::Release);
+        None
+    }
+
+    /// Fade the synth out over `fade`, and wait for that to make it all the way out of the
+    /// speakers, or for `timeout` if that's sooner (e.g. if the synth thread is stuck).
+    pub fn shutdown(&self, fade: Duration, timeout: Duration) {
+        self.send(SynthCommand::FadeOut(fade));
+        let start = Instant::now();
+        while !self.finished.load(Ordering::Acquire) && start.elapsed() < timeout {
+            thread::sleep(SHUTDOWN_POLL_INTERVAL);
+        }
+    }
+}
+
+/// The synth thread's end of the command queue.
+pub struct CommandReceiver {
+    consumer: Consumer<(Instant, Queued)>,
+    returned: Producer<Queued>,
+    setup: Arc<(Mutex<Option<Setup>>, Condvar)>,
+    snapshot: Arc<Snapshot>,
+    status: Arc<Status>,
+    finished: Arc<AtomicBool>,
+}
+
+impl CommandReceiver {
+    /// Let the other end know the synth has been made, so that it can get things ready for it.
+    /// Undo is turned off, as nothing here uses it, and saving for it allocates.
+    pub(super) fn attach(&self, synth: &mut Synth) {
+        synth.set_undo_enabled(false);
+        let preparer = synth.patch_preparer();
+        let mut forms = vec![synth.save_patch()];
+        if let Some((a, b)) = synth.morph_patches() {
+            forms.extend([preparer.saved(a), preparer.saved(b)]);
+        }
+        let (setup, made) = &*self.setup;
+        *setup.lock().unwrap() = Some(Setup { preparer, forms });
+        made.notify_all();
+    }
+
+    /// Schedule everything waiting in the queue, e.g. at the start of a block of `frames`, and
+    /// report the synth's status. None of this allocates.
+    ///
+    /// Each command lands that long after it was sent, so that commands sent while the last block
+    /// was playing are spread out over this one as they were sent, rather than all landing at its
+    /// start. Any which have waited longer are applied straight away, as are patches.
+    pub(super) fn drain(&mut self, synth: &mut Synth, frames: usize) {
+        if self.snapshot.wanted.load(Ordering::Acquire) {
+            // again, if the lock is busy, try next time
+            if let Ok(mut slot) = self.snapshot.slot.try_lock() {
+                let (forms, saved) = &mut *slot;
+                *saved = forms
+                    .iter_mut()
+                    .position(|form| synth.save_patch_into(form));
+                self.snapshot.wanted.store(false, Ordering::Release);
+            }
+        }
+        self.status.publish(synth);
+        let now = Instant::now();
+        let sample_rate = synth.sample_rate() as f64;
+        while let Some((sent, queued)) = self.consumer.pop() {
+            let command = match queued {
+                Queued::Command(command) => command,
+                Queued::Patch(mut prepared) => {
+                    synth.switch_patch(&mut prepared);
+                    self.give_back(Queued::Patch(prepared));
+                    continue;
+                }
+                Queued::Pattern(pattern) => {
+                    synth.load_pattern(&pattern);
+                    self.give_back(Queued::Pattern(pattern));
+                    continue;
+                }
+            };
+            let waited = (now.duration_since(sent).as_secs_f64() * sample_rate) as u64;
+            let frame = synth.clock() + (frames as u64).saturating_sub(waited);
+            // patches and patterns go separately, so cloning a command is only a copy
+            if synth.schedule(frame, command.clone()).is_err() {
+                log::warn!("Too many commands waiting, dropped {}", command);
+            }
+        }
+    }
+
+    /// Send something the synth has finished with back, to be freed on the other end.
+    fn give_back(&mut self, queued: Queued) {
+        // there's room for everything that can be in flight, so this can't fail, but leaking
+        // something would be better than freeing it here
+        if let Err(queued) = self.returned.push(queued) {
+            mem::forget(queued);
+        }
+    }
+
+    /// Let the other end know that a fade out has been heard in full.
+    pub(super) fn finish(&self) {
+        self.finished.store(true, Ordering::Release);
+    }
+}
+
+/// Create a bounded, lock-free queue of commands for the synth thread.
+pub fn command_queue() -> (CommandSender, CommandReceiver) {
+    let (producer, consumer) = ring_buffer(COMMAND_QUEUE_LEN);
+    // one more than can be queued, as the queue is emptied of what came back before each push
+    let (returned, returned_consumer) = ring_buffer(COMMAND_QUEUE_LEN + 1);
+    let setup = Arc::new((Mutex::new(None), Condvar::new()));
+    let snapshot = Arc::new(Snapshot::default());
+    let status = Arc::new(Status::default());
+    let finished = Arc::new(AtomicBool::new(false));
+    let sender = CommandSender {
+        queue: Arc::new(Mutex::new(Queue {
+            producer,
+            returned: returned_consumer,
+        })),
+        setup: setup.clone(),
+        snapshot: snapshot.clone(),
+        status: status.clone(),
+        finished: finished.clone(),
+    };
+    let receiver = CommandReceiver {
+        consumer,
+        returned,
+        setup,
+        snapshot,
+        status,
+        finished,
+    };
+    (sender, receiver)
+}
+
+/// Play a synth's output forever, applying commands as they arrive.
+///
+/// The synth is created by `make_synth`, given the sample rate it should run at. That's the one
+/// given with `--sample-rate` if there was one, in which case the output is resampled to suit the
+/// device, or else the device's own rate.
+pub fn run(opts: &Options, make_synth: impl FnOnce(u32) -> Synth, commands: CommandReceiver) -> ! {
+    let device_name = opts.audio_device.as_deref();
+    match opts.backend {
+        Backend::Cpal => run_backend(
+            CpalBackend::new(device_name).expect("Could not open audio output device"),
+            opts,
+            make_synth,
+            commands,
+        ),
+        Backend::Rodio => run_backend(
+            RodioBackend::new(device_name).expect("Could not open audio output device"),
+            opts,
+            make_synth,
+            commands,
+        ),
+        // JACK does its own routing, so there's no device to pick
+        #[cfg(feature = "jack")]
+        Backend::Jack => jack::run(make_synth, commands),
+    }
+}
+
+/// Render on this thread into a ring buffer, which the backend's callback plays from.
+///
+/// The callback wakes this thread whenever there's room for another block, so it spends the rest
+/// of its time asleep rather than polling.
+fn run_backend(
+    mut backend: impl AudioBackend,
+    opts: &Options,
+    make_synth: impl FnOnce(u32) -> Synth,
+    mut commands: CommandReceiver,
+) -> ! {
+    let device_rate = backend.sample_rate();
+    let mut synth = make_synth(opts.sample_rate.unwrap_or(device_rate));
+    commands.attach(&mut synth);
+    let mut resampler = if device_rate == synth.sample_rate() {
+        None
+    } else {
+        log::info!(
+            "Resampling from {} Hz to the device's {} Hz",
+            synth.sample_rate(),
+            device_rate
+        );
+        Some(Resampler::new(synth.sample_rate(), device_rate, CHANNELS))
+    };
+
+    let block_len = resampler
+        .as_ref()
+        .map_or(synth.block_size() * CHANNELS, |r| {
+            r.max_output_len(synth.block_size() * CHANNELS)
+        });
+    let mut resampled = Vec::with_capacity(block_len);
+
+    let mut input = opts.input.as_deref().map(|name| {
+        synth.set_voice_source(if opts.vocoder {
+            VoiceSource::Vocoder
+        } else {
+            VoiceSource::Input
+        });
+        if let Some(threshold) = opts.gate {
+            synth.set_input_gate(true);
+            if let Some(gate) = synth.input_gate_mut() {
+                gate.set_threshold(threshold);
+            }
+        }
+        input::Input::open(name, synth.sample_rate())
+    });
+    let mut input_block = vec![0.0; synth.block_size()];
+    let mut input_rendered = vec![0.0; synth.block_size() * CHANNELS];
+    let queue_blocks = opts.queue_blocks;
+    let (mut producer, mut consumer) = ring_buffer(block_len * queue_blocks);
+
+    if priority::raise_current_thread() == Priority::Normal {
+        log::warn!("Could not raise the render thread's priority, so expect dropouts under load");
+    }
+
+    let underruns = Arc::new(AtomicUsize::new(0));
+    let callback_underruns = underruns.clone();
+    let render_thread = thread::current();
+    backend
+        .start(Box::new(move |buffer| {
+            real_time(|| {
+                // if the synth thread falls behind, play silence rather than waiting
+                let taken = consumer.pop_slice(buffer);
+                if taken < buffer.len() {
+                    buffer[taken..].fill(0.0);
+                    callback_underruns.fetch_add(1, Ordering::Relaxed);
+                }
+                render_thread.unpark();
+            })
+        }))
+        .expect("Could not start audio output");
+
+    let block_frames = synth.block_size();
+    let block_duration = Duration::from_secs_f64(block_frames as f64 / synth.sample_rate() as f64);
+    log::info!(
+        "Output latency: {:.1} ms ({} blocks of {} frames), plus the device's own buffer",
+        (block_duration * queue_blocks as u32).as_secs_f64() * 1000.0,
+        queue_blocks,
+        synth.block_size()
+    );
+
+    let mut reported = 0;
+    let mut last_report = Instant::now();
+    #[cfg(feature = "profile")]
+    let mut last_profile = Instant::now();
+    let mut faded_at = None;
+    loop {
+        while producer.free_len() >= block_len {
+            real_time(|| commands.drain(&mut synth, block_frames));
+            let rendered = match &mut input {
+                None => real_time(|| synth.next_block()),
+                Some(input) => {
+                    input.read(&mut input_block);
+                    real_time(|| synth.process_with_input(&input_block, &mut input_rendered));
+                    &input_rendered
+                }
+            };
+            match &mut resampler {
+                None => producer.push_slice(rendered),
+                Some(resampler) => {
+                    resampled.clear();
+                    resampler.process(rendered, &mut resampled);
+                    producer.push_slice(&resampled)
+                }
+            };
+        }
+
+        if last_report.elapsed() >= UNDERRUN_REPORT_INTERVAL {
+            let total = underruns.load(Ordering::Relaxed);
+            if total > reported {
+                log::warn!(
+                    "Audio underruns: {} ({} new), try a larger --block-size or --queue-blocks",
+                    total,
+                    total - reported
+                );
+                reported = total;
+            }
+            last_report = Instant::now();
+        }
+
+        #[cfg(feature = "profile")]
+        if last_profile.elapsed() >= PROFILE_REPORT_INTERVAL {
+            if let Some(profile) = synth.profile() {
+                log::info!("{}", profile);
+            }
+            synth.reset_profile();
+            last_profile = Instant::now();
+        }
+
+        if synth.is_faded_out() {
+            let faded_at = *faded_at.get_or_insert_with(Instant::now);
+            if faded_at.elapsed() >= block_duration * queue_blocks as u32 + FLUSH_TIME {
+                backend.stop();
+                commands.finish();
+                loop {
+                    thread::park();
+                }
+            }
+        }
+
+        // in case the callback stalls, still pick up MIDI now and then
+        thread::park_timeout(block_duration);
+    }
+}
+
+/// Whether two patches have the same effects, with the same parameters given.
+fn same_effects(a: &Patch, b: &Patch) -> bool {
+    a.effects.len() == b.effects.len()
+        && a.effects.iter().zip(&b.effects).all(|(a, b)| {
+            a.name == b.name
+                && a.placement == b.placement
+                && a.parameters
+                    .iter()
+                    .map(|(n, _)| n)
+                    .eq(b.parameters.iter().map(|(n, _)| n))
+        })
+}
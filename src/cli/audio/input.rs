@@ -0,0 +1,74 @@
+use basic_synth::{
+    backend::{self, CpalInput},
+    resample::Resampler,
+    ring::{ring_buffer, Consumer},
+};
+
+/// How much input to buffer, in seconds. Anything beyond that is dropped, as is any which piles
+/// up because the input device runs slightly faster than the output.
+const BUFFER_TIME: f32 = 0.1;
+
+/// Audio coming in from an input device, brought to the synth's sample rate.
+pub struct Input {
+    _stream: CpalInput,
+    consumer: Consumer<f32>,
+    resampler: Option<Resampler>,
+    received: Vec<f32>,
+    pending: Vec<f32>,
+    max_pending: usize,
+}
+
+impl Input {
+    /// Start listening to the input device called `device_name`, or the default one if that is
+    /// `"default"`.
+    pub fn open(device_name: &str, sample_rate: u32) -> Self {
+        let capacity = (BUFFER_TIME * sample_rate as f32) as usize;
+        let (mut producer, consumer) = ring_buffer(capacity * 4);
+        let stream = CpalInput::start(
+            Some(device_name).filter(|name| *name != "default"),
+            Box::new(move |samples| {
+                producer.push_slice(samples);
+            }),
+        )
+        .unwrap_or_else(|e| {
+            log::error!("Could not open audio input {}: {}", device_name, e);
+            log::error!(
+                "Available inputs: {}",
+                backend::input_device_names().join(", ")
+            );
+            std::process::exit(1);
+        });
+
+        let resampler = if stream.sample_rate() == sample_rate {
+            None
+        } else {
+            Some(Resampler::new(stream.sample_rate(), sample_rate, 1))
+        };
+        Self {
+            _stream: stream,
+            consumer,
+            resampler,
+            received: vec![0.0; capacity * 4],
+            pending: Vec::with_capacity(capacity * 2),
+            max_pending: capacity,
+        }
+    }
+
+    /// Fill `out` with the oldest input not yet read, or silence if there isn't enough.
+    pub fn read(&mut self, out: &mut [f32]) {
+        let count = self.consumer.pop_slice(&mut self.received);
+        match &mut self.resampler {
+            None => self.pending.extend_from_slice(&self.received[..count]),
+            Some(resampler) => resampler.process(&self.received[..count], &mut self.pending),
+        }
+        if self.pending.len() > self.max_pending + out.len() {
+            let excess = self.pending.len() - self.max_pending - out.len();
+            self.pending.drain(..excess);
+        }
+
+        let available = self.pending.len().min(out.len());
+        out[..available].copy_from_slice(&self.pending[..available]);
+        out[available..].fill(0.0);
+        self.pending.drain(..available);
+    }
+}
@@ -0,0 +1,62 @@
+use std::thread;
+
+use {
+    super::{super::alloc_guard::real_time, CommandReceiver},
+    basic_synth::{Synth, SynthCommand},
+    jack::{AudioOut, Client, ClientOptions, ClosureProcessHandler, Control, MidiIn, ProcessScope},
+    midi_msg::MidiMsg,
+};
+
+/// Run as a JACK client with stereo outputs and a MIDI input, rendering inside JACK's process
+/// callback. Commands from the queue are scheduled a period after they were sent, while those
+/// arriving on the JACK MIDI port land on the exact frame they were sent for.
+pub fn run(make_synth: impl FnOnce(u32) -> Synth, mut commands: CommandReceiver) -> ! {
+    let (client, _status) = Client::new("basic-synth", ClientOptions::NO_START_SERVER)
+        .expect("Could not connect to the JACK server");
+    let mut out_left = client
+        .register_port("out_left", AudioOut)
+        .expect("Could not register JACK output port");
+    let mut out_right = client
+        .register_port("out_right", AudioOut)
+        .expect("Could not register JACK output port");
+    let midi_in = client
+        .register_port("midi_in", MidiIn)
+        .expect("Could not register JACK MIDI port");
+
+    let mut synth = make_synth(client.sample_rate() as u32);
+    commands.attach(&mut synth);
+
+    // everything in here runs on JACK's real-time thread, so none of it may allocate
+    let process = move |_: &Client, ps: &ProcessScope| -> Control {
+        real_time(|| {
+            commands.drain(&mut synth, ps.n_frames() as usize);
+
+            let start = synth.clock();
+            for event in midi_in.iter(ps) {
+                let command = MidiMsg::from_midi(event.bytes)
+                    .ok()
+                    .and_then(|(msg, _)| SynthCommand::from_midi(&msg));
+                if let Some(command) = command {
+                    // there's nobody to tell about dropped commands in here
+                    let _ = synth.schedule(start + event.time as u64, command);
+                }
+            }
+            synth.process_stereo(out_left.as_mut_slice(ps), out_right.as_mut_slice(ps));
+
+            // JACK doesn't buffer anything beyond this period, so that's the end of it
+            if synth.is_faded_out() {
+                commands.finish();
+            }
+        });
+
+        Control::Continue
+    };
+
+    let _active = client
+        .activate_async((), ClosureProcessHandler::new(process))
+        .expect("Could not activate JACK client");
+
+    loop {
+        thread::park();
+    }
+}
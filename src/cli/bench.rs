@@ -0,0 +1,96 @@
+//! Finding how many voices can play at once on this machine, with the current settings, by
+//! rendering with more and more of them as fast as possible.
+
+use std::time::{Duration, Instant};
+
+use basic_synth::{Synth, CHANNELS};
+
+/// How much audio is rendered with each number of voices.
+const AUDIO_PER_RUN: Duration = Duration::from_secs(2);
+
+/// How often every note is played again, so that the voices go through each stage of their
+/// envelopes rather than settling into the sustain (or silence). They're released halfway.
+const RETRIGGER_INTERVAL: Duration = Duration::from_millis(250);
+
+/// How many times faster than real time rendering has to be to keep up in practice, leaving the
+/// rest of the time for the audio device, the OS and everything else.
+const HEADROOM: f64 = 2.0;
+
+/// Most voices tried: one for every MIDI note.
+const MAX_VOICES: usize = 128;
+
+/// Render a chord as large as the synth has voices, with twice as many voices each time until it
+/// can't keep up, then narrow down the largest number which can, printing how each one did.
+pub fn run(make_synth: impl Fn(usize) -> Synth) {
+    if cfg!(debug_assertions) {
+        log::warn!("This is a debug build, which manages far fewer voices than a release build");
+    }
+    println!("Voices  Real time  Load");
+    let keeps_up = |voices| {
+        let speed = speed(&mut make_synth(voices), voices);
+        println!("{:>6}  {:>8.1}x  {:>3.0}%", voices, speed, 100.0 / speed);
+        speed >= HEADROOM
+    };
+
+    let mut sustainable = 0;
+    let mut too_many = None;
+    let mut voices = 1;
+    while voices <= MAX_VOICES {
+        if keeps_up(voices) {
+            sustainable = voices;
+            voices *= 2;
+        } else {
+            too_many = Some(voices);
+            break;
+        }
+    }
+    if let Some(mut too_many) = too_many {
+        while too_many - sustainable > 1 {
+            let voices = (sustainable + too_many) / 2;
+            if keeps_up(voices) {
+                sustainable = voices;
+            } else {
+                too_many = voices;
+            }
+        }
+    }
+
+    match sustainable {
+        0 => println!(
+            "Not even one voice renders {}x faster than real time",
+            HEADROOM
+        ),
+        MAX_VOICES => println!(
+            "All {} voices render at least {}x faster than real time",
+            MAX_VOICES, HEADROOM
+        ),
+        voices => println!(
+            "Maximum polyphony: {} voices, rendering at least {}x faster than real time",
+            voices, HEADROOM
+        ),
+    }
+}
+
+/// How many times faster than real time `synth` renders `voices` notes at once.
+fn speed(synth: &mut Synth, voices: usize) -> f64 {
+    // spread over the whole keyboard, in an order which doesn't repeat until all 128 are used
+    let notes: Vec<u8> = (0..voices).map(|i| (i * 37 % 128) as u8).collect();
+    let sample_rate = synth.sample_rate() as f64;
+    let half_frames = (RETRIGGER_INTERVAL.as_secs_f64() * sample_rate) as usize / 2;
+    let chunks = (AUDIO_PER_RUN.as_secs_f64() / RETRIGGER_INTERVAL.as_secs_f64()) as usize;
+    let mut buffer = vec![0.0; half_frames * CHANNELS];
+
+    let start = Instant::now();
+    for _ in 0..chunks {
+        for note in &notes {
+            let _ = synth.try_begin_note(*note, 100);
+        }
+        synth.process(&mut buffer);
+        for note in &notes {
+            let _ = synth.try_end_note(*note);
+        }
+        synth.process(&mut buffer);
+    }
+    let audio = (chunks * half_frames * 2) as f64 / sample_rate;
+    audio / start.elapsed().as_secs_f64()
+}
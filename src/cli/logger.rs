@@ -0,0 +1,85 @@
+//! Printing the library's and the CLI's log messages to stderr.
+//!
+//! Messages from `info` up are printed as they are, as they're meant for whoever is playing;
+//! `debug` and `trace` ones are marked with their level and where they came from.
+//!
+//! While the terminal UI is up, messages are kept for it to show instead, as printing them would
+//! scribble over it.
+
+use std::{
+    fmt,
+    io::{stderr, Write},
+    sync::Mutex,
+};
+
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+static LOGGER: Logger = Logger;
+
+/// The latest message, while they're being diverted from stderr.
+static DIVERTED: Mutex<Option<String>> = Mutex::new(None);
+
+/// Start printing messages at `level` and above.
+pub fn init(level: LevelFilter) {
+    // only fails if there's a logger already, which can only be this one
+    let _ = log::set_logger(&LOGGER);
+    log::set_max_level(level);
+}
+
+/// Keep messages for `last_message` instead of printing them, or go back to printing them.
+#[cfg(feature = "tui")]
+pub fn divert(on: bool) {
+    *DIVERTED.lock().unwrap() = on.then(String::new);
+}
+
+/// The latest message since `divert` was turned on, if there's been one.
+#[cfg(feature = "tui")]
+pub fn last_message() -> Option<String> {
+    DIVERTED
+        .lock()
+        .unwrap()
+        .clone()
+        .filter(|message| !message.is_empty())
+}
+
+struct Logger;
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    /// Writes straight to stderr, without allocating, as some messages come from the audio
+    /// thread. Diverted messages reuse the same string, so only allocate while it grows.
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        if let Some(last) = DIVERTED.lock().unwrap().as_mut() {
+            last.clear();
+            let _ = write_message(last, record);
+            return;
+        }
+        let mut stderr = IoWriter(stderr().lock());
+        let _ = write_message(&mut stderr, record)
+            .and_then(|()| fmt::Write::write_str(&mut stderr, "\n"));
+    }
+
+    fn flush(&self) {}
+}
+
+fn write_message(out: &mut impl fmt::Write, record: &Record) -> fmt::Result {
+    match record.level() {
+        Level::Error | Level::Warn | Level::Info => write!(out, "{}", record.args()),
+        level => write!(out, "[{} {}] {}", level, record.target(), record.args()),
+    }
+}
+
+/// Lets `write_message` print to stderr.
+struct IoWriter<W>(W);
+
+impl<W: Write> fmt::Write for IoWriter<W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.0.write_all(s.as_bytes()).map_err(|_| fmt::Error)
+    }
+}
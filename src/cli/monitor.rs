@@ -0,0 +1,98 @@
+use midi_msg::*;
+
+const NOTE_NAMES: [&str; 12] = [
+    "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
+];
+
+/// Prints incoming MIDI messages as rows of a table.
+#[derive(Debug, Default)]
+pub struct Monitor {
+    printed_header: bool,
+}
+
+impl Monitor {
+    /// Print a single message, along with the timestamp (in microseconds) it was received at.
+    pub fn log(&mut self, stamp: u64, msg: &MidiMsg) {
+        if !self.printed_header {
+            println!("{:>12}  {:>3}  {:<16}  DATA", "TIME (s)", "CH", "MESSAGE");
+            self.printed_header = true;
+        }
+
+        let (channel, kind, data) = describe(msg);
+        println!(
+            "{:>12.6}  {:>3}  {:<16}  {}",
+            stamp as f64 / 1_000_000.0,
+            channel.map_or_else(|| "-".to_string(), |c| (c as u8 + 1).to_string()),
+            kind,
+            data
+        );
+    }
+}
+
+fn describe(msg: &MidiMsg) -> (Option<Channel>, &'static str, String) {
+    match msg {
+        MidiMsg::ChannelVoice { channel, msg } | MidiMsg::RunningChannelVoice { channel, msg } => {
+            let (kind, data) = describe_voice(msg);
+            (Some(*channel), kind, data)
+        }
+        MidiMsg::ChannelMode { channel, msg } | MidiMsg::RunningChannelMode { channel, msg } => {
+            (Some(*channel), "Channel Mode", format!("{:?}", msg))
+        }
+        MidiMsg::SystemRealTime { msg } => {
+            let kind = match msg {
+                SystemRealTimeMsg::TimingClock => "Clock",
+                SystemRealTimeMsg::Start => "Start",
+                SystemRealTimeMsg::Continue => "Continue",
+                SystemRealTimeMsg::Stop => "Stop",
+                SystemRealTimeMsg::ActiveSensing => "Active Sensing",
+                SystemRealTimeMsg::SystemReset => "System Reset",
+            };
+            (None, kind, String::new())
+        }
+        MidiMsg::SystemCommon { msg } => (None, "System Common", format!("{:?}", msg)),
+        MidiMsg::SystemExclusive { .. } => (None, "SysEx", String::new()),
+    }
+}
+
+fn describe_voice(msg: &ChannelVoiceMsg) -> (&'static str, String) {
+    match *msg {
+        ChannelVoiceMsg::NoteOn { note, velocity } => (
+            "Note On",
+            format!("{:<4} vel {}", note_name(note), velocity),
+        ),
+        ChannelVoiceMsg::NoteOff { note, velocity } => (
+            "Note Off",
+            format!("{:<4} vel {}", note_name(note), velocity),
+        ),
+        ChannelVoiceMsg::HighResNoteOn { note, velocity } => (
+            "Note On",
+            format!("{:<4} vel {} (14-bit)", note_name(note), velocity),
+        ),
+        ChannelVoiceMsg::HighResNoteOff { note, velocity } => (
+            "Note Off",
+            format!("{:<4} vel {} (14-bit)", note_name(note), velocity),
+        ),
+        ChannelVoiceMsg::ControlChange { control } => {
+            let bytes = control.to_midi_running();
+            (
+                "Control Change",
+                format!("cc {:<3} = {:<3} {:?}", bytes[0], bytes[1], control),
+            )
+        }
+        ChannelVoiceMsg::PitchBend { bend } => ("Pitch Bend", format!("{:+}", bend as i32 - 8192)),
+        ChannelVoiceMsg::PolyPressure { note, pressure } => (
+            "Poly Pressure",
+            format!("{:<4} {}", note_name(note), pressure),
+        ),
+        ChannelVoiceMsg::ChannelPressure { pressure } => ("Channel Pressure", pressure.to_string()),
+        ChannelVoiceMsg::ProgramChange { program } => ("Program Change", program.to_string()),
+    }
+}
+
+fn note_name(note: u8) -> String {
+    format!(
+        "{}{}",
+        NOTE_NAMES[note as usize % 12],
+        (note / 12) as i8 - 1
+    )
+}
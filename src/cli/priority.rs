@@ -0,0 +1,46 @@
+//! Raising the priority of the render thread, so that other load on the system doesn't hold it up
+//! long enough for the audio device to run dry.
+
+/// Priority requested under `SCHED_FIFO`: above most things, but below the audio server (e.g.
+/// JACK's default of 70-80 for its own threads) and the kernel's interrupt handlers.
+#[cfg(unix)]
+const REALTIME_PRIORITY: libc::c_int = 60;
+
+/// Niceness to fall back to when real-time scheduling isn't allowed.
+#[cfg(unix)]
+const FALLBACK_NICENESS: libc::c_int = -10;
+
+/// What the current thread's priority ended up as.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Priority {
+    RealTime,
+    Raised,
+    Normal,
+}
+
+/// Ask for real-time scheduling for the current thread, or failing that a higher priority than
+/// normal. Both usually need extra permissions (e.g. an `rtprio` limit, or `CAP_SYS_NICE`), so
+/// it's quite possible neither works.
+#[cfg(unix)]
+pub fn raise_current_thread() -> Priority {
+    let param = libc::sched_param {
+        sched_priority: REALTIME_PRIORITY,
+    };
+    // SAFETY: only affects the calling thread, and `param` outlives the call
+    if unsafe { libc::pthread_setschedparam(libc::pthread_self(), libc::SCHED_FIFO, &param) } == 0 {
+        return Priority::RealTime;
+    }
+
+    // on Linux, a "process" ID of 0 here means just the calling thread
+    // SAFETY: no pointers involved
+    if unsafe { libc::setpriority(libc::PRIO_PROCESS, 0, FALLBACK_NICENESS) } == 0 {
+        return Priority::Raised;
+    }
+
+    Priority::Normal
+}
+
+#[cfg(not(unix))]
+pub fn raise_current_thread() -> Priority {
+    Priority::Normal
+}
@@ -0,0 +1,43 @@
+use std::{fs::File, io, path::PathBuf, time::Duration};
+
+use {
+    basic_synth::smf::{self, TimedMsg},
+    midi_msg::MidiMsg,
+};
+
+/// Collects incoming MIDI messages so they can be saved to a file at the end of a session.
+#[derive(Debug)]
+pub struct Recorder {
+    path: PathBuf,
+    first_stamp: Option<u64>,
+    events: Vec<TimedMsg>,
+}
+
+impl Recorder {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            first_stamp: None,
+            events: Vec::new(),
+        }
+    }
+
+    /// Capture a message received at the given timestamp (in microseconds). Times are saved
+    /// relative to the first message received, so recordings don't start with dead air.
+    pub fn record(&mut self, stamp: u64, msg: &MidiMsg) {
+        let first_stamp = *self.first_stamp.get_or_insert(stamp);
+        self.events.push(TimedMsg {
+            time: Duration::from_micros(stamp.saturating_sub(first_stamp)),
+            msg: msg.clone(),
+        });
+    }
+
+    /// Write everything captured so far out as a Standard MIDI File.
+    pub fn save(&self) -> io::Result<()> {
+        smf::write(io::BufWriter::new(File::create(&self.path)?), &self.events)
+    }
+
+    pub fn path(&self) -> &PathBuf {
+        &self.path
+    }
+}
@@ -0,0 +1,275 @@
+//! Remote control over a WebSocket, so that an editor running in a browser (or anything else
+//! which speaks WebSocket) can drive the running synth.
+//!
+//! Every message, each way, is a JSON object with a `type`. Requests are answered in order, with
+//! `{"type": "ok"}` unless they ask for something, or `{"type": "error", "message": "..."}` if
+//! they fail:
+//!
+//! - `{"type": "list-params"}` is answered with `{"type": "params", "params": [...]}`: each fixed
+//!   parameter's `name`, `min`, `max`, `default`, `unit`, `curve` (`linear`, `logarithmic` or
+//!   `stepped`) and current `value`, and for the waveform, the `choices` its steps stand for.
+//! - `{"type": "set", "param": "cutoff", "value": 800}` sets a parameter, named as in the text
+//!   form of commands, e.g. `send 1 level` or `effect master 0 1`.
+//! - `{"type": "list-presets"}` is answered with `{"type": "presets", "presets": [...]}`, the
+//!   names of the presets, as `category/name` for those in a category.
+//! - `{"type": "load-preset", "name": "lead/saw"}` switches to a preset.
+//! - `{"type": "note-on", "note": 60, "velocity": 100}` and `{"type": "note-off", "note": 60}`
+//!   play notes.
+//! - `{"type": "subscribe-meters"}` starts `{"type": "meters", "peak": [l, r], "rms": [l, r],
+//!   "clipped": false}` coming every `METER_INTERVAL`, where `clipped` is whether the output has
+//!   clipped since the last one, until `{"type": "unsubscribe-meters"}`.
+
+mod json;
+mod websocket;
+
+use std::{
+    io::{self, BufReader},
+    net::{TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+use basic_synth::{
+    frame::Frame,
+    param::{Curve, ParamId},
+    SynthCommand, Waveform,
+};
+
+use {
+    super::audio::{CommandSender, Status},
+    json::Value,
+    websocket::Message,
+};
+
+/// How often meter readings are sent to subscribers.
+const METER_INTERVAL: Duration = Duration::from_millis(50);
+
+/// The preset bank, as far as the remote control needs it.
+pub trait Presets: Send + Sync {
+    /// Every preset's name, as `category/name` for those in a category.
+    fn names(&self) -> Vec<String>;
+
+    /// Switch to the preset called `name`, as for `PresetBank::find`.
+    fn load(&self, name: &str) -> Result<(), String>;
+}
+
+/// Listen for remote control connections on `addr`, in the background, serving each on a thread
+/// of its own. Browsers can only connect from local pages, or those in `allowed_origins`.
+pub fn spawn(
+    addr: &str,
+    commands: CommandSender,
+    presets: Arc<dyn Presets>,
+    allowed_origins: Vec<String>,
+) -> io::Result<()> {
+    let allowed_origins: Arc<[String]> = allowed_origins.into();
+    let listener = TcpListener::bind(addr)?;
+    log::info!(
+        "Listening for remote control at ws://{}",
+        listener.local_addr()?
+    );
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    let commands = commands.clone();
+                    let presets = presets.clone();
+                    let allowed_origins = allowed_origins.clone();
+                    thread::spawn(move || {
+                        if let Err(e) = serve(stream, commands, presets, &allowed_origins) {
+                            log::warn!("Remote control connection failed: {}", e);
+                        }
+                    });
+                }
+                Err(e) => log::warn!("Failed to accept a remote control connection: {}", e),
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Answer one client's requests until it goes away, sending it meter readings meanwhile if it
+/// asks for them.
+fn serve(
+    stream: TcpStream,
+    commands: CommandSender,
+    presets: Arc<dyn Presets>,
+    allowed_origins: &[String],
+) -> io::Result<()> {
+    let peer = stream.peer_addr()?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    websocket::accept(&mut reader, allowed_origins)?;
+    log::info!("Remote control connected from {}", peer);
+
+    let writer = Arc::new(Mutex::new(stream));
+    let subscribed = Arc::new(AtomicBool::new(false));
+    let closed = Arc::new(AtomicBool::new(false));
+    {
+        let (commands, writer, subscribed, closed) = (
+            commands.clone(),
+            writer.clone(),
+            subscribed.clone(),
+            closed.clone(),
+        );
+        thread::spawn(move || {
+            let mut clipped_blocks = commands.status().clipped_blocks();
+            while !closed.load(Ordering::Relaxed) {
+                thread::sleep(METER_INTERVAL);
+                if !subscribed.load(Ordering::Relaxed) {
+                    continue;
+                }
+                let message = meters(commands.status(), &mut clipped_blocks).to_string();
+                if websocket::write_text(&mut *writer.lock().unwrap(), &message).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+
+    let result = (|| loop {
+        match websocket::read_message(&mut reader)? {
+            Message::Text(text) => {
+                let reply =
+                    respond(&text, &commands, &*presets, &subscribed).unwrap_or_else(|message| {
+                        message_of_type("error", [("message", message.into())])
+                    });
+                websocket::write_text(&mut *writer.lock().unwrap(), &reply.to_string())?;
+            }
+            Message::Ping(data) => websocket::write_pong(&mut *writer.lock().unwrap(), &data)?,
+            Message::Close => return websocket::write_close(&mut *writer.lock().unwrap()),
+        }
+    })();
+    closed.store(true, Ordering::Relaxed);
+    log::info!("Remote control from {} disconnected", peer);
+    result
+}
+
+/// A message of the given type, with the rest of its members.
+fn message_of_type<const N: usize>(kind: &str, members: [(&str, Value); N]) -> Value {
+    let mut message = vec![("type", kind.into())];
+    message.extend(IntoIterator::into_iter(members));
+    Value::object(message)
+}
+
+/// The answer to a request, or why it failed.
+fn respond(
+    text: &str,
+    commands: &CommandSender,
+    presets: &dyn Presets,
+    subscribed: &AtomicBool,
+) -> Result<Value, String> {
+    let request = json::parse(text)?;
+    let kind = request
+        .get("type")
+        .and_then(Value::as_str)
+        .ok_or("Expected an object with a type")?;
+    let string = |key: &str| {
+        request
+            .get(key)
+            .and_then(Value::as_str)
+            .ok_or_else(|| format!("Expected a string for {}", key))
+    };
+    let number = |key: &str| {
+        request
+            .get(key)
+            .and_then(Value::as_f64)
+            .ok_or_else(|| format!("Expected a number for {}", key))
+    };
+    // notes and velocities, from 0 to 127
+    let midi_value = |key: &str| {
+        let value = number(key)?;
+        if value.fract() == 0.0 && (0.0..128.0).contains(&value) {
+            Ok(value as u8)
+        } else {
+            Err(format!("Expected a whole number from 0 to 127 for {}", key))
+        }
+    };
+
+    let command = match kind {
+        "list-params" => return Ok(params(commands.status())),
+        "list-presets" => {
+            let names = presets.names().into_iter().map(Value::from).collect();
+            return Ok(message_of_type(
+                "presets",
+                [("presets", Value::Array(names))],
+            ));
+        }
+        "load-preset" => {
+            presets.load(string("name")?)?;
+            None
+        }
+        "set" => Some(SynthCommand::SetParam {
+            param: string("param")?.parse()?,
+            value: number("value")? as f32,
+        }),
+        "note-on" => Some(SynthCommand::NoteOn {
+            note: midi_value("note")?,
+            velocity: midi_value("velocity")?,
+        }),
+        "note-off" => Some(SynthCommand::NoteOff {
+            note: midi_value("note")?,
+        }),
+        "subscribe-meters" | "unsubscribe-meters" => {
+            subscribed.store(kind == "subscribe-meters", Ordering::Relaxed);
+            None
+        }
+        other => return Err(format!("Unknown request type: {}", other)),
+    };
+    if let Some(command) = command {
+        commands.send(command);
+    }
+    Ok(message_of_type("ok", []))
+}
+
+/// Every fixed parameter, with what its values mean and its current value.
+fn params(status: &Status) -> Value {
+    let params = ParamId::fixed()
+        .filter_map(|param| {
+            let info = param.info()?;
+            let curve = match info.curve {
+                Curve::Linear => "linear",
+                Curve::Logarithmic => "logarithmic",
+                Curve::Stepped => "stepped",
+            };
+            let mut members = vec![
+                ("name", param.to_string().into()),
+                ("min", info.min.into()),
+                ("max", info.max.into()),
+                ("default", info.default.into()),
+                ("unit", info.unit.symbol().into()),
+                ("curve", curve.into()),
+                (
+                    "value",
+                    status.param(param).map_or(Value::Null, Value::from),
+                ),
+            ];
+            if param == ParamId::Waveform {
+                let names = Waveform::ALL.iter().map(|w| w.name().into()).collect();
+                members.push(("choices", Value::Array(names)));
+            }
+            Some(Value::object(members))
+        })
+        .collect();
+    message_of_type("params", [("params", Value::Array(params))])
+}
+
+/// The latest meter readings, noting whether the output has clipped since `clipped_blocks` was
+/// counted, and counting it again.
+fn meters(status: &Status, clipped_blocks: &mut usize) -> Value {
+    let levels = status.levels();
+    let channels = |frame: Frame| Value::Array(frame.into_iter().map(Value::from).collect());
+    let clipped = status.clipped_blocks();
+    let message = message_of_type(
+        "meters",
+        [
+            ("peak", channels(levels.peak)),
+            ("rms", channels(levels.rms)),
+            ("clipped", (clipped > *clipped_blocks).into()),
+        ],
+    );
+    *clipped_blocks = clipped;
+    message
+}
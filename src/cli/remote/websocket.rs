@@ -0,0 +1,323 @@
+//! The server side of the WebSocket protocol (RFC 6455), as much of it as the remote control
+//! needs: the opening handshake, and text messages, pings and closing after that.
+
+use std::io::{self, BufRead, BufReader, Read, Write};
+
+/// Appended to the client's key before hashing it, to prove the server speaks WebSocket.
+const HANDSHAKE_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// Longest message accepted, across all its frames. Nothing the remote control understands comes
+/// close.
+const MAX_MESSAGE_LEN: usize = 1 << 20;
+
+/// Longest request head accepted in the handshake.
+const MAX_HEADER_LEN: usize = 16 * 1024;
+
+const OPCODE_CONTINUATION: u8 = 0x0;
+const OPCODE_TEXT: u8 = 0x1;
+const OPCODE_BINARY: u8 = 0x2;
+const OPCODE_CLOSE: u8 = 0x8;
+const OPCODE_PING: u8 = 0x9;
+const OPCODE_PONG: u8 = 0xa;
+
+/// Something received from the client.
+#[derive(Debug)]
+pub enum Message {
+    Text(String),
+    /// A ping, to be answered with the same data.
+    Ping(Vec<u8>),
+    /// The client is closing the connection, and should be answered with a close of our own.
+    Close,
+}
+
+/// Hosts which pages can connect from without being allowed by name.
+const LOCAL_HOSTS: [&str; 3] = ["localhost", "127.0.0.1", "[::1]"];
+
+/// Read the client's opening handshake, and answer it. Requests which aren't for a WebSocket
+/// are answered with an HTTP error, and give an error here.
+///
+/// Browsers say which page a connection comes from, as its `Origin`, and let any page connect to
+/// any server, so those from pages which aren't served locally or in `allowed_origins` (e.g.
+/// `https://example.com`) are refused, as otherwise any page could take over the synth. Clients
+/// which aren't browsers don't send an origin, and are let in.
+pub fn accept<S: Read + Write>(
+    stream: &mut BufReader<S>,
+    allowed_origins: &[String],
+) -> io::Result<()> {
+    let mut key = None;
+    let mut upgrade = false;
+    let mut origin = None;
+    let mut total = 0;
+    loop {
+        let mut line = String::new();
+        let len = stream.read_line(&mut line)?;
+        total += len;
+        if len == 0 || total > MAX_HEADER_LEN {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Incomplete request",
+            ));
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            let value = value.trim();
+            if name.eq_ignore_ascii_case("upgrade") {
+                upgrade = value.eq_ignore_ascii_case("websocket");
+            } else if name.eq_ignore_ascii_case("sec-websocket-key") {
+                key = Some(value.to_string());
+            } else if name.eq_ignore_ascii_case("origin") {
+                origin = Some(value.to_string());
+            }
+        }
+    }
+
+    let stream = stream.get_mut();
+    if let Some(origin) = origin.filter(|o| !origin_allowed(o, allowed_origins)) {
+        let body = "Connections from this page aren't allowed, see --allow-origin\n";
+        write!(
+            stream,
+            "HTTP/1.1 403 Forbidden\r\nConnection: close\r\nContent-Type: text/plain\r\n\
+             Content-Length: {}\r\n\r\n{}",
+            body.len(),
+            body
+        )?;
+        stream.flush()?;
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("Refused a connection from {}", origin),
+        ));
+    }
+    match key.filter(|_| upgrade) {
+        Some(key) => {
+            let accept = base64(&sha1(format!("{}{}", key, HANDSHAKE_GUID).as_bytes()));
+            write!(
+                stream,
+                "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+                 Sec-WebSocket-Accept: {}\r\n\r\n",
+                accept
+            )?;
+            stream.flush()
+        }
+        None => {
+            let body = "This is the basic-synth remote control, which only speaks WebSocket\n";
+            write!(
+                stream,
+                "HTTP/1.1 426 Upgrade Required\r\nUpgrade: websocket\r\nConnection: close\r\n\
+                 Content-Type: text/plain\r\nContent-Length: {}\r\n\r\n{}",
+                body.len(),
+                body
+            )?;
+            stream.flush()?;
+            Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Not a WebSocket request",
+            ))
+        }
+    }
+}
+
+/// Whether a page at `origin`, as `scheme://host[:port]`, may connect: if it's served locally, or
+/// is one of `allowed`.
+fn origin_allowed(origin: &str, allowed: &[String]) -> bool {
+    if allowed.iter().any(|a| a.eq_ignore_ascii_case(origin)) {
+        return true;
+    }
+    let host = match origin.split_once("://") {
+        Some((_, rest)) => rest,
+        // e.g. `null`, from a file or a sandboxed page
+        None => return false,
+    };
+    // the port follows the last colon, unless that's inside an IPv6 address
+    let host = match host.rsplit_once(':') {
+        Some((host, port)) if !port.contains(']') => host,
+        _ => host,
+    };
+    LOCAL_HOSTS
+        .iter()
+        .any(|local| local.eq_ignore_ascii_case(host))
+}
+
+/// Read the next message, putting it back together if it came in several frames. Pongs are
+/// skipped, and binary messages refused.
+pub fn read_message(stream: &mut impl Read) -> io::Result<Message> {
+    let mut message = Vec::new();
+    loop {
+        let (fin, opcode, payload) = read_frame(stream)?;
+        match opcode {
+            OPCODE_PING => return Ok(Message::Ping(payload)),
+            OPCODE_PONG => continue,
+            OPCODE_CLOSE => return Ok(Message::Close),
+            OPCODE_BINARY => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "Binary messages aren't understood",
+                ))
+            }
+            OPCODE_TEXT | OPCODE_CONTINUATION => {
+                if message.len() + payload.len() > MAX_MESSAGE_LEN {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "Message too long",
+                    ));
+                }
+                message.extend_from_slice(&payload);
+                if fin {
+                    return String::from_utf8(message)
+                        .map(Message::Text)
+                        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e));
+                }
+            }
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Unknown opcode {:#x}", opcode),
+                ))
+            }
+        }
+    }
+}
+
+/// Read one frame, unmasking its payload, which clients always mask.
+fn read_frame(stream: &mut impl Read) -> io::Result<(bool, u8, Vec<u8>)> {
+    let mut header = [0; 2];
+    stream.read_exact(&mut header)?;
+    let fin = header[0] & 0x80 != 0;
+    let opcode = header[0] & 0x0f;
+    let masked = header[1] & 0x80 != 0;
+    let len = match header[1] & 0x7f {
+        126 => {
+            let mut len = [0; 2];
+            stream.read_exact(&mut len)?;
+            u16::from_be_bytes(len) as u64
+        }
+        127 => {
+            let mut len = [0; 8];
+            stream.read_exact(&mut len)?;
+            u64::from_be_bytes(len)
+        }
+        len => len as u64,
+    };
+    if len > MAX_MESSAGE_LEN as u64 {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "Frame too long"));
+    }
+    let mut mask = [0; 4];
+    if masked {
+        stream.read_exact(&mut mask)?;
+    }
+    let mut payload = vec![0; len as usize];
+    stream.read_exact(&mut payload)?;
+    for (i, byte) in payload.iter_mut().enumerate() {
+        *byte ^= mask[i % 4];
+    }
+    Ok((fin, opcode, payload))
+}
+
+/// Send a text message, in one frame.
+pub fn write_text(stream: &mut impl Write, text: &str) -> io::Result<()> {
+    write_frame(stream, OPCODE_TEXT, text.as_bytes())
+}
+
+/// Answer a ping.
+pub fn write_pong(stream: &mut impl Write, data: &[u8]) -> io::Result<()> {
+    write_frame(stream, OPCODE_PONG, data)
+}
+
+/// Close the connection from our end, or answer the client closing it.
+pub fn write_close(stream: &mut impl Write) -> io::Result<()> {
+    write_frame(stream, OPCODE_CLOSE, &[])
+}
+
+fn write_frame(stream: &mut impl Write, opcode: u8, payload: &[u8]) -> io::Result<()> {
+    let mut frame = Vec::with_capacity(payload.len() + 10);
+    frame.push(0x80 | opcode);
+    match payload.len() {
+        len if len < 126 => frame.push(len as u8),
+        len if len <= u16::MAX as usize => {
+            frame.push(126);
+            frame.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            frame.push(127);
+            frame.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+    frame.extend_from_slice(payload);
+    stream.write_all(&frame)?;
+    stream.flush()
+}
+
+/// The SHA-1 digest of `data`, which the handshake needs and nothing else: it's long broken as a
+/// cryptographic hash.
+fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut h: [u32; 5] = [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476, 0xc3d2e1f0];
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&((data.len() as u64) * 8).to_be_bytes());
+
+    for chunk in message.chunks(64) {
+        let mut w = [0u32; 80];
+        for (i, word) in chunk.chunks(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e] = h;
+        for (i, word) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5a827999),
+                20..=39 => (b ^ c ^ d, 0x6ed9eba1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8f1bbcdc),
+                _ => (b ^ c ^ d, 0xca62c1d6),
+            };
+            let temp = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(*word);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+        for (h, x) in h.iter_mut().zip([a, b, c, d, e]) {
+            *h = h.wrapping_add(x);
+        }
+    }
+
+    let mut digest = [0; 20];
+    for (bytes, h) in digest.chunks_mut(4).zip(h) {
+        bytes.copy_from_slice(&h.to_be_bytes());
+    }
+    digest
+}
+
+/// Standard base64, with padding.
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let bits = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(bits >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
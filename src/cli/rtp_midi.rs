@@ -0,0 +1,230 @@
+//! Network MIDI input: an AppleMIDI (RTP-MIDI) session, for playing the synth over the LAN from
+//! macOS, iOS, or Windows with rtpMIDI, without a hardware interface.
+//!
+//! The session listens on a control port, given with `--rtp-midi`, and the data port after it,
+//! accepting every invitation, so several senders can play at once. It isn't advertised with
+//! Bonjour, so senders add it by address and port: on macOS, in the Directory of the Network
+//! panel of Audio MIDI Setup. The recovery journal isn't read, so a lost packet's notes are lost,
+//! which a wired LAN or a good WiFi network makes rare.
+
+use std::{
+    io::{self, ErrorKind},
+    net::{SocketAddr, UdpSocket},
+    process,
+    sync::atomic::{AtomicBool, Ordering},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use midi_msg::{MidiMsg, ReceiverContext};
+
+/// Name the session goes by, shown to senders.
+const SESSION_NAME: &str = "basic-synth";
+
+/// How long to wait for data before checking the control port, and whether to stop.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// The largest packet accepted. Senders keep to the usual network MTU.
+const MAX_PACKET: usize = 1500;
+
+/// What session packets start with, before their two letter command.
+const SIGNATURE: [u8; 2] = [0xff, 0xff];
+
+const PROTOCOL_VERSION: u32 = 2;
+
+/// RTP version 2, in the top bits of the first byte of data packets.
+const RTP_VERSION: u8 = 0x80;
+
+const RTP_HEADER_LEN: usize = 12;
+
+/// Something a sender did.
+pub enum Event {
+    Midi(MidiMsg),
+    /// A sender ended its session, leaving any notes it was playing held.
+    Left,
+}
+
+/// A session which senders can join, bound to its ports.
+pub struct Session {
+    control: UdpSocket,
+    data: UdpSocket,
+    /// Our own RTP synchronization source identifier, made up afresh for every run.
+    ssrc: u32,
+    start: Instant,
+    /// Senders which have joined, by their identifiers, with their names.
+    peers: Vec<(u32, String)>,
+}
+
+impl Session {
+    /// Listen for senders on `addr`, the control port, and the port after it, for data.
+    pub fn bind(addr: &str) -> io::Result<Self> {
+        let control = UdpSocket::bind(addr)?;
+        let mut data_addr = control.local_addr()?;
+        data_addr.set_port(data_addr.port().checked_add(1).ok_or_else(|| {
+            io::Error::new(ErrorKind::InvalidInput, "No port follows the control port")
+        })?);
+        let data = UdpSocket::bind(data_addr)?;
+        control.set_nonblocking(true)?;
+        data.set_read_timeout(Some(POLL_INTERVAL))?;
+
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |t| t.subsec_nanos());
+        let ssrc = process::id().rotate_left(16) ^ nanos;
+        log::info!(
+            "Listening for network MIDI at {} (data at {})",
+            control.local_addr()?,
+            data_addr
+        );
+        Ok(Self {
+            control,
+            data,
+            ssrc,
+            start: Instant::now(),
+            peers: Vec::new(),
+        })
+    }
+
+    /// Answer senders and pass on what they play to `handle`, until `stop` is set.
+    pub fn run(&mut self, stop: &AtomicBool, mut handle: impl FnMut(Event)) -> io::Result<()> {
+        let mut packet = [0; MAX_PACKET];
+        while !stop.load(Ordering::Relaxed) {
+            loop {
+                match self.control.recv_from(&mut packet) {
+                    Ok((len, from)) => {
+                        self.session_packet(false, &packet[..len], from, &mut handle)?
+                    }
+                    Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                    Err(e) => return Err(e),
+                }
+            }
+            match self.data.recv_from(&mut packet) {
+                Ok((len, from)) => {
+                    let packet = &packet[..len];
+                    if packet.starts_with(&SIGNATURE) {
+                        self.session_packet(true, packet, from, &mut handle)?;
+                    } else {
+                        read_midi(packet, &mut handle);
+                    }
+                }
+                Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => (),
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    /// Answer a session packet, arriving at the data port or else the control port: an
+    /// invitation, clock synchronization, or a sender leaving.
+    fn session_packet(
+        &mut self,
+        data: bool,
+        packet: &[u8],
+        from: SocketAddr,
+        handle: &mut impl FnMut(Event),
+    ) -> io::Result<()> {
+        let socket = if data { &self.data } else { &self.control };
+        match &packet[2..packet.len().min(4)] {
+            b"IN" if packet.len() >= 16 => {
+                let token = read_u32(packet, 8);
+                let ssrc = read_u32(packet, 12);
+                let name = String::from_utf8_lossy(&packet[16..]);
+                let name = name.trim_end_matches('\0').to_string();
+                let mut reply = Vec::with_capacity(17 + SESSION_NAME.len());
+                reply.extend_from_slice(&SIGNATURE);
+                reply.extend_from_slice(b"OK");
+                reply.extend_from_slice(&PROTOCOL_VERSION.to_be_bytes());
+                reply.extend_from_slice(&token.to_be_bytes());
+                reply.extend_from_slice(&self.ssrc.to_be_bytes());
+                reply.extend_from_slice(SESSION_NAME.as_bytes());
+                reply.push(0);
+                socket.send_to(&reply, from)?;
+                self.join(ssrc, name, from);
+            }
+            b"BY" if packet.len() >= 16 => {
+                let ssrc = read_u32(packet, 12);
+                if let Some(index) = self.peers.iter().position(|(peer, _)| *peer == ssrc) {
+                    let (_, name) = self.peers.remove(index);
+                    log::info!("{} left the network MIDI session", name);
+                    handle(Event::Left);
+                }
+            }
+            // the sender starts each exchange of timestamps, and we answer the first
+            b"CK" if packet.len() >= 36 && packet[8] == 0 => {
+                let mut reply = packet[..36].to_vec();
+                reply[4..8].copy_from_slice(&self.ssrc.to_be_bytes());
+                reply[8] = 1;
+                reply[20..28].copy_from_slice(&self.timestamp().to_be_bytes());
+                socket.send_to(&reply, from)?;
+            }
+            _ => (),
+        }
+        Ok(())
+    }
+
+    /// Note a sender joining. Senders invite the control port and then the data port, so each
+    /// one is seen twice.
+    fn join(&mut self, ssrc: u32, name: String, from: SocketAddr) {
+        if !self.peers.iter().any(|(peer, _)| *peer == ssrc) {
+            log::info!("{} joined the network MIDI session from {}", name, from);
+            self.peers.push((ssrc, name));
+        }
+    }
+
+    /// Time since the session started, in the units of clock synchronization, 100 microseconds.
+    fn timestamp(&self) -> u64 {
+        (self.start.elapsed().as_micros() / 100) as u64
+    }
+}
+
+/// Pass on the MIDI commands in an RTP-MIDI data packet.
+fn read_midi(packet: &[u8], handle: &mut impl FnMut(Event)) {
+    if packet.len() < RTP_HEADER_LEN + 1 || packet[0] & 0xc0 != RTP_VERSION {
+        return;
+    }
+    let section = &packet[RTP_HEADER_LEN..];
+    // B: a long, 12-bit length; Z: the first command has a delta time
+    let (len, header_len) = if section[0] & 0x80 != 0 {
+        match section.get(1) {
+            Some(low) => (((section[0] as usize & 0x0f) << 8) | *low as usize, 2),
+            None => return,
+        }
+    } else {
+        (section[0] as usize & 0x0f, 1)
+    };
+    let first_delta = section[0] & 0x20 != 0;
+    let list = match section.get(header_len..header_len + len) {
+        Some(list) => list,
+        None => return,
+    };
+
+    let mut ctx = ReceiverContext::default();
+    let mut position = 0;
+    while position < list.len() {
+        if position > 0 || first_delta {
+            position += delta_time_len(&list[position..]);
+        }
+        match MidiMsg::from_midi_with_context(&list[position.min(list.len())..], &mut ctx) {
+            Ok((msg, msg_len)) => {
+                position += msg_len;
+                handle(Event::Midi(msg));
+            }
+            // nothing after a bad command can be found
+            Err(e) => {
+                log::debug!("Skipping the rest of a network MIDI packet: {}", e);
+                return;
+            }
+        }
+    }
+}
+
+/// How many bytes a delta time takes: up to four, each but the last with its top bit set.
+fn delta_time_len(list: &[u8]) -> usize {
+    list.iter()
+        .take(4)
+        .position(|byte| byte & 0x80 == 0)
+        .map_or(4, |last| last + 1)
+}
+
+fn read_u32(bytes: &[u8], at: usize) -> u32 {
+    u32::from_be_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]])
+}
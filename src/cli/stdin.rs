@@ -0,0 +1,153 @@
+use std::{
+    io::{self, BufRead, Read},
+    str::FromStr,
+    thread,
+    time::Duration,
+};
+
+use midi_msg::*;
+
+/// How MIDI input read from stdin is encoded.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    /// Raw MIDI bytes, exactly as they would arrive from a port.
+    Raw,
+    /// One event per line, e.g. `on 60 100`, `off 60`, `cc 74 20`, `bend -400`, `program 3`, or
+    /// `wait 250` to pause for that many milliseconds. Blank lines and `#` comments are ignored.
+    Text,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "raw" => Ok(Self::Raw),
+            "text" => Ok(Self::Text),
+            other => Err(format!("Unknown stdin format: {}", other)),
+        }
+    }
+}
+
+/// Read MIDI messages from stdin until it is closed, calling `handle` for each one as soon as it
+/// is available.
+pub fn read(format: Format, handle: impl FnMut(MidiMsg)) -> io::Result<()> {
+    let stdin = io::stdin();
+    let lock = stdin.lock();
+    match format {
+        Format::Raw => read_raw(lock, handle),
+        Format::Text => read_text(lock, handle),
+    }
+}
+
+fn read_raw(mut input: impl Read, mut handle: impl FnMut(MidiMsg)) -> io::Result<()> {
+    let mut ctx = ReceiverContext::default();
+    let mut pending = Vec::new();
+    let mut chunk = [0; 256];
+
+    loop {
+        let len = input.read(&mut chunk)?;
+        if len == 0 {
+            return Ok(());
+        }
+        pending.extend_from_slice(&chunk[..len]);
+
+        let mut consumed = 0;
+        while consumed < pending.len() {
+            match MidiMsg::from_midi_with_context(&pending[consumed..], &mut ctx) {
+                Ok((msg, msg_len)) => {
+                    consumed += msg_len;
+                    handle(msg);
+                }
+                // wait for the rest of the message to arrive
+                Err(ParseError::UnexpectedEnd) | Err(ParseError::NoEndOfSystemExclusiveFlag) => {
+                    break
+                }
+                Err(e) => {
+                    log::warn!("Skipping bad MIDI byte {:#04x}: {}", pending[consumed], e);
+                    consumed += 1;
+                }
+            }
+        }
+        pending.drain(..consumed);
+    }
+}
+
+fn read_text(input: impl BufRead, mut handle: impl FnMut(MidiMsg)) -> io::Result<()> {
+    for (line_num, line) in input.lines().enumerate() {
+        let line = line?;
+        let line = line.split('#').next().unwrap_or_default().trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        match parse_line(line) {
+            Ok(Line::Wait(duration)) => thread::sleep(duration),
+            Ok(Line::Msg(msg)) => handle(msg),
+            Err(e) => log::warn!("Line {}: {}", line_num + 1, e),
+        }
+    }
+
+    Ok(())
+}
+
+enum Line {
+    Wait(Duration),
+    Msg(MidiMsg),
+}
+
+fn parse_line(line: &str) -> Result<Line, String> {
+    let mut words = line.split_whitespace();
+    let command = words.next().unwrap_or_default();
+    let mut arg = |name: &str, default: Option<i32>| -> Result<i32, String> {
+        match words.next() {
+            Some(word) => word
+                .parse::<i32>()
+                .map_err(|_| format!("Invalid {}: {}", name, word)),
+            None => default.ok_or_else(|| format!("Missing {}", name)),
+        }
+    };
+
+    let msg = match command {
+        "wait" => {
+            return Ok(Line::Wait(Duration::from_millis(
+                arg("time", None)?.max(0) as u64
+            )))
+        }
+        "on" => ChannelVoiceMsg::NoteOn {
+            note: to_u7(arg("note", None)?),
+            velocity: to_u7(arg("velocity", Some(100))?),
+        },
+        "off" => ChannelVoiceMsg::NoteOff {
+            note: to_u7(arg("note", None)?),
+            velocity: to_u7(arg("velocity", Some(0))?),
+        },
+        "cc" => {
+            // go through the parser, so controllers are interpreted as if they came from a port
+            let bytes = [
+                0xB0,
+                to_u7(arg("controller", None)?),
+                to_u7(arg("value", None)?),
+            ];
+            return MidiMsg::from_midi(&bytes)
+                .map(|(msg, _)| Line::Msg(msg))
+                .map_err(|e| e.to_string());
+        }
+        "bend" => ChannelVoiceMsg::PitchBend {
+            bend: (arg("bend", None)?.clamp(-8192, 8191) + 8192) as u16,
+        },
+        "program" => ChannelVoiceMsg::ProgramChange {
+            program: to_u7(arg("program", None)?),
+        },
+        other => return Err(format!("Unknown command: {}", other)),
+    };
+
+    Ok(Line::Msg(MidiMsg::ChannelVoice {
+        channel: Channel::Ch1,
+        msg,
+    }))
+}
+
+fn to_u7(value: i32) -> u8 {
+    value.clamp(0, 127) as u8
+}
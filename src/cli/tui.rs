@@ -0,0 +1,381 @@
+//! A full-screen terminal UI, for shaping the sound while playing: it shows how many voices are
+//! playing, the output levels, the preset and where the sequencer is, and lists the fixed
+//! parameters to pick and change from the keyboard.
+//!
+//! It's drawn with ratatui, through crossterm, so it works in the terminals of every desktop
+//! platform.
+
+use std::{
+    io::{self, stdin, stdout, IsTerminal},
+    time::{Duration, Instant},
+};
+
+use ratatui::{
+    backend::CrosstermBackend,
+    crossterm::{
+        event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+        execute,
+        terminal::{self, EnterAlternateScreen, LeaveAlternateScreen},
+    },
+    layout::{Constraint, Layout},
+    style::{Modifier, Style, Stylize},
+    text::{Line, Span},
+    widgets::{HighlightSpacing, List, ListState, Paragraph},
+    DefaultTerminal, Frame, Terminal,
+};
+
+use basic_synth::{
+    param::{Curve, ParamId, ParamInfo},
+    sequencer::SequencerCommand,
+    SynthCommand, Waveform, CHANNELS,
+};
+
+use super::{
+    audio::{CommandSender, Status},
+    logger,
+};
+
+/// How often the screen is redrawn, at most, while no keys are pressed.
+const FRAME_INTERVAL: Duration = Duration::from_millis(50);
+
+/// How many presses of an arrow key take a parameter across its whole range.
+const FINE_STEPS: f32 = 100.0;
+
+/// The same for `+` and `-`.
+const COARSE_STEPS: f32 = 10.0;
+
+/// Width of the level meters, in characters.
+const METER_WIDTH: usize = 50;
+
+/// Width of the bars showing where parameters are in their ranges.
+const PARAM_BAR_WIDTH: usize = 20;
+
+/// The quietest level the meters show.
+const METER_FLOOR_DB: f32 = -60.0;
+
+/// How long the clip indicator stays lit after the output clips.
+const CLIP_HOLD: Duration = Duration::from_secs(1);
+
+const HELP: &str = "up/down: pick  left/right: adjust  -/+: adjust more  d: default  \
+                    pgup/pgdn: preset  s: save  l: latch  space: sequencer  q: quit";
+
+/// The preset bank, as far as the terminal UI needs it.
+pub trait Presets {
+    /// The name of the preset playing, as `category/name` for those in a category, unless the
+    /// sound didn't come from one.
+    fn current(&self) -> Option<String>;
+
+    /// Switch to the preset `offset` places after the one playing, wrapping around the bank.
+    fn step(&self, offset: isize);
+
+    /// Save the sound playing now as a new preset.
+    fn save(&self);
+}
+
+/// Run the terminal UI until the user quits. Fails straight away if stdin isn't a terminal.
+pub fn run(commands: &CommandSender, presets: &dyn Presets) -> io::Result<()> {
+    if !stdin().is_terminal() {
+        return Err(io::Error::other("stdin is not a terminal"));
+    }
+    let mut terminal = RawTerminal::enter()?;
+    logger::divert(true);
+    let result = Ui::new(commands, presets).run(&mut terminal.0);
+    logger::divert(false);
+    drop(terminal);
+    result
+}
+
+struct Ui<'a> {
+    commands: &'a CommandSender,
+    presets: &'a dyn Presets,
+    params: Vec<(ParamId, ParamInfo)>,
+    /// The parameters' values, as last reported and then changed from here, so that keys pressed
+    /// faster than the synth reports back build on each other.
+    values: Vec<f32>,
+    /// The parameter picked, and how far the list is scrolled when it doesn't fit.
+    list: ListState,
+    /// Whether a parameter has changed since the preset was loaded or saved.
+    edited: bool,
+    clipped_blocks: usize,
+    clipped_at: Option<Instant>,
+}
+
+impl<'a> Ui<'a> {
+    fn new(commands: &'a CommandSender, presets: &'a dyn Presets) -> Self {
+        let params: Vec<_> = ParamId::fixed()
+            .filter_map(|param| Some((param, param.info()?)))
+            .collect();
+        Self {
+            commands,
+            presets,
+            values: vec![0.0; params.len()],
+            params,
+            list: ListState::default().with_selected(Some(0)),
+            edited: false,
+            clipped_blocks: commands.status().clipped_blocks(),
+            clipped_at: None,
+        }
+    }
+
+    fn run(&mut self, terminal: &mut DefaultTerminal) -> io::Result<()> {
+        loop {
+            let status = self.commands.status();
+            for ((param, _), value) in self.params.iter().zip(&mut self.values) {
+                *value = status.param(*param).unwrap_or_default();
+            }
+
+            terminal.draw(|frame| self.draw(frame, status))?;
+
+            // wait a frame for the first key, then take any others already pressed
+            let mut timeout = FRAME_INTERVAL;
+            while event::poll(timeout)? {
+                timeout = Duration::ZERO;
+                if let Event::Key(key) = event::read()? {
+                    if key.kind != KeyEventKind::Release && !self.press(key) {
+                        return Ok(());
+                    }
+                }
+            }
+        }
+    }
+
+    /// Do what `key` asks, returning whether to carry on.
+    fn press(&mut self, key: KeyEvent) -> bool {
+        let selected = self.selected();
+        match key.code {
+            KeyCode::Up => self.list.select(Some(selected.saturating_sub(1))),
+            KeyCode::Down => self
+                .list
+                .select(Some((selected + 1).min(self.params.len() - 1))),
+            KeyCode::Left => self.adjust(-1.0 / FINE_STEPS),
+            KeyCode::Right => self.adjust(1.0 / FINE_STEPS),
+            KeyCode::Char('-') => self.adjust(-1.0 / COARSE_STEPS),
+            KeyCode::Char('+') | KeyCode::Char('=') => self.adjust(1.0 / COARSE_STEPS),
+            // Ctrl-C arrives as a key, as raw mode turns off the signal
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return false,
+            KeyCode::Char('d') => {
+                let (_, info) = self.params[selected];
+                self.set(info.default);
+            }
+            KeyCode::PageUp | KeyCode::PageDown => {
+                self.presets
+                    .step(if key.code == KeyCode::PageUp { -1 } else { 1 });
+                self.edited = false;
+            }
+            KeyCode::Char('s') => {
+                self.presets.save();
+                self.edited = false;
+            }
+            KeyCode::Char('l') => {
+                let latch = self.commands.status().latch();
+                self.commands.send(SynthCommand::Latch(!latch));
+            }
+            KeyCode::Char(' ') => {
+                let (running, _, _) = self.commands.status().sequencer();
+                let command = if running {
+                    SequencerCommand::Stop
+                } else {
+                    SequencerCommand::Continue
+                };
+                self.commands.send(SynthCommand::Sequencer(command));
+            }
+            KeyCode::Char('q') | KeyCode::Esc => return false,
+            _ => (),
+        }
+        true
+    }
+
+    /// Where the picked parameter is in `params`.
+    fn selected(&self) -> usize {
+        self.list.selected().unwrap_or_default()
+    }
+
+    /// Move the selected parameter by `amount` of its range, along its curve, or for one which
+    /// picks a setting, to the next setting that way.
+    fn adjust(&mut self, amount: f32) {
+        let selected = self.selected();
+        let (_, info) = self.params[selected];
+        let value = self.values[selected];
+        let value = match info.curve {
+            Curve::Stepped => (value + amount.signum()).clamp(info.min, info.max),
+            Curve::Linear | Curve::Logarithmic => {
+                info.from_normalized(info.to_normalized(value) + amount)
+            }
+        };
+        self.set(value);
+    }
+
+    fn set(&mut self, value: f32) {
+        let selected = self.selected();
+        let (param, _) = self.params[selected];
+        self.values[selected] = value;
+        self.commands.send(SynthCommand::SetParam { param, value });
+        self.edited = true;
+    }
+
+    /// The whole screen: the state of the synth at the top, the parameters below, scrolled to
+    /// keep the picked one in sight if they don't fit, and the keys to press at the bottom.
+    fn draw(&mut self, frame: &mut Frame, status: &Status) {
+        let preset = self
+            .presets
+            .current()
+            .unwrap_or_else(|| "(unsaved)".to_string());
+        let mut lines = vec![
+            Line::from(vec![
+                "basic-synth".bold(),
+                Span::raw(format!(
+                    "   preset: {}{}   voices: {}{}",
+                    preset,
+                    if self.edited { " (edited)" } else { "" },
+                    status.active_voices(),
+                    if status.latch() { "   latched" } else { "" }
+                )),
+            ]),
+            Line::default(),
+        ];
+
+        let clipped_blocks = status.clipped_blocks();
+        if clipped_blocks > self.clipped_blocks {
+            self.clipped_blocks = clipped_blocks;
+            self.clipped_at = Some(Instant::now());
+        }
+        let clipping = self.clipped_at.is_some_and(|at| at.elapsed() < CLIP_HOLD);
+        let levels = status.levels();
+        for channel in 0..CHANNELS {
+            let label = if CHANNELS == 2 {
+                ["L", "R"][channel].to_string()
+            } else {
+                channel.to_string()
+            };
+            lines.push(Line::from(vec![
+                Span::raw(format!(
+                    "{} {} {:>6.1} dB ",
+                    label,
+                    meter(levels.rms[channel], levels.peak[channel]),
+                    to_db(levels.peak[channel]),
+                )),
+                if clipping {
+                    "CLIP".red().bold()
+                } else {
+                    Span::default()
+                },
+            ]));
+        }
+        lines.push(Line::default());
+
+        let (running, position, len) = status.sequencer();
+        let steps: String = (0..len)
+            .map(|step| if step == position { '#' } else { '.' })
+            .collect();
+        lines.push(Line::raw(format!(
+            "sequencer [{}] {}",
+            steps,
+            if running { "playing" } else { "stopped" }
+        )));
+        lines.push(Line::default());
+
+        let params = self
+            .params
+            .iter()
+            .zip(&self.values)
+            .map(|((param, info), value)| {
+                format!(
+                    "{:<14} {} {}",
+                    param.to_string(),
+                    bar(info.to_normalized(*value), PARAM_BAR_WIDTH),
+                    format_value(*param, info, *value)
+                )
+            });
+        let footer = vec![
+            Line::default(),
+            HELP.dim().into(),
+            Line::raw(logger::last_message().unwrap_or_default()),
+        ];
+
+        let [top, middle, bottom] = Layout::vertical([
+            Constraint::Length(lines.len() as u16),
+            Constraint::Min(1),
+            Constraint::Length(footer.len() as u16),
+        ])
+        .areas(frame.area());
+        frame.render_widget(Paragraph::new(lines), top);
+        frame.render_stateful_widget(
+            List::new(params)
+                .highlight_symbol("> ")
+                .highlight_spacing(HighlightSpacing::Always)
+                .highlight_style(Style::new().add_modifier(Modifier::REVERSED)),
+            middle,
+            &mut self.list,
+        );
+        frame.render_widget(Paragraph::new(footer), bottom);
+    }
+}
+
+/// A level meter: a bar filled up to the RMS level, with a mark at the peak.
+fn meter(rms: f32, peak: f32) -> String {
+    let position = |level: f32| {
+        let fraction = (to_db(level) - METER_FLOOR_DB) / -METER_FLOOR_DB;
+        (fraction.clamp(0.0, 1.0) * METER_WIDTH as f32).round() as usize
+    };
+    let (rms, peak) = (position(rms), position(peak));
+    let cells: String = (0..METER_WIDTH)
+        .map(|i| match i {
+            i if i < rms => '#',
+            i if i + 1 == peak => '|',
+            _ => '.',
+        })
+        .collect();
+    format!("[{}]", cells)
+}
+
+/// A bar filled up to `fraction` of `width`.
+fn bar(fraction: f32, width: usize) -> String {
+    let filled = (fraction.clamp(0.0, 1.0) * width as f32).round() as usize;
+    format!("[{}{}]", "=".repeat(filled), " ".repeat(width - filled))
+}
+
+fn to_db(level: f32) -> f32 {
+    (20.0 * level.log10()).max(METER_FLOOR_DB)
+}
+
+/// A parameter's value as it's best read: the name of the waveform, and the rest with their
+/// units, to as many decimal places as suit their size.
+fn format_value(param: ParamId, info: &ParamInfo, value: f32) -> String {
+    if param == ParamId::Waveform {
+        return Waveform::ALL
+            .get(value as usize)
+            .map_or("?", |waveform| waveform.name())
+            .to_string();
+    }
+    let precision = match value.abs() {
+        v if v >= 100.0 => 0,
+        v if v >= 10.0 => 1,
+        v if v >= 1.0 => 2,
+        _ => 3,
+    };
+    format!("{:.*} {}", precision, value, info.unit.symbol())
+}
+
+/// The terminal in raw mode, reading keys as they're pressed, and switched to its alternate
+/// screen, until dropped. Only this thread puts it back, so that a panic on another, such as the
+/// audio thread's, doesn't leave the UI running in a terminal which isn't set up for it.
+struct RawTerminal(DefaultTerminal);
+
+impl RawTerminal {
+    fn enter() -> io::Result<Self> {
+        let terminal = Terminal::new(CrosstermBackend::new(stdout()))?;
+        terminal::enable_raw_mode()?;
+        // from here on, dropping this puts the terminal back
+        let raw = Self(terminal);
+        execute!(stdout(), EnterAlternateScreen)?;
+        Ok(raw)
+    }
+}
+
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        let _ = execute!(stdout(), LeaveAlternateScreen);
+        let _ = self.0.show_cursor();
+        let _ = terminal::disable_raw_mode();
+    }
+}
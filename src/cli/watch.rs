@@ -0,0 +1,64 @@
+use std::{
+    fs::{self, File},
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, SystemTime},
+};
+
+use basic_synth::patch::Patch;
+
+use super::audio::CommandSender;
+
+/// How often to check whether the patch file has changed.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// How long a reloaded patch takes to fade in, so that edits don't click.
+const RELOAD_GLIDE: Duration = Duration::from_millis(50);
+
+/// Keeps an eye on the current patch file, and sends it to the synth again whenever it's saved,
+/// so that it can be edited while playing.
+#[derive(Clone)]
+pub struct PatchWatcher {
+    path: Arc<Mutex<Option<PathBuf>>>,
+}
+
+impl PatchWatcher {
+    /// Start watching in the background.
+    pub fn spawn(commands: CommandSender) -> Self {
+        let path = Arc::new(Mutex::new(None::<PathBuf>));
+        let watched = path.clone();
+        thread::spawn(move || {
+            let mut last: Option<(PathBuf, SystemTime)> = None;
+            loop {
+                thread::sleep(POLL_INTERVAL);
+                let path = match watched.lock().unwrap().clone() {
+                    Some(path) => path,
+                    None => continue,
+                };
+                let modified = match fs::metadata(&path).and_then(|m| m.modified()) {
+                    Ok(modified) => modified,
+                    // e.g. while an editor is replacing the file
+                    Err(_) => continue,
+                };
+                let seen = last.replace((path.clone(), modified));
+                if seen.is_none_or(|(p, m)| p != path || m == modified) {
+                    continue;
+                }
+                match File::open(&path).and_then(Patch::read) {
+                    Ok(patch) => {
+                        log::info!("Reloading {}", path.display());
+                        commands.glide_patch(patch, RELOAD_GLIDE);
+                    }
+                    Err(e) => log::error!("Failed to reload {}: {}", path.display(), e),
+                }
+            }
+        });
+        Self { path }
+    }
+
+    /// Watch a different file, or none (e.g. for a factory preset).
+    pub fn watch(&self, path: Option<PathBuf>) {
+        *self.path.lock().unwrap() = path;
+    }
+}
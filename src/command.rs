@@ -0,0 +1,613 @@
+//! Instructions for the synth: the one interface every frontend (the CLI, MIDI, OSC, plugins,
+//! tests) drives it through, compact enough to pass between threads through a lock-free queue
+//! (see `Synth::apply_queued`).
+//!
+//! With the `presets` feature, each command also has a one-line text form, e.g. `note-on 60 100` or
+//! `set cutoff = 800`, which `Display` writes and `FromStr` reads. With the `serde` feature,
+//! commands also serialize field by field, named as in the text form, e.g. `note-on`.
+
+use core::time::Duration;
+
+use alloc::boxed::Box;
+
+#[cfg(feature = "presets")]
+use {
+    crate::sequencer::SequencerClock,
+    alloc::{
+        format,
+        string::{String, ToString},
+        vec::Vec,
+    },
+    core::{fmt, str::FromStr},
+};
+#[cfg(feature = "midi")]
+use {
+    crate::{
+        map_range,
+        ump::{self, per_note, status},
+        PITCH_BEND_RANGE,
+    },
+    midi_msg::{ChannelModeMsg, ChannelVoiceMsg, ControlChange, MidiMsg, SystemRealTimeMsg},
+};
+
+use crate::{
+    generate::GeneratorSettings, param::ParamId, patch::Patch, sequencer::SequencerCommand,
+    NoteExpression,
+};
+
+/// Something for the synth to do, as applied by `Synth::apply`.
+///
+/// Patches are boxed, to keep the others small; dropping one frees it, so a real-time thread
+/// should carry those out somewhere it may allocate.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case"))]
+pub enum SynthCommand {
+    NoteOn {
+        note: u8,
+        velocity: u8,
+    },
+    /// Start a note with a 16-bit velocity, as MIDI 2.0 sends.
+    HighResNoteOn {
+        note: u8,
+        velocity: u16,
+    },
+    NoteOff {
+        note: u8,
+    },
+    /// Change one note alone, as `Synth::set_note_expression` does.
+    NoteExpression {
+        note: u8,
+        expression: NoteExpression,
+    },
+    /// Bend all notes by a number of semitones.
+    PitchBend(f32),
+    /// Set the filter cutoff frequency, in Hz.
+    Cutoff(f32),
+    /// Set the master volume, as a linear gain.
+    Volume(f32),
+    /// Release every note that is currently playing.
+    AllNotesOff,
+    /// Stop every voice immediately.
+    AllSoundOff,
+    /// Release every note and fade the output to silence over the given time, for good.
+    FadeOut(Duration),
+    /// Set the tempo, in beats per minute.
+    Tempo(f32),
+    /// A MIDI clock message, 24 of which make a beat.
+    ClockTick,
+    /// Move between the morph patches, from 0 to 1.
+    Morph(f32),
+    /// Set any parameter, as `Synth::set_param` does.
+    SetParam {
+        param: ParamId,
+        value: f32,
+    },
+    /// Switch to a patch, as `Synth::load_patch` does.
+    LoadPatch(Box<Patch>),
+    /// Move to a patch gradually over the given time, as `Synth::glide_to_patch` does.
+    GlideToPatch {
+        patch: Box<Patch>,
+        time: Duration,
+    },
+    /// Start, stop or change the step sequencer, as `Synth::control_sequencer` does.
+    Sequencer(SequencerCommand),
+    /// Latch notes or stop, as `Synth::set_latch` does.
+    Latch(bool),
+    /// Start playing random notes, or stop with `None`, as `Synth::set_generator` does.
+    Generate(Option<GeneratorSettings>),
+}
+
+impl SynthCommand {
+    /// Translate a MIDI message, on any channel.
+    ///
+    /// Notes, pitch bend, brightness (CC 74, mapped to the filter cutoff), volume (CC 7), general
+    /// purpose controller 1 (CC 16, mapped to the morph), the "all notes off" and "all sound off"
+    /// channel mode messages and timing clock are understood; anything else gives `None`.
+    #[cfg(feature = "midi")]
+    pub fn from_midi(msg: &MidiMsg) -> Option<Self> {
+        match msg {
+            MidiMsg::ChannelVoice { msg, .. } | MidiMsg::RunningChannelVoice { msg, .. } => {
+                match *msg {
+                    ChannelVoiceMsg::NoteOn { note, velocity: 0 }
+                    | ChannelVoiceMsg::NoteOff { note, .. }
+                    | ChannelVoiceMsg::HighResNoteOff { note, .. } => Some(Self::NoteOff { note }),
+                    ChannelVoiceMsg::NoteOn { note, velocity } => {
+                        Some(Self::NoteOn { note, velocity })
+                    }
+                    // from 14 bits to 16, repeating the top bits so that the largest stays largest
+                    ChannelVoiceMsg::HighResNoteOn { note, velocity } => {
+                        Some(Self::HighResNoteOn {
+                            note,
+                            velocity: velocity << 2 | velocity >> 12,
+                        })
+                    }
+                    ChannelVoiceMsg::PitchBend { bend } => Some(Self::PitchBend(map_range(
+                        bend as f32,
+                        (0.0, 16384.0),
+                        (-PITCH_BEND_RANGE, PITCH_BEND_RANGE),
+                    ))),
+                    ChannelVoiceMsg::ControlChange {
+                        control: ControlChange::Brightness(value),
+                    }
+                    | ChannelVoiceMsg::ControlChange {
+                        control: ControlChange::SoundControl5(value),
+                    } => Some(Self::cutoff_control(value as f32 / 127.0)),
+                    ChannelVoiceMsg::ControlChange {
+                        control: ControlChange::Volume(value),
+                    } => Some(Self::volume_control(value as f32 / 16383.0)),
+                    ChannelVoiceMsg::ControlChange {
+                        control: ControlChange::GeneralPurpose1(value),
+                    } => Some(Self::Morph(value as f32 / 16383.0)),
+                    _ => None,
+                }
+            }
+            MidiMsg::ChannelMode { msg, .. } | MidiMsg::RunningChannelMode { msg, .. } => match msg
+            {
+                ChannelModeMsg::AllNotesOff => Some(Self::AllNotesOff),
+                ChannelModeMsg::AllSoundOff => Some(Self::AllSoundOff),
+                _ => None,
+            },
+            MidiMsg::SystemRealTime { msg } => match msg {
+                SystemRealTimeMsg::TimingClock => Some(Self::ClockTick),
+                SystemRealTimeMsg::Start => Some(Self::Sequencer(SequencerCommand::Start)),
+                SystemRealTimeMsg::Continue => Some(Self::Sequencer(SequencerCommand::Continue)),
+                SystemRealTimeMsg::Stop => Some(Self::Sequencer(SequencerCommand::Stop)),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Translate a Universal MIDI Packet (see `ump`), on any group and channel.
+    ///
+    /// MIDI 1.0 messages are understood as by `from_midi`. Of MIDI 2.0 messages, notes are
+    /// understood with their full 16-bit velocities, pitch bend and the controllers `from_midi`
+    /// understands with their full 32 bits, along with "all sound off" (CC 120) and "all notes off"
+    /// (CC 123). Notes can be bent on their own, by the same range as the whole synth, and the
+    /// registered per-note controllers for pitch, volume and pan change single notes' expression.
+    /// Anything else gives `None`.
+    #[cfg(feature = "midi")]
+    pub fn from_ump(packet: &[u32]) -> Option<Self> {
+        let first = *packet.first()?;
+        match ump::message_type(first) {
+            ump::SYSTEM | ump::MIDI1_CHANNEL_VOICE => {
+                let (msg, _) = MidiMsg::from_midi(&ump::midi1_bytes(first)).ok()?;
+                Self::from_midi(&msg)
+            }
+            ump::MIDI2_CHANNEL_VOICE => {
+                let data = *packet.get(1)?;
+                let note = (first >> 8) as u8 & 0x7f;
+                let index = first as u8;
+                match (first >> 20) as u8 & 0xf {
+                    status::NOTE_ON => Some(Self::HighResNoteOn {
+                        note,
+                        velocity: (data >> 16) as u16,
+                    }),
+                    status::NOTE_OFF => Some(Self::NoteOff { note }),
+                    status::PER_NOTE_PITCH_BEND => Some(Self::NoteExpression {
+                        note,
+                        expression: NoteExpression::Tuning(Self::bend(data)),
+                    }),
+                    status::REGISTERED_PER_NOTE_CONTROLLER => {
+                        let expression = match index {
+                            per_note::PITCH => NoteExpression::Tuning(
+                                (data as f64 / (1 << 25) as f64 - note as f64) as f32,
+                            ),
+                            per_note::VOLUME => NoteExpression::Volume(ump::unit(data).powi(2)),
+                            per_note::PAN => NoteExpression::Pan(ump::unit(data) * 2.0 - 1.0),
+                            _ => return None,
+                        };
+                        Some(Self::NoteExpression { note, expression })
+                    }
+                    // a controller's number is where a note's would be
+                    status::CONTROL_CHANGE => match note {
+                        7 => Some(Self::volume_control(ump::unit(data))),
+                        16 => Some(Self::Morph(ump::unit(data))),
+                        74 => Some(Self::cutoff_control(ump::unit(data))),
+                        120 => Some(Self::AllSoundOff),
+                        123 => Some(Self::AllNotesOff),
+                        _ => None,
+                    },
+                    status::PITCH_BEND => Some(Self::PitchBend(Self::bend(data))),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// The cutoff for a controller from 0 to 1: exponential, so the knob feels even across its
+    /// range.
+    #[cfg(feature = "midi")]
+    fn cutoff_control(value: f32) -> Self {
+        Self::Cutoff(20.0 * 1000_f32.powf(value))
+    }
+
+    /// The volume for a controller from 0 to 1: squared, as recommended by the MIDI spec.
+    #[cfg(feature = "midi")]
+    fn volume_control(value: f32) -> Self {
+        Self::Volume(value.powi(2))
+    }
+
+    /// Semitones of bend for a 32-bit pitch bend.
+    #[cfg(feature = "midi")]
+    fn bend(value: u32) -> f32 {
+        let offset = value as i64 - ump::CENTER as i64;
+        offset as f32 / ump::CENTER as f32 * PITCH_BEND_RANGE
+    }
+}
+
+/// The command's text form, all on one line: patches are written with `; ` between their lines.
+#[cfg(feature = "presets")]
+impl fmt::Display for SynthCommand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::NoteOn { note, velocity } => write!(f, "note-on {} {}", note, velocity),
+            Self::HighResNoteOn { note, velocity } => {
+                write!(f, "high-res-note-on {} {}", note, velocity)
+            }
+            Self::NoteOff { note } => write!(f, "note-off {}", note),
+            Self::NoteExpression { note, expression } => {
+                let (name, value) = match expression {
+                    NoteExpression::Volume(gain) => ("volume", gain),
+                    NoteExpression::Pan(pan) => ("pan", pan),
+                    NoteExpression::Tuning(semitones) => ("tuning", semitones),
+                };
+                write!(f, "expression {} {} {}", note, name, value)
+            }
+            Self::PitchBend(semitones) => write!(f, "pitch-bend {}", semitones),
+            Self::Cutoff(cutoff) => write!(f, "cutoff {}", cutoff),
+            Self::Volume(volume) => write!(f, "volume {}", volume),
+            Self::AllNotesOff => write!(f, "all-notes-off"),
+            Self::AllSoundOff => write!(f, "all-sound-off"),
+            Self::FadeOut(time) => write!(f, "fade-out {}", time.as_secs_f32()),
+            Self::Tempo(bpm) => write!(f, "tempo {}", bpm),
+            Self::ClockTick => write!(f, "clock-tick"),
+            Self::Morph(amount) => write!(f, "morph {}", amount),
+            Self::SetParam { param, value } => write!(f, "set {} = {}", param, value),
+            Self::LoadPatch(patch) => write!(f, "load {}", patch_line(patch)?),
+            Self::GlideToPatch { patch, time } => {
+                write!(f, "glide {} {}", time.as_secs_f32(), patch_line(patch)?)
+            }
+            Self::Sequencer(command) => {
+                write!(f, "sequencer ")?;
+                match command {
+                    SequencerCommand::Start => write!(f, "start"),
+                    SequencerCommand::Continue => write!(f, "continue"),
+                    SequencerCommand::Stop => write!(f, "stop"),
+                    SequencerCommand::SetClock(SequencerClock::Internal) => {
+                        write!(f, "clock internal")
+                    }
+                    SequencerCommand::SetClock(SequencerClock::Midi) => write!(f, "clock midi"),
+                    SequencerCommand::SetStep { index, step } => {
+                        write!(f, "step {} {}", index, step)
+                    }
+                    SequencerCommand::SetLength(len) => write!(f, "length {}", len),
+                    SequencerCommand::LoadPattern(pattern) => {
+                        let text = pattern.to_string();
+                        write!(f, "pattern {}", text.lines().collect::<Vec<_>>().join("; "))
+                    }
+                }
+            }
+            Self::Latch(enabled) => write!(f, "latch {}", if *enabled { "on" } else { "off" }),
+            Self::Generate(Some(settings)) => write!(f, "generate {}", settings),
+            Self::Generate(None) => write!(f, "generate off"),
+        }
+    }
+}
+
+#[cfg(feature = "presets")]
+impl FromStr for SynthCommand {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (name, args) = s.split_once(' ').unwrap_or((s, ""));
+        let args = args.trim();
+        let number = |arg: &str| -> Result<f32, String> {
+            arg.parse::<f32>()
+                .ok()
+                .filter(|n| n.is_finite())
+                .ok_or_else(|| format!("Invalid number: {}", arg))
+        };
+        // notes and velocities, as in MIDI
+        let data_byte = |arg: &str, what: &str| -> Result<u8, String> {
+            arg.parse::<u8>()
+                .ok()
+                .filter(|n| *n < 128)
+                .ok_or_else(|| format!("Invalid {}: {}", what, arg))
+        };
+        let time = |arg: &str| -> Result<Duration, String> {
+            Duration::try_from_secs_f32(number(arg)?).map_err(|_| format!("Invalid time: {}", arg))
+        };
+        let no_args = |command| {
+            if args.is_empty() {
+                Ok(command)
+            } else {
+                Err(format!("Unexpected arguments: {}", args))
+            }
+        };
+        Ok(match name {
+            "note-on" => {
+                let (note_arg, velocity) = args.split_once(' ').unwrap_or((args, ""));
+                Self::NoteOn {
+                    note: data_byte(note_arg, "note")?,
+                    velocity: data_byte(velocity.trim(), "velocity")?,
+                }
+            }
+            "high-res-note-on" => {
+                let (note_arg, velocity) = args.split_once(' ').unwrap_or((args, ""));
+                Self::HighResNoteOn {
+                    note: data_byte(note_arg, "note")?,
+                    velocity: velocity
+                        .trim()
+                        .parse()
+                        .map_err(|_| format!("Invalid velocity: {}", velocity.trim()))?,
+                }
+            }
+            "note-off" => Self::NoteOff {
+                note: data_byte(args, "note")?,
+            },
+            "expression" => {
+                let words: Vec<&str> = args.split_whitespace().collect();
+                let (note, name, value) = match words[..] {
+                    [note, name, value] => (note, name, number(value)?),
+                    _ => return Err(format!("Expected `note expression value`: {}", args)),
+                };
+                let expression = match name {
+                    "volume" => NoteExpression::Volume(value),
+                    "pan" => NoteExpression::Pan(value),
+                    "tuning" => NoteExpression::Tuning(value),
+                    _ => return Err(format!("Unknown expression: {}", name)),
+                };
+                Self::NoteExpression {
+                    note: data_byte(note, "note")?,
+                    expression,
+                }
+            }
+            "pitch-bend" => Self::PitchBend(number(args)?),
+            "cutoff" => Self::Cutoff(number(args)?),
+            "volume" => Self::Volume(number(args)?),
+            "all-notes-off" => no_args(Self::AllNotesOff)?,
+            "all-sound-off" => no_args(Self::AllSoundOff)?,
+            "fade-out" => Self::FadeOut(time(args)?),
+            "tempo" => Self::Tempo(number(args)?),
+            "clock-tick" => no_args(Self::ClockTick)?,
+            "morph" => Self::Morph(number(args)?),
+            "set" => {
+                let (key, value) = args
+                    .split_once('=')
+                    .ok_or_else(|| format!("Expected `parameter = value`: {}", args))?;
+                let param = key.trim().parse()?;
+                Self::SetParam {
+                    param,
+                    value: number(value.trim())?,
+                }
+            }
+            "load" => Self::LoadPatch(Box::new(parse_patch_line(args)?)),
+            "glide" => {
+                let (time_arg, patch) = args.split_once(' ').unwrap_or((args, ""));
+                Self::GlideToPatch {
+                    patch: Box::new(parse_patch_line(patch)?),
+                    time: time(time_arg)?,
+                }
+            }
+            "sequencer" => {
+                let (command, args) = args.split_once(' ').unwrap_or((args, ""));
+                let args = args.trim();
+                let count = |arg: &str| -> Result<usize, String> {
+                    arg.parse().map_err(|_| format!("Invalid step: {}", arg))
+                };
+                Self::Sequencer(match (command, args) {
+                    ("start", "") => SequencerCommand::Start,
+                    ("continue", "") => SequencerCommand::Continue,
+                    ("stop", "") => SequencerCommand::Stop,
+                    ("clock", "internal") => SequencerCommand::SetClock(SequencerClock::Internal),
+                    ("clock", "midi") => SequencerCommand::SetClock(SequencerClock::Midi),
+                    ("step", args) => {
+                        let (index, step) = args.split_once(' ').unwrap_or((args, ""));
+                        SequencerCommand::SetStep {
+                            index: count(index)?,
+                            step: step.parse()?,
+                        }
+                    }
+                    ("length", len) => SequencerCommand::SetLength(count(len)?),
+                    ("pattern", steps) => {
+                        SequencerCommand::LoadPattern(Box::new(steps.replace("; ", "\n").parse()?))
+                    }
+                    _ => {
+                        let text = format!("{} {}", command, args);
+                        return Err(format!("Unknown sequencer command: {}", text.trim_end()));
+                    }
+                })
+            }
+            "latch" => Self::Latch(match args {
+                "on" => true,
+                "off" => false,
+                _ => return Err(format!("Expected `on` or `off`: {}", args)),
+            }),
+            "generate" => Self::Generate(match args {
+                "off" => None,
+                settings => Some(settings.parse()?),
+            }),
+            _ => return Err(format!("Unknown command: {}", name)),
+        })
+    }
+}
+
+/// A patch's text, with its lines joined by `; `.
+#[cfg(feature = "presets")]
+fn patch_line(patch: &Patch) -> Result<String, fmt::Error> {
+    let mut text = Vec::new();
+    patch.write(&mut text).map_err(|_| fmt::Error)?;
+    let text = String::from_utf8_lossy(&text);
+    Ok(text.lines().collect::<Vec<_>>().join("; "))
+}
+
+#[cfg(feature = "presets")]
+fn parse_patch_line(line: &str) -> Result<Patch, String> {
+    Patch::read(line.replace("; ", "\n").as_bytes()).map_err(|e| e.to_string())
+}
+
+#[cfg(all(test, feature = "presets"))]
+mod tests {
+    use super::*;
+    use crate::{
+        effects::Placement,
+        generate::Scale,
+        sequencer::{Pattern, Step},
+        Waveform,
+    };
+
+    fn round_trip(command: SynthCommand) {
+        let text = command.to_string();
+        assert_eq!(text.parse(), Ok(command), "{}", text);
+    }
+
+    #[test]
+    fn commands_round_trip() {
+        let patch = Patch {
+            waveform: Waveform::Pulse,
+            cutoff: 1234.5,
+            volume: 0.45,
+            send_levels: [0.2, 0.0],
+            ..Patch::default()
+        };
+        let mut pattern = Pattern::new(3);
+        pattern.set_step(0, Step::note(60, 100));
+        pattern.set_step(
+            2,
+            Step {
+                tie: true,
+                cutoff: Some(800.0),
+                ..Step::note(67, 90)
+            },
+        );
+        for command in [
+            SynthCommand::NoteOn {
+                note: 60,
+                velocity: 100,
+            },
+            SynthCommand::HighResNoteOn {
+                note: 127,
+                velocity: 65535,
+            },
+            SynthCommand::NoteOff { note: 0 },
+            SynthCommand::NoteExpression {
+                note: 64,
+                expression: NoteExpression::Volume(0.5),
+            },
+            SynthCommand::NoteExpression {
+                note: 64,
+                expression: NoteExpression::Pan(-0.25),
+            },
+            SynthCommand::NoteExpression {
+                note: 64,
+                expression: NoteExpression::Tuning(1.5),
+            },
+            SynthCommand::PitchBend(-2.0),
+            SynthCommand::Cutoff(1234.5),
+            SynthCommand::Volume(0.8),
+            SynthCommand::AllNotesOff,
+            SynthCommand::AllSoundOff,
+            SynthCommand::FadeOut(Duration::from_millis(250)),
+            SynthCommand::Tempo(128.0),
+            SynthCommand::ClockTick,
+            SynthCommand::Morph(0.75),
+            SynthCommand::SetParam {
+                param: ParamId::Cutoff,
+                value: 800.0,
+            },
+            SynthCommand::SetParam {
+                param: ParamId::ReturnLevel(1),
+                value: 0.5,
+            },
+            SynthCommand::SetParam {
+                param: ParamId::Effect {
+                    placement: Placement::Send(0),
+                    slot: 1,
+                    parameter: 2,
+                },
+                value: 0.3,
+            },
+            SynthCommand::LoadPatch(Box::default()),
+            SynthCommand::LoadPatch(Box::new(patch.clone())),
+            SynthCommand::GlideToPatch {
+                patch: Box::new(patch),
+                time: Duration::from_millis(1500),
+            },
+            SynthCommand::Sequencer(SequencerCommand::Start),
+            SynthCommand::Sequencer(SequencerCommand::Continue),
+            SynthCommand::Sequencer(SequencerCommand::Stop),
+            SynthCommand::Sequencer(SequencerCommand::SetClock(SequencerClock::Internal)),
+            SynthCommand::Sequencer(SequencerCommand::SetClock(SequencerClock::Midi)),
+            SynthCommand::Sequencer(SequencerCommand::SetStep {
+                index: 5,
+                step: Step::note(48, 127),
+            }),
+            SynthCommand::Sequencer(SequencerCommand::SetStep {
+                index: 0,
+                step: Step::REST,
+            }),
+            SynthCommand::Sequencer(SequencerCommand::SetLength(12)),
+            SynthCommand::Sequencer(SequencerCommand::LoadPattern(Box::new(pattern))),
+            SynthCommand::Latch(true),
+            SynthCommand::Latch(false),
+            SynthCommand::Generate(None),
+            SynthCommand::Generate(Some(GeneratorSettings {
+                density: 0.6,
+                scale: Scale::Dorian,
+                root: 36,
+                octaves: 3,
+                grid: 3,
+                seed: 42,
+            })),
+        ] {
+            round_trip(command);
+        }
+    }
+
+    #[cfg(feature = "effects")]
+    #[test]
+    fn patches_with_effects_round_trip() {
+        let mut delay = crate::patch::EffectPatch::new("delay");
+        delay.placement = Placement::Send(1);
+        delay.parameters = vec![("feedback".to_string(), 0.4)];
+        let patch = Patch {
+            effects: vec![delay, crate::patch::EffectPatch::new("chorus")],
+            ..Patch::default()
+        };
+        round_trip(SynthCommand::LoadPatch(Box::new(patch)));
+    }
+
+    #[test]
+    fn rejects_bad_commands() {
+        for text in [
+            "",
+            "note-on",
+            "note-on 128 100",
+            "note-on 60 -1",
+            "note-off sixty",
+            "expression 60 volume",
+            "expression 60 loudness 1",
+            "cutoff NaN",
+            "volume inf",
+            "fade-out -1",
+            "all-notes-off now",
+            "set cutoff 800",
+            "set loudness = 1",
+            "load cutoff = -5",
+            "glide 1 waveform = kazoo",
+            "sequencer rewind",
+            "sequencer clock external",
+            "sequencer step x 60",
+            "sequencer pattern 60 100 2",
+            "latch maybe",
+            "generate density=2",
+            "bogus",
+        ] {
+            assert!(text.parse::<SynthCommand>().is_err(), "{:?} parsed", text);
+        }
+    }
+}
@@ -0,0 +1,118 @@
+//! Changing a synth's parameters from other threads without ever blocking the audio thread, through
+//! a `SynthController` from `Synth::controller`.
+//!
+//! Every parameter has its own atomic slot, which the controller writes and the synth reads at the
+//! start of each block, so neither side waits for the other. Only the latest value of a parameter
+//! is kept: if it's set twice within a block, only the second counts.
+
+use {
+    alloc::{sync::Arc, vec::Vec},
+    core::sync::atomic::{AtomicBool, AtomicU32, Ordering},
+};
+
+use crate::{effects::Placement, param::ParamId, send::SENDS, Synth};
+
+/// Most effects in a chain whose parameters can be set through a controller.
+const MAX_SLOTS: usize = 8;
+
+/// Most parameters of an effect which can be set through a controller.
+const MAX_PARAMETERS: usize = 16;
+
+/// The values written by the controllers, waiting for the synth to pick them up.
+pub(crate) struct Controls {
+    params: Vec<ParamId>,
+    /// The `f32` bits of each parameter's latest value.
+    values: Vec<AtomicU32>,
+    /// Which of `values` have changed since the synth last looked.
+    changed: Vec<AtomicBool>,
+    /// Whether any of `changed` are set, so that the synth doesn't have to look through them all
+    /// every block.
+    pending: AtomicBool,
+}
+
+impl Controls {
+    pub(crate) fn new() -> Self {
+        let placements = [Placement::Voice, Placement::Master]
+            .iter()
+            .copied()
+            .chain((0..SENDS).map(Placement::Send));
+        let effects = placements.flat_map(|placement| {
+            (0..MAX_SLOTS * MAX_PARAMETERS).map(move |index| ParamId::Effect {
+                placement,
+                slot: index / MAX_PARAMETERS,
+                parameter: index % MAX_PARAMETERS,
+            })
+        });
+        let params: Vec<ParamId> = ParamId::fixed().chain(effects).collect();
+        Self {
+            values: params.iter().map(|_| AtomicU32::new(0)).collect(),
+            changed: params.iter().map(|_| AtomicBool::new(false)).collect(),
+            params,
+            pending: AtomicBool::new(false),
+        }
+    }
+
+    /// Where a parameter is kept, if it can be set through a controller.
+    fn index(&self, param: ParamId) -> Option<usize> {
+        let fixed = self.params.len() - (2 + SENDS) * MAX_SLOTS * MAX_PARAMETERS;
+        match param {
+            ParamId::Effect {
+                placement,
+                slot,
+                parameter,
+            } => {
+                let chain = match placement {
+                    Placement::Voice => 0,
+                    Placement::Master => 1,
+                    Placement::Send(send) if send < SENDS => 2 + send,
+                    Placement::Send(_) => return None,
+                };
+                if slot >= MAX_SLOTS || parameter >= MAX_PARAMETERS {
+                    return None;
+                }
+                Some(fixed + (chain * MAX_SLOTS + slot) * MAX_PARAMETERS + parameter)
+            }
+            _ => self.params[..fixed].iter().position(|p| *p == param),
+        }
+    }
+
+    /// Apply every value written since the last time, from the audio thread.
+    pub(crate) fn apply(&self, synth: &mut Synth) {
+        if !self.pending.swap(false, Ordering::Acquire) {
+            return;
+        }
+        for ((param, value), changed) in self.params.iter().zip(&self.values).zip(&self.changed) {
+            if changed.swap(false, Ordering::Acquire) {
+                // parameters of effects which have since been removed are left alone
+                let _ = synth.set_param(*param, f32::from_bits(value.load(Ordering::Relaxed)));
+            }
+        }
+    }
+}
+
+/// A handle for changing a synth's parameters from any thread, which never blocks. Changes are
+/// picked up at the start of the synth's next block. Clones all control the same synth.
+#[derive(Clone)]
+pub struct SynthController {
+    controls: Arc<Controls>,
+}
+
+impl SynthController {
+    pub(crate) fn new(controls: Arc<Controls>) -> Self {
+        Self { controls }
+    }
+
+    /// Set a parameter, as `Synth::set_param` would. Returns false if it can't be set through a
+    /// controller, which is the case for effects after the first 8 in a chain, and for effect
+    /// parameters after the first 16.
+    pub fn set_param(&self, param: ParamId, value: f32) -> bool {
+        let index = match self.controls.index(param) {
+            Some(index) => index,
+            None => return false,
+        };
+        self.controls.values[index].store(value.to_bits(), Ordering::Relaxed);
+        self.controls.changed[index].store(true, Ordering::Release);
+        self.controls.pending.store(true, Ordering::Release);
+        true
+    }
+}
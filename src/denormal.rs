@@ -0,0 +1,130 @@
+//! Keeping denormal numbers out of the signal path.
+//!
+//! Feedback loops and filters fading out towards silence end up on values so small that they're
+//! stored as denormals, which many CPUs handle dozens of times slower than ordinary numbers, so a
+//! long release tail can suddenly eat up the whole audio callback. Where the CPU can be told to
+//! treat them as zero, that's done while rendering; elsewhere (and in the voices' own filters
+//! either way), values that small are flushed by hand.
+
+use crate::Sample;
+
+/// Anything smaller than this is as good as silence: hundreds of decibels down, yet still well
+/// above the denormal range.
+const THRESHOLD: f64 = 1e-20;
+
+/// `value`, or zero if it's too small to matter.
+pub(crate) fn flush<T: Sample>(value: T) -> T {
+    if value.abs() < T::from_f64(THRESHOLD) {
+        T::ZERO
+    } else {
+        value
+    }
+}
+
+/// Has the CPU flush denormals to zero for as long as it lives, putting back the previous setting
+/// when dropped. Does nothing on CPUs it doesn't know how to tell.
+pub(crate) struct FlushDenormals {
+    #[cfg(any(
+        all(
+            any(target_arch = "x86", target_arch = "x86_64"),
+            target_feature = "sse"
+        ),
+        target_arch = "aarch64"
+    ))]
+    previous: usize,
+}
+
+#[cfg(all(
+    any(target_arch = "x86", target_arch = "x86_64"),
+    target_feature = "sse"
+))]
+impl FlushDenormals {
+    /// The "flush to zero" and "denormals are zero" bits of MXCSR.
+    const FLAGS: u32 = 1 << 15 | 1 << 6;
+
+    pub(crate) fn new() -> Self {
+        let previous = Self::get();
+        Self::set(previous | Self::FLAGS);
+        Self {
+            previous: previous as usize,
+        }
+    }
+
+    fn get() -> u32 {
+        let mut csr = 0_u32;
+        // SAFETY: only stores the control register into `csr`
+        unsafe {
+            core::arch::asm!("stmxcsr [{}]", in(reg) &mut csr, options(nostack, preserves_flags));
+        }
+        csr
+    }
+
+    fn set(csr: u32) {
+        // SAFETY: only changes how floating point numbers are rounded and flushed
+        unsafe {
+            core::arch::asm!("ldmxcsr [{}]", in(reg) &csr, options(nostack, readonly));
+        }
+    }
+}
+
+#[cfg(all(
+    any(target_arch = "x86", target_arch = "x86_64"),
+    target_feature = "sse"
+))]
+impl Drop for FlushDenormals {
+    fn drop(&mut self) {
+        Self::set(self.previous as u32);
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+impl FlushDenormals {
+    /// The "flush to zero" bit of FPCR.
+    const FLAGS: usize = 1 << 24;
+
+    pub(crate) fn new() -> Self {
+        let previous = Self::get();
+        Self::set(previous | Self::FLAGS);
+        Self { previous }
+    }
+
+    fn get() -> usize {
+        let fpcr: usize;
+        // SAFETY: only reads the control register
+        unsafe {
+            core::arch::asm!(
+                "mrs {}, fpcr",
+                out(reg) fpcr,
+                options(nomem, nostack, preserves_flags)
+            );
+        }
+        fpcr
+    }
+
+    fn set(fpcr: usize) {
+        // SAFETY: only changes how floating point numbers are rounded and flushed
+        unsafe {
+            core::arch::asm!("msr fpcr, {}", in(reg) fpcr, options(nomem, nostack));
+        }
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+impl Drop for FlushDenormals {
+    fn drop(&mut self) {
+        Self::set(self.previous);
+    }
+}
+
+#[cfg(not(any(
+    all(
+        any(target_arch = "x86", target_arch = "x86_64"),
+        target_feature = "sse"
+    ),
+    target_arch = "aarch64"
+)))]
+impl FlushDenormals {
+    pub(crate) fn new() -> Self {
+        Self {}
+    }
+}
@@ -0,0 +1,52 @@
+//! Conversion of float samples to integer formats, with TPDF (triangular probability density
+//! function) dither.
+//!
+//! Rounding straight to the nearest integer leaves quantization error which follows the signal,
+//! so quiet passages and fade-outs pick up harmonic distortion. Adding a little noise first turns
+//! that error into a steady, signal-independent hiss, at the level of the least significant bit.
+
+#[cfg(not(any(feature = "std", test)))]
+use crate::math::Float;
+
+/// Adds dither noise while converting samples to integers.
+#[derive(Debug, Clone)]
+pub struct Dither {
+    state: u32,
+}
+
+impl Default for Dither {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Dither {
+    pub fn new() -> Self {
+        Self { state: 0x2545_F491 }
+    }
+
+    /// Convert a sample in the range -1 to 1 to 16 bits.
+    pub fn to_i16(&mut self, sample: f32) -> i16 {
+        self.quantize(sample, 16) as i16
+    }
+
+    /// Convert a sample in the range -1 to 1 to 24 bits, in the low bits of an `i32`.
+    pub fn to_i24(&mut self, sample: f32) -> i32 {
+        self.quantize(sample, 24)
+    }
+
+    fn quantize(&mut self, sample: f32, bits: u32) -> i32 {
+        let max = ((1_i64 << (bits - 1)) - 1) as f64;
+        // the difference of two uniform values is triangular, spanning one LSB either way
+        let noise = self.uniform() - self.uniform();
+        (sample as f64 * max + noise).round().clamp(-max - 1.0, max) as i32
+    }
+
+    /// Uniformly distributed noise from 0 to 1, from a xorshift generator.
+    fn uniform(&mut self) -> f64 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 17;
+        self.state ^= self.state << 5;
+        self.state as f64 / u32::MAX as f64
+    }
+}
@@ -0,0 +1,499 @@
+//! Effects which process the synth's output (mixed, or one voice at a time), and the chain of
+//! slots they're arranged in.
+
+#[cfg(feature = "effects")]
+pub mod autopan;
+pub mod biquad;
+#[cfg(feature = "effects")]
+pub mod bitcrusher;
+#[cfg(feature = "effects")]
+pub mod chorus;
+#[cfg(feature = "effects")]
+pub mod compressor;
+#[cfg(feature = "effects")]
+pub mod delay;
+#[cfg(feature = "effects")]
+pub mod distortion;
+#[cfg(feature = "effects")]
+pub mod eq;
+pub mod gate;
+#[cfg(feature = "effects")]
+pub mod phaser;
+#[cfg(feature = "effects")]
+pub mod pitch_shifter;
+#[cfg(feature = "effects")]
+pub mod reverb;
+#[cfg(feature = "effects")]
+pub mod rotary;
+#[cfg(feature = "effects")]
+pub mod tape;
+#[cfg(feature = "effects")]
+pub mod tremolo;
+pub mod vocoder;
+#[cfg(feature = "effects")]
+pub mod wavefolder;
+#[cfg(feature = "effects")]
+pub mod widener;
+
+use {
+    alloc::{boxed::Box, vec::Vec},
+    core::fmt,
+};
+
+use crate::{
+    param::{ParamInfo, Unit},
+    Frame,
+};
+
+#[cfg(feature = "effects")]
+use {
+    crate::{Decimator, Oversampling},
+    core::mem,
+};
+
+#[cfg(not(any(feature = "std", test)))]
+use crate::math::Float;
+
+/// Names of the built-in effects, as accepted by `by_name`.
+#[cfg(feature = "effects")]
+pub const NAMES: &[&str] = &[
+    "autopan",
+    "bitcrusher",
+    "chorus",
+    "compressor",
+    "delay",
+    "distortion",
+    "eq",
+    "gate",
+    "phaser",
+    "pitch",
+    "reverb",
+    "rotary",
+    "tape",
+    "tremolo",
+    "wavefolder",
+    "widener",
+];
+
+/// Without the `effects` feature, there are no built-in effects.
+#[cfg(not(feature = "effects"))]
+pub const NAMES: &[&str] = &[];
+
+/// Tempo assumed until one is set, in beats per minute.
+pub const DEFAULT_TEMPO: f32 = 120.0;
+
+/// Highest value of a sync parameter, as read by `NoteDivision::from_parameter`.
+#[cfg(feature = "effects")]
+const SYNC_STEPS: f32 = NoteDivision::ALL.len() as f32;
+
+/// A length of time as a fraction of a bar of 4/4, for timings locked to the tempo.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NoteDivision {
+    Whole,
+    Half,
+    DottedQuarter,
+    Quarter,
+    QuarterTriplet,
+    DottedEighth,
+    Eighth,
+    EighthTriplet,
+    Sixteenth,
+    SixteenthTriplet,
+    ThirtySecond,
+}
+
+impl NoteDivision {
+    /// Every division, longest first, in the order used for their indices.
+    pub const ALL: [Self; 11] = [
+        Self::Whole,
+        Self::Half,
+        Self::DottedQuarter,
+        Self::Quarter,
+        Self::QuarterTriplet,
+        Self::DottedEighth,
+        Self::Eighth,
+        Self::EighthTriplet,
+        Self::Sixteenth,
+        Self::SixteenthTriplet,
+        Self::ThirtySecond,
+    ];
+
+    /// Look up a division by its position in `ALL`.
+    pub fn from_index(index: usize) -> Option<Self> {
+        Self::ALL.get(index).copied()
+    }
+
+    pub fn index(self) -> usize {
+        Self::ALL.iter().position(|d| *d == self).unwrap()
+    }
+
+    /// Read a sync setting from an effect parameter: 0 for free time, or 1 more than the index of
+    /// a note division.
+    pub fn from_parameter(value: f32) -> Option<Self> {
+        (value.round() as usize)
+            .checked_sub(1)
+            .and_then(Self::from_index)
+    }
+
+    /// Write a sync setting as an effect parameter, the opposite of `from_parameter`.
+    pub fn to_parameter(division: Option<Self>) -> f32 {
+        division.map_or(0.0, |d| (d.index() + 1) as f32)
+    }
+
+    /// How many quarter notes long the division is.
+    pub fn beats(self) -> f32 {
+        match self {
+            Self::Whole => 4.0,
+            Self::Half => 2.0,
+            Self::DottedQuarter => 1.5,
+            Self::Quarter => 1.0,
+            Self::QuarterTriplet => 2.0 / 3.0,
+            Self::DottedEighth => 0.75,
+            Self::Eighth => 0.5,
+            Self::EighthTriplet => 1.0 / 3.0,
+            Self::Sixteenth => 0.25,
+            Self::SixteenthTriplet => 1.0 / 6.0,
+            Self::ThirtySecond => 0.125,
+        }
+    }
+
+    /// How long the division lasts at `bpm` beats per minute, in seconds.
+    pub fn seconds(self, bpm: f32) -> f32 {
+        self.beats() * 60.0 / bpm
+    }
+}
+
+/// Read from a circular delay line `delay` samples back from the write position, interpolating
+/// between samples if need be.
+#[cfg(feature = "effects")]
+fn read_delay_line(line: &[f32], write: usize, delay: f32) -> f32 {
+    let position = write as f32 + line.len() as f32 - delay;
+    let index = position as usize;
+    let fraction = position - index as f32;
+    let a = line[index % line.len()];
+    let b = line[(index + 1) % line.len()];
+    a + (b - a) * fraction
+}
+
+/// Runs a nonlinear process at a multiple of the sample rate, so that the harmonics it adds don't
+/// alias back down, for one channel.
+#[cfg(feature = "effects")]
+struct Oversampler {
+    upsampler: Upsampler,
+    decimator: Decimator,
+    /// The input, delayed to line up with the processed signal.
+    dry: [f32; OVERSAMPLING_LATENCY],
+    position: usize,
+}
+
+#[cfg(feature = "effects")]
+impl Oversampler {
+    fn new(oversampling: Oversampling) -> Self {
+        Self {
+            upsampler: Upsampler::new(oversampling),
+            decimator: Decimator::new(oversampling),
+            dry: [0.0; OVERSAMPLING_LATENCY],
+            position: 0,
+        }
+    }
+
+    /// Run `input` through `process` at the higher rate, giving the input and the result, both
+    /// delayed by the same amount.
+    fn process(&mut self, input: f32, mut process: impl FnMut(f32) -> f32) -> (f32, f32) {
+        let ratio = self.upsampler.ratio;
+        if ratio == 1 {
+            return (input, process(input));
+        }
+
+        let mut oversampled = [0.0; 8];
+        self.upsampler.process(input, &mut oversampled);
+        for sample in &oversampled[..ratio] {
+            self.decimator.push(process(*sample));
+        }
+        let dry = mem::replace(&mut self.dry[self.position], input);
+        self.position = (self.position + 1) % OVERSAMPLING_LATENCY;
+        (dry, self.decimator.output())
+    }
+
+    /// Forget the signal so far, without reallocating.
+    fn reset(&mut self) {
+        self.upsampler.filter.reset();
+        self.decimator.reset();
+        self.dry = [0.0; OVERSAMPLING_LATENCY];
+        self.position = 0;
+    }
+}
+
+/// Raises the sample rate by stuffing zeros between samples and filtering out the images.
+#[cfg(feature = "effects")]
+struct Upsampler {
+    ratio: usize,
+    filter: Decimator,
+}
+
+#[cfg(feature = "effects")]
+impl Upsampler {
+    fn new(oversampling: Oversampling) -> Self {
+        Self {
+            ratio: oversampling.ratio() as usize,
+            filter: Decimator::new(oversampling),
+        }
+    }
+
+    /// Turn one sample into as many as the oversampling ratio, at the start of `output`.
+    fn process(&mut self, input: f32, output: &mut [f32]) {
+        for (i, out) in output[..self.ratio].iter_mut().enumerate() {
+            // scaled up, to make up for the energy in the zeros
+            let sample = if i == 0 {
+                input * self.ratio as f32
+            } else {
+                0.0
+            };
+            self.filter.push(sample);
+            *out = self.filter.output();
+        }
+    }
+}
+
+/// Frames an `Oversampler` delays the signal by, when oversampling. Each of its filters delays by
+/// half its length, less a fraction of a frame since the output is taken from the last oversampled
+/// sample of each.
+#[cfg(feature = "effects")]
+const OVERSAMPLING_LATENCY: usize = Decimator::TAPS_PER_RATIO - 1;
+
+fn db_to_gain(db: f32) -> f32 {
+    10f32.powf(db / 20.0)
+}
+
+/// Create one of the built-in effects with its default settings, by name.
+#[cfg(feature = "effects")]
+pub fn by_name(name: &str) -> Option<Box<dyn Effect>> {
+    match name {
+        "autopan" => Some(Box::new(autopan::AutoPan::new())),
+        "bitcrusher" => Some(Box::new(bitcrusher::Bitcrusher::new())),
+        "chorus" => Some(Box::new(chorus::Chorus::new())),
+        "compressor" => Some(Box::new(compressor::Compressor::new())),
+        "delay" => Some(Box::new(delay::Delay::new())),
+        "distortion" => Some(Box::new(distortion::Distortion::new())),
+        "eq" => Some(Box::new(eq::Eq::new())),
+        "gate" => Some(Box::new(gate::Gate::new())),
+        "phaser" => Some(Box::new(phaser::Phaser::new())),
+        "pitch" => Some(Box::new(pitch_shifter::PitchShifter::new())),
+        "reverb" => Some(Box::new(reverb::Reverb::new())),
+        "rotary" => Some(Box::new(rotary::Rotary::new())),
+        "tape" => Some(Box::new(tape::Tape::new())),
+        "tremolo" => Some(Box::new(tremolo::Tremolo::new())),
+        "wavefolder" => Some(Box::new(wavefolder::Wavefolder::new())),
+        "widener" => Some(Box::new(widener::Widener::new())),
+        _ => None,
+    }
+}
+
+/// Without the `effects` feature, no name is known.
+#[cfg(not(feature = "effects"))]
+pub fn by_name(_name: &str) -> Option<Box<dyn Effect>> {
+    None
+}
+
+/// Something which processes stereo audio, such as a delay or reverb.
+///
+/// Parameters are exposed by index, in their natural units (e.g. seconds, or a 0-1 mix), so that
+/// they can be automated and saved along with the rest of a sound without knowing the concrete
+/// type.
+pub trait Effect: Send + Sync {
+    /// A short name to show for the effect. For the built-in effects, this is the name
+    /// `by_name` takes.
+    fn name(&self) -> &str;
+
+    /// Called with the rate audio will run at, before any processing.
+    fn set_sample_rate(&mut self, _sample_rate: u32) {}
+
+    /// Called with the tempo in beats per minute, before any processing and whenever it changes,
+    /// for effects with timings locked to it.
+    fn set_tempo(&mut self, _bpm: f32) {}
+
+    /// Process a block of audio in place.
+    fn process(&mut self, block: &mut [Frame]);
+
+    /// Forget any audio still ringing out (e.g. in a delay line).
+    fn reset(&mut self) {}
+
+    /// Names of the parameters which can be read and changed, in index order.
+    fn parameter_names(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    /// The current value of a parameter.
+    fn parameter(&self, _index: usize) -> Option<f32> {
+        None
+    }
+
+    /// Change a parameter. Out of range values are clamped, and unknown indices ignored.
+    fn set_parameter(&mut self, _index: usize, _value: f32) {}
+
+    /// Whether a parameter picks between settings (e.g. a curve, or on and off) rather than
+    /// varying smoothly, so that values in between mean nothing and shouldn't be blended.
+    fn is_discrete(&self, _index: usize) -> bool {
+        false
+    }
+
+    /// What a parameter's values mean. Unless the effect says otherwise, a parameter is taken to
+    /// run from 0 to 1 (picking between the two if it's discrete), by default at its current
+    /// value.
+    fn parameter_info(&self, index: usize) -> Option<ParamInfo> {
+        let name = self.parameter_names().get(index)?;
+        let default = self.parameter(index)?;
+        Some(if self.is_discrete(index) {
+            ParamInfo::stepped(name, 0.0, 1.0, default)
+        } else {
+            ParamInfo::linear(name, 0.0, 1.0, default, Unit::None)
+        })
+    }
+}
+
+struct Slot {
+    effect: Box<dyn Effect>,
+    bypassed: bool,
+}
+
+/// Where an effect goes in the signal path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case"))]
+pub enum Placement {
+    /// On the mixed output of all the voices, e.g. `Synth::effects_mut`.
+    #[default]
+    Master,
+    /// Inserted into each voice, before they're mixed, e.g. `Synth::push_voice_effect`. Each
+    /// voice processes its own signal, so distortion only ever sees one note at a time and
+    /// doesn't intermodulate chords; but every voice pays for its own copy.
+    Voice,
+    /// On one of the send buses, e.g. `Synth::send_mut`.
+    Send(usize),
+}
+
+/// An ordered series of effects, each of which can be bypassed without losing its place or
+/// settings.
+pub struct EffectsChain {
+    sample_rate: u32,
+    tempo: f32,
+    slots: Vec<Slot>,
+}
+
+impl EffectsChain {
+    pub fn new(sample_rate: u32) -> Self {
+        Self {
+            sample_rate,
+            tempo: DEFAULT_TEMPO,
+            slots: Vec::new(),
+        }
+    }
+
+    /// How many slots are in the chain.
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+
+    /// Add an effect to the end of the chain.
+    pub fn push(&mut self, effect: Box<dyn Effect>) {
+        self.insert(self.slots.len(), effect);
+    }
+
+    /// Add an effect at `index`, moving later effects along. Panics if `index` is past the end.
+    pub fn insert(&mut self, index: usize, mut effect: Box<dyn Effect>) {
+        effect.set_sample_rate(self.sample_rate);
+        effect.set_tempo(self.tempo);
+        self.slots.insert(
+            index,
+            Slot {
+                effect,
+                bypassed: false,
+            },
+        );
+    }
+
+    /// Take the effect at `index` out of the chain, if there is one.
+    pub fn remove(&mut self, index: usize) -> Option<Box<dyn Effect>> {
+        if index < self.slots.len() {
+            Some(self.slots.remove(index).effect)
+        } else {
+            None
+        }
+    }
+
+    /// Move the effect at `from` so that it ends up at `to`, shifting those in between.
+    pub fn move_effect(&mut self, from: usize, to: usize) {
+        if from < self.slots.len() && to < self.slots.len() {
+            let slot = self.slots.remove(from);
+            self.slots.insert(to, slot);
+        }
+    }
+
+    /// Tell every effect the tempo, in beats per minute.
+    pub fn set_tempo(&mut self, bpm: f32) {
+        self.tempo = bpm;
+        for slot in &mut self.slots {
+            slot.effect.set_tempo(bpm);
+        }
+    }
+
+    pub fn tempo(&self) -> f32 {
+        self.tempo
+    }
+
+    /// Skip (or stop skipping) the effect at `index`.
+    pub fn set_bypassed(&mut self, index: usize, bypassed: bool) {
+        if let Some(slot) = self.slots.get_mut(index) {
+            slot.bypassed = bypassed;
+            if bypassed {
+                // don't let an old tail play out when it's turned back on
+                slot.effect.reset();
+            }
+        }
+    }
+
+    pub fn is_bypassed(&self, index: usize) -> bool {
+        self.slots.get(index).is_some_and(|s| s.bypassed)
+    }
+
+    pub fn get(&self, index: usize) -> Option<&dyn Effect> {
+        self.slots.get(index).map(|s| s.effect.as_ref())
+    }
+
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut (dyn Effect + 'static)> {
+        self.slots.get_mut(index).map(|s| s.effect.as_mut())
+    }
+
+    /// Run a block of audio through every effect which isn't bypassed, in order.
+    pub fn process(&mut self, block: &mut [Frame]) {
+        for slot in &mut self.slots {
+            if !slot.bypassed {
+                slot.effect.process(block);
+            }
+        }
+    }
+
+    /// Take every effect out of the chain.
+    pub fn clear(&mut self) {
+        self.slots.clear();
+    }
+
+    /// Forget any audio still ringing out in any of the effects.
+    pub fn reset(&mut self) {
+        for slot in &mut self.slots {
+            slot.effect.reset();
+        }
+    }
+}
+
+impl fmt::Debug for EffectsChain {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list()
+            .entries(self.slots.iter().map(|s| s.effect.name()))
+            .finish()
+    }
+}
@@ -0,0 +1,136 @@
+//! An auto-panner, which sweeps the sound from side to side with an LFO, optionally in time with
+//! the tempo.
+
+use core::f32::consts::{FRAC_PI_4, SQRT_2, TAU};
+
+use super::{Effect, NoteDivision, DEFAULT_TEMPO, SYNC_STEPS};
+use crate::{
+    param::{ParamInfo, Unit},
+    Frame,
+};
+
+#[cfg(not(any(feature = "std", test)))]
+use crate::math::Float;
+
+const PARAMETERS: &[&str] = &["rate", "depth", "sync"];
+
+const PARAMETER_INFO: &[ParamInfo] = &[
+    ParamInfo::log("rate", 0.01, 20.0, 0.25, Unit::Hz),
+    ParamInfo::linear("depth", 0.0, 1.0, 0.8, Unit::None),
+    ParamInfo::stepped("sync", 0.0, SYNC_STEPS, 0.0),
+];
+
+pub struct AutoPan {
+    sample_rate: f32,
+    rate: f32,
+    depth: f32,
+    sync: Option<NoteDivision>,
+    tempo: f32,
+    /// Position through the LFO cycle, from 0 to 1.
+    phase: f32,
+}
+
+impl Default for AutoPan {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AutoPan {
+    /// A slow sweep most of the way to each side.
+    pub fn new() -> Self {
+        let mut pan = Self {
+            sample_rate: 0.0,
+            rate: 0.25,
+            depth: 0.8,
+            sync: None,
+            tempo: DEFAULT_TEMPO,
+            phase: 0.0,
+        };
+        pan.set_sample_rate(48000);
+        pan
+    }
+
+    /// Sweeps per second, when not synced to the tempo.
+    pub fn set_rate(&mut self, rate: f32) {
+        self.rate = rate.clamp(0.01, 20.0);
+    }
+
+    /// How far the sound sweeps, from 0 (not at all) to 1 (hard left to hard right).
+    pub fn set_depth(&mut self, depth: f32) {
+        self.depth = depth.clamp(0.0, 1.0);
+    }
+
+    /// Sweep once per note division at the current tempo, or at the free rate again with `None`.
+    pub fn set_sync(&mut self, division: Option<NoteDivision>) {
+        self.sync = division;
+    }
+
+    /// Sweeps per second actually in use.
+    pub fn effective_rate(&self) -> f32 {
+        match self.sync {
+            Some(division) => 1.0 / division.seconds(self.tempo),
+            None => self.rate,
+        }
+    }
+}
+
+impl Effect for AutoPan {
+    fn name(&self) -> &str {
+        "autopan"
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: u32) {
+        self.sample_rate = sample_rate as f32;
+    }
+
+    fn set_tempo(&mut self, bpm: f32) {
+        self.tempo = bpm;
+    }
+
+    fn process(&mut self, block: &mut [Frame]) {
+        let step = self.effective_rate() / self.sample_rate;
+
+        for frame in block.iter_mut() {
+            let position = self.depth * (TAU * self.phase).sin();
+            // equal power, and unity gain in the middle
+            let angle = (position + 1.0) * FRAC_PI_4;
+            *frame *= Frame::new(angle.cos(), angle.sin()) * SQRT_2;
+            self.phase = (self.phase + step).fract();
+        }
+    }
+
+    fn reset(&mut self) {
+        self.phase = 0.0;
+    }
+
+    fn parameter_names(&self) -> &'static [&'static str] {
+        PARAMETERS
+    }
+
+    fn parameter(&self, index: usize) -> Option<f32> {
+        match index {
+            0 => Some(self.rate),
+            1 => Some(self.depth),
+            2 => Some(NoteDivision::to_parameter(self.sync)),
+            _ => None,
+        }
+    }
+
+    fn set_parameter(&mut self, index: usize, value: f32) {
+        match index {
+            0 => self.set_rate(value),
+            1 => self.set_depth(value),
+            2 => self.set_sync(NoteDivision::from_parameter(value)),
+            _ => (),
+        }
+    }
+
+    fn is_discrete(&self, index: usize) -> bool {
+        matches!(index, 2)
+    }
+
+    fn parameter_info(&self, index: usize) -> Option<ParamInfo> {
+        PARAMETER_INFO.get(index).copied()
+    }
+}
@@ -0,0 +1,165 @@
+//! Second order (biquad) filters, with coefficients from Robert Bristow-Johnson's Audio EQ
+//! Cookbook, which can be recalculated as they run without clicks or resetting their state.
+
+use core::f32::consts::{FRAC_1_SQRT_2, TAU};
+
+#[cfg(not(any(feature = "std", test)))]
+use crate::math::Float;
+
+/// Multipliers for a biquad filter, normalized so that the first feedback coefficient is 1.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Coefficients {
+    b0: f32,
+    b1: f32,
+    b2: f32,
+    a1: f32,
+    a2: f32,
+}
+
+impl Default for Coefficients {
+    /// Passes everything through untouched.
+    fn default() -> Self {
+        Self {
+            b0: 1.0,
+            b1: 0.0,
+            b2: 0.0,
+            a1: 0.0,
+            a2: 0.0,
+        }
+    }
+}
+
+impl Coefficients {
+    /// Cuts everything above `frequency` (in Hz), with a resonant peak there if `q` is above
+    /// `1 / sqrt(2)`.
+    pub fn low_pass(sample_rate: f32, frequency: f32, q: f32) -> Self {
+        let (cos, alpha) = Self::intermediates(sample_rate, frequency, q);
+        Self::normalize(
+            (1.0 - cos) / 2.0,
+            1.0 - cos,
+            (1.0 - cos) / 2.0,
+            1.0 + alpha,
+            -2.0 * cos,
+            1.0 - alpha,
+        )
+    }
+
+    /// Cuts everything below `frequency` (in Hz).
+    pub fn high_pass(sample_rate: f32, frequency: f32, q: f32) -> Self {
+        let (cos, alpha) = Self::intermediates(sample_rate, frequency, q);
+        Self::normalize(
+            (1.0 + cos) / 2.0,
+            -(1.0 + cos),
+            (1.0 + cos) / 2.0,
+            1.0 + alpha,
+            -2.0 * cos,
+            1.0 - alpha,
+        )
+    }
+
+    /// Passes a band around `frequency` (in Hz), `q` times narrower than the frequency, at unity
+    /// gain.
+    pub fn band_pass(sample_rate: f32, frequency: f32, q: f32) -> Self {
+        let (cos, alpha) = Self::intermediates(sample_rate, frequency, q);
+        Self::normalize(alpha, 0.0, -alpha, 1.0 + alpha, -2.0 * cos, 1.0 - alpha)
+    }
+
+    /// Boosts or cuts a band around `frequency` (in Hz) by `gain` dB.
+    pub fn peaking(sample_rate: f32, frequency: f32, q: f32, gain: f32) -> Self {
+        let (cos, alpha) = Self::intermediates(sample_rate, frequency, q);
+        let a = 10f32.powf(gain / 40.0);
+        Self::normalize(
+            1.0 + alpha * a,
+            -2.0 * cos,
+            1.0 - alpha * a,
+            1.0 + alpha / a,
+            -2.0 * cos,
+            1.0 - alpha / a,
+        )
+    }
+
+    /// Boosts or cuts everything below `frequency` (in Hz) by `gain` dB.
+    pub fn low_shelf(sample_rate: f32, frequency: f32, gain: f32) -> Self {
+        let (cos, alpha) = Self::intermediates(sample_rate, frequency, FRAC_1_SQRT_2);
+        let a = 10f32.powf(gain / 40.0);
+        let root = 2.0 * a.sqrt() * alpha;
+        Self::normalize(
+            a * ((a + 1.0) - (a - 1.0) * cos + root),
+            2.0 * a * ((a - 1.0) - (a + 1.0) * cos),
+            a * ((a + 1.0) - (a - 1.0) * cos - root),
+            (a + 1.0) + (a - 1.0) * cos + root,
+            -2.0 * ((a - 1.0) + (a + 1.0) * cos),
+            (a + 1.0) + (a - 1.0) * cos - root,
+        )
+    }
+
+    /// Boosts or cuts everything above `frequency` (in Hz) by `gain` dB.
+    pub fn high_shelf(sample_rate: f32, frequency: f32, gain: f32) -> Self {
+        let (cos, alpha) = Self::intermediates(sample_rate, frequency, FRAC_1_SQRT_2);
+        let a = 10f32.powf(gain / 40.0);
+        let root = 2.0 * a.sqrt() * alpha;
+        Self::normalize(
+            a * ((a + 1.0) + (a - 1.0) * cos + root),
+            -2.0 * a * ((a - 1.0) + (a + 1.0) * cos),
+            a * ((a + 1.0) + (a - 1.0) * cos - root),
+            (a + 1.0) - (a - 1.0) * cos + root,
+            2.0 * ((a - 1.0) - (a + 1.0) * cos),
+            (a + 1.0) - (a - 1.0) * cos - root,
+        )
+    }
+
+    fn intermediates(sample_rate: f32, frequency: f32, q: f32) -> (f32, f32) {
+        // stay clear of Nyquist, where the formulas fall apart
+        let frequency = frequency.clamp(1.0, sample_rate * 0.49);
+        let omega = TAU * frequency / sample_rate;
+        (omega.cos(), omega.sin() / (2.0 * q.max(0.01)))
+    }
+
+    fn normalize(b0: f32, b1: f32, b2: f32, a0: f32, a1: f32, a2: f32) -> Self {
+        Self {
+            b0: b0 / a0,
+            b1: b1 / a0,
+            b2: b2 / a0,
+            a1: a1 / a0,
+            a2: a2 / a0,
+        }
+    }
+}
+
+/// A biquad filter for one channel, in transposed direct form II, which copes well with its
+/// coefficients changing as it runs.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Biquad {
+    coefficients: Coefficients,
+    z1: f32,
+    z2: f32,
+}
+
+impl Biquad {
+    pub fn new(coefficients: Coefficients) -> Self {
+        Self {
+            coefficients,
+            z1: 0.0,
+            z2: 0.0,
+        }
+    }
+
+    /// Change the filter's response, keeping its state.
+    pub fn set_coefficients(&mut self, coefficients: Coefficients) {
+        self.coefficients = coefficients;
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        let Coefficients { b0, b1, b2, a1, a2 } = self.coefficients;
+        let output = b0 * input + self.z1;
+        self.z1 = b1 * input - a1 * output + self.z2;
+        self.z2 = b2 * input - a2 * output;
+        output
+    }
+
+    /// Forget any signal still in the filter.
+    pub fn reset(&mut self) {
+        self.z1 = 0.0;
+        self.z2 = 0.0;
+    }
+}
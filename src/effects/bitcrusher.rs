@@ -0,0 +1,124 @@
+//! A lo-fi bitcrusher, which rounds samples off to fewer bits and holds each one for longer, for
+//! the gritty sound of early samplers and game consoles.
+
+use super::Effect;
+use crate::{
+    param::{ParamInfo, Unit},
+    Frame,
+};
+
+#[cfg(not(any(feature = "std", test)))]
+use crate::math::Float;
+
+const PARAMETERS: &[&str] = &["bits", "rate", "mix"];
+
+const PARAMETER_INFO: &[ParamInfo] = &[
+    ParamInfo::linear("bits", 1.0, 24.0, 8.0, Unit::None),
+    ParamInfo::log("rate", 100.0, 192000.0, 11025.0, Unit::Hz),
+    ParamInfo::linear("mix", 0.0, 1.0, 1.0, Unit::None),
+];
+
+pub struct Bitcrusher {
+    sample_rate: f32,
+    bits: f32,
+    rate: f32,
+    mix: f32,
+    /// Progress towards taking the next sample, from 0 to 1.
+    phase: f32,
+    held: Frame,
+}
+
+impl Default for Bitcrusher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Bitcrusher {
+    /// Eight bits at 11kHz.
+    pub fn new() -> Self {
+        let mut crusher = Self {
+            sample_rate: 0.0,
+            bits: 8.0,
+            rate: 11025.0,
+            mix: 1.0,
+            // take a sample straight away
+            phase: 1.0,
+            held: Frame::ZERO,
+        };
+        crusher.set_sample_rate(48000);
+        crusher
+    }
+
+    /// Resolution samples are rounded to, from 1 to 24 bits. Fractional values are allowed, so it
+    /// can be swept smoothly.
+    pub fn set_bits(&mut self, bits: f32) {
+        self.bits = bits.clamp(1.0, 24.0);
+    }
+
+    /// Rate samples are taken at, in Hz. Anything at or above the real sample rate leaves it alone.
+    pub fn set_rate(&mut self, rate: f32) {
+        self.rate = rate.clamp(100.0, 192000.0);
+    }
+
+    /// Balance between the dry signal (0) and the crushed signal (1).
+    pub fn set_mix(&mut self, mix: f32) {
+        self.mix = mix.clamp(0.0, 1.0);
+    }
+}
+
+impl Effect for Bitcrusher {
+    fn name(&self) -> &str {
+        "bitcrusher"
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: u32) {
+        self.sample_rate = sample_rate as f32;
+    }
+
+    fn process(&mut self, block: &mut [Frame]) {
+        let step = (self.rate / self.sample_rate).min(1.0);
+        let levels = 2f32.powf(self.bits - 1.0);
+
+        for frame in block.iter_mut() {
+            if self.phase >= 1.0 {
+                self.phase -= 1.0;
+                self.held = frame.map(|sample| (sample * levels).round() / levels);
+            }
+            self.phase += step;
+
+            *frame += (self.held - *frame) * self.mix;
+        }
+    }
+
+    fn reset(&mut self) {
+        self.phase = 1.0;
+        self.held = Frame::ZERO;
+    }
+
+    fn parameter_names(&self) -> &'static [&'static str] {
+        PARAMETERS
+    }
+
+    fn parameter(&self, index: usize) -> Option<f32> {
+        match index {
+            0 => Some(self.bits),
+            1 => Some(self.rate),
+            2 => Some(self.mix),
+            _ => None,
+        }
+    }
+
+    fn set_parameter(&mut self, index: usize, value: f32) {
+        match index {
+            0 => self.set_bits(value),
+            1 => self.set_rate(value),
+            2 => self.set_mix(value),
+            _ => (),
+        }
+    }
+
+    fn parameter_info(&self, index: usize) -> Option<ParamInfo> {
+        PARAMETER_INFO.get(index).copied()
+    }
+}
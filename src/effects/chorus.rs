@@ -0,0 +1,162 @@
+//! A chorus: several copies of the signal, each delayed by a slowly wobbling amount, so that they
+//! drift in and out of tune with each other.
+
+use alloc::{vec, vec::Vec};
+use core::f32::consts::TAU;
+
+use super::{read_delay_line, Effect};
+use crate::{
+    param::{ParamInfo, Unit},
+    Frame, CHANNELS,
+};
+
+#[cfg(not(any(feature = "std", test)))]
+use crate::math::Float;
+
+/// How many delayed copies are mixed together in each channel.
+const VOICES: usize = 3;
+/// Delay around which the copies wobble, in seconds.
+const BASE_DELAY: f32 = 0.012;
+/// Furthest the copies wobble either way at full depth, in seconds.
+const MAX_DEPTH: f32 = 0.008;
+
+const PARAMETERS: &[&str] = &["rate", "depth", "spread", "mix"];
+
+const PARAMETER_INFO: &[ParamInfo] = &[
+    ParamInfo::log("rate", 0.01, 10.0, 0.5, Unit::Hz),
+    ParamInfo::linear("depth", 0.0, 1.0, 0.5, Unit::None),
+    ParamInfo::linear("spread", 0.0, 1.0, 1.0, Unit::None),
+    ParamInfo::linear("mix", 0.0, 1.0, 0.5, Unit::None),
+];
+
+pub struct Chorus {
+    sample_rate: f32,
+    rate: f32,
+    depth: f32,
+    spread: f32,
+    mix: f32,
+    lines: [Vec<f32>; CHANNELS],
+    write: usize,
+    /// Position through the modulation cycle, from 0 to 1.
+    phase: f32,
+}
+
+impl Default for Chorus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Chorus {
+    /// A slow, fairly deep and wide chorus, mixed in evenly with the dry signal.
+    pub fn new() -> Self {
+        let mut chorus = Self {
+            sample_rate: 0.0,
+            rate: 0.5,
+            depth: 0.5,
+            spread: 1.0,
+            mix: 0.5,
+            lines: Default::default(),
+            write: 0,
+            phase: 0.0,
+        };
+        chorus.set_sample_rate(48000);
+        chorus
+    }
+
+    /// How fast the copies wobble, in Hz.
+    pub fn set_rate(&mut self, rate: f32) {
+        self.rate = rate.clamp(0.01, 10.0);
+    }
+
+    /// How far the copies wobble, from 0 (not at all) to 1.
+    pub fn set_depth(&mut self, depth: f32) {
+        self.depth = depth.clamp(0.0, 1.0);
+    }
+
+    /// How far apart the left and right channels' wobbles are, from 0 (together, so the chorus
+    /// stays in the middle) to 1 (opposite, for the widest sound).
+    pub fn set_spread(&mut self, spread: f32) {
+        self.spread = spread.clamp(0.0, 1.0);
+    }
+
+    /// Balance between the dry signal (0) and the delayed copies (1).
+    pub fn set_mix(&mut self, mix: f32) {
+        self.mix = mix.clamp(0.0, 1.0);
+    }
+}
+
+impl Effect for Chorus {
+    fn name(&self) -> &str {
+        "chorus"
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: u32) {
+        self.sample_rate = sample_rate as f32;
+        let len = ((BASE_DELAY + MAX_DEPTH) * self.sample_rate) as usize + 2;
+        self.lines = [(); CHANNELS].map(|_| vec![0.0; len]);
+        self.write = 0;
+    }
+
+    fn process(&mut self, block: &mut [Frame]) {
+        let base = BASE_DELAY * self.sample_rate;
+        let depth = self.depth * MAX_DEPTH * self.sample_rate;
+        let step = self.rate / self.sample_rate;
+        let spread = self.spread * 0.5;
+
+        for frame in block.iter_mut() {
+            let (write, phase) = (self.write, self.phase);
+            for (channel, out) in frame.iter_mut().enumerate() {
+                let line = &mut self.lines[channel];
+                line[write] = *out;
+
+                let offset = channel as f32 * spread;
+                let wet = (0..VOICES)
+                    .map(|voice| {
+                        let phase = phase + offset + voice as f32 / VOICES as f32;
+                        let delay = base + depth * (TAU * phase).sin();
+                        read_delay_line(line, write, delay)
+                    })
+                    .sum::<f32>()
+                    / VOICES as f32;
+                *out += (wet - *out) * self.mix;
+            }
+            self.write = (self.write + 1) % self.lines[0].len();
+            self.phase = (self.phase + step).fract();
+        }
+    }
+
+    fn reset(&mut self) {
+        for line in &mut self.lines {
+            line.fill(0.0);
+        }
+    }
+
+    fn parameter_names(&self) -> &'static [&'static str] {
+        PARAMETERS
+    }
+
+    fn parameter(&self, index: usize) -> Option<f32> {
+        match index {
+            0 => Some(self.rate),
+            1 => Some(self.depth),
+            2 => Some(self.spread),
+            3 => Some(self.mix),
+            _ => None,
+        }
+    }
+
+    fn set_parameter(&mut self, index: usize, value: f32) {
+        match index {
+            0 => self.set_rate(value),
+            1 => self.set_depth(value),
+            2 => self.set_spread(value),
+            3 => self.set_mix(value),
+            _ => (),
+        }
+    }
+
+    fn parameter_info(&self, index: usize) -> Option<ParamInfo> {
+        PARAMETER_INFO.get(index).copied()
+    }
+}
@@ -0,0 +1,186 @@
+//! A feed-forward bus compressor, which turns the output down when it gets loud, to glue dense
+//! playing together and tame peaks before they reach the limiter.
+
+use super::{db_to_gain, Effect};
+use crate::{
+    param::{ParamInfo, Unit},
+    Frame,
+};
+
+#[cfg(not(any(feature = "std", test)))]
+use crate::math::Float;
+
+const PARAMETERS: &[&str] = &["threshold", "ratio", "attack", "release", "makeup", "knee"];
+
+const PARAMETER_INFO: &[ParamInfo] = &[
+    ParamInfo::linear("threshold", -60.0, 0.0, -18.0, Unit::Decibels),
+    ParamInfo::log("ratio", 1.0, 20.0, 2.0, Unit::None),
+    ParamInfo::log("attack", 0.0001, 0.5, 0.01, Unit::Seconds),
+    ParamInfo::log("release", 0.005, 5.0, 0.15, Unit::Seconds),
+    ParamInfo::linear("makeup", 0.0, 24.0, 0.0, Unit::Decibels),
+    ParamInfo::linear("knee", 0.0, 24.0, 6.0, Unit::Decibels),
+];
+
+pub struct Compressor {
+    sample_rate: f32,
+    threshold: f32,
+    ratio: f32,
+    attack: f32,
+    release: f32,
+    makeup: f32,
+    knee: f32,
+    attack_coefficient: f32,
+    release_coefficient: f32,
+    /// Current gain reduction, in dB (positive meaning quieter).
+    reduction: f32,
+}
+
+impl Default for Compressor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Compressor {
+    /// Gentle glue compression: 2:1 above -18dB, with a soft knee.
+    pub fn new() -> Self {
+        let mut compressor = Self {
+            sample_rate: 0.0,
+            threshold: -18.0,
+            ratio: 2.0,
+            attack: 0.01,
+            release: 0.15,
+            makeup: 0.0,
+            knee: 6.0,
+            attack_coefficient: 0.0,
+            release_coefficient: 0.0,
+            reduction: 0.0,
+        };
+        compressor.set_sample_rate(48000);
+        compressor
+    }
+
+    /// Level above which the signal is turned down, in dB below full scale.
+    pub fn set_threshold(&mut self, threshold: f32) {
+        self.threshold = threshold.clamp(-60.0, 0.0);
+    }
+
+    /// How much the signal is turned down above the threshold: for a ratio of 4, every 4dB over
+    /// comes out as 1dB over. Up to 20, which is near enough limiting.
+    pub fn set_ratio(&mut self, ratio: f32) {
+        self.ratio = ratio.clamp(1.0, 20.0);
+    }
+
+    /// Time taken to turn the signal down, in seconds.
+    pub fn set_attack(&mut self, attack: f32) {
+        self.attack = attack.clamp(0.0001, 0.5);
+        self.attack_coefficient = Self::coefficient(self.attack, self.sample_rate);
+    }
+
+    /// Time taken to turn the signal back up once it drops, in seconds.
+    pub fn set_release(&mut self, release: f32) {
+        self.release = release.clamp(0.005, 5.0);
+        self.release_coefficient = Self::coefficient(self.release, self.sample_rate);
+    }
+
+    /// Gain added after compression, in dB, to make up for what was taken away.
+    pub fn set_makeup(&mut self, makeup: f32) {
+        self.makeup = makeup.clamp(0.0, 24.0);
+    }
+
+    /// Width of the region around the threshold where the ratio eases in, in dB. 0 gives a hard
+    /// knee.
+    pub fn set_knee(&mut self, knee: f32) {
+        self.knee = knee.clamp(0.0, 24.0);
+    }
+
+    /// How far the signal is currently being turned down, in dB, e.g. for a meter.
+    pub fn gain_reduction(&self) -> f32 {
+        self.reduction
+    }
+
+    fn coefficient(time: f32, sample_rate: f32) -> f32 {
+        1.0 - (-1.0 / (time * sample_rate)).exp()
+    }
+
+    /// How far a signal at `level` dB should be turned down, in dB.
+    fn target_reduction(&self, level: f32) -> f32 {
+        let over = level - self.threshold;
+        let slope = 1.0 - 1.0 / self.ratio;
+        if 2.0 * over <= -self.knee {
+            0.0
+        } else if 2.0 * over < self.knee {
+            // quadratic through the knee, meeting the straight parts smoothly at either end
+            let into = over + self.knee / 2.0;
+            slope * into * into / (2.0 * self.knee)
+        } else {
+            slope * over
+        }
+    }
+}
+
+impl Effect for Compressor {
+    fn name(&self) -> &str {
+        "compressor"
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: u32) {
+        self.sample_rate = sample_rate as f32;
+        self.set_attack(self.attack);
+        self.set_release(self.release);
+    }
+
+    fn process(&mut self, block: &mut [Frame]) {
+        let makeup = db_to_gain(self.makeup);
+
+        for frame in block.iter_mut() {
+            // both channels are turned down together, so the stereo image doesn't wander
+            let level = 20.0 * frame.peak().max(1e-6).log10();
+            let target = self.target_reduction(level);
+            let rate = if target > self.reduction {
+                self.attack_coefficient
+            } else {
+                self.release_coefficient
+            };
+            self.reduction += (target - self.reduction) * rate;
+
+            *frame *= db_to_gain(-self.reduction) * makeup;
+        }
+    }
+
+    fn reset(&mut self) {
+        self.reduction = 0.0;
+    }
+
+    fn parameter_names(&self) -> &'static [&'static str] {
+        PARAMETERS
+    }
+
+    fn parameter(&self, index: usize) -> Option<f32> {
+        match index {
+            0 => Some(self.threshold),
+            1 => Some(self.ratio),
+            2 => Some(self.attack),
+            3 => Some(self.release),
+            4 => Some(self.makeup),
+            5 => Some(self.knee),
+            _ => None,
+        }
+    }
+
+    fn set_parameter(&mut self, index: usize, value: f32) {
+        match index {
+            0 => self.set_threshold(value),
+            1 => self.set_ratio(value),
+            2 => self.set_attack(value),
+            3 => self.set_release(value),
+            4 => self.set_makeup(value),
+            5 => self.set_knee(value),
+            _ => (),
+        }
+    }
+
+    fn parameter_info(&self, index: usize) -> Option<ParamInfo> {
+        PARAMETER_INFO.get(index).copied()
+    }
+}
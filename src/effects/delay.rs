@@ -0,0 +1,242 @@
+//! A stereo delay, with filtered feedback, an optional ping-pong mode and times which can be
+//! locked to the tempo.
+
+use alloc::{vec, vec::Vec};
+use core::f32::consts::TAU;
+
+use super::{read_delay_line, Effect, NoteDivision, DEFAULT_TEMPO, SYNC_STEPS};
+use crate::{
+    param::{ParamInfo, Unit},
+    Frame, CHANNELS,
+};
+
+#[cfg(not(any(feature = "std", test)))]
+use crate::math::Float;
+
+/// Longest delay time allowed, in seconds.
+pub const MAX_TIME: f32 = 2.0;
+
+/// How long changes of delay time are crossfaded over, in seconds. Sliding the read position
+/// instead would bend the pitch of everything in the line.
+const CROSSFADE_TIME: f32 = 0.05;
+
+const PARAMETERS: &[&str] = &["time", "feedback", "mix", "high cut", "ping pong", "sync"];
+
+const PARAMETER_INFO: &[ParamInfo] = &[
+    ParamInfo::log("time", 0.001, MAX_TIME, 0.25, Unit::Seconds),
+    ParamInfo::linear("feedback", 0.0, 0.95, 0.4, Unit::None),
+    ParamInfo::linear("mix", 0.0, 1.0, 0.3, Unit::None),
+    ParamInfo::log("high cut", 200.0, 20000.0, 6000.0, Unit::Hz),
+    ParamInfo::stepped("ping pong", 0.0, 1.0, 0.0),
+    ParamInfo::stepped("sync", 0.0, SYNC_STEPS, 0.0),
+];
+
+pub struct Delay {
+    sample_rate: f32,
+    time: f32,
+    feedback: f32,
+    mix: f32,
+    high_cut: f32,
+    ping_pong: bool,
+    sync: Option<NoteDivision>,
+    tempo: f32,
+    lines: [Vec<f32>; CHANNELS],
+    write: usize,
+    /// Delay in frames currently being read from.
+    current: f32,
+    /// Delay in frames being crossfaded to, if the time has changed.
+    next: Option<f32>,
+    /// Delay in frames asked for, which is crossfaded to once any crossfade in progress is done.
+    target: f32,
+    crossfade: f32,
+    damping: f32,
+    damped: Frame,
+}
+
+impl Default for Delay {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Delay {
+    /// A quarter second delay, with a few repeats mixed in at a third.
+    pub fn new() -> Self {
+        let mut delay = Self {
+            sample_rate: 0.0,
+            time: 0.25,
+            feedback: 0.4,
+            mix: 0.3,
+            high_cut: 6000.0,
+            ping_pong: false,
+            sync: None,
+            tempo: DEFAULT_TEMPO,
+            lines: Default::default(),
+            write: 0,
+            current: 0.0,
+            next: None,
+            target: 0.0,
+            crossfade: 0.0,
+            damping: 0.0,
+            damped: Frame::ZERO,
+        };
+        delay.set_sample_rate(48000);
+        delay
+    }
+
+    /// Set the delay time in seconds, up to `MAX_TIME`. This is only used while the delay isn't
+    /// synced to the tempo.
+    pub fn set_time(&mut self, seconds: f32) {
+        self.time = seconds.clamp(0.001, MAX_TIME);
+        self.update_target();
+    }
+
+    /// Lock the delay time to a note division at the current tempo, or set it freely again with
+    /// `None`.
+    pub fn set_sync(&mut self, division: Option<NoteDivision>) {
+        self.sync = division;
+        self.update_target();
+    }
+
+    /// The delay time actually in use, in seconds.
+    pub fn effective_time(&self) -> f32 {
+        match self.sync {
+            Some(division) => division.seconds(self.tempo).clamp(0.001, MAX_TIME),
+            None => self.time,
+        }
+    }
+
+    /// Aim for the current delay time, which is crossfaded to as the audio runs.
+    fn update_target(&mut self) {
+        self.target = self.effective_time() * self.sample_rate;
+    }
+
+    /// How much of the output is fed back in, from 0 (a single echo) to just under 1.
+    pub fn set_feedback(&mut self, feedback: f32) {
+        self.feedback = feedback.clamp(0.0, 0.95);
+    }
+
+    /// Balance between the dry signal (0) and the echoes (1).
+    pub fn set_mix(&mut self, mix: f32) {
+        self.mix = mix.clamp(0.0, 1.0);
+    }
+
+    /// Cutoff of the low-pass filter in the feedback loop, in Hz, so that each repeat is darker.
+    pub fn set_high_cut(&mut self, cutoff: f32) {
+        self.high_cut = cutoff.clamp(200.0, 20000.0);
+        self.damping = 1.0 - (-TAU * self.high_cut / self.sample_rate).exp();
+    }
+
+    /// Bounce the echoes between the left and right channels.
+    pub fn set_ping_pong(&mut self, ping_pong: bool) {
+        self.ping_pong = ping_pong;
+    }
+}
+
+impl Effect for Delay {
+    fn name(&self) -> &str {
+        "delay"
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: u32) {
+        self.sample_rate = sample_rate as f32;
+        let len = (MAX_TIME * self.sample_rate) as usize + 2;
+        self.lines = [(); CHANNELS].map(|_| vec![0.0; len]);
+        self.write = 0;
+        self.current = self.effective_time() * self.sample_rate;
+        self.target = self.current;
+        self.next = None;
+        self.set_high_cut(self.high_cut);
+    }
+
+    fn set_tempo(&mut self, bpm: f32) {
+        self.tempo = bpm;
+        self.update_target();
+    }
+
+    fn process(&mut self, block: &mut [Frame]) {
+        let crossfade_step = 1.0 / (CROSSFADE_TIME * self.sample_rate);
+        for frame in block.iter_mut() {
+            if self.next.is_none() && self.target != self.current {
+                self.next = Some(self.target);
+                self.crossfade = 0.0;
+            }
+
+            let mut wet = Frame::ZERO;
+            for (channel, wet) in wet.iter_mut().enumerate() {
+                let line = &self.lines[channel];
+                *wet = read_delay_line(line, self.write, self.current);
+                if let Some(next) = self.next {
+                    let target = read_delay_line(line, self.write, next);
+                    *wet += (target - *wet) * self.crossfade;
+                }
+            }
+            if let Some(next) = self.next {
+                self.crossfade += crossfade_step;
+                if self.crossfade >= 1.0 {
+                    self.current = next;
+                    self.next = None;
+                }
+            }
+
+            self.damped += (wet - self.damped) * self.damping;
+            let fed_back = self.damped * self.feedback;
+
+            let input = if self.ping_pong {
+                // everything goes in on the left, and each channel feeds the other
+                Frame::new(frame.to_mono() + fed_back.right(), fed_back.left())
+            } else {
+                *frame + fed_back
+            };
+            for (line, input) in self.lines.iter_mut().zip(input) {
+                line[self.write] = input;
+            }
+            self.write = (self.write + 1) % self.lines[0].len();
+
+            *frame += (wet - *frame) * self.mix;
+        }
+    }
+
+    fn reset(&mut self) {
+        for line in &mut self.lines {
+            line.fill(0.0);
+        }
+        self.damped = Frame::ZERO;
+    }
+
+    fn parameter_names(&self) -> &'static [&'static str] {
+        PARAMETERS
+    }
+
+    fn parameter(&self, index: usize) -> Option<f32> {
+        match index {
+            0 => Some(self.time),
+            1 => Some(self.feedback),
+            2 => Some(self.mix),
+            3 => Some(self.high_cut),
+            4 => Some(self.ping_pong as u8 as f32),
+            5 => Some(NoteDivision::to_parameter(self.sync)),
+            _ => None,
+        }
+    }
+
+    fn set_parameter(&mut self, index: usize, value: f32) {
+        match index {
+            0 => self.set_time(value),
+            1 => self.set_feedback(value),
+            2 => self.set_mix(value),
+            3 => self.set_high_cut(value),
+            4 => self.set_ping_pong(value >= 0.5),
+            5 => self.set_sync(NoteDivision::from_parameter(value)),
+            _ => (),
+        }
+    }
+
+    fn is_discrete(&self, index: usize) -> bool {
+        matches!(index, 4 | 5)
+    }
+
+    fn parameter_info(&self, index: usize) -> Option<ParamInfo> {
+        PARAMETER_INFO.get(index).copied()
+    }
+}
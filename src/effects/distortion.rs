@@ -0,0 +1,194 @@
+//! Distortion, by running the signal through one of several waveshaping curves, oversampled so
+//! the harmonics it adds don't alias back down.
+
+use super::{db_to_gain, Effect, Oversampler};
+use crate::{
+    param::{ParamInfo, Unit},
+    DcBlocker, Frame, Oversampling, CHANNELS,
+};
+
+#[cfg(not(any(feature = "std", test)))]
+use crate::math::Float;
+
+const PARAMETERS: &[&str] = &["curve", "drive", "output", "oversampling", "mix"];
+
+const PARAMETER_INFO: &[ParamInfo] = &[
+    ParamInfo::stepped("curve", 0.0, (Curve::ALL.len() - 1) as f32, 0.0),
+    ParamInfo::linear("drive", 0.0, 48.0, 12.0, Unit::Decibels),
+    ParamInfo::linear("output", -48.0, 12.0, -6.0, Unit::Decibels),
+    ParamInfo::stepped("oversampling", 1.0, 8.0, 4.0),
+    ParamInfo::linear("mix", 0.0, 1.0, 1.0, Unit::None),
+];
+
+/// How the signal is bent once it's been driven.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Curve {
+    /// A cubic which rounds off gently into clipping.
+    #[default]
+    SoftClip,
+    /// Hyperbolic tangent, smooth at any level.
+    Tanh,
+    /// Flat at full scale, for a harsh fuzz.
+    HardClip,
+    /// Shaped differently on each side, like an overdriven valve, adding even harmonics.
+    Tube,
+}
+
+impl Curve {
+    /// Every curve, in the order used for their indices.
+    pub const ALL: [Self; 4] = [Self::SoftClip, Self::Tanh, Self::HardClip, Self::Tube];
+
+    fn shape(self, x: f32) -> f32 {
+        match self {
+            Self::SoftClip => {
+                let x = x.clamp(-1.0, 1.0);
+                1.5 * (x - x * x * x / 3.0)
+            }
+            Self::Tanh => x.tanh(),
+            Self::HardClip => x.clamp(-1.0, 1.0),
+            Self::Tube => {
+                // biased off center, so the negative side clips sooner than the positive
+                const BIAS: f32 = 0.3;
+                (x + BIAS).tanh() - BIAS.tanh()
+            }
+        }
+    }
+}
+
+pub struct Distortion {
+    sample_rate: f32,
+    curve: Curve,
+    drive: f32,
+    output: f32,
+    oversampling: Oversampling,
+    mix: f32,
+    oversamplers: [Oversampler; CHANNELS],
+    dc_blockers: [DcBlocker; CHANNELS],
+}
+
+impl Default for Distortion {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Distortion {
+    /// A soft clipper with some drive, oversampled four times.
+    pub fn new() -> Self {
+        let oversampling = Oversampling::X4;
+        let mut distortion = Self {
+            sample_rate: 0.0,
+            curve: Curve::default(),
+            drive: 12.0,
+            output: -6.0,
+            oversampling,
+            mix: 1.0,
+            oversamplers: [(); CHANNELS].map(|_| Oversampler::new(oversampling)),
+            dc_blockers: [(); CHANNELS].map(|_| DcBlocker::new(48000.0)),
+        };
+        distortion.set_sample_rate(48000);
+        distortion
+    }
+
+    pub fn set_curve(&mut self, curve: Curve) {
+        self.curve = curve;
+    }
+
+    /// Gain before the curve, in dB, from 0 to 48.
+    pub fn set_drive(&mut self, drive: f32) {
+        self.drive = drive.clamp(0.0, 48.0);
+    }
+
+    /// Gain after the curve, in dB, from -48 to 12.
+    pub fn set_output(&mut self, output: f32) {
+        self.output = output.clamp(-48.0, 12.0);
+    }
+
+    /// How much to oversample the waveshaping by. More aliasing is removed at higher ratios, at a
+    /// proportional cost in CPU time.
+    pub fn set_oversampling(&mut self, oversampling: Oversampling) {
+        if oversampling != self.oversampling {
+            self.oversampling = oversampling;
+            self.oversamplers = [(); CHANNELS].map(|_| Oversampler::new(oversampling));
+        }
+    }
+
+    /// Balance between the dry signal (0) and the distorted signal (1).
+    pub fn set_mix(&mut self, mix: f32) {
+        self.mix = mix.clamp(0.0, 1.0);
+    }
+}
+
+impl Effect for Distortion {
+    fn name(&self) -> &str {
+        "distortion"
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: u32) {
+        self.sample_rate = sample_rate as f32;
+        self.dc_blockers = [(); CHANNELS].map(|_| DcBlocker::new(self.sample_rate));
+    }
+
+    fn process(&mut self, block: &mut [Frame]) {
+        let drive = db_to_gain(self.drive);
+        let output = db_to_gain(self.output);
+        let curve = self.curve;
+
+        for frame in block.iter_mut() {
+            for (channel, out) in frame.iter_mut().enumerate() {
+                let (dry, wet) =
+                    self.oversamplers[channel].process(*out, |x| curve.shape(x * drive));
+                let wet = self.dc_blockers[channel].process(wet) * output;
+                *out = dry + (wet - dry) * self.mix;
+            }
+        }
+    }
+
+    fn reset(&mut self) {
+        for oversampler in &mut self.oversamplers {
+            oversampler.reset();
+        }
+        self.set_sample_rate(self.sample_rate as u32);
+    }
+
+    fn parameter_names(&self) -> &'static [&'static str] {
+        PARAMETERS
+    }
+
+    fn parameter(&self, index: usize) -> Option<f32> {
+        match index {
+            0 => Some(Curve::ALL.iter().position(|c| *c == self.curve).unwrap() as f32),
+            1 => Some(self.drive),
+            2 => Some(self.output),
+            3 => Some(self.oversampling.ratio() as f32),
+            4 => Some(self.mix),
+            _ => None,
+        }
+    }
+
+    fn set_parameter(&mut self, index: usize, value: f32) {
+        match index {
+            0 => {
+                let index = (value.round().max(0.0) as usize).min(Curve::ALL.len() - 1);
+                self.set_curve(Curve::ALL[index]);
+            }
+            1 => self.set_drive(value),
+            2 => self.set_output(value),
+            3 => {
+                if let Some(oversampling) = Oversampling::from_ratio(value.round() as u32) {
+                    self.set_oversampling(oversampling);
+                }
+            }
+            4 => self.set_mix(value),
+            _ => (),
+        }
+    }
+
+    fn is_discrete(&self, index: usize) -> bool {
+        matches!(index, 0 | 3)
+    }
+
+    fn parameter_info(&self, index: usize) -> Option<ParamInfo> {
+        PARAMETER_INFO.get(index).copied()
+    }
+}
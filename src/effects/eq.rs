@@ -0,0 +1,194 @@
+//! A three band EQ, for balancing the tone of the whole output: a low shelf, a parametric mid and a
+//! high shelf.
+
+use super::{
+    biquad::{Biquad, Coefficients},
+    Effect,
+};
+use crate::{
+    param::{ParamInfo, Unit},
+    Frame, CHANNELS,
+};
+
+/// Most boost or cut allowed in each band, in dB.
+pub const MAX_GAIN: f32 = 18.0;
+
+const PARAMETERS: &[&str] = &[
+    "low gain",
+    "low frequency",
+    "mid gain",
+    "mid frequency",
+    "mid q",
+    "high gain",
+    "high frequency",
+];
+
+const PARAMETER_INFO: &[ParamInfo] = &[
+    ParamInfo::linear("low gain", -MAX_GAIN, MAX_GAIN, 0.0, Unit::Decibels),
+    ParamInfo::log("low frequency", 20.0, 1000.0, 200.0, Unit::Hz),
+    ParamInfo::linear("mid gain", -MAX_GAIN, MAX_GAIN, 0.0, Unit::Decibels),
+    ParamInfo::log("mid frequency", 100.0, 10000.0, 1000.0, Unit::Hz),
+    ParamInfo::log("mid q", 0.2, 10.0, 0.7, Unit::None),
+    ParamInfo::linear("high gain", -MAX_GAIN, MAX_GAIN, 0.0, Unit::Decibels),
+    ParamInfo::log("high frequency", 1000.0, 20000.0, 5000.0, Unit::Hz),
+];
+
+pub struct Eq {
+    sample_rate: f32,
+    low_gain: f32,
+    low_frequency: f32,
+    mid_gain: f32,
+    mid_frequency: f32,
+    mid_q: f32,
+    high_gain: f32,
+    high_frequency: f32,
+    /// Low, mid and high bands, in that order, for each channel.
+    bands: [[Biquad; 3]; CHANNELS],
+}
+
+impl Default for Eq {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Eq {
+    /// Flat, with the bands at 200Hz, 1kHz and 5kHz.
+    pub fn new() -> Self {
+        let mut eq = Self {
+            sample_rate: 0.0,
+            low_gain: 0.0,
+            low_frequency: 200.0,
+            mid_gain: 0.0,
+            mid_frequency: 1000.0,
+            mid_q: 0.7,
+            high_gain: 0.0,
+            high_frequency: 5000.0,
+            bands: Default::default(),
+        };
+        eq.set_sample_rate(48000);
+        eq
+    }
+
+    /// Boost (or cut, if negative) the low shelf by this many dB.
+    pub fn set_low_gain(&mut self, gain: f32) {
+        self.low_gain = gain.clamp(-MAX_GAIN, MAX_GAIN);
+        self.update();
+    }
+
+    /// Corner frequency of the low shelf, in Hz.
+    pub fn set_low_frequency(&mut self, frequency: f32) {
+        self.low_frequency = frequency.clamp(20.0, 1000.0);
+        self.update();
+    }
+
+    /// Boost (or cut, if negative) the mid band by this many dB.
+    pub fn set_mid_gain(&mut self, gain: f32) {
+        self.mid_gain = gain.clamp(-MAX_GAIN, MAX_GAIN);
+        self.update();
+    }
+
+    /// Center frequency of the mid band, in Hz.
+    pub fn set_mid_frequency(&mut self, frequency: f32) {
+        self.mid_frequency = frequency.clamp(100.0, 10000.0);
+        self.update();
+    }
+
+    /// Narrowness of the mid band, from 0.2 (several octaves wide) to 10 (surgical).
+    pub fn set_mid_q(&mut self, q: f32) {
+        self.mid_q = q.clamp(0.2, 10.0);
+        self.update();
+    }
+
+    /// Boost (or cut, if negative) the high shelf by this many dB.
+    pub fn set_high_gain(&mut self, gain: f32) {
+        self.high_gain = gain.clamp(-MAX_GAIN, MAX_GAIN);
+        self.update();
+    }
+
+    /// Corner frequency of the high shelf, in Hz.
+    pub fn set_high_frequency(&mut self, frequency: f32) {
+        self.high_frequency = frequency.clamp(1000.0, 20000.0);
+        self.update();
+    }
+
+    /// Recalculate the filters to match the settings.
+    fn update(&mut self) {
+        let coefficients = [
+            Coefficients::low_shelf(self.sample_rate, self.low_frequency, self.low_gain),
+            Coefficients::peaking(
+                self.sample_rate,
+                self.mid_frequency,
+                self.mid_q,
+                self.mid_gain,
+            ),
+            Coefficients::high_shelf(self.sample_rate, self.high_frequency, self.high_gain),
+        ];
+        for bands in &mut self.bands {
+            for (band, coefficients) in bands.iter_mut().zip(coefficients) {
+                band.set_coefficients(coefficients);
+            }
+        }
+    }
+}
+
+impl Effect for Eq {
+    fn name(&self) -> &str {
+        "eq"
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: u32) {
+        self.sample_rate = sample_rate as f32;
+        self.update();
+    }
+
+    fn process(&mut self, block: &mut [Frame]) {
+        for frame in block.iter_mut() {
+            for (out, bands) in frame.iter_mut().zip(&mut self.bands) {
+                *out = bands
+                    .iter_mut()
+                    .fold(*out, |sample, band| band.process(sample));
+            }
+        }
+    }
+
+    fn reset(&mut self) {
+        for band in self.bands.iter_mut().flatten() {
+            band.reset();
+        }
+    }
+
+    fn parameter_names(&self) -> &'static [&'static str] {
+        PARAMETERS
+    }
+
+    fn parameter(&self, index: usize) -> Option<f32> {
+        match index {
+            0 => Some(self.low_gain),
+            1 => Some(self.low_frequency),
+            2 => Some(self.mid_gain),
+            3 => Some(self.mid_frequency),
+            4 => Some(self.mid_q),
+            5 => Some(self.high_gain),
+            6 => Some(self.high_frequency),
+            _ => None,
+        }
+    }
+
+    fn set_parameter(&mut self, index: usize, value: f32) {
+        match index {
+            0 => self.set_low_gain(value),
+            1 => self.set_low_frequency(value),
+            2 => self.set_mid_gain(value),
+            3 => self.set_mid_frequency(value),
+            4 => self.set_mid_q(value),
+            5 => self.set_high_gain(value),
+            6 => self.set_high_frequency(value),
+            _ => (),
+        }
+    }
+
+    fn parameter_info(&self, index: usize) -> Option<ParamInfo> {
+        PARAMETER_INFO.get(index).copied()
+    }
+}
@@ -0,0 +1,172 @@
+//! A noise gate, which silences the signal whenever it falls below a threshold, e.g. to keep the
+//! hiss of an idle external input out of the voices.
+
+use super::{db_to_gain, Effect};
+use crate::{
+    param::{ParamInfo, Unit},
+    Frame,
+};
+
+#[cfg(not(any(feature = "std", test)))]
+use crate::math::Float;
+
+/// How far below the threshold the signal has to fall before the gate starts closing, in dB, so
+/// that a signal hovering around the threshold doesn't make it chatter.
+const HYSTERESIS: f32 = 6.0;
+
+/// How quickly the level detector lets go of a peak, in seconds. Long enough to ride over the
+/// troughs of a low note's waveform.
+const DETECTOR_RELEASE: f32 = 0.02;
+
+const PARAMETERS: &[&str] = &["threshold", "attack", "hold", "release"];
+
+const PARAMETER_INFO: &[ParamInfo] = &[
+    ParamInfo::linear("threshold", -96.0, 0.0, -50.0, Unit::Decibels),
+    ParamInfo::log("attack", 0.0001, 0.5, 0.001, Unit::Seconds),
+    ParamInfo::linear("hold", 0.0, 2.0, 0.05, Unit::Seconds),
+    ParamInfo::log("release", 0.001, 5.0, 0.1, Unit::Seconds),
+];
+
+pub struct Gate {
+    sample_rate: f32,
+    threshold: f32,
+    attack: f32,
+    hold: f32,
+    release: f32,
+    detector_coefficient: f32,
+    /// Peak level of the signal, following it down slowly.
+    level: f32,
+    /// Frames left before the gate starts closing.
+    holding: u32,
+    gain: f32,
+}
+
+impl Default for Gate {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Gate {
+    /// Closing below -50dB, opening quickly and closing gently after a short hold.
+    pub fn new() -> Self {
+        let mut gate = Self {
+            sample_rate: 0.0,
+            threshold: -50.0,
+            attack: 0.001,
+            hold: 0.05,
+            release: 0.1,
+            detector_coefficient: 0.0,
+            level: 0.0,
+            holding: 0,
+            gain: 0.0,
+        };
+        gate.set_sample_rate(48000);
+        gate
+    }
+
+    /// Level below which the gate closes, in dB below full scale.
+    pub fn set_threshold(&mut self, threshold: f32) {
+        self.threshold = threshold.clamp(-96.0, 0.0);
+    }
+
+    /// Time taken to open fully, in seconds.
+    pub fn set_attack(&mut self, attack: f32) {
+        self.attack = attack.clamp(0.0001, 0.5);
+    }
+
+    /// Time the gate stays open after the signal drops, in seconds.
+    pub fn set_hold(&mut self, hold: f32) {
+        self.hold = hold.clamp(0.0, 2.0);
+    }
+
+    /// Time taken to close fully, in seconds.
+    pub fn set_release(&mut self, release: f32) {
+        self.release = release.clamp(0.001, 5.0);
+    }
+
+    /// Whether the gate is letting anything through at the moment, e.g. for an indicator.
+    pub fn is_open(&self) -> bool {
+        self.gain > 0.0
+    }
+
+    /// Work out the gain for a frame with the given peak level, linear.
+    fn next_gain(&mut self, peak: f32) -> f32 {
+        if peak > self.level {
+            self.level = peak;
+        } else {
+            self.level += (peak - self.level) * self.detector_coefficient;
+        }
+
+        let open = db_to_gain(self.threshold);
+        let close = db_to_gain(self.threshold - HYSTERESIS);
+        if self.level >= open || (self.gain > 0.0 && self.level >= close) {
+            self.holding = (self.hold * self.sample_rate) as u32;
+            self.gain = (self.gain + 1.0 / (self.attack * self.sample_rate)).min(1.0);
+        } else if self.holding > 0 {
+            self.holding -= 1;
+        } else {
+            self.gain = (self.gain - 1.0 / (self.release * self.sample_rate)).max(0.0);
+        }
+        self.gain
+    }
+
+    /// Gate a mono signal in place.
+    pub fn process_mono(&mut self, samples: &mut [f32]) {
+        for sample in samples {
+            *sample *= self.next_gain(sample.abs());
+        }
+    }
+}
+
+impl Effect for Gate {
+    fn name(&self) -> &str {
+        "gate"
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: u32) {
+        self.sample_rate = sample_rate as f32;
+        self.detector_coefficient = 1.0 - (-1.0 / (DETECTOR_RELEASE * self.sample_rate)).exp();
+    }
+
+    fn process(&mut self, block: &mut [Frame]) {
+        for frame in block.iter_mut() {
+            // both channels open and close together
+            *frame *= self.next_gain(frame.peak());
+        }
+    }
+
+    fn reset(&mut self) {
+        self.level = 0.0;
+        self.holding = 0;
+        self.gain = 0.0;
+    }
+
+    fn parameter_names(&self) -> &'static [&'static str] {
+        PARAMETERS
+    }
+
+    fn parameter(&self, index: usize) -> Option<f32> {
+        match index {
+            0 => Some(self.threshold),
+            1 => Some(self.attack),
+            2 => Some(self.hold),
+            3 => Some(self.release),
+            _ => None,
+        }
+    }
+
+    fn set_parameter(&mut self, index: usize, value: f32) {
+        match index {
+            0 => self.set_threshold(value),
+            1 => self.set_attack(value),
+            2 => self.set_hold(value),
+            3 => self.set_release(value),
+            _ => (),
+        }
+    }
+
+    fn parameter_info(&self, index: usize) -> Option<ParamInfo> {
+        PARAMETER_INFO.get(index).copied()
+    }
+}
@@ -0,0 +1,186 @@
+//! A phaser: a chain of all-pass filters with swept corner frequencies, mixed back with the dry
+//! signal so that notches sweep up and down the spectrum.
+
+use core::f32::consts::{PI, TAU};
+
+use super::Effect;
+use crate::{
+    param::{ParamInfo, Unit},
+    Frame, CHANNELS,
+};
+
+#[cfg(not(any(feature = "std", test)))]
+use crate::math::Float;
+
+/// Most all-pass stages allowed. Each pair of stages adds a notch.
+pub const MAX_STAGES: usize = 12;
+/// Lowest corner frequency swept to, in Hz.
+const MIN_FREQUENCY: f32 = 100.0;
+/// Octaves swept above `MIN_FREQUENCY` at full depth.
+const SWEEP_OCTAVES: f32 = 6.0;
+
+const PARAMETERS: &[&str] = &["rate", "depth", "feedback", "stages", "mix"];
+
+const PARAMETER_INFO: &[ParamInfo] = &[
+    ParamInfo::log("rate", 0.01, 10.0, 0.3, Unit::Hz),
+    ParamInfo::linear("depth", 0.0, 1.0, 0.7, Unit::None),
+    ParamInfo::linear("feedback", -0.9, 0.9, 0.3, Unit::None),
+    ParamInfo::stepped("stages", 2.0, MAX_STAGES as f32, 4.0),
+    ParamInfo::linear("mix", 0.0, 1.0, 0.5, Unit::None),
+];
+
+/// State of one first-order all-pass filter.
+#[derive(Clone, Copy, Default)]
+struct Stage {
+    last_input: f32,
+    last_output: f32,
+}
+
+impl Stage {
+    fn process(&mut self, input: f32, coefficient: f32) -> f32 {
+        let output = coefficient * input + self.last_input - coefficient * self.last_output;
+        self.last_input = input;
+        self.last_output = output;
+        output
+    }
+}
+
+pub struct Phaser {
+    sample_rate: f32,
+    rate: f32,
+    depth: f32,
+    feedback: f32,
+    stages: usize,
+    mix: f32,
+    filters: [[Stage; MAX_STAGES]; CHANNELS],
+    /// The output of the last stage, fed back into the first.
+    last: [f32; CHANNELS],
+    /// Position through the sweep, from 0 to 1.
+    phase: f32,
+}
+
+impl Default for Phaser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Phaser {
+    /// A slow four stage phaser, with a little feedback.
+    pub fn new() -> Self {
+        let mut phaser = Self {
+            sample_rate: 0.0,
+            rate: 0.3,
+            depth: 0.7,
+            feedback: 0.3,
+            stages: 4,
+            mix: 0.5,
+            filters: [[Stage::default(); MAX_STAGES]; CHANNELS],
+            last: [0.0; CHANNELS],
+            phase: 0.0,
+        };
+        phaser.set_sample_rate(48000);
+        phaser
+    }
+
+    /// How fast the notches sweep, in Hz.
+    pub fn set_rate(&mut self, rate: f32) {
+        self.rate = rate.clamp(0.01, 10.0);
+    }
+
+    /// How far the notches sweep, from 0 (not at all) to 1.
+    pub fn set_depth(&mut self, depth: f32) {
+        self.depth = depth.clamp(0.0, 1.0);
+    }
+
+    /// How much of the output is fed back in, making the notches sharper. Negative values move
+    /// the peaks and notches around.
+    pub fn set_feedback(&mut self, feedback: f32) {
+        self.feedback = feedback.clamp(-0.9, 0.9);
+    }
+
+    /// How many all-pass stages to use: an even number from 2 up to `MAX_STAGES`.
+    pub fn set_stages(&mut self, stages: usize) {
+        self.stages = (stages.clamp(2, MAX_STAGES) / 2) * 2;
+    }
+
+    /// Balance between the dry signal (0) and the filtered signal (1). Notches are deepest when
+    /// they're even.
+    pub fn set_mix(&mut self, mix: f32) {
+        self.mix = mix.clamp(0.0, 1.0);
+    }
+}
+
+impl Effect for Phaser {
+    fn name(&self) -> &str {
+        "phaser"
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: u32) {
+        self.sample_rate = sample_rate as f32;
+    }
+
+    fn process(&mut self, block: &mut [Frame]) {
+        let step = self.rate / self.sample_rate;
+        // keep the sweep well clear of Nyquist, whatever the sample rate
+        let max_frequency = (MIN_FREQUENCY * 2f32.powf(SWEEP_OCTAVES)).min(self.sample_rate * 0.4);
+        let octaves = (max_frequency / MIN_FREQUENCY).log2() * self.depth;
+
+        for frame in block.iter_mut() {
+            // swept exponentially, so it spends as long in each octave
+            let sweep = 0.5 - 0.5 * (TAU * self.phase).cos();
+            let frequency = MIN_FREQUENCY * 2f32.powf(octaves * sweep);
+            let tan = (PI * frequency / self.sample_rate).tan();
+            let coefficient = (tan - 1.0) / (tan + 1.0);
+
+            for (channel, out) in frame.iter_mut().enumerate() {
+                let mut wet = *out + self.last[channel] * self.feedback;
+                for stage in &mut self.filters[channel][..self.stages] {
+                    wet = stage.process(wet, coefficient);
+                }
+                self.last[channel] = wet;
+                *out += (wet - *out) * self.mix;
+            }
+            self.phase = (self.phase + step).fract();
+        }
+    }
+
+    fn reset(&mut self) {
+        self.filters = [[Stage::default(); MAX_STAGES]; CHANNELS];
+        self.last = [0.0; CHANNELS];
+    }
+
+    fn parameter_names(&self) -> &'static [&'static str] {
+        PARAMETERS
+    }
+
+    fn parameter(&self, index: usize) -> Option<f32> {
+        match index {
+            0 => Some(self.rate),
+            1 => Some(self.depth),
+            2 => Some(self.feedback),
+            3 => Some(self.stages as f32),
+            4 => Some(self.mix),
+            _ => None,
+        }
+    }
+
+    fn set_parameter(&mut self, index: usize, value: f32) {
+        match index {
+            0 => self.set_rate(value),
+            1 => self.set_depth(value),
+            2 => self.set_feedback(value),
+            3 => self.set_stages(value.round().max(0.0) as usize),
+            4 => self.set_mix(value),
+            _ => (),
+        }
+    }
+
+    fn is_discrete(&self, index: usize) -> bool {
+        matches!(index, 3)
+    }
+
+    fn parameter_info(&self, index: usize) -> Option<ParamInfo> {
+        PARAMETER_INFO.get(index).copied()
+    }
+}
@@ -0,0 +1,159 @@
+//! A delay-based pitch shifter. Each channel is read from a short delay line through two taps whose
+//! delays sweep steadily, which changes the pitch; each tap fades out as it wraps around, while the
+//! other (half a window away) takes over.
+
+use alloc::{vec, vec::Vec};
+use core::f32::consts::TAU;
+
+use super::{read_delay_line, Effect};
+use crate::{
+    param::{ParamInfo, Unit},
+    Frame, CHANNELS,
+};
+
+#[cfg(not(any(feature = "std", test)))]
+use crate::math::Float;
+
+/// Length of the sweep window, in seconds. Shorter windows smear transients less, but flutter more.
+const WINDOW: f32 = 0.05;
+
+const PARAMETERS: &[&str] = &["pitch", "spread", "mix"];
+
+const PARAMETER_INFO: &[ParamInfo] = &[
+    ParamInfo::linear("pitch", -12.0, 12.0, 12.0, Unit::Semitones),
+    ParamInfo::linear("spread", 0.0, 50.0, 0.0, Unit::Cents),
+    ParamInfo::linear("mix", 0.0, 1.0, 0.5, Unit::None),
+];
+
+pub struct PitchShifter {
+    sample_rate: f32,
+    pitch: f32,
+    spread: f32,
+    mix: f32,
+    lines: [Vec<f32>; CHANNELS],
+    write: usize,
+    /// Delay of the first tap in each channel, in samples, from 0 up to the window length.
+    delays: [f32; CHANNELS],
+}
+
+impl Default for PitchShifter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PitchShifter {
+    /// An octave up, mixed in with the dry signal.
+    pub fn new() -> Self {
+        let mut shifter = Self {
+            sample_rate: 0.0,
+            pitch: 12.0,
+            spread: 0.0,
+            mix: 0.5,
+            lines: Default::default(),
+            write: 0,
+            delays: [0.0; CHANNELS],
+        };
+        shifter.set_sample_rate(48000);
+        shifter
+    }
+
+    /// How far to shift, in semitones, up to an octave either way.
+    pub fn set_pitch(&mut self, semitones: f32) {
+        self.pitch = semitones.clamp(-12.0, 12.0);
+    }
+
+    /// Detune the left channel down and the right channel up by this many cents, on top of the
+    /// pitch, to widen the sound.
+    pub fn set_spread(&mut self, cents: f32) {
+        self.spread = cents.clamp(0.0, 50.0);
+    }
+
+    /// Balance between the dry signal (0) and the shifted signal (1).
+    pub fn set_mix(&mut self, mix: f32) {
+        self.mix = mix.clamp(0.0, 1.0);
+    }
+}
+
+impl Effect for PitchShifter {
+    fn name(&self) -> &str {
+        "pitch"
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: u32) {
+        self.sample_rate = sample_rate as f32;
+        let len = (WINDOW * self.sample_rate) as usize + 3;
+        self.lines = [(); CHANNELS].map(|_| vec![0.0; len]);
+        self.write = 0;
+        self.delays = [0.0; CHANNELS];
+    }
+
+    fn process(&mut self, block: &mut [Frame]) {
+        let window = WINDOW * self.sample_rate;
+        let mut slopes = [0.0; CHANNELS];
+        for (channel, slope) in slopes.iter_mut().enumerate() {
+            let detune = if channel == 0 {
+                -self.spread
+            } else {
+                self.spread
+            };
+            let ratio = 2f32.powf((self.pitch + detune / 100.0) / 12.0);
+            // reading through the line faster than it's written raises the pitch
+            *slope = 1.0 - ratio;
+        }
+
+        for frame in block.iter_mut() {
+            let write = self.write;
+            for (channel, out) in frame.iter_mut().enumerate() {
+                let line = &mut self.lines[channel];
+                line[write] = *out;
+
+                let delay = &mut self.delays[channel];
+                let wet = [*delay, (*delay + window / 2.0) % window]
+                    .iter()
+                    .map(|&tap| {
+                        // Hann windows half a period apart, which always add up to 1
+                        let gain = 0.5 - 0.5 * (TAU * tap / window).cos();
+                        read_delay_line(line, write, tap + 1.0) * gain
+                    })
+                    .sum::<f32>();
+                *delay = (*delay + slopes[channel]).rem_euclid(window);
+
+                *out += (wet - *out) * self.mix;
+            }
+            self.write = (self.write + 1) % self.lines[0].len();
+        }
+    }
+
+    fn reset(&mut self) {
+        for line in &mut self.lines {
+            line.fill(0.0);
+        }
+    }
+
+    fn parameter_names(&self) -> &'static [&'static str] {
+        PARAMETERS
+    }
+
+    fn parameter(&self, index: usize) -> Option<f32> {
+        match index {
+            0 => Some(self.pitch),
+            1 => Some(self.spread),
+            2 => Some(self.mix),
+            _ => None,
+        }
+    }
+
+    fn set_parameter(&mut self, index: usize, value: f32) {
+        match index {
+            0 => self.set_pitch(value),
+            1 => self.set_spread(value),
+            2 => self.set_mix(value),
+            _ => (),
+        }
+    }
+
+    fn parameter_info(&self, index: usize) -> Option<ParamInfo> {
+        PARAMETER_INFO.get(index).copied()
+    }
+}
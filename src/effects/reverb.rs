@@ -0,0 +1,240 @@
+//! An algorithmic reverb, after Jezar's Freeverb: parallel damped comb filters feeding a series of
+//! all-pass filters, tuned slightly differently for each channel.
+
+use alloc::{vec, vec::Vec};
+
+use super::Effect;
+use crate::{
+    param::{ParamInfo, Unit},
+    Frame, CHANNELS,
+};
+
+/// Longest pre-delay allowed, in seconds.
+pub const MAX_PRE_DELAY: f32 = 0.2;
+
+/// Comb filter lengths, in samples at 44.1kHz. They're mutually prime-ish so their echoes don't
+/// pile up at the same times.
+const COMB_TUNING: [usize; 8] = [1116, 1188, 1277, 1356, 1422, 1491, 1557, 1617];
+const ALLPASS_TUNING: [usize; 4] = [556, 441, 341, 225];
+/// How much longer the right channel's filters are, in samples at 44.1kHz.
+const STEREO_SPREAD: usize = 23;
+const TUNING_RATE: f32 = 44100.0;
+
+/// The input is scaled down this much, since the combs sum together, and the output back up.
+/// (Freeverb sums the channels rather than averaging them, so scales by half this.)
+const INPUT_GAIN: f32 = 0.03;
+const OUTPUT_GAIN: f32 = 3.0;
+const ALLPASS_FEEDBACK: f32 = 0.5;
+
+const PARAMETERS: &[&str] = &["size", "damping", "pre-delay", "mix"];
+
+const PARAMETER_INFO: &[ParamInfo] = &[
+    ParamInfo::linear("size", 0.0, 1.0, 0.7, Unit::None),
+    ParamInfo::linear("damping", 0.0, 1.0, 0.5, Unit::None),
+    ParamInfo::linear("pre-delay", 0.0, MAX_PRE_DELAY, 0.02, Unit::Seconds),
+    ParamInfo::linear("mix", 0.0, 1.0, 0.25, Unit::None),
+];
+
+/// A feedback comb filter with a low-pass in its loop, so that high frequencies die away sooner.
+struct Comb {
+    buffer: Vec<f32>,
+    position: usize,
+    damped: f32,
+}
+
+impl Comb {
+    fn new(len: usize) -> Self {
+        Self {
+            buffer: vec![0.0; len.max(1)],
+            position: 0,
+            damped: 0.0,
+        }
+    }
+
+    fn process(&mut self, input: f32, feedback: f32, damping: f32) -> f32 {
+        let output = self.buffer[self.position];
+        self.damped = output + (self.damped - output) * damping;
+        self.buffer[self.position] = input + self.damped * feedback;
+        self.position = (self.position + 1) % self.buffer.len();
+        output
+    }
+
+    fn reset(&mut self) {
+        self.buffer.fill(0.0);
+        self.damped = 0.0;
+    }
+}
+
+/// A Schroeder all-pass filter, which smears the echoes out without colouring them much.
+struct Allpass {
+    buffer: Vec<f32>,
+    position: usize,
+}
+
+impl Allpass {
+    fn new(len: usize) -> Self {
+        Self {
+            buffer: vec![0.0; len.max(1)],
+            position: 0,
+        }
+    }
+
+    fn process(&mut self, input: f32) -> f32 {
+        let delayed = self.buffer[self.position];
+        self.buffer[self.position] = input + delayed * ALLPASS_FEEDBACK;
+        self.position = (self.position + 1) % self.buffer.len();
+        delayed - input
+    }
+
+    fn reset(&mut self) {
+        self.buffer.fill(0.0);
+    }
+}
+
+pub struct Reverb {
+    sample_rate: f32,
+    size: f32,
+    damping: f32,
+    pre_delay: f32,
+    mix: f32,
+    combs: [Vec<Comb>; CHANNELS],
+    allpasses: [Vec<Allpass>; CHANNELS],
+    /// Mono input, delayed before it reaches the filters.
+    pre_delay_line: Vec<f32>,
+    pre_delay_position: usize,
+}
+
+impl Default for Reverb {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Reverb {
+    /// A medium sized room, fairly dark, mixed in at a quarter.
+    pub fn new() -> Self {
+        let mut reverb = Self {
+            sample_rate: 0.0,
+            size: 0.7,
+            damping: 0.5,
+            pre_delay: 0.02,
+            mix: 0.25,
+            combs: Default::default(),
+            allpasses: Default::default(),
+            pre_delay_line: Vec::new(),
+            pre_delay_position: 0,
+        };
+        reverb.set_sample_rate(48000);
+        reverb
+    }
+
+    /// How long the reverb rings out, from 0 (a small room) to 1 (a huge hall).
+    pub fn set_size(&mut self, size: f32) {
+        self.size = size.clamp(0.0, 1.0);
+    }
+
+    /// How quickly high frequencies die away, from 0 (bright) to 1 (dark).
+    pub fn set_damping(&mut self, damping: f32) {
+        self.damping = damping.clamp(0.0, 1.0);
+    }
+
+    /// Time before the reverb starts, in seconds, up to `MAX_PRE_DELAY`.
+    pub fn set_pre_delay(&mut self, seconds: f32) {
+        self.pre_delay = seconds.clamp(0.0, MAX_PRE_DELAY);
+    }
+
+    /// Balance between the dry signal (0) and the reverb (1).
+    pub fn set_mix(&mut self, mix: f32) {
+        self.mix = mix.clamp(0.0, 1.0);
+    }
+}
+
+impl Effect for Reverb {
+    fn name(&self) -> &str {
+        "reverb"
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: u32) {
+        self.sample_rate = sample_rate as f32;
+        let ratio = self.sample_rate / TUNING_RATE;
+        let scale = |len: usize| (len as f32 * ratio) as usize;
+        for channel in 0..CHANNELS {
+            let spread = channel * STEREO_SPREAD;
+            self.combs[channel] = COMB_TUNING
+                .iter()
+                .map(|len| Comb::new(scale(len + spread)))
+                .collect();
+            self.allpasses[channel] = ALLPASS_TUNING
+                .iter()
+                .map(|len| Allpass::new(scale(len + spread)))
+                .collect();
+        }
+        self.pre_delay_line = vec![0.0; (MAX_PRE_DELAY * self.sample_rate) as usize + 1];
+        self.pre_delay_position = 0;
+    }
+
+    fn process(&mut self, block: &mut [Frame]) {
+        // scaled as in Freeverb, so the tail never quite becomes infinite
+        let feedback = 0.7 + self.size * 0.28;
+        let damping = self.damping * 0.4;
+        let pre_delay = (self.pre_delay * self.sample_rate) as usize;
+        let line_len = self.pre_delay_line.len();
+
+        for frame in block.iter_mut() {
+            self.pre_delay_line[self.pre_delay_position] = frame.to_mono() * INPUT_GAIN;
+            let input =
+                self.pre_delay_line[(self.pre_delay_position + line_len - pre_delay) % line_len];
+            self.pre_delay_position = (self.pre_delay_position + 1) % line_len;
+
+            for (channel, out) in frame.iter_mut().enumerate() {
+                let mut wet = self.combs[channel]
+                    .iter_mut()
+                    .map(|comb| comb.process(input, feedback, damping))
+                    .sum::<f32>();
+                for allpass in &mut self.allpasses[channel] {
+                    wet = allpass.process(wet);
+                }
+                wet *= OUTPUT_GAIN;
+                *out += (wet - *out) * self.mix;
+            }
+        }
+    }
+
+    fn reset(&mut self) {
+        for comb in self.combs.iter_mut().flatten() {
+            comb.reset();
+        }
+        for allpass in self.allpasses.iter_mut().flatten() {
+            allpass.reset();
+        }
+        self.pre_delay_line.fill(0.0);
+    }
+
+    fn parameter_names(&self) -> &'static [&'static str] {
+        PARAMETERS
+    }
+
+    fn parameter(&self, index: usize) -> Option<f32> {
+        match index {
+            0 => Some(self.size),
+            1 => Some(self.damping),
+            2 => Some(self.pre_delay),
+            3 => Some(self.mix),
+            _ => None,
+        }
+    }
+
+    fn set_parameter(&mut self, index: usize, value: f32) {
+        match index {
+            0 => self.set_size(value),
+            1 => self.set_damping(value),
+            2 => self.set_pre_delay(value),
+            3 => self.set_mix(value),
+            _ => (),
+        }
+    }
+
+    fn parameter_info(&self, index: usize) -> Option<ParamInfo> {
+        PARAMETER_INFO.get(index).copied()
+    }
+}
@@ -0,0 +1,244 @@
+//! A rotary speaker (Leslie) simulation. The signal is split into highs, played through a spinning
+//! horn, and lows, through a spinning drum; each rotor's movement towards and away from a pair of
+//! microphones gives it a Doppler wobble in pitch and a swell in volume.
+
+use alloc::{vec, vec::Vec};
+use core::f32::consts::{FRAC_1_SQRT_2, TAU};
+
+use super::{
+    biquad::{Biquad, Coefficients},
+    read_delay_line, Effect,
+};
+use crate::{
+    param::{ParamInfo, Unit},
+    Frame,
+};
+
+#[cfg(not(any(feature = "std", test)))]
+use crate::math::Float;
+
+/// Where the signal is split between the drum and the horn, in Hz.
+const CROSSOVER: f32 = 800.0;
+
+const PARAMETERS: &[&str] = &["speed", "mic angle", "mix"];
+
+const PARAMETER_INFO: &[ParamInfo] = &[
+    ParamInfo::stepped("speed", 0.0, 1.0, 0.0),
+    ParamInfo::linear("mic angle", 0.0, 0.25, 0.125, Unit::None),
+    ParamInfo::linear("mix", 0.0, 1.0, 1.0, Unit::None),
+];
+
+/// How one rotor moves and sounds.
+struct RotorModel {
+    /// Revolutions per second on the slow (chorale) and fast (tremolo) settings.
+    slow: f32,
+    fast: f32,
+    /// Time taken to mostly reach a new speed, in seconds. The heavy drum takes much longer.
+    inertia: f32,
+    /// Furthest the rotor's sound is delayed either way as it turns, in seconds.
+    doppler: f32,
+    /// How far the volume dips when the rotor faces away.
+    tremolo: f32,
+}
+
+const HORN: RotorModel = RotorModel {
+    slow: 0.8,
+    fast: 6.7,
+    inertia: 0.6,
+    doppler: 0.0006,
+    tremolo: 0.5,
+};
+
+const DRUM: RotorModel = RotorModel {
+    slow: 0.7,
+    fast: 5.9,
+    inertia: 3.0,
+    doppler: 0.0003,
+    tremolo: 0.3,
+};
+
+struct Rotor {
+    model: &'static RotorModel,
+    /// Revolutions per second.
+    speed: f32,
+    acceleration: f32,
+    /// Position through a revolution, from 0 to 1.
+    angle: f32,
+    line: Vec<f32>,
+    write: usize,
+}
+
+impl Rotor {
+    fn new(model: &'static RotorModel) -> Self {
+        Self {
+            model,
+            speed: model.slow,
+            acceleration: 0.0,
+            angle: 0.0,
+            line: Vec::new(),
+            write: 0,
+        }
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.acceleration = 1.0 - (-1.0 / (self.model.inertia * sample_rate)).exp();
+        self.line = vec![0.0; (2.0 * self.model.doppler * sample_rate) as usize + 3];
+        self.write = 0;
+    }
+
+    /// Play one sample through the rotor, as heard by each microphone.
+    fn process(&mut self, input: f32, fast: bool, mic_angle: f32, sample_rate: f32) -> Frame {
+        self.line[self.write] = input;
+
+        let depth = self.model.doppler * sample_rate;
+        let mut output = Frame::ZERO;
+        for (channel, out) in output.iter_mut().enumerate() {
+            // the microphones sit either side of the cabinet
+            let offset = if channel == 0 { -mic_angle } else { mic_angle };
+            let facing = (TAU * (self.angle + offset)).cos();
+            let delayed = read_delay_line(&self.line, self.write, depth * (1.0 + facing) + 1.0);
+            *out = delayed * (1.0 - self.model.tremolo * 0.5 * (1.0 - facing));
+        }
+
+        self.write = (self.write + 1) % self.line.len();
+        let target = if fast {
+            self.model.fast
+        } else {
+            self.model.slow
+        };
+        self.speed += (target - self.speed) * self.acceleration;
+        self.angle = (self.angle + self.speed / sample_rate).fract();
+        output
+    }
+
+    fn reset(&mut self) {
+        self.line.fill(0.0);
+    }
+}
+
+pub struct Rotary {
+    sample_rate: f32,
+    fast: bool,
+    mic_angle: f32,
+    mix: f32,
+    /// Two of each, for a fourth order (Linkwitz-Riley) crossover which sums back flat.
+    low_pass: [Biquad; 2],
+    high_pass: [Biquad; 2],
+    horn: Rotor,
+    drum: Rotor,
+}
+
+impl Default for Rotary {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Rotary {
+    /// Spinning slowly, with the microphones a quarter turn apart.
+    pub fn new() -> Self {
+        let mut rotary = Self {
+            sample_rate: 0.0,
+            fast: false,
+            mic_angle: 0.125,
+            mix: 1.0,
+            low_pass: Default::default(),
+            high_pass: Default::default(),
+            horn: Rotor::new(&HORN),
+            drum: Rotor::new(&DRUM),
+        };
+        rotary.set_sample_rate(48000);
+        rotary
+    }
+
+    /// Switch between the fast and slow speeds. The rotors speed up or slow down gradually, the
+    /// drum much more slowly than the horn.
+    pub fn set_fast(&mut self, fast: bool) {
+        self.fast = fast;
+    }
+
+    /// How far each microphone is turned away from the front, as a fraction of a revolution, from
+    /// 0 (both in the same place, so mono) to 0.25 (opposite sides).
+    pub fn set_mic_angle(&mut self, angle: f32) {
+        self.mic_angle = angle.clamp(0.0, 0.25);
+    }
+
+    /// Balance between the dry signal (0) and the rotary speaker (1).
+    pub fn set_mix(&mut self, mix: f32) {
+        self.mix = mix.clamp(0.0, 1.0);
+    }
+}
+
+impl Effect for Rotary {
+    fn name(&self) -> &str {
+        "rotary"
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: u32) {
+        self.sample_rate = sample_rate as f32;
+        let low_pass = Coefficients::low_pass(self.sample_rate, CROSSOVER, FRAC_1_SQRT_2);
+        let high_pass = Coefficients::high_pass(self.sample_rate, CROSSOVER, FRAC_1_SQRT_2);
+        for filter in &mut self.low_pass {
+            filter.set_coefficients(low_pass);
+        }
+        for filter in &mut self.high_pass {
+            filter.set_coefficients(high_pass);
+        }
+        self.horn.set_sample_rate(self.sample_rate);
+        self.drum.set_sample_rate(self.sample_rate);
+    }
+
+    fn process(&mut self, block: &mut [Frame]) {
+        for frame in block.iter_mut() {
+            let input = frame.to_mono();
+            let lows = self.low_pass.iter_mut().fold(input, |s, f| f.process(s));
+            let highs = self.high_pass.iter_mut().fold(input, |s, f| f.process(s));
+
+            let drum = self
+                .drum
+                .process(lows, self.fast, self.mic_angle, self.sample_rate);
+            let horn = self
+                .horn
+                .process(highs, self.fast, self.mic_angle, self.sample_rate);
+            *frame += (drum + horn - *frame) * self.mix;
+        }
+    }
+
+    fn reset(&mut self) {
+        for filter in self.low_pass.iter_mut().chain(&mut self.high_pass) {
+            filter.reset();
+        }
+        self.horn.reset();
+        self.drum.reset();
+    }
+
+    fn parameter_names(&self) -> &'static [&'static str] {
+        PARAMETERS
+    }
+
+    fn parameter(&self, index: usize) -> Option<f32> {
+        match index {
+            0 => Some(self.fast as u8 as f32),
+            1 => Some(self.mic_angle),
+            2 => Some(self.mix),
+            _ => None,
+        }
+    }
+
+    fn set_parameter(&mut self, index: usize, value: f32) {
+        match index {
+            0 => self.set_fast(value >= 0.5),
+            1 => self.set_mic_angle(value),
+            2 => self.set_mix(value),
+            _ => (),
+        }
+    }
+
+    fn is_discrete(&self, index: usize) -> bool {
+        matches!(index, 0)
+    }
+
+    fn parameter_info(&self, index: usize) -> Option<ParamInfo> {
+        PARAMETER_INFO.get(index).copied()
+    }
+}
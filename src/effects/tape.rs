@@ -0,0 +1,148 @@
+//! Tape style saturation, to warm up the clean digital output: loud parts are gently compressed
+//! and rounded off, a little lopsidedly, and the very top end rolls away.
+
+use core::f32::consts::TAU;
+
+use super::{db_to_gain, Effect, Oversampler};
+use crate::{
+    param::{ParamInfo, Unit},
+    DcBlocker, Frame, Oversampling, CHANNELS,
+};
+
+#[cfg(not(any(feature = "std", test)))]
+use crate::math::Float;
+
+const PARAMETERS: &[&str] = &["drive", "bias", "rolloff", "mix"];
+
+const PARAMETER_INFO: &[ParamInfo] = &[
+    ParamInfo::linear("drive", 0.0, 24.0, 6.0, Unit::Decibels),
+    ParamInfo::linear("bias", -1.0, 1.0, 0.1, Unit::None),
+    ParamInfo::log("rolloff", 1000.0, 20000.0, 14000.0, Unit::Hz),
+    ParamInfo::linear("mix", 0.0, 1.0, 1.0, Unit::None),
+];
+
+pub struct Tape {
+    sample_rate: f32,
+    drive: f32,
+    bias: f32,
+    rolloff: f32,
+    mix: f32,
+    smoothing: f32,
+    oversamplers: [Oversampler; CHANNELS],
+    dc_blockers: [DcBlocker; CHANNELS],
+    smoothed: [f32; CHANNELS],
+}
+
+impl Default for Tape {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Tape {
+    /// Lightly driven, with a touch of bias and the top end rolling off from 14kHz.
+    pub fn new() -> Self {
+        let mut tape = Self {
+            sample_rate: 0.0,
+            drive: 6.0,
+            bias: 0.1,
+            rolloff: 14000.0,
+            mix: 1.0,
+            smoothing: 0.0,
+            oversamplers: [(); CHANNELS].map(|_| Oversampler::new(Oversampling::X2)),
+            dc_blockers: [(); CHANNELS].map(|_| DcBlocker::new(48000.0)),
+            smoothed: [0.0; CHANNELS],
+        };
+        tape.set_sample_rate(48000);
+        tape
+    }
+
+    /// How hard the tape is driven, in dB, from 0 to 24. Quiet signals come out at the same
+    /// level whatever the drive; louder ones are squashed more and more.
+    pub fn set_drive(&mut self, drive: f32) {
+        self.drive = drive.clamp(0.0, 24.0);
+    }
+
+    /// Offset added before saturating, from -1 to 1, so that the two halves of the wave saturate
+    /// differently and even harmonics come in.
+    pub fn set_bias(&mut self, bias: f32) {
+        self.bias = bias.clamp(-1.0, 1.0);
+    }
+
+    /// Frequency above which the output rolls off, in Hz.
+    pub fn set_rolloff(&mut self, frequency: f32) {
+        self.rolloff = frequency.clamp(1000.0, 20000.0);
+        self.smoothing = 1.0 - (-TAU * self.rolloff / self.sample_rate).exp();
+    }
+
+    /// Balance between the dry signal (0) and the saturated signal (1).
+    pub fn set_mix(&mut self, mix: f32) {
+        self.mix = mix.clamp(0.0, 1.0);
+    }
+}
+
+impl Effect for Tape {
+    fn name(&self) -> &str {
+        "tape"
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: u32) {
+        self.sample_rate = sample_rate as f32;
+        self.dc_blockers = [(); CHANNELS].map(|_| DcBlocker::new(self.sample_rate));
+        self.set_rolloff(self.rolloff);
+    }
+
+    fn process(&mut self, block: &mut [Frame]) {
+        let drive = db_to_gain(self.drive);
+        let bias = self.bias;
+        // taking off the bias's own offset keeps silence silent
+        let offset = bias.tanh();
+
+        for frame in block.iter_mut() {
+            for (channel, out) in frame.iter_mut().enumerate() {
+                let (dry, wet) = self.oversamplers[channel]
+                    .process(*out, |x| ((x * drive + bias).tanh() - offset) / drive);
+                let wet = self.dc_blockers[channel].process(wet);
+                let smoothed = &mut self.smoothed[channel];
+                *smoothed += (wet - *smoothed) * self.smoothing;
+                *out = dry + (*smoothed - dry) * self.mix;
+            }
+        }
+    }
+
+    fn reset(&mut self) {
+        for oversampler in &mut self.oversamplers {
+            oversampler.reset();
+        }
+        self.dc_blockers = [(); CHANNELS].map(|_| DcBlocker::new(self.sample_rate));
+        self.smoothed = [0.0; CHANNELS];
+    }
+
+    fn parameter_names(&self) -> &'static [&'static str] {
+        PARAMETERS
+    }
+
+    fn parameter(&self, index: usize) -> Option<f32> {
+        match index {
+            0 => Some(self.drive),
+            1 => Some(self.bias),
+            2 => Some(self.rolloff),
+            3 => Some(self.mix),
+            _ => None,
+        }
+    }
+
+    fn set_parameter(&mut self, index: usize, value: f32) {
+        match index {
+            0 => self.set_drive(value),
+            1 => self.set_bias(value),
+            2 => self.set_rolloff(value),
+            3 => self.set_mix(value),
+            _ => (),
+        }
+    }
+
+    fn parameter_info(&self, index: usize) -> Option<ParamInfo> {
+        PARAMETER_INFO.get(index).copied()
+    }
+}
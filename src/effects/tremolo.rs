@@ -0,0 +1,148 @@
+//! A tremolo, which pulses the volume up and down with an LFO, optionally in time with the tempo.
+
+use core::f32::consts::TAU;
+
+use super::{Effect, NoteDivision, DEFAULT_TEMPO, SYNC_STEPS};
+use crate::{
+    param::{ParamInfo, Unit},
+    Frame,
+};
+
+#[cfg(not(any(feature = "std", test)))]
+use crate::math::Float;
+
+const PARAMETERS: &[&str] = &["rate", "depth", "stereo phase", "sync"];
+
+const PARAMETER_INFO: &[ParamInfo] = &[
+    ParamInfo::log("rate", 0.05, 20.0, 5.0, Unit::Hz),
+    ParamInfo::linear("depth", 0.0, 1.0, 0.5, Unit::None),
+    ParamInfo::linear("stereo phase", 0.0, 1.0, 0.0, Unit::None),
+    ParamInfo::stepped("sync", 0.0, SYNC_STEPS, 0.0),
+];
+
+pub struct Tremolo {
+    sample_rate: f32,
+    rate: f32,
+    depth: f32,
+    stereo_phase: f32,
+    sync: Option<NoteDivision>,
+    tempo: f32,
+    /// Position through the LFO cycle, from 0 to 1.
+    phase: f32,
+}
+
+impl Default for Tremolo {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Tremolo {
+    /// A fairly quick, fairly deep pulse, the same in both channels.
+    pub fn new() -> Self {
+        let mut tremolo = Self {
+            sample_rate: 0.0,
+            rate: 5.0,
+            depth: 0.5,
+            stereo_phase: 0.0,
+            sync: None,
+            tempo: DEFAULT_TEMPO,
+            phase: 0.0,
+        };
+        tremolo.set_sample_rate(48000);
+        tremolo
+    }
+
+    /// Pulses per second, when not synced to the tempo.
+    pub fn set_rate(&mut self, rate: f32) {
+        self.rate = rate.clamp(0.05, 20.0);
+    }
+
+    /// How far the volume dips, from 0 (not at all) to 1 (all the way to silence).
+    pub fn set_depth(&mut self, depth: f32) {
+        self.depth = depth.clamp(0.0, 1.0);
+    }
+
+    /// How far out of step the right channel is with the left, as a fraction of a cycle. At 0.5
+    /// the sound bounces from side to side.
+    pub fn set_stereo_phase(&mut self, phase: f32) {
+        self.stereo_phase = phase.clamp(0.0, 1.0);
+    }
+
+    /// Pulse once per note division at the current tempo, or at the free rate again with `None`.
+    pub fn set_sync(&mut self, division: Option<NoteDivision>) {
+        self.sync = division;
+    }
+
+    /// Pulses per second actually in use.
+    pub fn effective_rate(&self) -> f32 {
+        match self.sync {
+            Some(division) => 1.0 / division.seconds(self.tempo),
+            None => self.rate,
+        }
+    }
+}
+
+impl Effect for Tremolo {
+    fn name(&self) -> &str {
+        "tremolo"
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: u32) {
+        self.sample_rate = sample_rate as f32;
+    }
+
+    fn set_tempo(&mut self, bpm: f32) {
+        self.tempo = bpm;
+    }
+
+    fn process(&mut self, block: &mut [Frame]) {
+        let step = self.effective_rate() / self.sample_rate;
+
+        for frame in block.iter_mut() {
+            for (channel, sample) in frame.iter_mut().enumerate() {
+                let phase = self.phase + channel as f32 * self.stereo_phase;
+                // a raised cosine, so the volume starts off at full
+                let lfo = 0.5 + 0.5 * (TAU * phase).cos();
+                *sample *= 1.0 - self.depth * (1.0 - lfo);
+            }
+            self.phase = (self.phase + step).fract();
+        }
+    }
+
+    fn reset(&mut self) {
+        self.phase = 0.0;
+    }
+
+    fn parameter_names(&self) -> &'static [&'static str] {
+        PARAMETERS
+    }
+
+    fn parameter(&self, index: usize) -> Option<f32> {
+        match index {
+            0 => Some(self.rate),
+            1 => Some(self.depth),
+            2 => Some(self.stereo_phase),
+            3 => Some(NoteDivision::to_parameter(self.sync)),
+            _ => None,
+        }
+    }
+
+    fn set_parameter(&mut self, index: usize, value: f32) {
+        match index {
+            0 => self.set_rate(value),
+            1 => self.set_depth(value),
+            2 => self.set_stereo_phase(value),
+            3 => self.set_sync(NoteDivision::from_parameter(value)),
+            _ => (),
+        }
+    }
+
+    fn is_discrete(&self, index: usize) -> bool {
+        matches!(index, 3)
+    }
+
+    fn parameter_info(&self, index: usize) -> Option<ParamInfo> {
+        PARAMETER_INFO.get(index).copied()
+    }
+}
@@ -0,0 +1,159 @@
+//! A channel vocoder. The modulator (e.g. a voice, from an external input) is split into bands,
+//! and how loud it is in each one sets the level of the same band of the carrier (the synth's
+//! own voices), so the synth takes on the modulator's formants and talks.
+
+use alloc::vec::Vec;
+use core::f32::consts::FRAC_1_SQRT_2;
+
+use super::biquad::{Biquad, Coefficients};
+use crate::{Frame, CHANNELS};
+
+#[cfg(not(any(feature = "std", test)))]
+use crate::math::Float;
+
+/// Range of the filter bank, in Hz.
+const LOWEST_BAND: f32 = 100.0;
+const HIGHEST_BAND: f32 = 8000.0;
+/// Above which the modulator is passed straight through, for sibilance, in Hz.
+const SIBILANCE_CUTOFF: f32 = 6000.0;
+/// How quickly the band levels follow the modulator rising, in seconds.
+const ATTACK_TIME: f32 = 0.002;
+/// Makes up for the level lost by splitting the carrier into narrow bands.
+const OUTPUT_GAIN: f32 = 8.0;
+
+/// Most bands allowed in the filter bank.
+pub const MAX_BANDS: usize = 32;
+
+struct Band {
+    analysis: Biquad,
+    synthesis: [Biquad; CHANNELS],
+    envelope: f32,
+}
+
+pub struct Vocoder {
+    sample_rate: f32,
+    band_count: usize,
+    release: f32,
+    sibilance: f32,
+    bands: Vec<Band>,
+    attack_coefficient: f32,
+    release_coefficient: f32,
+    sibilance_filter: Biquad,
+}
+
+impl Vocoder {
+    /// Sixteen bands, with a little sibilance let through.
+    pub fn new(sample_rate: u32) -> Self {
+        let mut vocoder = Self {
+            sample_rate: sample_rate as f32,
+            band_count: 16,
+            release: 0.03,
+            sibilance: 0.2,
+            bands: Vec::with_capacity(MAX_BANDS),
+            attack_coefficient: 0.0,
+            release_coefficient: 0.0,
+            sibilance_filter: Biquad::default(),
+        };
+        vocoder.attack_coefficient = vocoder.time_coefficient(ATTACK_TIME);
+        vocoder.set_release(vocoder.release);
+        vocoder.update();
+        vocoder
+    }
+
+    /// How many bands the spectrum is split into, from 4 to `MAX_BANDS`. More bands make speech
+    /// clearer.
+    pub fn set_bands(&mut self, bands: usize) {
+        self.band_count = bands.clamp(4, MAX_BANDS);
+        self.update();
+    }
+
+    pub fn bands(&self) -> usize {
+        self.band_count
+    }
+
+    /// How quickly the band levels follow the modulator falling, in seconds. Longer is smoother,
+    /// but less articulate.
+    pub fn set_release(&mut self, release: f32) {
+        self.release = release.clamp(0.005, 1.0);
+        self.release_coefficient = self.time_coefficient(self.release);
+    }
+
+    pub fn release(&self) -> f32 {
+        self.release
+    }
+
+    /// How much of the modulator's highs to mix straight in, from 0 to 1, so that consonants like
+    /// "s" and "t" (which the carrier probably lacks) still come through.
+    pub fn set_sibilance(&mut self, sibilance: f32) {
+        self.sibilance = sibilance.clamp(0.0, 1.0);
+    }
+
+    pub fn sibilance(&self) -> f32 {
+        self.sibilance
+    }
+
+    fn time_coefficient(&self, time: f32) -> f32 {
+        1.0 - (-1.0 / (time * self.sample_rate)).exp()
+    }
+
+    /// Rebuild the filter bank to match the settings.
+    fn update(&mut self) {
+        // spaced evenly in pitch, each band about as wide as the gap between them
+        let octaves = (HIGHEST_BAND / LOWEST_BAND).log2();
+        let spacing = octaves / (self.band_count - 1) as f32;
+        let q = 2f32.powf(spacing / 2.0) / (2f32.powf(spacing) - 1.0);
+        self.bands.clear();
+        for band in 0..self.band_count {
+            let frequency = LOWEST_BAND * 2f32.powf(band as f32 * spacing);
+            let coefficients = Coefficients::band_pass(self.sample_rate, frequency, q);
+            self.bands.push(Band {
+                analysis: Biquad::new(coefficients),
+                synthesis: [Biquad::new(coefficients); CHANNELS],
+                envelope: 0.0,
+            });
+        }
+        self.sibilance_filter = Biquad::new(Coefficients::high_pass(
+            self.sample_rate,
+            SIBILANCE_CUTOFF,
+            FRAC_1_SQRT_2,
+        ));
+    }
+
+    /// Replace the `carrier` with the vocoded sound, following one (mono) sample of `modulator`
+    /// per frame.
+    pub fn process(&mut self, carrier: &mut [Frame], modulator: &[f32]) {
+        for (frame, modulator) in carrier.iter_mut().zip(modulator) {
+            let mut output = Frame::ZERO;
+            for band in &mut self.bands {
+                let level = band.analysis.process(*modulator).abs();
+                let rate = if level > band.envelope {
+                    self.attack_coefficient
+                } else {
+                    self.release_coefficient
+                };
+                band.envelope += (level - band.envelope) * rate;
+
+                for ((out, filter), sample) in
+                    output.iter_mut().zip(&mut band.synthesis).zip(&*frame)
+                {
+                    *out += filter.process(*sample) * band.envelope;
+                }
+            }
+
+            let sibilance = self.sibilance_filter.process(*modulator) * self.sibilance;
+            *frame = output * OUTPUT_GAIN + sibilance;
+        }
+    }
+
+    /// Forget the signal in the filters, and the band levels.
+    pub fn reset(&mut self) {
+        for band in &mut self.bands {
+            band.analysis.reset();
+            for filter in &mut band.synthesis {
+                filter.reset();
+            }
+            band.envelope = 0.0;
+        }
+        self.sibilance_filter.reset();
+    }
+}
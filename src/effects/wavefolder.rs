@@ -0,0 +1,135 @@
+//! A West coast style wavefolder: rather than clipping, peaks which go past full scale are
+//! reflected back down, over and over as the fold amount goes up. On a sine this sweeps through
+//! a whole series of harmonics, much like a filter sweep in reverse.
+
+use super::{Effect, Oversampler};
+use crate::{
+    param::{ParamInfo, Unit},
+    DcBlocker, Frame, Oversampling, CHANNELS,
+};
+
+#[cfg(not(any(feature = "std", test)))]
+use crate::math::Float;
+
+/// Gain before folding at full fold amount.
+const MAX_GAIN: f32 = 10.0;
+
+const PARAMETERS: &[&str] = &["fold", "symmetry", "mix"];
+
+const PARAMETER_INFO: &[ParamInfo] = &[
+    ParamInfo::linear("fold", 0.0, 1.0, 0.3, Unit::None),
+    ParamInfo::linear("symmetry", -1.0, 1.0, 0.0, Unit::None),
+    ParamInfo::linear("mix", 0.0, 1.0, 1.0, Unit::None),
+];
+
+/// Fold `x` back and forth into the range -1 to 1, as a triangle wave of it.
+pub fn fold(x: f32) -> f32 {
+    let t = (x + 1.0) * 0.25;
+    4.0 * (t - (t + 0.5).floor()).abs() - 1.0
+}
+
+pub struct Wavefolder {
+    sample_rate: u32,
+    fold: f32,
+    symmetry: f32,
+    mix: f32,
+    oversamplers: [Oversampler; CHANNELS],
+    dc_blockers: [DcBlocker; CHANNELS],
+}
+
+impl Default for Wavefolder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Wavefolder {
+    /// A moderate amount of symmetrical folding.
+    pub fn new() -> Self {
+        let mut folder = Self {
+            sample_rate: 0,
+            fold: 0.3,
+            symmetry: 0.0,
+            mix: 1.0,
+            oversamplers: [(); CHANNELS].map(|_| Oversampler::new(Oversampling::X4)),
+            dc_blockers: [(); CHANNELS].map(|_| DcBlocker::new(48000.0)),
+        };
+        folder.set_sample_rate(48000);
+        folder
+    }
+
+    /// How hard the signal is driven into the folder, from 0 (not at all) to 1 (many folds).
+    pub fn set_fold(&mut self, fold: f32) {
+        self.fold = fold.clamp(0.0, 1.0);
+    }
+
+    /// Offset added before folding, from -1 to 1, so that the two halves of the wave fold
+    /// differently and even harmonics come in.
+    pub fn set_symmetry(&mut self, symmetry: f32) {
+        self.symmetry = symmetry.clamp(-1.0, 1.0);
+    }
+
+    /// Balance between the dry signal (0) and the folded signal (1).
+    pub fn set_mix(&mut self, mix: f32) {
+        self.mix = mix.clamp(0.0, 1.0);
+    }
+}
+
+impl Effect for Wavefolder {
+    fn name(&self) -> &str {
+        "wavefolder"
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: u32) {
+        self.sample_rate = sample_rate;
+        self.dc_blockers = [(); CHANNELS].map(|_| DcBlocker::new(sample_rate as f32));
+    }
+
+    fn process(&mut self, block: &mut [Frame]) {
+        let gain = 1.0 + self.fold * (MAX_GAIN - 1.0);
+        let offset = self.symmetry;
+
+        for frame in block.iter_mut() {
+            for (channel, out) in frame.iter_mut().enumerate() {
+                let (dry, wet) =
+                    self.oversamplers[channel].process(*out, |x| fold(x * gain + offset));
+                // the offset leaves the output lopsided
+                let wet = self.dc_blockers[channel].process(wet);
+                *out = dry + (wet - dry) * self.mix;
+            }
+        }
+    }
+
+    fn reset(&mut self) {
+        for oversampler in &mut self.oversamplers {
+            oversampler.reset();
+        }
+        self.set_sample_rate(self.sample_rate);
+    }
+
+    fn parameter_names(&self) -> &'static [&'static str] {
+        PARAMETERS
+    }
+
+    fn parameter(&self, index: usize) -> Option<f32> {
+        match index {
+            0 => Some(self.fold),
+            1 => Some(self.symmetry),
+            2 => Some(self.mix),
+            _ => None,
+        }
+    }
+
+    fn set_parameter(&mut self, index: usize, value: f32) {
+        match index {
+            0 => self.set_fold(value),
+            1 => self.set_symmetry(value),
+            2 => self.set_mix(value),
+            _ => (),
+        }
+    }
+
+    fn parameter_info(&self, index: usize) -> Option<ParamInfo> {
+        PARAMETER_INFO.get(index).copied()
+    }
+}
@@ -0,0 +1,112 @@
+//! A stereo widener, which splits the signal into mid (what both channels share) and side (how
+//! they differ), and turns the side up or down.
+
+use super::{
+    biquad::{Biquad, Coefficients},
+    Effect,
+};
+use crate::{
+    param::{ParamInfo, Unit},
+    Frame,
+};
+
+const PARAMETERS: &[&str] = &["width", "bass mono"];
+
+const PARAMETER_INFO: &[ParamInfo] = &[
+    ParamInfo::linear("width", 0.0, 2.0, 1.5, Unit::None),
+    ParamInfo::linear("bass mono", 0.0, 500.0, 120.0, Unit::Hz),
+];
+
+pub struct Widener {
+    sample_rate: f32,
+    width: f32,
+    bass_mono: f32,
+    /// Takes the lows out of the side signal.
+    side_filter: Biquad,
+}
+
+impl Default for Widener {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Widener {
+    /// Half as wide again, keeping the bass in the middle.
+    pub fn new() -> Self {
+        let mut widener = Self {
+            sample_rate: 0.0,
+            width: 1.5,
+            bass_mono: 120.0,
+            side_filter: Biquad::default(),
+        };
+        widener.set_sample_rate(48000);
+        widener
+    }
+
+    /// Gain on the side signal, from 0 (mono) through 1 (unchanged) to 2 (very wide).
+    pub fn set_width(&mut self, width: f32) {
+        self.width = width.clamp(0.0, 2.0);
+    }
+
+    /// Frequency below which the sound is kept in the middle, in Hz, so that widening doesn't
+    /// make the bass vague or cancel out in mono. 0 turns this off.
+    pub fn set_bass_mono(&mut self, frequency: f32) {
+        self.bass_mono = frequency.clamp(0.0, 500.0);
+        self.update();
+    }
+
+    fn update(&mut self) {
+        self.side_filter.set_coefficients(if self.bass_mono > 0.0 {
+            Coefficients::high_pass(self.sample_rate, self.bass_mono, 0.5)
+        } else {
+            Coefficients::default()
+        });
+    }
+}
+
+impl Effect for Widener {
+    fn name(&self) -> &str {
+        "widener"
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: u32) {
+        self.sample_rate = sample_rate as f32;
+        self.update();
+    }
+
+    fn process(&mut self, block: &mut [Frame]) {
+        for frame in block.iter_mut() {
+            let side = self.side_filter.process(frame.side()) * self.width;
+            *frame = Frame::from_mid_side(frame.to_mono(), side);
+        }
+    }
+
+    fn reset(&mut self) {
+        self.side_filter.reset();
+    }
+
+    fn parameter_names(&self) -> &'static [&'static str] {
+        PARAMETERS
+    }
+
+    fn parameter(&self, index: usize) -> Option<f32> {
+        match index {
+            0 => Some(self.width),
+            1 => Some(self.bass_mono),
+            _ => None,
+        }
+    }
+
+    fn set_parameter(&mut self, index: usize, value: f32) {
+        match index {
+            0 => self.set_width(value),
+            1 => self.set_bass_mono(value),
+            _ => (),
+        }
+    }
+
+    fn parameter_info(&self, index: usize) -> Option<ParamInfo> {
+        PARAMETER_INFO.get(index).copied()
+    }
+}
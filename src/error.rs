@@ -0,0 +1,49 @@
+//! What can go wrong when making or controlling a synth.
+
+use core::fmt;
+
+use crate::param::ParamId;
+
+/// Why a synth couldn't do what it was asked.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SynthError {
+    /// Every voice was already playing, so a note couldn't be started (see
+    /// `Synth::set_voice_stealing`).
+    OutOfVoices,
+    /// No voice was playing the note, so it couldn't be ended.
+    NoSuchNote,
+    /// The parameter doesn't exist (e.g. an effect slot with nothing in it), or can't take the
+    /// value.
+    InvalidParameter { param: ParamId, value: f32 },
+    /// The sample rate is outside of what the synth can run at, from `MIN_SAMPLE_RATE` to
+    /// `MAX_SAMPLE_RATE`.
+    UnsupportedSampleRate(u32),
+    /// Blocks have to be at least one frame long.
+    InvalidBlockSize,
+    /// A synth needs at least one voice.
+    NoVoices,
+    /// Too many commands are already waiting to be carried out.
+    ScheduleFull,
+    /// The synth has fewer voices than that.
+    NoSuchVoice(usize),
+}
+
+impl fmt::Display for SynthError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::OutOfVoices => write!(f, "All voices are busy"),
+            Self::NoSuchNote => write!(f, "The note isn't playing"),
+            Self::InvalidParameter { param, value } => {
+                write!(f, "Invalid value for {:?}: {}", param, value)
+            }
+            Self::UnsupportedSampleRate(rate) => write!(f, "Unsupported sample rate: {} Hz", rate),
+            Self::InvalidBlockSize => write!(f, "Block size must be at least one frame"),
+            Self::NoVoices => write!(f, "A synth needs at least one voice"),
+            Self::ScheduleFull => write!(f, "Too many commands are waiting"),
+            Self::NoSuchVoice(index) => write!(f, "No such voice: {}", index),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for SynthError {}
@@ -0,0 +1,395 @@
+//! A C API, for embedding the synth in C and C++ hosts, game engines, and other languages which
+//! can call C.
+//!
+//! Build the library with the `ffi` feature, e.g. `cargo build --release --features ffi`, and
+//! include `include/basic_synth.h`, which is generated from this module with cbindgen:
+//! `cbindgen --config cbindgen.toml --output include/basic_synth.h`.
+//!
+//! A synth is created with `basic_synth_new`, and is used from one thread at a time. Functions
+//! which can fail return one of the `BASIC_SYNTH_*` status codes, which are zero for success and
+//! negative otherwise. Parameters are numbered as `ParamId::fixed` orders them.
+
+use core::{
+    ffi::{c_char, CStr},
+    ptr, slice,
+};
+
+#[cfg(feature = "midi")]
+use {crate::ump, midi_msg::MidiMsg};
+
+use crate::{
+    param::{Curve, ParamId},
+    patch::Patch,
+    Synth, SynthCommand, SynthError, CHANNELS,
+};
+
+pub const BASIC_SYNTH_OK: i32 = 0;
+/// A pointer which shouldn't have been null was.
+pub const BASIC_SYNTH_ERROR_NULL: i32 = -1;
+/// An argument was out of range, or couldn't be understood.
+pub const BASIC_SYNTH_ERROR_INVALID_ARGUMENT: i32 = -2;
+/// Every voice was busy, so a note couldn't be started.
+pub const BASIC_SYNTH_ERROR_OUT_OF_VOICES: i32 = -3;
+/// No voice was playing the note, so it couldn't be ended.
+pub const BASIC_SYNTH_ERROR_NO_SUCH_NOTE: i32 = -4;
+/// The parameter can't take the value.
+pub const BASIC_SYNTH_ERROR_INVALID_PARAMETER: i32 = -5;
+/// Too many events are waiting for later in the buffer.
+pub const BASIC_SYNTH_ERROR_SCHEDULE_FULL: i32 = -6;
+
+/// Start a note, at `note` and `velocity`.
+pub const BASIC_SYNTH_EVENT_NOTE_ON: u32 = 0;
+/// Release a note, at `note`.
+pub const BASIC_SYNTH_EVENT_NOTE_OFF: u32 = 1;
+/// Bend all notes by `value` semitones.
+pub const BASIC_SYNTH_EVENT_PITCH_BEND: u32 = 2;
+/// Release every note.
+pub const BASIC_SYNTH_EVENT_ALL_NOTES_OFF: u32 = 3;
+/// Silence every voice at once.
+pub const BASIC_SYNTH_EVENT_ALL_SOUND_OFF: u32 = 4;
+
+pub const BASIC_SYNTH_CURVE_LINEAR: u32 = 0;
+/// Evenly in ratio rather than difference, as suits frequencies and times.
+pub const BASIC_SYNTH_CURVE_LOGARITHMIC: u32 = 1;
+/// Whole numbers only, each picking a different setting.
+pub const BASIC_SYNTH_CURVE_STEPPED: u32 = 2;
+
+/// Samples in each frame of audio, which is interleaved.
+pub const BASIC_SYNTH_CHANNELS: usize = 2;
+
+// spelled out above so that the header has the number
+const _: () = assert!(BASIC_SYNTH_CHANNELS == CHANNELS);
+
+/// A synth, made by `basic_synth_new` and freed by `basic_synth_free`.
+pub struct BasicSynth(Synth);
+
+/// Something for the synth to do, as pushed with `basic_synth_push_event`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct BasicSynthEvent {
+    /// One of the `BASIC_SYNTH_EVENT_*` kinds.
+    pub kind: u32,
+    /// How many frames into the next call to `basic_synth_process` it happens. Zero has it
+    /// happen straight away.
+    pub offset: u32,
+    pub note: u8,
+    pub velocity: u8,
+    pub value: f32,
+}
+
+/// What a parameter's values mean, as filled in by `basic_synth_param_info`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct BasicSynthParamInfo {
+    pub min: f32,
+    pub max: f32,
+    pub default_value: f32,
+    /// One of the `BASIC_SYNTH_CURVE_*` curves.
+    pub curve: u32,
+}
+
+fn status(result: Result<(), SynthError>) -> i32 {
+    match result {
+        Ok(()) => BASIC_SYNTH_OK,
+        Err(SynthError::OutOfVoices) => BASIC_SYNTH_ERROR_OUT_OF_VOICES,
+        Err(SynthError::NoSuchNote) => BASIC_SYNTH_ERROR_NO_SUCH_NOTE,
+        Err(SynthError::InvalidParameter { .. }) => BASIC_SYNTH_ERROR_INVALID_PARAMETER,
+        Err(SynthError::ScheduleFull) => BASIC_SYNTH_ERROR_SCHEDULE_FULL,
+        Err(
+            SynthError::UnsupportedSampleRate(_)
+            | SynthError::InvalidBlockSize
+            | SynthError::NoVoices
+            | SynthError::NoSuchVoice(_),
+        ) => BASIC_SYNTH_ERROR_INVALID_ARGUMENT,
+    }
+}
+
+fn param(index: usize) -> Option<ParamId> {
+    ParamId::fixed().nth(index)
+}
+
+/// Make a synth with `voices` voices, rendering at `sample_rate`. Returns null if the sample rate
+/// isn't supported, or `voices` is zero.
+#[no_mangle]
+pub extern "C" fn basic_synth_new(sample_rate: u32, voices: usize) -> *mut BasicSynth {
+    if voices == 0 {
+        return ptr::null_mut();
+    }
+    match Synth::builder()
+        .voices(voices)
+        .sample_rate(sample_rate)
+        .build()
+    {
+        Ok(mut synth) => {
+            // there's no undo in the API, and saving settings for it would allocate while the
+            // host pushes MIDI from its audio callback
+            synth.set_undo_enabled(false);
+            Box::into_raw(Box::new(BasicSynth(synth)))
+        }
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+/// Free a synth made by `basic_synth_new`. Does nothing if `synth` is null.
+///
+/// # Safety
+///
+/// `synth` must have come from `basic_synth_new`, and not have been freed already.
+#[no_mangle]
+pub unsafe extern "C" fn basic_synth_free(synth: *mut BasicSynth) {
+    if !synth.is_null() {
+        drop(Box::from_raw(synth));
+    }
+}
+
+/// Render `frames` frames of interleaved audio into `out`, which holds `BASIC_SYNTH_CHANNELS`
+/// samples for each. This doesn't allocate, so is fine to call from an audio callback.
+///
+/// # Safety
+///
+/// `synth` must be a live synth, and `out` must point to at least `frames *
+/// BASIC_SYNTH_CHANNELS` floats.
+#[no_mangle]
+pub unsafe extern "C" fn basic_synth_process(
+    synth: *mut BasicSynth,
+    out: *mut f32,
+    frames: usize,
+) -> i32 {
+    let synth = match synth.as_mut() {
+        Some(synth) => synth,
+        None => return BASIC_SYNTH_ERROR_NULL,
+    };
+    if out.is_null() {
+        return BASIC_SYNTH_ERROR_NULL;
+    }
+    synth
+        .0
+        .process(slice::from_raw_parts_mut(out, frames * CHANNELS));
+    BASIC_SYNTH_OK
+}
+
+/// Have the synth do something, now or partway through the next buffer.
+///
+/// # Safety
+///
+/// `synth` must be a live synth, and `event` must point to an event.
+#[no_mangle]
+pub unsafe extern "C" fn basic_synth_push_event(
+    synth: *mut BasicSynth,
+    event: *const BasicSynthEvent,
+) -> i32 {
+    let (synth, event) = match (synth.as_mut(), event.as_ref()) {
+        (Some(synth), Some(event)) => (synth, event),
+        _ => return BASIC_SYNTH_ERROR_NULL,
+    };
+    let command = match event.kind {
+        BASIC_SYNTH_EVENT_NOTE_ON => SynthCommand::NoteOn {
+            note: event.note,
+            velocity: event.velocity,
+        },
+        BASIC_SYNTH_EVENT_NOTE_OFF => SynthCommand::NoteOff { note: event.note },
+        BASIC_SYNTH_EVENT_PITCH_BEND => SynthCommand::PitchBend(event.value),
+        BASIC_SYNTH_EVENT_ALL_NOTES_OFF => SynthCommand::AllNotesOff,
+        BASIC_SYNTH_EVENT_ALL_SOUND_OFF => SynthCommand::AllSoundOff,
+        _ => return BASIC_SYNTH_ERROR_INVALID_ARGUMENT,
+    };
+    if event.offset == 0 {
+        status(synth.0.apply(command))
+    } else {
+        let frame = synth.0.clock() + event.offset as u64;
+        status(synth.0.schedule(frame, command))
+    }
+}
+
+/// Have the synth respond to a MIDI message, `len` bytes long, straight away.
+///
+/// # Safety
+///
+/// `synth` must be a live synth, and `data` must point to `len` bytes.
+#[cfg(feature = "midi")]
+#[no_mangle]
+pub unsafe extern "C" fn basic_synth_push_midi(
+    synth: *mut BasicSynth,
+    data: *const u8,
+    len: usize,
+) -> i32 {
+    let synth = match synth.as_mut() {
+        Some(synth) => synth,
+        None => return BASIC_SYNTH_ERROR_NULL,
+    };
+    if data.is_null() {
+        return BASIC_SYNTH_ERROR_NULL;
+    }
+    match MidiMsg::from_midi(slice::from_raw_parts(data, len)) {
+        Ok((msg, _)) => status(synth.0.handle_midi(&msg)),
+        Err(_) => BASIC_SYNTH_ERROR_INVALID_ARGUMENT,
+    }
+}
+
+/// Have the synth respond to Universal MIDI Packets, as MIDI 2.0 sends, `len` 32-bit words of
+/// them, straight away. Stops at the first packet which fails, returning why.
+///
+/// # Safety
+///
+/// `synth` must be a live synth, and `words` must point to `len` words.
+#[cfg(feature = "midi")]
+#[no_mangle]
+pub unsafe extern "C" fn basic_synth_push_ump(
+    synth: *mut BasicSynth,
+    words: *const u32,
+    len: usize,
+) -> i32 {
+    let synth = match synth.as_mut() {
+        Some(synth) => synth,
+        None => return BASIC_SYNTH_ERROR_NULL,
+    };
+    if words.is_null() {
+        return BASIC_SYNTH_ERROR_NULL;
+    }
+    for packet in ump::packets(slice::from_raw_parts(words, len)) {
+        let result = status(synth.0.handle_ump(packet));
+        if result != BASIC_SYNTH_OK {
+            return result;
+        }
+    }
+    BASIC_SYNTH_OK
+}
+
+/// How many parameters there are, numbered from zero.
+#[no_mangle]
+pub extern "C" fn basic_synth_param_count() -> usize {
+    ParamId::fixed().count()
+}
+
+/// Fill in what parameter `index`'s values mean.
+///
+/// # Safety
+///
+/// `info` must point to somewhere to write the info.
+#[no_mangle]
+pub unsafe extern "C" fn basic_synth_param_info(
+    index: usize,
+    info: *mut BasicSynthParamInfo,
+) -> i32 {
+    if info.is_null() {
+        return BASIC_SYNTH_ERROR_NULL;
+    }
+    let param_info = match param(index).and_then(ParamId::info) {
+        Some(param_info) => param_info,
+        None => return BASIC_SYNTH_ERROR_INVALID_ARGUMENT,
+    };
+    info.write(BasicSynthParamInfo {
+        min: param_info.min,
+        max: param_info.max,
+        default_value: param_info.default,
+        curve: match param_info.curve {
+            Curve::Linear => BASIC_SYNTH_CURVE_LINEAR,
+            Curve::Logarithmic => BASIC_SYNTH_CURVE_LOGARITHMIC,
+            Curve::Stepped => BASIC_SYNTH_CURVE_STEPPED,
+        },
+    });
+    BASIC_SYNTH_OK
+}
+
+/// Write parameter `index`'s name into `buffer`, as a null-terminated string cut short to fit in
+/// `len` bytes, as `snprintf` does. Returns the length of the whole name, not counting the null,
+/// or a negative status if there's no such parameter.
+///
+/// # Safety
+///
+/// `buffer` must point to `len` bytes, or may be null if `len` is zero.
+#[no_mangle]
+pub unsafe extern "C" fn basic_synth_param_name(
+    index: usize,
+    buffer: *mut c_char,
+    len: usize,
+) -> i32 {
+    let name = match param(index) {
+        Some(param) => param.to_string(),
+        None => return BASIC_SYNTH_ERROR_INVALID_ARGUMENT,
+    };
+    if len > 0 {
+        if buffer.is_null() {
+            return BASIC_SYNTH_ERROR_NULL;
+        }
+        let copied = name.len().min(len - 1);
+        ptr::copy_nonoverlapping(name.as_ptr() as *const c_char, buffer, copied);
+        *buffer.add(copied) = 0;
+    }
+    name.len() as i32
+}
+
+/// Set parameter `index` to `value`.
+///
+/// # Safety
+///
+/// `synth` must be a live synth.
+#[no_mangle]
+pub unsafe extern "C" fn basic_synth_set_param(
+    synth: *mut BasicSynth,
+    index: usize,
+    value: f32,
+) -> i32 {
+    let synth = match synth.as_mut() {
+        Some(synth) => synth,
+        None => return BASIC_SYNTH_ERROR_NULL,
+    };
+    match param(index) {
+        Some(param) => status(synth.0.set_param(param, value)),
+        None => BASIC_SYNTH_ERROR_INVALID_ARGUMENT,
+    }
+}
+
+/// Read parameter `index`'s value into `value`.
+///
+/// # Safety
+///
+/// `synth` must be a live synth, and `value` must point to somewhere to write the value.
+#[no_mangle]
+pub unsafe extern "C" fn basic_synth_get_param(
+    synth: *const BasicSynth,
+    index: usize,
+    value: *mut f32,
+) -> i32 {
+    let synth = match synth.as_ref() {
+        Some(synth) => synth,
+        None => return BASIC_SYNTH_ERROR_NULL,
+    };
+    if value.is_null() {
+        return BASIC_SYNTH_ERROR_NULL;
+    }
+    match param(index).and_then(|param| synth.0.get_param(param)) {
+        Some(current) => {
+            value.write(current);
+            BASIC_SYNTH_OK
+        }
+        None => BASIC_SYNTH_ERROR_INVALID_ARGUMENT,
+    }
+}
+
+/// Switch to a patch, given in the text format of preset files. This allocates, so is best not
+/// done from an audio callback.
+///
+/// # Safety
+///
+/// `synth` must be a live synth, and `text` a null-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn basic_synth_load_patch(
+    synth: *mut BasicSynth,
+    text: *const c_char,
+) -> i32 {
+    let synth = match synth.as_mut() {
+        Some(synth) => synth,
+        None => return BASIC_SYNTH_ERROR_NULL,
+    };
+    if text.is_null() {
+        return BASIC_SYNTH_ERROR_NULL;
+    }
+    match Patch::read(CStr::from_ptr(text).to_bytes()) {
+        Ok(patch) => {
+            synth.0.load_patch(&patch);
+            BASIC_SYNTH_OK
+        }
+        Err(_) => BASIC_SYNTH_ERROR_INVALID_ARGUMENT,
+    }
+}
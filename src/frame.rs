@@ -0,0 +1,201 @@
+//! Stereo frames, the unit audio moves through the voices, buses and effects in, with the
+//! arithmetic to work on both channels at once.
+
+use core::{
+    array, iter, ops,
+    ops::{Add, AddAssign, Mul, MulAssign, Neg, Sub, SubAssign},
+    slice,
+};
+
+use crate::CHANNELS;
+
+/// One sample for each channel, left then right.
+///
+/// It dereferences to the array, so channels can be indexed and iterated over; arithmetic works
+/// channel by channel, and with an `f32` on both channels alike. Interleaved buffers can be
+/// viewed as frames, and back, without copying.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[repr(transparent)]
+pub struct Frame(pub [f32; CHANNELS]);
+
+impl Frame {
+    /// Silence.
+    pub const ZERO: Self = Self([0.0; CHANNELS]);
+
+    pub const fn new(left: f32, right: f32) -> Self {
+        Self([left, right])
+    }
+
+    /// The same sample in both channels.
+    pub const fn mono(sample: f32) -> Self {
+        Self([sample; CHANNELS])
+    }
+
+    /// A frame from its mid (what the channels share) and side (how they differ), the opposite
+    /// of `to_mono` and `side`.
+    pub fn from_mid_side(mid: f32, side: f32) -> Self {
+        Self([mid + side, mid - side])
+    }
+
+    pub fn left(self) -> f32 {
+        self.0[0]
+    }
+
+    pub fn right(self) -> f32 {
+        self.0[1]
+    }
+
+    /// The average of the channels. Everything which mixes stereo down to mono goes through
+    /// here, so that a mono signal comes back out unchanged.
+    pub fn to_mono(self) -> f32 {
+        (self.0[0] + self.0[1]) * 0.5
+    }
+
+    /// Half the difference between the channels, which `to_mono` leaves out.
+    pub fn side(self) -> f32 {
+        (self.0[0] - self.0[1]) * 0.5
+    }
+
+    /// The larger absolute value of the two channels.
+    pub fn peak(self) -> f32 {
+        self.0[0].abs().max(self.0[1].abs())
+    }
+
+    /// Apply `f` to each channel.
+    pub fn map(self, f: impl FnMut(f32) -> f32) -> Self {
+        Self(self.0.map(f))
+    }
+
+    /// View interleaved samples as frames. A sample left over at the end is left out.
+    pub fn from_interleaved(samples: &[f32]) -> &[Self] {
+        // `Frame` is `repr(transparent)` over `[f32; CHANNELS]`, which is laid out as that many
+        // `f32`s in a row, and aligned as one
+        unsafe { slice::from_raw_parts(samples.as_ptr().cast(), samples.len() / CHANNELS) }
+    }
+
+    /// View interleaved samples as frames, to change them. A sample left over at the end is left
+    /// out.
+    pub fn from_interleaved_mut(samples: &mut [f32]) -> &mut [Self] {
+        // as in `from_interleaved`
+        unsafe { slice::from_raw_parts_mut(samples.as_mut_ptr().cast(), samples.len() / CHANNELS) }
+    }
+
+    /// View frames as interleaved samples.
+    pub fn as_interleaved(frames: &[Self]) -> &[f32] {
+        // as in `from_interleaved`
+        unsafe { slice::from_raw_parts(frames.as_ptr().cast(), frames.len() * CHANNELS) }
+    }
+
+    /// View frames as interleaved samples, to change them.
+    pub fn as_interleaved_mut(frames: &mut [Self]) -> &mut [f32] {
+        // as in `from_interleaved`
+        unsafe { slice::from_raw_parts_mut(frames.as_mut_ptr().cast(), frames.len() * CHANNELS) }
+    }
+}
+
+impl From<[f32; CHANNELS]> for Frame {
+    fn from(samples: [f32; CHANNELS]) -> Self {
+        Self(samples)
+    }
+}
+
+impl From<Frame> for [f32; CHANNELS] {
+    fn from(frame: Frame) -> Self {
+        frame.0
+    }
+}
+
+impl ops::Deref for Frame {
+    type Target = [f32; CHANNELS];
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl ops::DerefMut for Frame {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl IntoIterator for Frame {
+    type Item = f32;
+    type IntoIter = array::IntoIter<f32, CHANNELS>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIterator::into_iter(self.0)
+    }
+}
+
+impl<'a> IntoIterator for &'a Frame {
+    type Item = &'a f32;
+    type IntoIter = slice::Iter<'a, f32>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a mut Frame {
+    type Item = &'a mut f32;
+    type IntoIter = slice::IterMut<'a, f32>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter_mut()
+    }
+}
+
+impl iter::Sum for Frame {
+    fn sum<I: Iterator<Item = Self>>(frames: I) -> Self {
+        frames.fold(Self::ZERO, Add::add)
+    }
+}
+
+/// Implements an arithmetic operator channel by channel, between frames and with an `f32` on
+/// each channel, along with its assigning version.
+macro_rules! impl_frame_op {
+    ($op:ident, $method:ident, $assign_op:ident, $assign_method:ident) => {
+        impl $op for Frame {
+            type Output = Self;
+
+            fn $method(self, rhs: Self) -> Self {
+                Self(array::from_fn(|channel| {
+                    $op::$method(self.0[channel], rhs.0[channel])
+                }))
+            }
+        }
+
+        impl $op<f32> for Frame {
+            type Output = Self;
+
+            fn $method(self, rhs: f32) -> Self {
+                self.map(|sample| $op::$method(sample, rhs))
+            }
+        }
+
+        impl $assign_op for Frame {
+            fn $assign_method(&mut self, rhs: Self) {
+                *self = $op::$method(*self, rhs);
+            }
+        }
+
+        impl $assign_op<f32> for Frame {
+            fn $assign_method(&mut self, rhs: f32) {
+                *self = $op::$method(*self, rhs);
+            }
+        }
+    };
+}
+
+impl_frame_op!(Add, add, AddAssign, add_assign);
+impl_frame_op!(Sub, sub, SubAssign, sub_assign);
+impl_frame_op!(Mul, mul, MulAssign, mul_assign);
+
+impl Neg for Frame {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        self.map(|sample| -sample)
+    }
+}
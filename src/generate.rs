@@ -0,0 +1,302 @@
+//! A generative mode, so that the synth can make music with nothing attached: notes picked at
+//! random from a scale, starting on a rhythmic grid at the synth's tempo, each held for a random
+//! number of steps, so that they overlap. The same seed always plays the same notes. Left
+//! running, it also makes a good soak test of voice allocation.
+//!
+//! The generator runs inside the synth, as the sequencer does, and its notes take voices as notes
+//! played any other way do. It is started and stopped with `Synth::set_generator`, or as
+//! `SynthCommand::Generate` from other threads.
+//!
+//! With the `presets` feature, settings have a text form: any of `density=0.3`, `scale=minor`,
+//! `root=48`, `octaves=2`, `grid=4` and `seed=1`, separated by spaces or commas, with those left
+//! out taking their defaults.
+
+use crate::{latch::Notes, random::Rng};
+
+#[cfg(not(any(feature = "std", test)))]
+use crate::math::Float;
+
+#[cfg(feature = "presets")]
+use {
+    alloc::{format, string::String},
+    core::{fmt, str::FromStr},
+};
+
+/// Most steps of the grid a note is held for.
+const MAX_LENGTH: u64 = 8;
+
+/// The range velocities are picked from.
+const VELOCITY: (u8, u8) = (60, 110);
+
+/// Scales notes are picked from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case"))]
+pub enum Scale {
+    Chromatic,
+    Major,
+    Minor,
+    Dorian,
+    Pentatonic,
+    MinorPentatonic,
+}
+
+impl Scale {
+    pub const ALL: [Scale; 6] = [
+        Scale::Chromatic,
+        Scale::Major,
+        Scale::Minor,
+        Scale::Dorian,
+        Scale::Pentatonic,
+        Scale::MinorPentatonic,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Scale::Chromatic => "chromatic",
+            Scale::Major => "major",
+            Scale::Minor => "minor",
+            Scale::Dorian => "dorian",
+            Scale::Pentatonic => "pentatonic",
+            Scale::MinorPentatonic => "minor-pentatonic",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|s| s.name() == name)
+    }
+
+    /// Semitones above the root of each note of an octave of the scale.
+    pub fn intervals(self) -> &'static [u8] {
+        match self {
+            Scale::Chromatic => &[0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11],
+            Scale::Major => &[0, 2, 4, 5, 7, 9, 11],
+            Scale::Minor => &[0, 2, 3, 5, 7, 8, 10],
+            Scale::Dorian => &[0, 2, 3, 5, 7, 9, 10],
+            Scale::Pentatonic => &[0, 2, 4, 7, 9],
+            Scale::MinorPentatonic => &[0, 3, 5, 7, 10],
+        }
+    }
+}
+
+/// What the generator plays.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct GeneratorSettings {
+    /// How likely a note is to start on each step of the grid, from 0 to 1.
+    pub density: f32,
+    pub scale: Scale,
+    /// The MIDI note number of the lowest root of the scale.
+    pub root: u8,
+    /// How many octaves up from the root notes are picked from, from 1 to 4.
+    pub octaves: u8,
+    /// Steps of the grid to each beat, from 1 to 8: 4 for sixteenth notes, 3 for eighth note
+    /// triplets.
+    pub grid: u8,
+    pub seed: u32,
+}
+
+impl GeneratorSettings {
+    /// The settings with out of range values clamped.
+    fn clamped(self) -> Self {
+        Self {
+            density: self.density.clamp(0.0, 1.0),
+            root: self.root.min(127),
+            octaves: self.octaves.clamp(1, 4),
+            grid: self.grid.clamp(1, 8),
+            ..self
+        }
+    }
+}
+
+impl Default for GeneratorSettings {
+    fn default() -> Self {
+        Self {
+            density: 0.3,
+            scale: Scale::MinorPentatonic,
+            root: 48,
+            octaves: 2,
+            grid: 4,
+            seed: 1,
+        }
+    }
+}
+
+/// Notes for the synth to end, and then start, on a step of the grid.
+#[derive(Debug, Default)]
+pub(crate) struct Due {
+    pub(crate) release: Notes,
+    pub(crate) play: Option<(u8, u8)>,
+}
+
+/// Plays random notes, telling the synth which to start and end, and when.
+#[derive(Debug)]
+pub(crate) struct Generator {
+    settings: GeneratorSettings,
+    rng: Rng,
+    /// When the next step is due, in frames, with the fraction kept so that steps don't drift.
+    next_step_at: f64,
+    /// Steps taken so far.
+    step: u64,
+    /// The notes playing, and the steps they end on. A note starts on each step at most, and
+    /// ends within `MAX_LENGTH` steps, so that's as many as there can be.
+    held: [Option<(u8, u64)>; MAX_LENGTH as usize],
+}
+
+impl Generator {
+    /// A generator taking its first step at frame `now`.
+    pub(crate) fn new(settings: GeneratorSettings, now: u64) -> Self {
+        let settings = settings.clamped();
+        Self {
+            settings,
+            rng: Rng::new(settings.seed),
+            next_step_at: now as f64,
+            step: 0,
+            held: [None; MAX_LENGTH as usize],
+        }
+    }
+
+    pub(crate) fn settings(&self) -> GeneratorSettings {
+        self.settings
+    }
+
+    /// How many frames each step of the grid lasts, at `tempo` in beats per minute.
+    pub(crate) fn step_len(&self, sample_rate: u32, tempo: f32) -> f64 {
+        sample_rate as f64 * 60.0 / (tempo as f64 * self.settings.grid as f64)
+    }
+
+    /// When the next step is due, following the synth's own clock.
+    pub(crate) fn next_event(&self) -> u64 {
+        self.next_step_at.ceil() as u64
+    }
+
+    /// Take a step, if one is due at frame `now`, with steps `step_len` frames long: ending the
+    /// notes which have been held long enough, then maybe starting another.
+    pub(crate) fn run(&mut self, now: u64, step_len: f64) -> Due {
+        let mut due = Due::default();
+        if self.next_event() > now {
+            return due;
+        }
+        self.next_step_at += step_len;
+        self.step += 1;
+
+        for slot in &mut self.held {
+            if let Some((note, end)) = *slot {
+                if end <= self.step {
+                    due.release.insert(note);
+                    *slot = None;
+                }
+            }
+        }
+
+        if self.rng.uniform() >= self.settings.density {
+            return due;
+        }
+        let intervals = self.settings.scale.intervals();
+        let degree = self
+            .rng
+            .index(intervals.len() * self.settings.octaves as usize);
+        let octave = (degree / intervals.len()) as u8;
+        let note = self.settings.root + octave * 12 + intervals[degree % intervals.len()];
+        let (low, high) = VELOCITY;
+        let velocity = low + self.rng.index((high - low) as usize + 1) as u8;
+        let end = self.step + 1 + self.rng.index(MAX_LENGTH as usize) as u64;
+        if note > 127 {
+            return due;
+        }
+
+        // a note played again carries on in the same voice, for its new length
+        let slot = match self
+            .held
+            .iter()
+            .position(|slot| slot.is_some_and(|(n, _)| n == note))
+        {
+            Some(index) => Some(index),
+            None => self.held.iter().position(Option::is_none),
+        };
+        if let Some(index) = slot {
+            self.held[index] = Some((note, end));
+            due.play = Some((note, velocity));
+        }
+        due
+    }
+
+    /// Stop, giving the notes to release.
+    pub(crate) fn stop(&mut self) -> Notes {
+        let mut notes = Notes::NONE;
+        for (note, _) in self.held.iter_mut().filter_map(Option::take) {
+            notes.insert(note);
+        }
+        notes
+    }
+}
+
+/// The settings' text form, all on one line.
+#[cfg(feature = "presets")]
+impl fmt::Display for GeneratorSettings {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "density={} scale={} root={} octaves={} grid={} seed={}",
+            self.density,
+            self.scale.name(),
+            self.root,
+            self.octaves,
+            self.grid,
+            self.seed
+        )
+    }
+}
+
+#[cfg(feature = "presets")]
+impl FromStr for GeneratorSettings {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut settings = Self::default();
+        for word in s.split(|c: char| c == ',' || c.is_whitespace()) {
+            if word.is_empty() {
+                continue;
+            }
+            let (key, value) = word
+                .split_once('=')
+                .ok_or_else(|| format!("Expected `setting=value`: {}", word))?;
+            let invalid = || format!("Invalid {}: {}", key, value);
+            match key {
+                "density" => {
+                    settings.density = value
+                        .parse::<f32>()
+                        .ok()
+                        .filter(|density| (0.0..=1.0).contains(density))
+                        .ok_or_else(invalid)?
+                }
+                "scale" => settings.scale = Scale::from_name(value).ok_or_else(invalid)?,
+                "root" => {
+                    settings.root = value
+                        .parse()
+                        .ok()
+                        .filter(|root| *root < 128)
+                        .ok_or_else(invalid)?
+                }
+                "octaves" => {
+                    settings.octaves = value
+                        .parse()
+                        .ok()
+                        .filter(|octaves| (1..=4).contains(octaves))
+                        .ok_or_else(invalid)?
+                }
+                "grid" => {
+                    settings.grid = value
+                        .parse()
+                        .ok()
+                        .filter(|grid| (1..=8).contains(grid))
+                        .ok_or_else(invalid)?
+                }
+                "seed" => settings.seed = value.parse().map_err(|_| invalid())?,
+                _ => return Err(format!("Unknown generator setting: {}", key)),
+            }
+        }
+        Ok(settings)
+    }
+}
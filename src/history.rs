@@ -0,0 +1,71 @@
+//! A bounded history of the sound's settings, so that changes can be undone and redone.
+//!
+//! The whole patch is saved before each change. A run of changes to the same thing in quick
+//! succession, like the stream of messages from turning a knob, is saved once, and undone in one
+//! go.
+
+use alloc::{collections::VecDeque, vec::Vec};
+
+use crate::patch::Patch;
+
+/// Most changes which can be undone.
+const HISTORY_LEN: usize = 64;
+
+/// What a change was made to, for telling runs of changes apart. Changes without one always start
+/// a new entry.
+pub(crate) type ChangeKind = &'static str;
+
+#[derive(Default)]
+pub(crate) struct History {
+    undo: VecDeque<Patch>,
+    redo: Vec<Patch>,
+    /// What was last changed, and when (in frames).
+    last: Option<(ChangeKind, u64)>,
+}
+
+impl History {
+    /// Note that something is about to change at `now`. Returns whether the settings from before
+    /// it need saving with `push`, which they don't if this carries on from the last change,
+    /// within `merge_time` frames of it.
+    pub(crate) fn record(&mut self, kind: Option<ChangeKind>, now: u64, merge_time: u64) -> bool {
+        let merge = match (kind, self.last) {
+            (Some(kind), Some((last, then))) => kind == last && now - then < merge_time,
+            _ => false,
+        };
+        self.last = kind.map(|kind| (kind, now));
+        !merge
+    }
+
+    /// Save the settings from before a change.
+    pub(crate) fn push(&mut self, patch: Patch) {
+        if self.undo.len() == HISTORY_LEN {
+            self.undo.pop_front();
+        }
+        self.undo.push_back(patch);
+        self.redo.clear();
+    }
+
+    /// The settings to go back to, if any, given the current ones to come back to with `redo`.
+    pub(crate) fn undo(&mut self, current: Patch) -> Option<Patch> {
+        let patch = self.undo.pop_back()?;
+        self.redo.push(current);
+        self.last = None;
+        Some(patch)
+    }
+
+    /// The settings last undone, if any, given the current ones to come back to with `undo`.
+    pub(crate) fn redo(&mut self, current: Patch) -> Option<Patch> {
+        let patch = self.redo.pop()?;
+        self.undo.push_back(current);
+        self.last = None;
+        Some(patch)
+    }
+
+    pub(crate) fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub(crate) fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+}
@@ -0,0 +1,96 @@
+//! Latching notes, so that they keep sounding after their keys are let go, e.g. for holding a
+//! drone or a chord with both hands free. The notes played while any key is down make a chord,
+//! which lasts until the next chord starts, or latching is turned off.
+//!
+//! Only notes played from outside count: the sequencer and the generator play theirs unlatched.
+
+/// A set of MIDI note numbers, one bit each.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct Notes(u128);
+
+impl Notes {
+    pub(crate) const NONE: Self = Self(0);
+
+    fn bit(note: u8) -> u128 {
+        1 << (note & 0x7f)
+    }
+
+    pub(crate) fn contains(self, note: u8) -> bool {
+        self.0 & Self::bit(note) != 0
+    }
+
+    pub(crate) fn insert(&mut self, note: u8) {
+        self.0 |= Self::bit(note);
+    }
+
+    fn remove(&mut self, note: u8) {
+        self.0 &= !Self::bit(note);
+    }
+
+    pub(crate) fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// The notes in the set, lowest first.
+    pub(crate) fn iter(self) -> impl Iterator<Item = u8> {
+        (0..128).filter(move |&note| self.contains(note))
+    }
+}
+
+/// Which keys are down, and which notes are only sounding because they're latched.
+#[derive(Debug, Default)]
+pub(crate) struct Latch {
+    enabled: bool,
+    pressed: Notes,
+    latched: Notes,
+}
+
+impl Latch {
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Turn latching on or off. Returns the notes to release: those latched, when turning it
+    /// off.
+    pub(crate) fn set_enabled(&mut self, enabled: bool) -> Notes {
+        self.enabled = enabled;
+        if enabled {
+            Notes::NONE
+        } else {
+            core::mem::take(&mut self.latched)
+        }
+    }
+
+    /// Whether a note is sounding after its key was let go.
+    pub(crate) fn is_latched(&self, note: u8) -> bool {
+        self.latched.contains(note)
+    }
+
+    /// Note a key going down. Returns the notes to release first: the last chord, if this starts
+    /// a new one, apart from this note itself, which carries on in the same voice.
+    pub(crate) fn press(&mut self, note: u8) -> Notes {
+        let mut replaced = Notes::NONE;
+        if self.pressed.is_empty() {
+            replaced = core::mem::take(&mut self.latched);
+            replaced.remove(note);
+        }
+        self.latched.remove(note);
+        self.pressed.insert(note);
+        replaced
+    }
+
+    /// Note a key going up. Returns whether to release the note, which it isn't when latching.
+    pub(crate) fn release(&mut self, note: u8) -> bool {
+        self.pressed.remove(note);
+        if self.enabled {
+            self.latched.insert(note);
+        }
+        !self.enabled
+    }
+
+    /// Forget every key and latched note, when every note is ended some other way.
+    pub(crate) fn clear(&mut self) {
+        self.pressed = Notes::NONE;
+        self.latched = Notes::NONE;
+    }
+}
@@ -1,69 +1,2120 @@
-use std::{
-    f32::consts::{PI, TAU},
-    mem, ops,
-    rc::Rc,
-    time,
+//! A polyphonic subtractive synthesizer, with effects, patches and MIDI control.
+//!
+//! With the default `std` feature off, the crate is `no_std`, needing only an allocator: the
+//! synth, its effects and patches (though not reading or writing them) all still work, for
+//! running on microcontrollers. Everything is allocated up front, including a fixed pool of
+//! voices, so nothing is allocated while rendering (see `Synth`). Files and audio devices need
+//! `std`.
+//!
+//! The larger parts are behind features of their own, all on by default, so that embedding just
+//! the voice engine doesn't pull in what goes unused: `effects` (the built-in effects), `midi`
+//! (MIDI messages and files), `presets` (patches as text, and preset banks), `wav` (writing WAV
+//! files) and `audio` (playing through audio devices). All but `effects` need `std`. The default
+//! `cli` feature is the command line player, which needs all of them, and brings in its own MIDI
+//! input and so on; leave it out when embedding the synth.
+//!
+//! With the `ffi` feature, the library has a C API (see `ffi`), declared in
+//! `include/basic_synth.h`.
+//! With `analysis`, it has a spectrum analyzer for the output (see `analysis`).
+//! With the `clap` feature, the library is also a CLAP plugin, for playing the synth inside a DAW.
+//! With `lv2`, it's an LV2 plugin, described by the files `lv2::write_bundle` writes. A VST3 build,
+//! made with nih-plug, is a crate of its own in `plugins/vst3`, as is a graphical editor built
+//! with egui, in `editor`, Python bindings made with PyO3, in `bindings/python`, and a sound for
+//! the kira game audio engine, in `integrations/kira`.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+use {
+    alloc::{boxed::Box, string::ToString, sync::Arc, vec, vec::Vec},
+    core::{
+        array,
+        f32::consts::{PI, TAU},
+        iter, mem, ops,
+        sync::atomic::{AtomicU32, Ordering},
+        time::Duration,
+    },
+};
+
+#[cfg(feature = "midi")]
+use midi_msg::MidiMsg;
+#[cfg(all(feature = "midi", feature = "wav"))]
+use {
+    smf::{Playback, TimedMsg},
+    std::{
+        fs::File,
+        io::{self, BufWriter},
+        path::Path,
+    },
+    wav::{SampleFormat, WavWriter},
+};
+
+#[cfg(feature = "analysis")]
+pub mod analysis;
+#[cfg(all(feature = "audio", not(target_arch = "wasm32")))]
+pub mod backend;
+pub mod builder;
+#[cfg(feature = "clap")]
+mod clap;
+pub mod command;
+pub mod controller;
+mod denormal;
+pub mod dither;
+pub mod effects;
+pub mod error;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod frame;
+pub mod generate;
+mod history;
+mod latch;
+#[cfg(feature = "lv2")]
+pub mod lv2;
+#[cfg(not(feature = "std"))]
+mod math;
+mod morph;
+pub mod param;
+pub mod patch;
+pub mod prepare;
+#[cfg(feature = "presets")]
+pub mod preset;
+#[cfg(feature = "profile")]
+pub mod profile;
+pub mod random;
+pub mod resample;
+pub mod ring;
+pub mod sample;
+mod schedule;
+pub mod send;
+pub mod sequencer;
+#[cfg(feature = "midi")]
+pub mod smf;
+#[cfg(all(feature = "audio", not(target_arch = "wasm32")))]
+pub mod source;
+pub mod tap;
+#[cfg(feature = "midi")]
+pub mod ump;
+#[cfg(feature = "wav")]
+pub mod wav;
+#[cfg(all(feature = "midi", target_arch = "wasm32"))]
+pub mod web;
+
+pub use {
+    builder::SynthBuilder, command::SynthCommand, error::SynthError, frame::Frame, sample::Sample,
 };
 
-/// Modify this value to work at a different sample rate.
-pub static mut SAMPLE_RATE: u32 = 48000;
+#[cfg(feature = "fixed")]
+pub use sample::Fixed;
+
+#[cfg(feature = "profile")]
+use {
+    profile::{Profile, Stopwatch},
+    std::time::Instant,
+};
+
+#[cfg(not(any(feature = "std", test)))]
+use math::Float;
+use {
+    controller::{Controls, SynthController},
+    denormal::FlushDenormals,
+    effects::{gate::Gate, vocoder::Vocoder, Effect, EffectsChain, Placement},
+    generate::{Generator, GeneratorSettings},
+    history::{ChangeKind, History},
+    latch::Latch,
+    morph::Morph,
+    param::{ParamId, ParamInfo, ParamObserver},
+    patch::{EffectPatch, Patch},
+    prepare::{PatchPreparer, PreparedPatch},
+    ring::Consumer,
+    schedule::Schedule,
+    send::{SendBus, SENDS},
+    sequencer::{Notes, Pattern, Sequencer, SequencerCommand},
+    tap::{Tap, TapPoint},
+};
+
+/// Number of frames rendered at a time, unless changed with `Synth::set_block_size`.
+pub const DEFAULT_BLOCK_SIZE: usize = 64;
+
+/// Lowest sample rate a synth can be built for, in Hz.
+pub const MIN_SAMPLE_RATE: u32 = 8000;
+
+/// Highest sample rate a synth can be built for, in Hz.
+pub const MAX_SAMPLE_RATE: u32 = 768_000;
+
+/// Number of audio channels produced. Samples are interleaved, left channel first.
+pub const CHANNELS: usize = 2;
+
+/// How far voices are panned away from the center, unless changed with
+/// `Synth::set_stereo_spread`.
+const DEFAULT_STEREO_SPREAD: f32 = 0.5;
+
+/// Master volume, unless changed with `Synth::set_volume` or CC7.
+const DEFAULT_VOLUME: f32 = 0.75;
+
+/// Cutoff of the voices' filters, in Hz, unless changed with `Synth::set_cutoff` or CC74.
+const DEFAULT_CUTOFF: f32 = 5000.0;
+
+/// Number of oscillators each voice has, unless changed with `SynthBuilder::oscillators`.
+const DEFAULT_OSCILLATORS: usize = 3;
+
+/// How far apart each voice's oscillators are tuned, in cents, unless changed with
+/// `Synth::set_detune`.
+const DEFAULT_DETUNE: f32 = 5.0;
+
+/// Time taken for changes to the volume, cutoff and detune to mostly take effect, in seconds, to
+/// avoid zipper noise, unless changed with `Synth::set_smoothing_time`.
+const DEFAULT_SMOOTHING_TIME: f32 = 0.02;
+
+/// Corner frequency of the DC blocking filters, in Hz. Low enough to leave the bass alone.
+const DC_BLOCKER_CUTOFF: f32 = 10.0;
+
+/// Level the output is kept under by the limiter.
+const LIMITER_THRESHOLD: f32 = 0.98;
+
+/// Range of the pitch bend wheel, in semitones either way.
+const PITCH_BEND_RANGE: f32 = 2.0;
+
+/// How long after changing something (e.g. turning a knob) further changes to it are undone along
+/// with it, in seconds.
+const HISTORY_MERGE_TIME: f32 = 0.5;
+
+/// Range of tempos accepted, in beats per minute.
+const MIN_TEMPO: f32 = 20.0;
+const MAX_TEMPO: f32 = 300.0;
 
 /// Level of oversampling applied for antialiasing purposes.
-pub static mut OVERSAMPLE_RATIO: u32 = 4;
+///
+/// Higher ratios cost proportionally more CPU time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Oversampling {
+    Off,
+    X2,
+    #[default]
+    X4,
+    X8,
+}
 
-fn oversample_rate() -> u32 {
-    unsafe { SAMPLE_RATE * OVERSAMPLE_RATIO }
+impl Oversampling {
+    /// Look up the setting corresponding to a ratio of 1, 2, 4 or 8.
+    pub fn from_ratio(ratio: u32) -> Option<Self> {
+        match ratio {
+            1 => Some(Self::Off),
+            2 => Some(Self::X2),
+            4 => Some(Self::X4),
+            8 => Some(Self::X8),
+            _ => None,
+        }
+    }
+
+    /// How many samples are processed internally for each one that is output.
+    pub fn ratio(self) -> u32 {
+        match self {
+            Self::Off => 1,
+            Self::X2 => 2,
+            Self::X4 => 4,
+            Self::X8 => 8,
+        }
+    }
+}
+
+/// Signal levels measured over one block of output, e.g. for drawing a level meter.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Levels {
+    /// Highest absolute sample value in each channel.
+    pub peak: Frame,
+    /// Root mean square level of each channel.
+    pub rms: Frame,
+    /// Whether the output would have clipped, had the limiter not turned it down.
+    pub clipped: bool,
+}
+
+/// What happens to a note when every voice is already playing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum VoiceStealing {
+    /// It isn't played.
+    #[default]
+    Off,
+    /// It takes over the voice which started longest ago, preferring ones which have already been
+    /// released.
+    Oldest,
+}
+
+/// A change to one note alone, on top of the settings every note shares, as made by
+/// `Synth::set_note_expression`. It lasts until the note is played again.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case"))]
+pub enum NoteExpression {
+    /// Gain, from 0 up, on top of the velocity's. 1 leaves the note as loud as it was.
+    Volume(f32),
+    /// Position from -1 (hard left) to 1 (hard right), in place of the note's place in the stereo
+    /// spread.
+    Pan(f32),
+    /// Semitones up or down, on top of the pitch bend.
+    Tuning(f32),
+}
+
+/// What the voices play through their filter and envelope.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum VoiceSource {
+    /// Their own oscillators, as a normal synth.
+    #[default]
+    Oscillators,
+    /// Audio passed to `Synth::process_with_input`, so that notes gate and shape it.
+    Input,
+    /// Their own oscillators, as the carrier of a vocoder which is modulated by the audio passed
+    /// to `Synth::process_with_input`. See `Synth::vocoder_mut`.
+    Vocoder,
 }
 
+// a synth has to be able to go to the audio thread
+const _: fn() = || {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Synth>();
+};
+
 /// Represents a full instance of a synthesizer.
+///
+/// A synth is `Send` and `Sync`, so it can be made on one thread and moved to the audio thread to
+/// run. To keep controlling it from elsewhere, either share it behind a lock (see
+/// `source::SharedSynth`), or keep it to the audio thread and set its parameters through a
+/// `SynthController` or send it `SynthCommand`s to `apply` (or `schedule`), e.g. through a queue
+/// drained by `apply_queued`.
+///
+/// Once made, rendering (`process`, `process_stereo`, `next_block`, `process_with_input` and
+/// `process_voices`) and setting parameters don't allocate, lock or panic, so they're safe on the
+/// audio thread. The exceptions are changes to which effects there are (loading a patch that has
+/// different ones, or a distortion's oversampling), saving the settings for `undo` at the start of
+/// a run of changes, and `set_block_size`. To change patches on the audio thread, get them ready
+/// elsewhere (see `prepare`) and `switch_patch` to them.
+///
+/// What it does is logged through the `log` crate: which voices notes go to at trace level, and
+/// voices being stolen or running out at debug level. That happens while rendering, so a logger
+/// which allocates or locks shouldn't let those levels through from the audio thread.
 pub struct Synth {
     voices: Vec<Voice>,
+    waveform: Waveform,
+    detune: f32,
+    cutoff: f32,
+    amp_envelope: AdsrConfig,
+    stereo_spread: f32,
+    sample_rate: u32,
+    oversampling: Oversampling,
+    decimators: [Decimator; CHANNELS],
+    voice_decimators: Vec<Decimator>,
+    volume: f32,
+    smoothed_volume: f32,
+    /// How far the volume moves towards its setting each frame.
+    volume_smoothing: f32,
+    smoothing_time: f32,
+    /// The cutoff the voices' filters are actually at, as a log so that sweeps sound even.
+    smoothed_cutoff: Smoothed,
+    smoothed_detune: Smoothed,
+    /// Extra gain on the output, which only ever moves towards zero, for fading out at the end.
+    fade_gain: f32,
+    fade_step: f32,
+    dc_blockers: [DcBlocker; CHANNELS],
+    effects: EffectsChain,
+    sends: [SendBus; SENDS],
+    limiter: Limiter,
+    levels: Levels,
+    #[cfg(feature = "profile")]
+    profile: Option<Profile>,
+    block: Vec<f32>,
+    block_len: usize,
+    block_position: usize,
+    voice_buffer: Vec<f32>,
+    /// One voice's output in stereo, for its own effects to process.
+    voice_frames: Vec<Frame>,
+    bus: Vec<Frame>,
+    source: VoiceSource,
+    voice_stealing: VoiceStealing,
+    /// External input for the block being rendered, at the internal sample rate.
+    input: Vec<f32>,
+    /// The last input sample, to interpolate from at the start of the next block.
+    last_input: f32,
+    /// External input for the block being rendered, at the output sample rate, for the vocoder.
+    modulator: Vec<f32>,
+    input_gate: Option<Gate>,
+    vocoder: Vocoder,
+    pitch_bend: f32,
+    clock: u64,
+    /// Commands waiting for the frame they're due at.
+    scheduled: Schedule,
+    midi_clock: ClockFollower,
+    note_timeout: Option<u64>,
+    morph: Option<Morph>,
+    /// The morph behind a finished glide, kept until the next patch change rather than freed while
+    /// rendering.
+    finished_morph: Option<Morph>,
+    morph_amount: f32,
+    /// How far a glide to a new patch moves the morph every frame.
+    glide: Option<f32>,
+    history: History,
+    undo_enabled: bool,
+    /// As given to `set_seed`, for `reset` to start the oscillators from the same phases again.
+    seed: Option<u32>,
+    param_observer: Option<ParamObserver>,
+    controls: Option<Arc<Controls>>,
+    taps: Vec<Tap>,
+    sequencer: Sequencer,
+    latch: Latch,
+    generator: Option<Generator>,
 }
 
 impl Synth {
-    /// Create a new synth, with the specified number of voices.
-    pub fn new(voices: usize) -> Self {
-        let amp_env_config = Rc::new(AdsrConfig::default());
-        Self {
+    /// Create a new synth, with the specified number of voices (at least one is made), producing
+    /// audio at the specified sample rate (in Hz) and processing internally at a multiple of it.
+    ///
+    /// See `builder` for more options.
+    pub fn new(voices: usize, sample_rate: u32, oversampling: Oversampling) -> Self {
+        Self::with_oscillators(voices, DEFAULT_OSCILLATORS, sample_rate, oversampling)
+    }
+
+    /// Start building a synth, with more options than `new` takes.
+    pub fn builder() -> SynthBuilder {
+        SynthBuilder::default()
+    }
+
+    fn with_oscillators(
+        voices: usize,
+        oscillators: usize,
+        sample_rate: u32,
+        oversampling: Oversampling,
+    ) -> Self {
+        let internal_rate = sample_rate * oversampling.ratio();
+        // so that there's always a first voice, whose effects stand for every voice's
+        let voices = voices.max(1);
+        let mut synth = Self {
             voices: (0..voices)
-                .map(move |_| Voice::new(amp_env_config.clone()))
+                .map(move |_| Voice::new(internal_rate, oscillators))
                 .collect(),
+            waveform: Waveform::default(),
+            detune: DEFAULT_DETUNE,
+            cutoff: DEFAULT_CUTOFF,
+            amp_envelope: AdsrConfig::default(),
+            stereo_spread: DEFAULT_STEREO_SPREAD,
+            sample_rate,
+            oversampling,
+            decimators: [(); CHANNELS].map(|_| Decimator::new(oversampling)),
+            voice_decimators: (0..voices).map(|_| Decimator::new(oversampling)).collect(),
+            volume: DEFAULT_VOLUME,
+            smoothed_volume: DEFAULT_VOLUME,
+            volume_smoothing: 1.0,
+            smoothing_time: DEFAULT_SMOOTHING_TIME,
+            smoothed_cutoff: Smoothed::new(DEFAULT_CUTOFF.ln()),
+            smoothed_detune: Smoothed::new(DEFAULT_DETUNE),
+            fade_gain: 1.0,
+            fade_step: 0.0,
+            dc_blockers: [(); CHANNELS].map(|_| DcBlocker::new(sample_rate as f32)),
+            effects: EffectsChain::new(sample_rate),
+            sends: [(); SENDS].map(|_| SendBus::new(sample_rate, oversampling)),
+            limiter: Limiter::new(sample_rate as f32),
+            levels: Levels::default(),
+            #[cfg(feature = "profile")]
+            profile: None,
+            block: Vec::new(),
+            block_len: 0,
+            block_position: 0,
+            voice_buffer: Vec::new(),
+            voice_frames: Vec::new(),
+            bus: Vec::new(),
+            source: VoiceSource::default(),
+            voice_stealing: VoiceStealing::default(),
+            input: Vec::new(),
+            last_input: 0.0,
+            modulator: Vec::new(),
+            input_gate: None,
+            vocoder: Vocoder::new(sample_rate),
+            pitch_bend: 0.0,
+            clock: 0,
+            scheduled: Schedule::new(),
+            midi_clock: ClockFollower::default(),
+            note_timeout: None,
+            morph: None,
+            finished_morph: None,
+            morph_amount: 0.0,
+            glide: None,
+            history: History::default(),
+            undo_enabled: true,
+            seed: None,
+            param_observer: None,
+            controls: None,
+            taps: Vec::new(),
+            sequencer: Sequencer::new(),
+            latch: Latch::default(),
+            generator: None,
+        };
+        synth.set_block_size(DEFAULT_BLOCK_SIZE);
+        synth.set_stereo_spread(DEFAULT_STEREO_SPREAD);
+        synth.set_smoothing_time(Duration::from_secs_f32(DEFAULT_SMOOTHING_TIME));
+        synth
+    }
+
+    /// Set how long changes to the volume, cutoff and detune take to (mostly) take effect, so that
+    /// sweeping them, e.g. from a controller which only sends 128 steps, doesn't sound stepped.
+    /// Each is smoothed separately. Zero makes changes immediate.
+    ///
+    /// The volume is smoothed every frame, the others once per block (see `set_block_size`).
+    pub fn set_smoothing_time(&mut self, time: Duration) {
+        self.smoothing_time = time.as_secs_f32();
+        self.volume_smoothing = smoothing_amount(1, self.smoothing_time, self.sample_rate);
+    }
+
+    pub fn smoothing_time(&self) -> Duration {
+        Duration::from_secs_f32(self.smoothing_time)
+    }
+
+    /// Force notes to be released once they have been held for longer than `timeout`, in case
+    /// their note off message went missing (e.g. a controller glitched or was unplugged).
+    ///
+    /// Pass `None` (the default) to let notes be held indefinitely.
+    pub fn set_note_timeout(&mut self, timeout: Option<Duration>) {
+        self.note_timeout = timeout.map(|t| (t.as_secs_f64() * self.sample_rate as f64) as u64);
+    }
+
+    /// The sample rate audio is produced at, in Hz.
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// The level of oversampling used internally.
+    pub fn oversampling(&self) -> Oversampling {
+        self.oversampling
+    }
+
+    /// Pan voices across the stereo field, from 0 (all in the center) to 1 (from hard left to
+    /// hard right).
+    pub fn set_stereo_spread(&mut self, spread: f32) {
+        let old = self.get_param(ParamId::StereoSpread);
+        let spread = spread.clamp(0.0, 1.0);
+        self.stereo_spread = spread;
+        let last = (self.voices.len() as f32 - 1.0).max(1.0);
+        for (index, voice) in self.voices.iter_mut().enumerate() {
+            voice.set_pan(spread * map_range(index as f32, (0.0, last), (-1.0, 1.0)));
+        }
+        self.changed(ParamId::StereoSpread, old);
+    }
+
+    pub fn stereo_spread(&self) -> f32 {
+        self.stereo_spread
+    }
+
+    /// Restart every oscillator from a phase worked out from `seed`, rather than one which depends
+    /// on how many oscillators the program has made before. Two synths made alike and given the
+    /// same seed then render the same input to exactly the same audio, e.g. to compare against a
+    /// known-good recording.
+    pub fn set_seed(&mut self, seed: u32) {
+        self.seed = Some(seed);
+        self.reset_phases();
+    }
+
+    /// Start every oscillator from a new phase: worked out from the seed, if there is one, or
+    /// otherwise at random.
+    fn reset_phases(&mut self) {
+        let mut seed = self.seed;
+        for voice in &mut self.voices {
+            for osc in &mut voice.oscillators {
+                let phase = match &mut seed {
+                    Some(seed) => {
+                        *seed = seed.wrapping_add(PHASE_SEED_STEP);
+                        seeded_phase(*seed)
+                    }
+                    None => random_phase(),
+                };
+                osc.set_phase(phase as f32);
+            }
+        }
+    }
+
+    /// Choose the shape of every oscillator's wave.
+    pub fn set_waveform(&mut self, waveform: Waveform) {
+        let old = self.get_param(ParamId::Waveform);
+        self.waveform = waveform;
+        for voice in &mut self.voices {
+            for osc in &mut voice.oscillators {
+                osc.wave = waveform;
+            }
+        }
+        self.changed(ParamId::Waveform, old);
+    }
+
+    pub fn waveform(&self) -> Waveform {
+        self.waveform
+    }
+
+    /// Spread each voice's oscillators apart in pitch by this many cents (up to 100), thickening
+    /// the sound.
+    pub fn set_detune(&mut self, cents: f32) {
+        let old = self.get_param(ParamId::Detune);
+        self.detune = cents.clamp(0.0, 100.0);
+        self.changed(ParamId::Detune, old);
+    }
+
+    pub fn detune(&self) -> f32 {
+        self.detune
+    }
+
+    /// Change the shape of every voice's amplitude envelope, with times from 1 ms to 10 s. Notes
+    /// already playing pick it up straight away.
+    pub fn set_amp_envelope(&mut self, envelope: AdsrConfig) {
+        let old = self.amp_envelope;
+        let envelope = AdsrConfig {
+            attack_time: ParamId::Attack.clamp(envelope.attack_time),
+            decay_time: ParamId::Decay.clamp(envelope.decay_time),
+            sustain_amount: ParamId::Sustain.clamp(envelope.sustain_amount),
+            release_time: ParamId::Release.clamp(envelope.release_time),
+        };
+        self.amp_envelope = envelope;
+        for voice in &mut self.voices {
+            voice.amp_eg.set_config(envelope);
+        }
+        self.changed(ParamId::Attack, Some(old.attack_time));
+        self.changed(ParamId::Decay, Some(old.decay_time));
+        self.changed(ParamId::Sustain, Some(old.sustain_amount));
+        self.changed(ParamId::Release, Some(old.release_time));
+    }
+
+    pub fn amp_envelope(&self) -> AdsrConfig {
+        self.amp_envelope
+    }
+
+    /// Take a copy of the current sound: everything except what's being played with it (and the
+    /// settings of particular voices, such as their individual send levels).
+    pub fn save_patch(&self) -> Patch {
+        let mut effects = Vec::new();
+        for (chain, placement) in self.chains() {
+            effects.extend(chain_patches(chain, placement));
+        }
+        let mut patch = Patch {
+            effects,
+            ..Patch::default()
+        };
+        self.save_settings(&mut patch);
+        patch
+    }
+
+    /// Save the current sound into `patch`, as `save_patch` does, but without allocating, so
+    /// that it can be done on the audio thread. That only works if `patch` already has the same
+    /// effects, with every parameter given, as it does if it was saved from them, or came from
+    /// `PatchPreparer::saved` for the patch the synth is on. Returns whether it did; if not,
+    /// `patch` is left partly filled in.
+    pub fn save_patch_into(&self, patch: &mut Patch) -> bool {
+        self.save_settings(patch);
+        let mut saved = patch.effects.iter_mut();
+        for (chain, placement) in self.chains() {
+            for index in 0..chain.len() {
+                let (effect, saved) = match (chain.get(index), saved.next()) {
+                    (Some(effect), Some(saved)) => (effect, saved),
+                    _ => return false,
+                };
+                if saved.name != effect.name() || saved.placement != placement {
+                    return false;
+                }
+                saved.bypassed = chain.is_bypassed(index);
+                let mut parameters = saved.parameters.iter_mut();
+                let names = effect.parameter_names().iter().enumerate();
+                for (i, name) in names {
+                    let value = match effect.parameter(i) {
+                        Some(value) => value,
+                        None => continue,
+                    };
+                    match parameters.next() {
+                        Some((saved, saved_value)) if saved == name => *saved_value = value,
+                        _ => return false,
+                    }
+                }
+                if parameters.next().is_some() {
+                    return false;
+                }
+            }
+        }
+        saved.next().is_none()
+    }
+
+    /// Everything in a patch but its effects.
+    fn save_settings(&self, patch: &mut Patch) {
+        patch.waveform = self.waveform;
+        patch.detune = self.detune;
+        patch.cutoff = self.cutoff;
+        patch.amp_envelope = self.amp_envelope;
+        patch.stereo_spread = self.stereo_spread;
+        patch.volume = self.volume;
+        patch.send_levels = self.voices.first().map_or([0.0; SENDS], |v| v.sends);
+        patch.return_levels = array::from_fn(|send| self.sends[send].return_level());
+    }
+
+    /// The effects chains a patch is saved from: the master chain, the first voice's and each
+    /// send bus's.
+    fn chains(&self) -> impl Iterator<Item = (&EffectsChain, Placement)> {
+        let voice = self.voices.first().map(|v| (&v.effects, Placement::Voice));
+        let sends = self.sends.iter().enumerate();
+        iter::once((&self.effects, Placement::Master))
+            .chain(voice)
+            .chain(sends.map(|(index, send)| (send.effects(), Placement::Send(index))))
+    }
+
+    /// Set up two patches to morph between with `set_morph`, which starts at A.
+    pub fn set_morph_patches(&mut self, a: Patch, b: Patch) {
+        self.glide = None;
+        self.finished_morph = None;
+        self.morph = Some(Morph::new(&self.patch_preparer(), a, b));
+        self.set_morph(0.0);
+    }
+
+    /// Move between the patches given to `set_morph_patches`, from 0 (A) to 1 (B), e.g. from a
+    /// controller (CC 16). Does nothing if there aren't any.
+    pub fn set_morph(&mut self, amount: f32) {
+        let old = self.get_param(ParamId::Morph);
+        self.morph_amount = amount.clamp(0.0, 1.0);
+        if let Some(mut morph) = self.morph.take() {
+            morph.apply(self, self.morph_amount);
+            self.morph = Some(morph);
+        }
+        self.changed(ParamId::Morph, old);
+    }
+
+    /// The patches being morphed between, from `set_morph_patches`, or a glide's start and end.
+    pub fn morph_patches(&self) -> Option<(&Patch, &Patch)> {
+        self.morph.as_ref().map(Morph::patches)
+    }
+
+    /// How far between the morph patches the sound is.
+    pub fn morph(&self) -> f32 {
+        self.morph_amount
+    }
+
+    /// Switch to a different sound. Notes already playing carry on, with the new settings.
+    /// Effects with unknown names are skipped.
+    ///
+    /// Chains which already hold the patch's effects keep them, just changing their parameters,
+    /// so that e.g. a reverb tail isn't cut off. Otherwise the chain is replaced.
+    ///
+    /// This stops any morphing set up with `set_morph_patches`, or glide from `glide_to_patch`.
+    pub fn load_patch(&mut self, patch: &Patch) {
+        self.record_change(None);
+        self.replace_patch(patch);
+    }
+
+    /// Move to a different sound gradually over `time`, blending between the current settings
+    /// and the patch's as `set_morph` does. A zero `time` is the same as `load_patch`.
+    pub fn glide_to_patch(&mut self, patch: Patch, time: Duration) {
+        self.record_change(None);
+        self.start_glide(patch, time);
+    }
+
+    /// Something to get patches ready for `switch_patch` with, e.g. on another thread.
+    pub fn patch_preparer(&self) -> PatchPreparer {
+        PatchPreparer::new(self)
+    }
+
+    /// Switch to a patch got ready by `patch_preparer`, loading it or gliding to it as it was
+    /// prepared. Unlike `load_patch` and `glide_to_patch`, this doesn't make any effects, or free
+    /// anything: whatever the synth lets go of is left in `prepared`, to drop somewhere other
+    /// than the audio thread. It still saves the settings for `undo`, unless that's turned off.
+    ///
+    /// Does nothing if `prepared` has already been switched to.
+    pub fn switch_patch(&mut self, prepared: &mut PreparedPatch) {
+        let mut morph = match prepared.morph.take() {
+            Some(morph) => morph,
+            None => return,
+        };
+        self.record_change(None);
+        prepared.released[0] = self.morph.take();
+        prepared.released[1] = self.finished_morph.take();
+        self.glide = None;
+        if prepared.frames >= 1.0 && morph.starts_from(self) {
+            self.morph = Some(morph);
+            self.set_morph(0.0);
+            self.glide = Some(1.0 / prepared.frames);
+        } else {
+            morph.apply(self, 1.0);
+            // holding the effects it replaced
+            prepared.released[2] = Some(morph);
+        }
+    }
+
+    /// `glide_to_patch`, without saving anything for `undo`.
+    fn start_glide(&mut self, patch: Patch, time: Duration) {
+        let frames = time.as_secs_f32() * self.sample_rate as f32;
+        if frames < 1.0 {
+            self.replace_patch(&patch);
+            return;
+        }
+        let current = self.save_patch();
+        self.set_morph_patches(current, patch);
+        self.glide = Some(1.0 / frames);
+    }
+
+    /// Save the current settings, so that `undo` can go back to them. Loading a patch, and
+    /// changes made by commands (e.g. from MIDI controllers) do this already, but anything else
+    /// changed through the API needs it first.
+    pub fn checkpoint(&mut self) {
+        self.record_change(None);
+    }
+
+    /// Go back to the settings from before the last change, as recorded by `checkpoint`. Returns
+    /// whether there was anything to undo. This stops any morphing, like `load_patch`.
+    pub fn undo(&mut self) -> bool {
+        if !self.can_undo() {
+            return false;
+        }
+        let current = self.save_patch();
+        match self.history.undo(current) {
+            Some(patch) => {
+                self.replace_patch(&patch);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Put back the change last undone. Returns whether there was anything to redo.
+    pub fn redo(&mut self) -> bool {
+        if !self.can_redo() {
+            return false;
+        }
+        let current = self.save_patch();
+        match self.history.redo(current) {
+            Some(patch) => {
+                self.replace_patch(&patch);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn can_undo(&self) -> bool {
+        self.history.can_undo()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        self.history.can_redo()
+    }
+
+    /// Choose whether changes are saved for `undo`, which they are by default. Turning it off
+    /// forgets what was saved, and keeps changes from allocating to save the settings, e.g. in a
+    /// plugin, whose host keeps its own undo history.
+    pub fn set_undo_enabled(&mut self, enabled: bool) {
+        self.undo_enabled = enabled;
+        if !enabled {
+            self.history = History::default();
+        }
+    }
+
+    fn record_change(&mut self, kind: Option<ChangeKind>) {
+        if !self.undo_enabled {
+            return;
+        }
+        let merge_time = (HISTORY_MERGE_TIME * self.sample_rate as f32) as u64;
+        if self.history.record(kind, self.clock, merge_time) {
+            let current = self.save_patch();
+            self.history.push(current);
+        }
+    }
+
+    /// Switch to a patch for good, stopping any morphing.
+    fn replace_patch(&mut self, patch: &Patch) {
+        self.morph = None;
+        self.finished_morph = None;
+        self.glide = None;
+        self.set_patch(patch);
+    }
+
+    /// Move the cutoff and detune the voices are at towards their settings, by `frames`' worth.
+    fn smooth_controls(&mut self, frames: usize) {
+        let amount = smoothing_amount(frames, self.smoothing_time, self.sample_rate);
+        let cutoff = match self.sequencer.cutoff_lock() {
+            Some(cutoff) => self.limit_cutoff(cutoff),
+            None => self.cutoff,
+        };
+        if let Some(cutoff) = self.smoothed_cutoff.follow(cutoff.ln(), amount) {
+            for voice in &mut self.voices {
+                voice.filter.set_cutoff(cutoff.exp() as f64);
+            }
+        }
+        if let Some(detune) = self.smoothed_detune.follow(self.detune, amount) {
+            for voice in &mut self.voices {
+                voice.detune = detune;
+                voice.tune(self.pitch_bend);
+            }
+        }
+    }
+
+    /// Move a glide started by `glide_to_patch` along by `frames`.
+    fn advance_glide(&mut self, frames: usize) {
+        if let Some(step) = self.glide {
+            let amount = self.morph_amount + step * frames as f32;
+            self.set_morph(amount);
+            if amount >= 1.0 {
+                self.finished_morph = self.morph.take();
+                self.glide = None;
+            }
+        }
+    }
+
+    pub(crate) fn set_patch(&mut self, patch: &Patch) {
+        self.set_waveform(patch.waveform);
+        self.set_detune(patch.detune);
+        self.set_cutoff(patch.cutoff);
+        self.set_amp_envelope(patch.amp_envelope);
+        self.set_stereo_spread(patch.stereo_spread);
+        self.set_volume(patch.volume);
+        for (send, level) in patch.send_levels.iter().enumerate() {
+            self.set_send_level(send, *level);
+        }
+        for (send, level) in patch.return_levels.iter().enumerate() {
+            let _ = self.set_param(ParamId::ReturnLevel(send), *level);
+        }
+
+        load_chain(&mut self.effects, placed(patch, Placement::Master));
+        for voice in &mut self.voices {
+            load_chain(&mut voice.effects, placed(patch, Placement::Voice));
+        }
+        for (index, send) in self.sends.iter_mut().enumerate() {
+            load_chain(send.effects_mut(), placed(patch, Placement::Send(index)));
+        }
+
+        // the effects may all be different, so send every one of their parameters
+        if let Some(mut observer) = self.param_observer.take() {
+            for param in self.effect_params() {
+                if let Some(value) = self.get_param(param) {
+                    observer(param, value);
+                }
+            }
+            self.param_observer = Some(observer);
+        }
+    }
+
+    /// Set the master volume, as a linear gain from 0 to 2. Changes are smoothed (see
+    /// `set_smoothing_time`).
+    pub fn set_volume(&mut self, volume: f32) {
+        let old = self.get_param(ParamId::Volume);
+        self.volume = ParamId::Volume.clamp(volume);
+        self.changed(ParamId::Volume, old);
+    }
+
+    /// The master volume, as last set.
+    pub fn volume(&self) -> f32 {
+        self.volume
+    }
+
+    /// The effects the mixed voices go through, before the master volume and limiter.
+    pub fn effects(&self) -> &EffectsChain {
+        &self.effects
+    }
+
+    pub fn effects_mut(&mut self) -> &mut EffectsChain {
+        &mut self.effects
+    }
+
+    /// Add an effect to the end of every voice's own chain, from `make` (called once per voice).
+    /// These run at the internal sample rate, on each voice before it's panned and mixed, so that
+    /// e.g. distortion treats every note separately. Tails are cut off when a voice finishes.
+    pub fn push_voice_effect(&mut self, mut make: impl FnMut() -> Box<dyn Effect>) {
+        for voice in &mut self.voices {
+            voice.effects.push(make());
+        }
+    }
+
+    /// Take an effect out of every voice's chain.
+    pub fn remove_voice_effect(&mut self, index: usize) {
+        for voice in &mut self.voices {
+            voice.effects.remove(index);
+        }
+    }
+
+    /// The effects each voice goes through, as set up with `push_voice_effect`. Every voice has
+    /// its own copy of the same chain; this is the first one's, as there's always at least one.
+    pub fn voice_effects(&self) -> &EffectsChain {
+        &self.voices[0].effects
+    }
+
+    /// Change a parameter of one of the voice effects, in every voice.
+    pub fn set_voice_effect_parameter(&mut self, index: usize, parameter: usize, value: f32) {
+        let param = ParamId::Effect {
+            placement: Placement::Voice,
+            slot: index,
+            parameter,
+        };
+        let old = self.get_param(param);
+        for voice in &mut self.voices {
+            if let Some(effect) = voice.effects.get_mut(index) {
+                effect.set_parameter(parameter, value);
+            }
+        }
+        self.changed(param, old);
+    }
+
+    /// Skip (or stop skipping) one of the voice effects, in every voice.
+    pub fn set_voice_effect_bypassed(&mut self, index: usize, bypassed: bool) {
+        for voice in &mut self.voices {
+            voice.effects.set_bypassed(index, bypassed);
+        }
+    }
+
+    /// One of the `SENDS` send buses, whose effects are shared between all the voices and mixed
+    /// in alongside them, before the master effects. Panics if `index` is out of range.
+    pub fn send(&self, index: usize) -> &SendBus {
+        &self.sends[index]
+    }
+
+    pub fn send_mut(&mut self, index: usize) -> &mut SendBus {
+        &mut self.sends[index]
+    }
+
+    /// Set how much of every voice goes to a send bus, from 0 (the default) to 1.
+    pub fn set_send_level(&mut self, send: usize, level: f32) {
+        let old = self.get_param(ParamId::SendLevel(send));
+        for voice in &mut self.voices {
+            voice.sends[send] = level.clamp(0.0, 1.0);
+        }
+        self.changed(ParamId::SendLevel(send), old);
+    }
+
+    /// Set how much of one voice goes to a send bus, from 0 to 1, e.g. to give some voices more
+    /// reverb than others. Panics if either index is out of range.
+    pub fn set_voice_send_level(&mut self, voice: usize, send: usize, level: f32) {
+        self.voices[voice].sends[send] = level.clamp(0.0, 1.0);
+    }
+
+    /// How much of a voice goes to a send bus.
+    pub fn voice_send_level(&self, voice: usize, send: usize) -> f32 {
+        self.voices[voice].sends[send]
+    }
+
+    /// The vocoder the voices go through when the voice source is `VoiceSource::Vocoder`.
+    pub fn vocoder(&self) -> &Vocoder {
+        &self.vocoder
+    }
+
+    pub fn vocoder_mut(&mut self) -> &mut Vocoder {
+        &mut self.vocoder
+    }
+
+    /// Output levels over the most recently rendered block.
+    pub fn levels(&self) -> Levels {
+        self.levels
+    }
+
+    /// Start or stop timing each part of rendering, for `profile` to report. The oscillators,
+    /// filters and envelopes are then run one after the other over each block, rather than all
+    /// together a sample at a time, which costs a little more.
+    #[cfg(feature = "profile")]
+    pub fn set_profiling(&mut self, enabled: bool) {
+        self.profile = enabled.then(Profile::default);
+    }
+
+    /// Where the rendering time has gone since profiling was turned on or `reset_profile` was
+    /// last called, if it's on.
+    #[cfg(feature = "profile")]
+    pub fn profile(&self) -> Option<&Profile> {
+        self.profile.as_ref()
+    }
+
+    /// Start adding up the time again, e.g. after reporting it.
+    #[cfg(feature = "profile")]
+    pub fn reset_profile(&mut self) {
+        if let Some(profile) = &mut self.profile {
+            *profile = Profile::default();
+        }
+    }
+
+    /// Change the maximum number of frames rendered at a time. Smaller blocks allow for lower
+    /// latency, since control changes (e.g. the note timeout) are only applied between blocks.
+    ///
+    /// This is meant to be called before audio starts: any samples left over from the current
+    /// block are discarded.
+    pub fn set_block_size(&mut self, block_size: usize) {
+        assert!(block_size > 0, "Block size must be at least one frame");
+        let oversampled_len = block_size * self.oversampling.ratio() as usize;
+        self.block = vec![0.0; block_size * CHANNELS];
+        self.block_len = 0;
+        self.block_position = 0;
+        self.voice_buffer = vec![0.0; oversampled_len];
+        self.voice_frames = vec![Frame::ZERO; oversampled_len];
+        self.input = vec![0.0; oversampled_len];
+        self.modulator = vec![0.0; block_size];
+        self.bus = vec![Frame::ZERO; oversampled_len];
+        for send in &mut self.sends {
+            send.set_block_size(block_size, oversampled_len);
+        }
+    }
+
+    /// The maximum number of frames rendered at a time.
+    pub fn block_size(&self) -> usize {
+        self.block.len() / CHANNELS
+    }
+
+    /// Render a whole block of interleaved audio at once, for backends which deal in buffers.
+    ///
+    /// This shares a stream with the `Iterator` implementation, so that if some of the current
+    /// block has already been taken one sample at a time, only the rest of it is returned.
+    pub fn next_block(&mut self) -> &[f32] {
+        if self.block_position == self.block_len {
+            self.render(self.block_size());
+        }
+        let start = mem::replace(&mut self.block_position, self.block_len);
+        &self.block[start..self.block_len]
+    }
+
+    /// Fill `out` with interleaved audio. Its length should be a multiple of `CHANNELS`.
+    ///
+    /// Any number of frames can be requested, and exactly that many are rendered, so messages
+    /// handled between calls take effect at the first frame of the next one.
+    pub fn process(&mut self, out: &mut [f32]) {
+        let mut filled = 0;
+        while filled < out.len() {
+            if self.block_position == self.block_len {
+                let frames = ((out.len() - filled) / CHANNELS).clamp(1, self.block_size());
+                self.render(frames);
+            }
+            let count = (self.block_len - self.block_position).min(out.len() - filled);
+            out[filled..filled + count]
+                .copy_from_slice(&self.block[self.block_position..self.block_position + count]);
+            self.block_position += count;
+            filled += count;
+        }
+    }
+
+    /// Like `process`, but with a separate buffer for each channel. Both should be the same
+    /// length.
+    pub fn process_stereo(&mut self, left: &mut [f32], right: &mut [f32]) {
+        let frames = left.len().min(right.len());
+        let mut filled = 0;
+        while filled < frames {
+            if self.block_position == self.block_len {
+                self.render((frames - filled).min(self.block_size()));
+            }
+            let available = &self.block[self.block_position..self.block_len];
+            for (frame, (l, r)) in available.chunks_exact(CHANNELS).zip(
+                left[filled..frames]
+                    .iter_mut()
+                    .zip(&mut right[filled..frames]),
+            ) {
+                *l = frame[0];
+                *r = frame[1];
+                self.block_position += CHANNELS;
+                filled += 1;
+            }
+        }
+    }
+
+    /// Remove DC offset from each voice individually, as well as from the final mix (which is
+    /// always done). Off by default, since it costs a little per voice.
+    pub fn set_voice_dc_blocking(&mut self, enabled: bool) {
+        for voice in &mut self.voices {
+            voice.dc_blocker = enabled.then(|| DcBlocker::new(voice.filter.sample_rate as f32));
+        }
+    }
+
+    /// Choose what the voices play: their oscillators, or external input.
+    pub fn set_voice_source(&mut self, source: VoiceSource) {
+        self.source = source;
+    }
+
+    /// Gate the external input, so that it's silent (rather than hissing) while nothing is being
+    /// played into it; or pass `false` to let it all through, as by default.
+    pub fn set_input_gate(&mut self, enabled: bool) {
+        self.input_gate = if enabled {
+            let mut gate = Gate::new();
+            gate.set_sample_rate(self.sample_rate);
+            Some(gate)
+        } else {
+            None
+        };
+    }
+
+    /// The input's noise gate, if enabled, e.g. to change its threshold.
+    pub fn input_gate_mut(&mut self) -> Option<&mut Gate> {
+        self.input_gate.as_mut()
+    }
+
+    /// Like `process`, but also taking (mono) external input, one sample per frame of `out`.
+    ///
+    /// If the voice source is `VoiceSource::Input`, each playing voice runs the input through its
+    /// filter and envelope, turning the synth into a filter box gated by notes; if it's
+    /// `VoiceSource::Vocoder`, the input modulates the voices through the vocoder. At other times,
+    /// and in the other rendering methods, the input is silent. Anything left over from the
+    /// current block is dropped.
+    pub fn process_with_input(&mut self, input: &[f32], out: &mut [f32]) {
+        let frames = input.len().min(out.len() / CHANNELS);
+        self.block_position = self.block_len;
+
+        let ratio = self.oversampling.ratio() as usize;
+        let mut filled = 0;
+        while filled < frames {
+            let chunk = self.run_scheduled((frames - filled).min(self.block_size()));
+            self.modulator[..chunk].copy_from_slice(&input[filled..filled + chunk]);
+            if let Some(gate) = &mut self.input_gate {
+                gate.process_mono(&mut self.modulator[..chunk]);
+            }
+            // bring the input up to the internal rate, interpolating linearly
+            let oversampled = self.input[..chunk * ratio].chunks_exact_mut(ratio);
+            for (sample, oversampled) in self.modulator[..chunk].iter().zip(oversampled) {
+                for (i, out) in oversampled.iter_mut().enumerate() {
+                    let position = (i + 1) as f32 / ratio as f32;
+                    *out = self.last_input + (sample - self.last_input) * position;
+                }
+                self.last_input = *sample;
+            }
+
+            self.render(chunk);
+            out[filled * CHANNELS..(filled + chunk) * CHANNELS]
+                .copy_from_slice(&self.block[..self.block_len]);
+            self.block_position = self.block_len;
+            filled += chunk;
+        }
+    }
+
+    /// Play `events` (sorted by time) through the synth offline, as fast as possible, writing the
+    /// first `duration` of the result to a WAV file in the given sample format.
+    ///
+    /// Messages take effect at the frame they fall on. Notes which could not be played are
+    /// skipped.
+    #[cfg(all(feature = "midi", feature = "wav"))]
+    pub fn render_to_wav(
+        &mut self,
+        path: impl AsRef<Path>,
+        events: &[TimedMsg],
+        duration: Duration,
+        format: SampleFormat,
+    ) -> io::Result<()> {
+        let file = BufWriter::new(File::create(path)?);
+        let mut wav = WavWriter::with_format(file, CHANNELS as u16, self.sample_rate, format)?;
+        let mut playback = Playback::new(events.to_vec());
+        let sample_rate = self.sample_rate as f64;
+        let to_frames = |time: Duration| (time.as_secs_f64() * sample_rate) as u64;
+        let total_frames = to_frames(duration);
+        let mut buffer = vec![0.0; self.block_size() * CHANNELS];
+
+        let mut frames = 0;
+        while frames < total_frames {
+            let now = Duration::from_secs_f64(frames as f64 / sample_rate);
+            for event in playback.advance(now) {
+                let _ = self.handle_midi(&event.msg);
+            }
+
+            let until = playback.next_time().map_or(total_frames, to_frames);
+            let chunk_frames = (until.clamp(frames + 1, total_frames) - frames)
+                .min(self.block_size() as u64) as usize;
+            let chunk = &mut buffer[..chunk_frames * CHANNELS];
+            self.process(chunk);
+            wav.write_samples(chunk)?;
+            frames += chunk_frames as u64;
+        }
+
+        wav.finish()?;
+        Ok(())
+    }
+
+    /// Render each voice into its own buffer instead of mixing them, so they can be mixed or
+    /// processed separately elsewhere. There should be one buffer per voice (see `voice_count`),
+    /// all the same length.
+    ///
+    /// Voices come out in mono, before their own effects, panning, the master volume and the
+    /// limiter. Anything left over from the mixed output's current block is dropped.
+    pub fn process_voices(&mut self, outputs: &mut [&mut [f32]]) {
+        assert_eq!(
+            outputs.len(),
+            self.voices.len(),
+            "Need one output per voice"
+        );
+        let frames = outputs.iter().map(|o| o.len()).min().unwrap_or(0);
+        self.block_position = self.block_len;
+
+        let _flush = FlushDenormals::new();
+        let ratio = self.oversampling.ratio() as usize;
+        let mut filled = 0;
+        while filled < frames {
+            let chunk = self.run_scheduled((frames - filled).min(self.block_size()));
+            self.advance_clock(chunk);
+
+            let voices = self.voices.iter_mut().zip(&mut self.voice_decimators);
+            for ((voice, decimator), output) in voices.zip(outputs.iter_mut()) {
+                voice.fill(&mut self.voice_buffer[..chunk * ratio]);
+                let oversampled = self.voice_buffer[..chunk * ratio].chunks_exact(ratio);
+                for (out, oversampled) in output[filled..filled + chunk].iter_mut().zip(oversampled)
+                {
+                    for sample in oversampled {
+                        decimator.push(*sample);
+                    }
+                    *out = decimator.output();
+                }
+            }
+            filled += chunk;
+        }
+    }
+
+    /// How many voices the synth has.
+    pub fn voice_count(&self) -> usize {
+        self.voices.len()
+    }
+
+    /// How many voices are making sound, including those still fading out after their notes were
+    /// released.
+    pub fn active_voice_count(&self) -> usize {
+        self.voices
+            .iter()
+            .filter(|voice| voice.amp_eg.is_active())
+            .count()
+    }
+
+    /// Move time forward by `frames`, ending any notes which have been held too long, and moving
+    /// glides and smoothed controls along. Also picks up changes from controllers, as this happens
+    /// at the start of each block.
+    fn advance_clock(&mut self, frames: usize) {
+        if let Some(controls) = self.controls.take() {
+            controls.apply(self);
+            self.controls = Some(controls);
+        }
+        self.clock += frames as u64;
+        if let Some(timeout) = self.note_timeout {
+            for voice in &mut self.voices {
+                let latched = self.latch.is_latched(voice.note);
+                if voice.is_held() && !latched && self.clock - voice.started_at > timeout {
+                    voice.end_note();
+                }
+            }
+        }
+        self.advance_glide(frames);
+        self.smooth_controls(frames);
+    }
+
+    /// Carry out the scheduled commands due by now, and shorten `frames` to stop at the next one,
+    /// so that it lands on the right frame.
+    fn run_scheduled(&mut self, frames: usize) -> usize {
+        while let Some(command) = self.scheduled.pop_due(self.clock) {
+            // as in `render_to_wav`, notes which can't be played are skipped
+            let _ = self.perform(command);
+        }
+        match self.scheduled.next_time() {
+            Some(due) => (due - self.clock).min(frames as u64) as usize,
+            None => frames,
+        }
+    }
+
+    /// Play whatever the sequencer has due by now, and shorten `frames` to stop at the next thing
+    /// it does.
+    fn run_sequencer(&mut self, frames: usize) -> usize {
+        let notes = self.sequencer.run(self.clock, self.step_len());
+        self.play_sequenced(notes);
+        match self.sequencer.next_event() {
+            Some(due) => due.saturating_sub(self.clock).clamp(1, frames as u64) as usize,
+            None => frames,
+        }
+    }
+
+    /// Play whatever the generator has due by now, and shorten `frames` to stop at its next step.
+    fn run_generator(&mut self, frames: usize) -> usize {
+        let (sample_rate, tempo) = (self.sample_rate, self.tempo());
+        let generator = match &mut self.generator {
+            Some(generator) => generator,
+            None => return frames,
+        };
+        let due = generator.run(self.clock, generator.step_len(sample_rate, tempo));
+        let next = generator.next_event();
+        for note in due.release.iter() {
+            let _ = self.end_playing_note(note);
+        }
+        if let Some((note, velocity)) = due.play {
+            let _ = self.begin_note_at(note, velocity as f32 / 127.0);
+        }
+        next.saturating_sub(self.clock).clamp(1, frames as u64) as usize
+    }
+
+    /// Replace the current block with up to `frames` frames of new audio, stopping short at the
+    /// next scheduled command, sequencer step or generated note.
+    fn render(&mut self, frames: usize) {
+        let _flush = FlushDenormals::new();
+        let frames = self.run_scheduled(frames);
+        let frames = self.run_sequencer(frames);
+        let frames = self.run_generator(frames);
+        self.advance_clock(frames);
+        #[cfg(feature = "profile")]
+        let rendering = Stopwatch::start(&self.profile);
+
+        // work through one voice at a time, in simple loops over whole buffers
+        let ratio = self.oversampling.ratio() as usize;
+        let len = frames * ratio;
+        self.bus[..len].fill(Frame::ZERO);
+        for send in &mut self.sends {
+            send.clear(len);
+        }
+        for (index, voice) in self.voices.iter_mut().enumerate() {
+            if !voice.amp_eg.is_active() {
+                for tap in &mut self.taps {
+                    if tap.point() == TapPoint::Voice(index) {
+                        tap.feed(len, ratio, |_| Frame::ZERO);
+                    }
+                }
+                continue;
+            }
+            match self.source {
+                VoiceSource::Oscillators | VoiceSource::Vocoder => {
+                    #[cfg(feature = "profile")]
+                    if let Some(profile) = &mut self.profile {
+                        voice.fill_profiled(&mut self.voice_buffer[..len], profile);
+                    } else {
+                        voice.fill(&mut self.voice_buffer[..len]);
+                    }
+                    #[cfg(not(feature = "profile"))]
+                    voice.fill(&mut self.voice_buffer[..len]);
+                }
+                VoiceSource::Input => {
+                    for (sample, input) in self.voice_buffer[..len].iter_mut().zip(&self.input) {
+                        *sample = voice.process_input(*input);
+                    }
+                }
+            }
+            if voice.effects.is_empty() {
+                for (out, sample) in self.bus[..len].iter_mut().zip(&self.voice_buffer) {
+                    *out += Frame::mono(*sample) * voice.pan_gains;
+                }
+                for tap in &mut self.taps {
+                    if tap.point() == TapPoint::Voice(index) {
+                        let samples = &self.voice_buffer;
+                        tap.feed(len, ratio, |i| Frame::mono(samples[i]) * voice.pan_gains);
+                    }
+                }
+            } else {
+                let frames = &mut self.voice_frames[..len];
+                for (frame, sample) in frames.iter_mut().zip(&self.voice_buffer) {
+                    *frame = Frame::mono(*sample);
+                }
+                #[cfg(feature = "profile")]
+                let effects = Stopwatch::start(&self.profile);
+                voice.effects.process(frames);
+                #[cfg(feature = "profile")]
+                effects.stop(&mut self.profile, |p| &mut p.effects);
+                // the pan then balances whatever stereo image the effects made
+                for (out, frame) in self.bus[..len].iter_mut().zip(frames.iter()) {
+                    *out += *frame * voice.pan_gains;
+                }
+                for tap in &mut self.taps {
+                    if tap.point() == TapPoint::Voice(index) {
+                        tap.feed(len, ratio, |i| frames[i] * voice.pan_gains);
+                    }
+                }
+            }
+
+            for (send, level) in self.sends.iter_mut().zip(voice.sends) {
+                if level > 0.0 && send.is_active() {
+                    let gains = voice.pan_gains * level;
+                    if voice.effects.is_empty() {
+                        send.send_mono(&self.voice_buffer[..len], gains);
+                    } else {
+                        send.send_stereo(&self.voice_frames[..len], gains);
+                    }
+                }
+            }
+        }
+
+        let block = Frame::from_interleaved_mut(&mut self.block[..frames * CHANNELS]);
+        for (out, oversampled) in block.iter_mut().zip(self.bus[..len].chunks_exact(ratio)) {
+            for frame in oversampled {
+                for (decimator, sample) in self.decimators.iter_mut().zip(*frame) {
+                    decimator.push(sample);
+                }
+            }
+            for (sample, decimator) in out.iter_mut().zip(&mut self.decimators) {
+                *sample = decimator.output();
+            }
+        }
+
+        for frame in block.iter_mut() {
+            for (sample, blocker) in frame.iter_mut().zip(&mut self.dc_blockers) {
+                *sample = blocker.process(*sample);
+            }
+        }
+        if let VoiceSource::Vocoder = self.source {
+            self.vocoder.process(block, &self.modulator);
+        }
+        Self::feed_taps(&mut self.taps, TapPoint::Dry, block);
+        #[cfg(feature = "profile")]
+        let effects = Stopwatch::start(&self.profile);
+        for send in &mut self.sends {
+            if send.is_active() {
+                send.mix_into(block, ratio);
+            }
+        }
+        self.effects.process(block);
+        #[cfg(feature = "profile")]
+        effects.stop(&mut self.profile, |p| &mut p.effects);
+
+        let mut levels = Levels::default();
+        for frame in block.iter_mut() {
+            self.smoothed_volume += (self.volume - self.smoothed_volume) * self.volume_smoothing;
+            self.fade_gain = (self.fade_gain - self.fade_step).max(0.0);
+            let loud = *frame * (self.smoothed_volume * self.fade_gain);
+            levels.clipped |= loud.peak() > 1.0;
+
+            *frame = self.limiter.process(loud);
+            for (channel, sample) in frame.iter().enumerate() {
+                levels.peak[channel] = levels.peak[channel].max(sample.abs());
+                levels.rms[channel] += sample * sample;
+            }
+        }
+        levels.rms = levels.rms.map(|sum| (sum / frames as f32).sqrt());
+        self.levels = levels;
+        Self::feed_taps(&mut self.taps, TapPoint::Output, block);
+
+        // input only ever applies to the block it was given for
+        self.input[..len].fill(0.0);
+        self.modulator[..frames].fill(0.0);
+
+        self.block_len = frames * CHANNELS;
+        self.block_position = 0;
+
+        #[cfg(feature = "profile")]
+        {
+            rendering.stop(&mut self.profile, |p| &mut p.total);
+            if let Some(profile) = &mut self.profile {
+                profile.audio += Duration::from_secs_f64(frames as f64 / self.sample_rate as f64);
+                profile.blocks += 1;
+            }
+        }
+    }
+
+    /// Copy a block into the taps at `point`.
+    fn feed_taps(taps: &mut [Tap], point: TapPoint, block: &[Frame]) {
+        for tap in taps.iter_mut().filter(|tap| tap.point() == point) {
+            tap.feed(block.len(), 1, |i| block[i]);
+        }
+    }
+
+    /// Respond to an incoming MIDI message, on any channel.
+    ///
+    /// See `SynthCommand::from_midi` for what is understood; anything else is ignored. Returns an
+    /// error if a note could not be started or ended, as for `try_begin_note` and `try_end_note`.
+    #[cfg(feature = "midi")]
+    pub fn handle_midi(&mut self, msg: &MidiMsg) -> Result<(), SynthError> {
+        log::trace!("MIDI message: {:?}", msg);
+        match SynthCommand::from_midi(msg) {
+            Some(command) => self.apply(command),
+            None => Ok(()),
+        }
+    }
+
+    /// Respond to an incoming Universal MIDI Packet, as MIDI 2.0 sends, on any group and channel.
+    ///
+    /// See `SynthCommand::from_ump` for what is understood; anything else is ignored. Errors are
+    /// as for `handle_midi`.
+    #[cfg(feature = "midi")]
+    pub fn handle_ump(&mut self, packet: &[u32]) -> Result<(), SynthError> {
+        log::trace!("UMP: {:08x?}", packet);
+        match SynthCommand::from_ump(packet) {
+            Some(command) => self.apply(command),
+            None => Ok(()),
+        }
+    }
+
+    /// Carry out a command. Returns an error if a note could not be started or ended, as for
+    /// `try_begin_note` and `try_end_note`.
+    pub fn apply(&mut self, command: SynthCommand) -> Result<(), SynthError> {
+        self.record_command(&command);
+        self.perform(command)
+    }
+
+    /// Carry out every command waiting in `queue`, oldest first, e.g. at the start of each block
+    /// when another thread sends them through a `ring::ring_buffer`. Notes which can't be played
+    /// are skipped, as in `render_to_wav`.
+    pub fn apply_queued(&mut self, queue: &mut Consumer<SynthCommand>) {
+        while let Some(command) = queue.pop() {
+            let _ = self.apply(command);
+        }
+    }
+
+    /// Carry out a command at a given frame (see `clock`), rather than straight away, so that
+    /// e.g. notes played in quick succession keep their timing instead of all landing at the start
+    /// of a block. Rendering stops short wherever a command falls, and carries on after it.
+    /// Commands for frames which have already been rendered are carried out before the next one.
+    ///
+    /// Returns `SynthError::ScheduleFull` if too many commands (1024) are waiting already. Notes
+    /// which can't be played when the time comes are skipped. Patches are switched to while
+    /// rendering, which allocates; to avoid that, apply them rather than scheduling them.
+    pub fn schedule(&mut self, frame: u64, command: SynthCommand) -> Result<(), SynthError> {
+        if self.scheduled.is_full() {
+            return Err(SynthError::ScheduleFull);
+        }
+        // the settings are saved for `undo` now rather than later, as that may allocate
+        self.record_command(&command);
+        self.scheduled
+            .push(frame, command)
+            .map_err(|_| SynthError::ScheduleFull)
+    }
+
+    /// Forget every command waiting to be carried out.
+    pub fn clear_scheduled(&mut self) {
+        self.scheduled.clear();
+    }
+
+    /// How many frames have been rendered so far, from zero when the synth was made: the clock
+    /// `schedule` goes by. This can be ahead of the output by the rest of the current block, if
+    /// it's being taken through the `Iterator` or `next_block`.
+    pub fn clock(&self) -> u64 {
+        self.clock
+    }
+
+    /// Save the settings for `undo` if a command is about to change them.
+    fn record_command(&mut self, command: &SynthCommand) {
+        match command {
+            SynthCommand::Cutoff(_) => self.record_change(Some("cutoff")),
+            SynthCommand::Volume(_) => self.record_change(Some("volume")),
+            // effects' parameters are named by their effects, so they're merged together
+            SynthCommand::SetParam { param, .. } => {
+                self.record_change(Some(param.info().map_or("effect", |info| info.name)))
+            }
+            SynthCommand::LoadPatch(_) | SynthCommand::GlideToPatch { .. } => {
+                self.record_change(None)
+            }
+            _ => (),
+        }
+    }
+
+    /// Carry out a command, without saving anything for `undo`.
+    fn perform(&mut self, command: SynthCommand) -> Result<(), SynthError> {
+        match command {
+            SynthCommand::NoteOn { note, velocity } => return self.try_begin_note(note, velocity),
+            SynthCommand::HighResNoteOn { note, velocity } => {
+                return self.try_begin_note_high_res(note, velocity)
+            }
+            SynthCommand::NoteOff { note } => return self.try_end_note(note),
+            SynthCommand::NoteExpression { note, expression } => {
+                return self.set_note_expression(note, expression)
+            }
+            SynthCommand::PitchBend(semitones) => self.set_pitch_bend(semitones),
+            SynthCommand::Cutoff(cutoff) => self.set_cutoff(cutoff),
+            SynthCommand::Volume(volume) => self.set_volume(volume),
+            SynthCommand::AllNotesOff => self.release_all(),
+            SynthCommand::AllSoundOff => self.silence_all(),
+            SynthCommand::FadeOut(time) => self.fade_out(time),
+            SynthCommand::Tempo(bpm) => self.set_tempo(bpm),
+            SynthCommand::Morph(amount) => self.set_morph(amount),
+            SynthCommand::SetParam { param, value } => return self.set_param(param, value),
+            SynthCommand::LoadPatch(patch) => self.replace_patch(&patch),
+            SynthCommand::GlideToPatch { patch, time } => self.start_glide(*patch, time),
+            SynthCommand::ClockTick => {
+                if let Some(bpm) = self.midi_clock.tick(self.clock, self.sample_rate) {
+                    self.set_tempo(bpm);
+                }
+                let notes = self.sequencer.tick(self.clock, self.step_len());
+                self.play_sequenced(notes);
+            }
+            SynthCommand::Sequencer(command) => self.control_sequencer(command),
+            SynthCommand::Latch(enabled) => self.set_latch(enabled),
+            SynthCommand::Generate(settings) => self.set_generator(settings),
+        }
+        Ok(())
+    }
+
+    /// The step sequencer, to see its pattern and where it's up to.
+    pub fn sequencer(&self) -> &Sequencer {
+        &self.sequencer
+    }
+
+    /// Start, stop or change the step sequencer (see `sequencer`). Its notes take voices as
+    /// notes played any other way do, and any it can't play are skipped.
+    pub fn control_sequencer(&mut self, command: SequencerCommand) {
+        let notes = self.sequencer.control(command, self.clock, self.step_len());
+        self.play_sequenced(notes);
+    }
+
+    /// Give the step sequencer a new pattern, as `SequencerCommand::LoadPattern` does, but copying
+    /// it rather than taking a box to free, e.g. on the audio thread.
+    pub fn load_pattern(&mut self, pattern: &Pattern) {
+        self.sequencer.load(pattern);
+    }
+
+    /// Have the synth play random notes by itself (see `generate`), from its first step now, or
+    /// stop with `None`. Whatever notes the generator was playing are released. Its notes take
+    /// voices as notes played any other way do, and any it can't play are skipped.
+    pub fn set_generator(&mut self, settings: Option<GeneratorSettings>) {
+        if let Some(mut generator) = self.generator.take() {
+            for note in generator.stop().iter() {
+                let _ = self.end_playing_note(note);
+            }
+        }
+        self.generator = settings.map(|settings| Generator::new(settings, self.clock));
+    }
+
+    /// What the generator is playing, if it's running.
+    pub fn generator(&self) -> Option<GeneratorSettings> {
+        self.generator.as_ref().map(Generator::settings)
+    }
+
+    /// How many frames each step of the sequencer lasts at the current tempo.
+    fn step_len(&self) -> f64 {
+        sequencer::step_len(self.sample_rate, self.tempo())
+    }
+
+    fn play_sequenced(&mut self, notes: Notes) {
+        // a note which is played again is released first, so that its release doesn't end it
+        if let Some(note) = notes.release {
+            let _ = self.end_playing_note(note);
+        }
+        if let Some((note, velocity)) = notes.play {
+            let _ = self.begin_note_at(note, velocity as f32 / 127.0);
+        }
+    }
+
+    /// Set the tempo, in beats per minute, which tempo-synced effect timings follow. It is also
+    /// picked up from MIDI clock, if any arrives.
+    pub fn set_tempo(&mut self, bpm: f32) {
+        let old = self.get_param(ParamId::Tempo);
+        let bpm = bpm.clamp(MIN_TEMPO, MAX_TEMPO);
+        self.effects.set_tempo(bpm);
+        for send in &mut self.sends {
+            send.effects_mut().set_tempo(bpm);
+        }
+        for voice in &mut self.voices {
+            voice.effects.set_tempo(bpm);
         }
+        self.changed(ParamId::Tempo, old);
     }
 
-    /// Start playing the specified MIDI note number, if a voice is available.
+    /// The current tempo, in beats per minute.
+    pub fn tempo(&self) -> f32 {
+        self.effects.tempo()
+    }
+
+    /// Bend the pitch of all notes (including those played later) by a number of semitones.
+    pub fn set_pitch_bend(&mut self, semitones: f32) {
+        let old = self.get_param(ParamId::PitchBend);
+        self.pitch_bend = semitones;
+        for voice in &mut self.voices {
+            voice.tune(semitones);
+        }
+        self.changed(ParamId::PitchBend, old);
+    }
+
+    /// Set the cutoff frequency of every voice's filter, in Hz, from 20 Hz to 20 kHz or just
+    /// under the Nyquist frequency, whichever is lower. Changes are smoothed (see
+    /// `set_smoothing_time`).
+    pub fn set_cutoff(&mut self, cutoff: f32) {
+        if cutoff.is_nan() {
+            return;
+        }
+        let old = self.get_param(ParamId::Cutoff);
+        self.cutoff = self.limit_cutoff(cutoff);
+        self.changed(ParamId::Cutoff, old);
+    }
+
+    /// A cutoff kept within the range the filters can take.
+    fn limit_cutoff(&self, cutoff: f32) -> f32 {
+        // stay clear of Nyquist, where the filters' response folds back
+        ParamId::Cutoff
+            .clamp(cutoff)
+            .min(self.sample_rate as f32 * 0.49)
+    }
+
+    pub fn cutoff(&self) -> f32 {
+        self.cutoff
+    }
+
+    /// Set any parameter, as listed by `params`. Values out of the range given by `param_info` are
+    /// clamped into it. Returns `SynthError::InvalidParameter`, changing nothing, for parameters
+    /// which don't exist (such as a slot with no effect in it) and values which aren't numbers.
+    pub fn set_param(&mut self, param: ParamId, value: f32) -> Result<(), SynthError> {
+        let invalid = SynthError::InvalidParameter { param, value };
+        let info = match self.param_info(param) {
+            Some(info) if !value.is_nan() => info,
+            _ => return Err(invalid),
+        };
+        let value = value.clamp(info.min, info.max);
+        let mut envelope = self.amp_envelope;
+        match param {
+            ParamId::Waveform => self.set_waveform(Waveform::ALL[value.round() as usize]),
+            ParamId::Detune => self.set_detune(value),
+            ParamId::Cutoff => self.set_cutoff(value),
+            ParamId::Attack => {
+                envelope.attack_time = value;
+                self.set_amp_envelope(envelope);
+            }
+            ParamId::Decay => {
+                envelope.decay_time = value;
+                self.set_amp_envelope(envelope);
+            }
+            ParamId::Sustain => {
+                envelope.sustain_amount = value;
+                self.set_amp_envelope(envelope);
+            }
+            ParamId::Release => {
+                envelope.release_time = value;
+                self.set_amp_envelope(envelope);
+            }
+            ParamId::StereoSpread => self.set_stereo_spread(value),
+            ParamId::Volume => self.set_volume(value),
+            ParamId::PitchBend => self.set_pitch_bend(value),
+            ParamId::Tempo => self.set_tempo(value),
+            ParamId::Morph => self.set_morph(value),
+            ParamId::SendLevel(send) if send < SENDS => self.set_send_level(send, value),
+            ParamId::ReturnLevel(send) if send < SENDS => {
+                let old = self.get_param(param);
+                self.sends[send].set_return_level(value);
+                self.changed(param, old);
+            }
+            ParamId::SendLevel(_) | ParamId::ReturnLevel(_) => (),
+            ParamId::Effect {
+                placement: Placement::Voice,
+                slot,
+                parameter,
+            } => self.set_voice_effect_parameter(slot, parameter, value),
+            ParamId::Effect {
+                placement,
+                slot,
+                parameter,
+            } => {
+                let chain = match placement {
+                    Placement::Send(send) => self.sends.get_mut(send).map(|s| s.effects_mut()),
+                    _ => Some(&mut self.effects),
+                };
+                if let Some(effect) = chain.and_then(|c| c.get_mut(slot)) {
+                    let old = effect.parameter(parameter);
+                    effect.set_parameter(parameter, value);
+                    self.changed(param, old);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The current value of a parameter, if it exists.
+    pub fn get_param(&self, param: ParamId) -> Option<f32> {
+        let envelope = self.amp_envelope;
+        Some(match param {
+            ParamId::Waveform => Waveform::ALL.iter().position(|w| *w == self.waveform)? as f32,
+            ParamId::Detune => self.detune,
+            ParamId::Cutoff => self.cutoff,
+            ParamId::Attack => envelope.attack_time,
+            ParamId::Decay => envelope.decay_time,
+            ParamId::Sustain => envelope.sustain_amount,
+            ParamId::Release => envelope.release_time,
+            ParamId::StereoSpread => self.stereo_spread,
+            ParamId::Volume => self.volume,
+            ParamId::PitchBend => self.pitch_bend,
+            ParamId::Tempo => self.tempo(),
+            ParamId::Morph => self.morph_amount,
+            ParamId::SendLevel(send) if send < SENDS => self.voice_send_level(0, send),
+            ParamId::ReturnLevel(send) if send < SENDS => self.sends[send].return_level(),
+            ParamId::SendLevel(_) | ParamId::ReturnLevel(_) => return None,
+            ParamId::Effect {
+                placement,
+                slot,
+                parameter,
+            } => self
+                .effects_at(placement)?
+                .get(slot)?
+                .parameter(parameter)?,
+        })
+    }
+
+    /// A handle for setting parameters from other threads, e.g. a user interface's, while this
+    /// synth runs on the audio thread, without either waiting for the other. All the controllers
+    /// of a synth share the same state, so this is cheap to call again.
+    pub fn controller(&mut self) -> SynthController {
+        let controls = self
+            .controls
+            .get_or_insert_with(|| Arc::new(Controls::new()));
+        SynthController::new(controls.clone())
+    }
+
+    /// Be told whenever a parameter changes, however it was changed: through `set_param` or the
+    /// other setters, by MIDI, or by loading a patch. The observer is called on whichever thread
+    /// makes the change (usually the audio thread), so it should be quick, e.g. passing the change
+    /// on through a ring buffer. Changes made directly to an effect, through `effects_mut` or
+    /// `send_mut`, aren't seen.
+    pub fn set_param_observer(&mut self, observer: Option<ParamObserver>) {
+        self.param_observer = observer;
+    }
+
+    /// Start copying the audio at `point` into a ring buffer of `capacity` frames (at least one),
+    /// keeping one frame of every `decimation`, for reading on another thread, e.g. to draw an
+    /// oscilloscope. Voices are tapped at the internal sample rate, so the oversampling is
+    /// decimated away as well, for copies at the output sample rate over `decimation`.
     ///
-    /// Returns `Ok` if a voice was available to play the note, and `Err` if all voices are
-    /// already playing.
-    pub fn try_begin_note(&mut self, note: u8, velocity: u8) -> Result<(), ()> {
+    /// Frames which don't fit are dropped, so the reader should empty the buffer regularly. Drop
+    /// the consumer to stop tapping; taps which nobody reads any more are freed by `add_tap` and
+    /// `clear_taps`, rather than while rendering. Returns `SynthError::NoSuchVoice` for a voice
+    /// beyond those the synth has.
+    pub fn add_tap(
+        &mut self,
+        point: TapPoint,
+        decimation: usize,
+        capacity: usize,
+    ) -> Result<Consumer<Frame>, SynthError> {
+        if let TapPoint::Voice(index) = point {
+            if index >= self.voices.len() {
+                return Err(SynthError::NoSuchVoice(index));
+            }
+        }
+        self.taps.retain(|tap| !tap.is_abandoned());
+        let (tap, consumer) = Tap::new(point, decimation, capacity);
+        self.taps.push(tap);
+        Ok(consumer)
+    }
+
+    /// Stop every tap, so that their consumers see nothing more.
+    pub fn clear_taps(&mut self) {
+        self.taps.clear();
+    }
+
+    /// Tell the observer about a parameter, if it has changed from `old`.
+    fn changed(&mut self, param: ParamId, old: Option<f32>) {
+        if self.param_observer.is_none() {
+            return;
+        }
+        let new = self.get_param(param);
+        if let (Some(new), Some(observer)) = (new, &mut self.param_observer) {
+            if Some(new) != old {
+                observer(param, new);
+            }
+        }
+    }
+
+    /// What a parameter's values mean, if it exists.
+    pub fn param_info(&self, param: ParamId) -> Option<ParamInfo> {
+        match param {
+            ParamId::Effect {
+                placement,
+                slot,
+                parameter,
+            } => self
+                .effects_at(placement)?
+                .get(slot)?
+                .parameter_info(parameter),
+            _ => param.info(),
+        }
+    }
+
+    /// Every parameter there is, including those of the effects currently loaded.
+    pub fn params(&self) -> Vec<ParamId> {
+        ParamId::fixed().chain(self.effect_params()).collect()
+    }
+
+    /// The parameters of the effects currently loaded.
+    fn effect_params(&self) -> impl Iterator<Item = ParamId> + '_ {
+        let placements = [Placement::Voice, Placement::Master]
+            .iter()
+            .copied()
+            .chain((0..SENDS).map(Placement::Send));
+        placements.flat_map(move |placement| {
+            let chain = self.effects_at(placement);
+            let slots = (0..chain.map_or(0, EffectsChain::len)).map(move |slot| {
+                let count = chain
+                    .and_then(|c| c.get(slot))
+                    .map_or(0, |e| e.parameter_names().len());
+                (slot, count)
+            });
+            slots.flat_map(move |(slot, count)| {
+                (0..count).map(move |parameter| ParamId::Effect {
+                    placement,
+                    slot,
+                    parameter,
+                })
+            })
+        })
+    }
+
+    /// The effects chain at a placement, taking the first voice's for voice effects.
+    pub(crate) fn effects_at(&self, placement: Placement) -> Option<&EffectsChain> {
+        match placement {
+            Placement::Voice => self.voices.first().map(|v| &v.effects),
+            Placement::Master => Some(&self.effects),
+            Placement::Send(send) => self.sends.get(send).map(|s| s.effects()),
+        }
+    }
+
+    /// Release every note that is currently playing, latched or not.
+    pub fn release_all(&mut self) {
+        self.latch.clear();
+        for voice in &mut self.voices {
+            voice.check_note_done();
+            if voice.on {
+                voice.end_note();
+            }
+        }
+    }
+
+    /// Release every note, and fade the output to silence over `time`, e.g. before shutting down
+    /// so that audio doesn't stop with a click. The synth stays silent afterwards.
+    pub fn fade_out(&mut self, time: Duration) {
+        self.release_all();
+        let frames = time.as_secs_f32() * self.sample_rate as f32;
+        self.fade_step = if frames < 1.0 { 1.0 } else { 1.0 / frames };
+    }
+
+    /// Whether a fade out has finished.
+    pub fn is_faded_out(&self) -> bool {
+        self.fade_gain == 0.0
+    }
+
+    /// Stop every voice immediately, without waiting for envelopes to finish.
+    pub fn silence_all(&mut self) {
+        self.latch.clear();
+        for voice in &mut self.voices {
+            voice.on = false;
+            voice.amp_eg.reset();
+        }
+    }
+
+    /// Go back to sounding as the synth did when it was made, keeping its settings, e.g. for a
+    /// host starting playback over. Every voice stops, whatever is left ringing in the filters,
+    /// envelopes and effects is forgotten, and the oscillators start from new phases (the same as
+    /// at first, if a seed was set with `set_seed`). A fade out is undone, and scheduled commands
+    /// are dropped. Nothing is allocated, so this is fine to call on the audio thread.
+    pub fn reset(&mut self) {
+        for voice in &mut self.voices {
+            voice.on = false;
+            voice.amp_eg.reset();
+            voice.reset();
+        }
+        self.reset_phases();
+        for decimator in self.decimators.iter_mut().chain(&mut self.voice_decimators) {
+            decimator.reset();
+        }
+        for blocker in &mut self.dc_blockers {
+            blocker.reset();
+        }
+        self.effects.reset();
+        for send in &mut self.sends {
+            send.reset();
+        }
+        self.limiter.reset();
+        if let Some(gate) = &mut self.input_gate {
+            gate.reset();
+        }
+        self.vocoder.reset();
+        self.last_input = 0.0;
+        self.smoothed_volume = self.volume;
+        self.fade_gain = 1.0;
+        self.fade_step = 0.0;
+        self.levels = Levels::default();
+        self.scheduled.clear();
+        self.latch.clear();
+        // what's left of the current block was rendered before the reset
+        self.block_position = self.block_len;
+    }
+
+    /// Choose what happens to notes when every voice is already playing.
+    pub fn set_voice_stealing(&mut self, stealing: VoiceStealing) {
+        self.voice_stealing = stealing;
+    }
+
+    pub fn voice_stealing(&self) -> VoiceStealing {
+        self.voice_stealing
+    }
+
+    /// Latch notes, so that they keep sounding after they're ended, until the next chord starts:
+    /// the next note played while no others are held. Turning it off releases the latched notes.
+    /// Notes the sequencer and the generator play are never latched.
+    pub fn set_latch(&mut self, enabled: bool) {
+        for note in self.latch.set_enabled(enabled).iter() {
+            let _ = self.end_playing_note(note);
+        }
+    }
+
+    pub fn latch(&self) -> bool {
+        self.latch.is_enabled()
+    }
+
+    /// Start playing the specified MIDI note number, if a voice is available (or can be stolen,
+    /// see `set_voice_stealing`). If it starts a new chord while latching (see `set_latch`), the
+    /// latched notes are released first.
+    ///
+    /// Returns `SynthError::OutOfVoices` if all voices are already playing.
+    pub fn try_begin_note(&mut self, note: u8, velocity: u8) -> Result<(), SynthError> {
+        self.press(note);
+        self.begin_note_at(note, velocity as f32 / 127.0)
+    }
+
+    /// Start playing a note as `try_begin_note` does, with a 16-bit velocity, as MIDI 2.0 sends.
+    pub fn try_begin_note_high_res(&mut self, note: u8, velocity: u16) -> Result<(), SynthError> {
+        self.press(note);
+        self.begin_note_at(note, velocity as f32 / u16::MAX as f32)
+    }
+
+    /// Release whatever chord a note replaces, while latching.
+    fn press(&mut self, note: u8) {
+        for old in self.latch.press(note).iter() {
+            let _ = self.end_playing_note(old);
+        }
+    }
+
+    /// Start playing a note with a velocity from 0 to 1.
+    fn begin_note_at(&mut self, note: u8, velocity: f32) -> Result<(), SynthError> {
+        let pitch_bend = self.pitch_bend;
+        let clock = self.clock;
         if let Some(v) = self.get_playing_voice(note) {
-            v.begin_note(note, velocity);
+            v.begin_note(note, velocity, pitch_bend, clock);
             Ok(())
         } else if let Some(v) = self.get_new_voice() {
-            v.begin_note(note, velocity);
+            v.begin_note(note, velocity, pitch_bend, clock);
+            Ok(())
+        } else if let Some(v) = self.steal_voice() {
+            log::debug!("Note {} took over the voice playing note {}", note, v.note);
+            v.begin_note(note, velocity, pitch_bend, clock);
             Ok(())
         } else {
-            Err(())
+            log::debug!("No voice free for note {}", note);
+            Err(SynthError::OutOfVoices)
         }
     }
 
-    /// Stop playing the specified MIDI note number, if it is being played.
+    /// Stop playing the specified MIDI note number, if it is being played, unless it's latched
+    /// (see `set_latch`).
     ///
-    /// Returns `Ok` if the note was successfully ended, and `Err` if no voice was found playing
-    /// that note.
-    pub fn try_end_note(&mut self, note: u8) -> Result<(), ()> {
+    /// Returns `SynthError::NoSuchNote` if no voice was found playing that note.
+    pub fn try_end_note(&mut self, note: u8) -> Result<(), SynthError> {
+        if self.latch.release(note) {
+            self.end_playing_note(note)
+        } else {
+            self.get_playing_voice(note)
+                .map(|_| ())
+                .ok_or(SynthError::NoSuchNote)
+        }
+    }
+
+    /// Stop playing a note, latched or not.
+    fn end_playing_note(&mut self, note: u8) -> Result<(), SynthError> {
         if let Some(v) = self.get_playing_voice(note) {
             v.end_note();
             Ok(())
         } else {
-            Err(())
+            Err(SynthError::NoSuchNote)
         }
     }
 
+    /// Change one note alone (see `NoteExpression`), e.g. for MPE or a plugin host's per-note
+    /// expressions. Values out of range are clamped.
+    ///
+    /// Returns `SynthError::NoSuchNote` if no voice was found playing that note.
+    pub fn set_note_expression(
+        &mut self,
+        note: u8,
+        expression: NoteExpression,
+    ) -> Result<(), SynthError> {
+        let pitch_bend = self.pitch_bend;
+        let voice = self.get_playing_voice(note).ok_or(SynthError::NoSuchNote)?;
+        match expression {
+            NoteExpression::Volume(gain) => voice.gain = gain.max(0.0),
+            NoteExpression::Pan(pan) => voice.expression_pan = Some(pan.clamp(-1.0, 1.0)),
+            NoteExpression::Tuning(semitones) => {
+                voice.tuning = semitones;
+                voice.tune(pitch_bend);
+                return Ok(());
+            }
+        }
+        voice.update_pan_gains();
+        Ok(())
+    }
+
     fn get_new_voice(&mut self) -> Option<&mut Voice> {
-        for voice in &mut self.voices {
+        for (index, voice) in self.voices.iter_mut().enumerate() {
             voice.check_note_done();
             if !voice.on {
+                log::trace!("Voice {} is free", index);
                 return Some(voice);
             }
         }
@@ -71,6 +2122,16 @@ impl Synth {
         None
     }
 
+    fn steal_voice(&mut self) -> Option<&mut Voice> {
+        match self.voice_stealing {
+            VoiceStealing::Off => None,
+            VoiceStealing::Oldest => self
+                .voices
+                .iter_mut()
+                .min_by_key(|voice| (voice.is_held(), voice.started_at)),
+        }
+    }
+
     fn get_playing_voice(&mut self, note: u8) -> Option<&mut Voice> {
         for voice in &mut self.voices {
             voice.check_note_done();
@@ -85,24 +2146,350 @@ impl Synth {
 
 /// Audio generation is implemented as an Iterator of `f32`.
 ///
-/// Call the `next` method to generate the next sample. Note that the output is at the sample rate
-/// specified as a public mutable `static` rather than at the oversampled rate.
+/// Call the `next` method to generate the next sample. Samples are interleaved stereo (see
+/// `CHANNELS`), at the sample rate given to `Synth::new` rather than at the oversampled rate. They
+/// are still rendered a block at a time behind the scenes.
 impl Iterator for Synth {
     type Item = f32;
 
     fn next(&mut self) -> Option<Self::Item> {
-        Some(
-            (0..unsafe { OVERSAMPLE_RATIO })
-                .map(|_| {
-                    self.voices
-                        .iter_mut()
-                        .flat_map(|v| v.next())
-                        .map(|v| (v * 0.75).min(1.0))
-                        .sum::<f32>()
-                })
-                .nth(0)
-                .unwrap(),
-        )
+        if self.block_position == self.block_len {
+            self.render(self.block_size());
+        }
+
+        self.block_position += 1;
+        Some(self.block[self.block_position - 1])
+    }
+}
+
+/// A chain's effects, as saved in a patch at `placement`.
+fn chain_patches(chain: &EffectsChain, placement: Placement) -> Vec<EffectPatch> {
+    (0..chain.len())
+        .filter_map(|index| {
+            let effect = chain.get(index)?;
+            let parameters = effect.parameter_names().iter().enumerate();
+            Some(EffectPatch {
+                name: effect.name().to_string(),
+                placement,
+                bypassed: chain.is_bypassed(index),
+                parameters: parameters
+                    .filter_map(|(i, name)| Some((name.to_string(), effect.parameter(i)?)))
+                    .collect(),
+            })
+        })
+        .collect()
+}
+
+/// A patch's effects at one placement, leaving out any with unknown names.
+fn placed(patch: &Patch, placement: Placement) -> impl Iterator<Item = &EffectPatch> + Clone {
+    patch
+        .effects
+        .iter()
+        .filter(move |e| e.placement == placement && effects::NAMES.contains(&&*e.name))
+}
+
+/// Whether a chain holds exactly the given effects, in order.
+fn holds<'a>(chain: &EffectsChain, patches: impl Iterator<Item = &'a EffectPatch>) -> bool {
+    let mut names = patches.map(|p| p.name.as_str());
+    (0..chain.len()).all(|i| chain.get(i).map(|e| e.name()) == names.next())
+        && names.next().is_none()
+}
+
+/// Set up a chain with the given effects, keeping the ones already there if they match.
+fn load_chain<'a>(
+    chain: &mut EffectsChain,
+    patches: impl Iterator<Item = &'a EffectPatch> + Clone,
+) {
+    if !holds(chain, patches.clone()) {
+        chain.clear();
+        for patch in patches.clone() {
+            match effects::by_name(&patch.name) {
+                Some(effect) => chain.push(effect),
+                None => log::warn!("Skipping unknown effect {}", patch.name),
+            }
+        }
+    }
+
+    for (index, patch) in patches.enumerate() {
+        let effect = match chain.get_mut(index) {
+            Some(effect) => effect,
+            None => break,
+        };
+        // anything the patch leaves out goes back to its default
+        for (parameter, name) in effect.parameter_names().iter().enumerate() {
+            let value = patch
+                .parameters
+                .iter()
+                .find(|(n, _)| n == name)
+                .map(|(_, v)| *v)
+                .or_else(|| Some(effect.parameter_info(parameter)?.default));
+            if let Some(value) = value {
+                effect.set_parameter(parameter, value);
+            }
+        }
+        chain.set_bypassed(index, patch.bypassed);
+    }
+}
+
+/// Low-pass filters the oversampled signal, so it can be brought back down to the output sample
+/// rate without aliasing.
+#[derive(Debug)]
+struct Decimator {
+    taps: Vec<f32>,
+    history: Vec<f32>,
+    position: usize,
+}
+
+impl Decimator {
+    /// Number of filter taps per unit of oversampling ratio. More gives a steeper transition band.
+    const TAPS_PER_RATIO: usize = 16;
+
+    /// Cutoff, relative to the output sample rate. Leaves room for the transition band below the
+    /// output Nyquist frequency.
+    const CUTOFF: f32 = 0.45;
+
+    fn new(oversampling: Oversampling) -> Self {
+        let ratio = oversampling.ratio();
+        let taps = if ratio == 1 {
+            vec![1.0]
+        } else {
+            Self::design(
+                Self::TAPS_PER_RATIO * ratio as usize + 1,
+                Self::CUTOFF / ratio as f32,
+            )
+        };
+
+        Self {
+            history: vec![0.0; taps.len()],
+            taps,
+            position: 0,
+        }
+    }
+
+    /// Windowed-sinc low-pass filter, with `cutoff` in cycles per sample.
+    fn design(len: usize, cutoff: f32) -> Vec<f32> {
+        let middle = (len - 1) as f32 / 2.0;
+        let taps: Vec<f32> = (0..len)
+            .map(|i| {
+                let x = i as f32 - middle;
+                let sinc = if x == 0.0 {
+                    2.0 * cutoff
+                } else {
+                    Sample::sin(TAU * cutoff * x) / (PI * x)
+                };
+                // Blackman window
+                let phase = TAU * i as f32 / (len - 1) as f32;
+                let window = 0.42 - 0.5 * phase.cos() + 0.08 * (2.0 * phase).cos();
+                sinc * window
+            })
+            .collect();
+
+        // unity gain at DC
+        let sum: f32 = taps.iter().sum();
+        taps.into_iter().map(|t| t / sum).collect()
+    }
+
+    fn reset(&mut self) {
+        self.history.fill(0.0);
+        self.position = 0;
+    }
+
+    fn push(&mut self, sample: f32) {
+        self.history[self.position] = sample;
+        self.position = (self.position + 1) % self.history.len();
+    }
+
+    /// The filtered value at the most recently pushed sample. Only needs to be calculated once per
+    /// output sample, which is where the savings of decimating come from.
+    fn output(&self) -> f32 {
+        let (newer, older) = self.history.split_at(self.position);
+        older
+            .iter()
+            .chain(newer)
+            .zip(&self.taps)
+            .map(|(s, t)| s * t)
+            .sum()
+    }
+}
+
+/// A control value which follows its setting gradually rather than jumping, one step at a time.
+#[derive(Debug, Clone, Copy)]
+struct Smoothed {
+    value: f32,
+}
+
+impl Smoothed {
+    fn new(value: f32) -> Self {
+        Self { value }
+    }
+
+    /// Move `amount` of the way to `target` (from 0, not at all, to 1, all the way), returning the
+    /// new value if it moved. Targets which aren't finite are ignored, as there'd be no coming
+    /// back from them.
+    fn follow(&mut self, target: f32, amount: f32) -> Option<f32> {
+        if self.value == target || !target.is_finite() {
+            return None;
+        }
+        let value = self.value + (target - self.value) * amount;
+        // settle, rather than creeping closer forever
+        self.value = if (target - value).abs() < 1e-4 * target.abs().max(1.0) {
+            target
+        } else {
+            value
+        };
+        Some(self.value)
+    }
+}
+
+/// How far a one-pole smoother with time constant `time` (in seconds) moves in `frames`.
+fn smoothing_amount(frames: usize, time: f32, sample_rate: u32) -> f32 {
+    if time > 0.0 {
+        1.0 - (-(frames as f32) / (time * sample_rate as f32)).exp()
+    } else {
+        1.0
+    }
+}
+
+/// A one-pole high-pass filter with a very low cutoff, which removes any constant offset from the
+/// signal (e.g. from asymmetric waveforms) so it doesn't waste headroom or push speaker cones.
+#[derive(Debug)]
+struct DcBlocker {
+    coefficient: f32,
+    last_input: f32,
+    last_output: f32,
+}
+
+impl DcBlocker {
+    fn new(sample_rate: f32) -> Self {
+        Self {
+            coefficient: (-TAU * DC_BLOCKER_CUTOFF / sample_rate).exp(),
+            last_input: 0.0,
+            last_output: 0.0,
+        }
+    }
+
+    fn process(&mut self, input: f32) -> f32 {
+        let output = input - self.last_input + self.coefficient * self.last_output;
+        self.last_input = input;
+        self.last_output = denormal::flush(output);
+        output
+    }
+
+    fn reset(&mut self) {
+        self.last_input = 0.0;
+        self.last_output = 0.0;
+    }
+}
+
+/// Turns the output down ahead of peaks, so that stacks of voices and resonant filters never clip.
+///
+/// The signal is delayed slightly, and the gain needed over that window is approached gradually,
+/// so it's already low enough by the time a peak comes out. Both channels share the same gain to
+/// keep the stereo image steady.
+#[derive(Debug)]
+struct Limiter {
+    attack: f32,
+    release: f32,
+    gain: f32,
+    delay: Vec<Frame>,
+    /// The gain each frame in `delay` needs to stay under the threshold.
+    needed: Vec<f32>,
+    position: usize,
+}
+
+impl Limiter {
+    const LOOKAHEAD: f32 = 0.0015;
+    const RELEASE_TIME: f32 = 0.1;
+
+    fn new(sample_rate: f32) -> Self {
+        let lookahead = ((Self::LOOKAHEAD * sample_rate) as usize).max(1);
+        Self {
+            // mostly settled by the time the delay is up
+            attack: 1.0 - (-8.0 / lookahead as f32).exp(),
+            release: 1.0 - (-1.0 / (Self::RELEASE_TIME * sample_rate)).exp(),
+            gain: 1.0,
+            delay: vec![Frame::ZERO; lookahead],
+            needed: vec![1.0; lookahead],
+            position: 0,
+        }
+    }
+
+    fn reset(&mut self) {
+        self.gain = 1.0;
+        self.delay.fill(Frame::ZERO);
+        self.needed.fill(1.0);
+        self.position = 0;
+    }
+
+    fn process(&mut self, frame: Frame) -> Frame {
+        self.needed[self.position] = (LIMITER_THRESHOLD / frame.peak()).min(1.0);
+        let delayed = mem::replace(&mut self.delay[self.position], frame);
+        self.position = (self.position + 1) % self.delay.len();
+
+        let target = self.needed.iter().copied().fold(1.0, f32::min);
+        let rate = if target < self.gain {
+            self.attack
+        } else {
+            self.release
+        };
+        self.gain += (target - self.gain) * rate;
+
+        // anything the envelope didn't quite catch is clipped, as a last resort
+        delayed.map(|s| (s * self.gain).clamp(-LIMITER_THRESHOLD, LIMITER_THRESHOLD))
+    }
+}
+
+/// Works out the tempo from the timing of MIDI clock messages, which come 24 to a beat.
+///
+/// Messages are only timed to the block they arrive in, so the tempo is measured over a whole beat
+/// and smoothed, rather than jittering with every block.
+#[derive(Debug, Default)]
+struct ClockFollower {
+    ticks: u32,
+    beat_started_at: Option<u64>,
+    last_tick: u64,
+    tempo: Option<f32>,
+}
+
+impl ClockFollower {
+    const TICKS_PER_BEAT: u32 = 24;
+    /// A gap longer than this, in seconds, means the clock stopped and started again.
+    const TIMEOUT: f32 = 1.0;
+
+    /// Count a tick which arrived at `now` (in frames), giving a new tempo once a beat is up.
+    fn tick(&mut self, now: u64, sample_rate: u32) -> Option<f32> {
+        let timeout = (Self::TIMEOUT * sample_rate as f32) as u64;
+        let stalled = now - self.last_tick > timeout;
+        self.last_tick = now;
+        let started_at = match self.beat_started_at {
+            Some(started_at) if !stalled => started_at,
+            _ => {
+                self.beat_started_at = Some(now);
+                self.ticks = 0;
+                return None;
+            }
+        };
+
+        self.ticks += 1;
+        if self.ticks < Self::TICKS_PER_BEAT || now == started_at {
+            return None;
+        }
+        self.ticks = 0;
+        self.beat_started_at = Some(now);
+
+        let measured = 60.0 * sample_rate as f32 / (now - started_at) as f32;
+        let tempo = match self.tempo {
+            // follow real changes straight away, but average out the jitter
+            Some(tempo) if (measured - tempo).abs() < tempo * 0.1 => {
+                tempo + (measured - tempo) * 0.5
+            }
+            _ => measured,
+        };
+        let changed = self.tempo.is_none_or(|old| (tempo - old).abs() > 0.5);
+        if changed {
+            self.tempo = Some(tempo);
+        }
+        changed.then_some(tempo)
     }
 }
 
@@ -110,248 +2497,604 @@ impl Iterator for Synth {
 struct Voice {
     on: bool,
     note: u8,
-    detune: u8,
-    oscillators: [Oscillator; 3],
+    started_at: u64,
+    /// Where the stereo spread puts the voice, from -1 to 1.
+    pan: f32,
+    /// The gain of each channel, from the pan and the note's volume.
+    pan_gains: Frame,
+    /// The note's own volume, pan and tuning (see `NoteExpression`).
+    gain: f32,
+    expression_pan: Option<f32>,
+    tuning: f32,
+    /// In cents.
+    detune: f32,
+    oscillators: Vec<Oscillator>,
     filter: Filter<2>,
     amp_eg: Adsr,
+    dc_blocker: Option<DcBlocker>,
+    /// How much of the voice goes to each send bus.
+    sends: [f32; SENDS],
+    effects: EffectsChain,
 }
 
 impl Voice {
-    fn new(amp_env_config: Rc<AdsrConfig>) -> Self {
+    fn new(rate: u32, oscillators: usize) -> Self {
         Self {
             on: false,
             note: 0,
-            detune: 5,
-            oscillators: Default::default(),
-            filter: Default::default(),
-            amp_eg: Adsr::new(amp_env_config),
+            started_at: 0,
+            pan: 0.0,
+            pan_gains: Frame::mono(1.0),
+            gain: 1.0,
+            expression_pan: None,
+            tuning: 0.0,
+            detune: DEFAULT_DETUNE,
+            oscillators: (0..oscillators).map(|_| Oscillator::new(rate)).collect(),
+            filter: Filter::new(rate),
+            amp_eg: Adsr::new(rate),
+            dc_blocker: None,
+            sends: [0.0; SENDS],
+            effects: EffectsChain::new(rate),
         }
     }
 
-    fn begin_note(&mut self, new_note: u8, new_vel: u8) {
+    fn begin_note(&mut self, new_note: u8, new_vel: f32, pitch_bend: f32, clock: u64) {
+        if !self.amp_eg.is_active() {
+            // whatever was left in the filter and effects when the last note finished shouldn't
+            // color the start of this one; a stolen voice carries on, so as not to click
+            self.reset();
+        }
         self.on = true;
         self.note = new_note;
-        let detune_amount = self.detune as f32 / 100.0;
+        self.started_at = clock;
+        self.gain = 1.0;
+        self.expression_pan = None;
+        self.tuning = 0.0;
+        self.update_pan_gains();
+        self.tune(pitch_bend);
+        self.amp_eg.note_on(new_vel);
+    }
+
+    /// Forget the signal so far, in the filter, DC blocker and effects.
+    fn reset(&mut self) {
+        self.filter.reset();
+        if let Some(blocker) = &mut self.dc_blocker {
+            blocker.reset();
+        }
+        self.effects.reset();
+    }
+
+    /// Position the voice between -1 (hard left) and 1 (hard right).
+    fn set_pan(&mut self, pan: f32) {
+        self.pan = pan;
+        self.update_pan_gains();
+    }
+
+    fn update_pan_gains(&mut self) {
+        let pan = self.expression_pan.unwrap_or(self.pan);
+        // constant power, normalized so the center is at unity gain
+        let angle = map_range(pan, (-1.0, 1.0), (0.0, PI / 2.0));
+        self.pan_gains = Frame::new(angle.cos(), Sample::sin(angle)) * (2_f32.sqrt() * self.gain);
+    }
+
+    fn tune(&mut self, pitch_bend: f32) {
+        let detune_amount = self.detune / 100.0;
         let num_oscs = self.oscillators.len() as f32;
         for (index, osc) in self.oscillators.iter_mut().enumerate() {
             let note_plus_detune = self.note as f32
+                + pitch_bend
+                + self.tuning
                 + map_range(
                     index as f32,
                     (0.0, num_oscs),
                     (-detune_amount, detune_amount),
                 );
-            osc.current_freq = (2_f32).powf((note_plus_detune - 69.0) / 12.0) * 440.0;
+            osc.set_frequency(((2_f32).powf((note_plus_detune - 69.0) / 12.0) * 440.0) as f64);
         }
-        self.amp_eg.segment = AdsrSegment::Attack(0.0, self.amp_eg.next().unwrap());
-        self.amp_eg.velocity_ratio = new_vel as f32 / 127.0;
     }
 
     fn end_note(&mut self) {
-        let release_point = if let AdsrSegment::Sustain = self.amp_eg.segment {
-            self.amp_eg.config.sustain_amount
-        } else {
-            self.amp_eg.next().unwrap()
-        };
-        self.amp_eg.segment = AdsrSegment::Release(0.0, release_point);
+        self.amp_eg.note_off();
+    }
+
+    /// Whether the note is still being held, i.e. has not been released.
+    fn is_held(&self) -> bool {
+        self.on && self.amp_eg.is_held()
     }
 
     fn check_note_done(&mut self) {
-        if let AdsrSegment::Off = self.amp_eg.segment {
+        if !self.amp_eg.is_active() {
             self.on = false;
         }
     }
-}
 
-impl Iterator for Voice {
-    type Item = f32;
+    /// Run an external signal through the filter and envelope, in place of the oscillators.
+    fn process_input(&mut self, input: f32) -> f32 {
+        let filtered = self.filter.process(input);
+        let amp_volume = self.amp_eg.tick();
+        self.block_dc(filtered * amp_volume)
+    }
 
-    fn next(&mut self) -> Option<Self::Item> {
-        let osc_mix: f32 = self
-            .oscillators
-            .iter_mut()
-            .flat_map(|o| o.next())
-            .sum::<f32>()
-            / (self.oscillators.len() as f32);
-        let filtered = self.filter.process(osc_mix);
-        let amp_volume = self.amp_eg.next().unwrap();
-        Some(filtered * amp_volume)
+    /// Render the next `out.len()` samples.
+    fn fill(&mut self, out: &mut [f32]) {
+        for sample in out {
+            let osc_mix: f32 = self
+                .oscillators
+                .iter_mut()
+                .map(Oscillator::tick)
+                .sum::<f32>()
+                / (self.oscillators.len() as f32);
+            let filtered = self.filter.process(osc_mix);
+            let amp_volume = self.amp_eg.tick();
+            *sample = self.block_dc(filtered * amp_volume);
+        }
+    }
+
+    /// Render as `fill` does, but a stage at a time over the whole of `out`, timing each.
+    #[cfg(feature = "profile")]
+    fn fill_profiled(&mut self, out: &mut [f32], profile: &mut Profile) {
+        let started = Instant::now();
+        out.fill(0.0);
+        for osc in &mut self.oscillators {
+            for sample in out.iter_mut() {
+                *sample += osc.tick();
+            }
+        }
+        let num_oscs = self.oscillators.len() as f32;
+        for sample in out.iter_mut() {
+            *sample /= num_oscs;
+        }
+
+        let filtering = Instant::now();
+        profile.oscillators += filtering - started;
+        for sample in out.iter_mut() {
+            *sample = self.filter.process(*sample);
+        }
+
+        let enveloping = Instant::now();
+        profile.filters += enveloping - filtering;
+        for sample in out {
+            let amp_volume = self.amp_eg.tick();
+            *sample = self.block_dc(*sample * amp_volume);
+        }
+        profile.envelopes += enveloping.elapsed();
+    }
+
+    fn block_dc(&mut self, sample: f32) -> f32 {
+        match &mut self.dc_blocker {
+            Some(blocker) => blocker.process(sample),
+            None => sample,
+        }
     }
 }
 
+/// An oscillator playing one of the `Waveform`s, as each voice has three of. It isn't band-limited,
+/// so the pulse and saw waves alias at high pitches unless run at a higher rate and decimated, as
+/// the voices are.
+///
+/// It starts at a random phase, so that several playing together don't all start in step.
 #[derive(Debug)]
-struct Oscillator {
-    current_phase: f32,
-    current_freq: f32,
+pub struct Oscillator<T: Sample = f32> {
+    sample_rate: u32,
+    current_phase: T,
+    current_freq: f64,
+    /// How far the phase moves each sample.
+    phase_step: T,
     wave: Waveform,
 }
 
-impl Default for Oscillator {
-    fn default() -> Self {
+impl<T: Sample> Oscillator<T> {
+    /// Create an oscillator running at `sample_rate` (in Hz), silent until given a frequency.
+    pub fn new(sample_rate: u32) -> Self {
         Self {
-            current_phase: (time::SystemTime::now()
-                .duration_since(time::UNIX_EPOCH)
-                .unwrap()
-                .as_nanos()
-                % 360) as f32,
+            sample_rate,
+            current_phase: T::from_f64(random_phase()),
             current_freq: 0.0,
-            wave: Waveform::Saw,
+            phase_step: T::ZERO,
+            wave: Waveform::default(),
+        }
+    }
+
+    /// Set the pitch, in Hz.
+    pub fn set_frequency(&mut self, frequency: f64) {
+        self.current_freq = frequency;
+        self.phase_step = T::from_f64(core::f64::consts::TAU * frequency / self.sample_rate as f64);
+    }
+
+    pub fn frequency(&self) -> f64 {
+        self.current_freq
+    }
+
+    pub fn set_waveform(&mut self, waveform: Waveform) {
+        self.wave = waveform;
+    }
+
+    pub fn waveform(&self) -> Waveform {
+        self.wave
+    }
+
+    /// Jump to a point in the cycle, in radians from 0 to 2π.
+    pub fn set_phase(&mut self, phase: T) {
+        let phase = phase % T::TAU;
+        self.current_phase = if phase < T::ZERO {
+            phase + T::TAU
+        } else {
+            phase
+        };
+    }
+
+    /// The next sample, from -1 to 1.
+    pub fn tick(&mut self) -> T {
+        let next_phase = (self.current_phase + self.phase_step) % T::TAU;
+        self.wave
+            .sample(mem::replace(&mut self.current_phase, next_phase))
+    }
+
+    /// Fill `out` with the next samples.
+    pub fn process(&mut self, out: &mut [T]) {
+        for sample in out {
+            *sample = self.tick();
         }
     }
 }
 
-impl Iterator for Oscillator {
-    type Item = f32;
+/// Advanced for every new oscillator, so that they don't all start in phase with each other.
+static PHASE_SEED: AtomicU32 = AtomicU32::new(0);
+
+/// How far the seed moves on between one oscillator and the next.
+const PHASE_SEED_STEP: u32 = 0x9E37_79B9;
+
+/// A starting phase for an oscillator. This avoids the system clock, which isn't available
+/// everywhere (e.g. on the web).
+fn random_phase() -> f64 {
+    seeded_phase(
+        PHASE_SEED
+            .fetch_add(PHASE_SEED_STEP, Ordering::Relaxed)
+            .wrapping_add(PHASE_SEED_STEP),
+    )
+}
+
+/// A phase from 0 to 2π, the same every time for the same seed.
+fn seeded_phase(seed: u32) -> f64 {
+    let mut x = seed;
+    // xorshift, so that consecutive seeds end up far apart
+    x ^= x << 13;
+    x ^= x >> 17;
+    x ^= x << 5;
+    // rounded as it always has been, so that voices start where they used to
+    (TAU * (x as f32 / u32::MAX as f32) % TAU) as f64
+}
+
+impl<T: Sample> Iterator for Oscillator<T> {
+    type Item = T;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let next_phase =
-            (self.current_phase + TAU * self.current_freq / oversample_rate() as f32) % TAU;
-        Some(
-            self.wave
-                .sample(mem::replace(&mut self.current_phase, next_phase)),
-        )
+        Some(self.tick())
     }
 }
 
+/// A low-frequency oscillator, for moving other settings up and down rather than for listening to.
+/// Unlike `Oscillator`, it always starts from the beginning of its cycle, so that modulation is the
+/// same every time, and its phase is counted in cycles.
 #[derive(Debug)]
-enum Waveform {
+pub struct Lfo<T: Sample = f32> {
+    sample_rate: u32,
+    frequency: f64,
+    /// How far through the cycle it moves each sample.
+    phase_step: T,
+    waveform: Waveform,
+    /// Position through the cycle, from 0 to 1.
+    phase: T,
+}
+
+impl<T: Sample> Lfo<T> {
+    /// Create an LFO running at `sample_rate` (in Hz), playing a 1 Hz sine wave.
+    pub fn new(sample_rate: u32) -> Self {
+        let mut lfo = Self {
+            sample_rate,
+            frequency: 0.0,
+            phase_step: T::ZERO,
+            waveform: Waveform::Sine,
+            phase: T::ZERO,
+        };
+        lfo.set_frequency(1.0);
+        lfo
+    }
+
+    /// Set the rate, in Hz.
+    pub fn set_frequency(&mut self, frequency: f64) {
+        self.frequency = frequency;
+        self.phase_step = T::from_f64(frequency / self.sample_rate as f64);
+    }
+
+    pub fn frequency(&self) -> f64 {
+        self.frequency
+    }
+
+    pub fn set_waveform(&mut self, waveform: Waveform) {
+        self.waveform = waveform;
+    }
+
+    pub fn waveform(&self) -> Waveform {
+        self.waveform
+    }
+
+    /// Jump to a point in the cycle, from 0 to 1, e.g. to line it up with the beat.
+    pub fn set_phase(&mut self, phase: T) {
+        let phase = phase % T::ONE;
+        self.phase = if phase < T::ZERO {
+            phase + T::ONE
+        } else {
+            phase
+        };
+    }
+
+    pub fn phase(&self) -> T {
+        self.phase
+    }
+
+    /// Go back to the start of the cycle.
+    pub fn reset(&mut self) {
+        self.phase = T::ZERO;
+    }
+
+    /// The next value, from -1 to 1.
+    pub fn tick(&mut self) -> T {
+        let value = self.waveform.sample(T::TAU * self.phase);
+        self.set_phase(self.phase + self.phase_step);
+        value
+    }
+
+    /// Fill `out` with the next values.
+    pub fn process(&mut self, out: &mut [T]) {
+        for value in out {
+            *value = self.tick();
+        }
+    }
+}
+
+/// The shape of an oscillator's wave.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case"))]
+pub enum Waveform {
     Sine,
     Pulse,
+    #[default]
     Saw,
 }
 
 impl Waveform {
-    fn sample(&self, phase: f32) -> f32 {
+    pub const ALL: [Self; 3] = [Self::Sine, Self::Pulse, Self::Saw];
+
+    /// A lowercase name, e.g. for saving in a patch.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Sine => "sine",
+            Self::Pulse => "pulse",
+            Self::Saw => "saw",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|w| w.name() == name)
+    }
+
+    fn sample<T: Sample>(&self, phase: T) -> T {
         match self {
             Self::Sine => phase.sin(),
-            Self::Pulse if phase < PI => 1.0,
-            Self::Pulse => -1.0,
-            Self::Saw => (phase / PI) - 1.0,
+            Self::Pulse if phase < T::PI => T::ONE,
+            Self::Pulse => -T::ONE,
+            Self::Saw => (phase / T::PI) - T::ONE,
         }
     }
 }
 
+/// A low-pass filter made of `N` one-pole stages in a row, each rolling off another 6 dB per
+/// octave above the cutoff. The voices use two.
 #[derive(Debug)]
-struct Filter<const N: usize> {
-    alpha: f32,
-    last_per_pole: [f32; N],
+pub struct Filter<const N: usize, T: Sample = f32> {
+    sample_rate: u32,
+    cutoff: f64,
+    alpha: T,
+    last_per_pole: [T; N],
 }
 
-impl<const N: usize> Default for Filter<N> {
-    fn default() -> Self {
+impl<const N: usize, T: Sample> Filter<N, T> {
+    /// Create a filter running at `sample_rate` (in Hz), with its cutoff at 5 kHz.
+    pub fn new(sample_rate: u32) -> Self {
+        let cutoff = DEFAULT_CUTOFF as f64;
         Self {
-            alpha: Self::calculate_alpha(5000.0),
-            last_per_pole: [0.0; N],
+            sample_rate,
+            cutoff,
+            alpha: Self::calculate_alpha(cutoff, sample_rate),
+            last_per_pole: [T::ZERO; N],
         }
     }
-}
 
-impl<const N: usize> Filter<N> {
     // see https://dsp.stackexchange.com/a/54088
-    fn calculate_alpha(cutoff: f32) -> f32 {
-        let y = 1.0 - (TAU * cutoff / oversample_rate() as f32).cos();
-        -y + (y.powi(2) + 2.0 * y).sqrt()
+    fn calculate_alpha(cutoff: f64, sample_rate: u32) -> T {
+        let y = 1.0 - (core::f64::consts::TAU * cutoff / sample_rate as f64).cos();
+        T::from_f64(-y + (y.powi(2) + 2.0 * y).sqrt())
+    }
+
+    /// Set the cutoff frequency, in Hz.
+    pub fn set_cutoff(&mut self, cutoff: f64) {
+        self.cutoff = cutoff;
+        self.alpha = Self::calculate_alpha(cutoff, self.sample_rate);
     }
 
-    fn process(&mut self, mut sample: f32) -> f32 {
+    pub fn cutoff(&self) -> f64 {
+        self.cutoff
+    }
+
+    /// Filter the next sample.
+    pub fn process(&mut self, mut sample: T) -> T {
         for last in &mut self.last_per_pole {
-            sample *= self.alpha;
-            sample += (1.0 - self.alpha) * *last;
-            *last = sample;
+            sample = sample * self.alpha + (T::ONE - self.alpha) * *last;
+            // the poles ring on forever after the input stops, if left to
+            *last = denormal::flush(sample);
         }
         sample
     }
+
+    /// Forget the signal so far.
+    pub fn reset(&mut self) {
+        self.last_per_pole = [T::ZERO; N];
+    }
 }
 
+/// An attack-decay-sustain-release envelope, with straight-line segments. Harder notes are louder,
+/// and move through the attack, decay and release faster.
 #[derive(Debug)]
-struct Adsr {
-    config: Rc<AdsrConfig>,
-    segment: AdsrSegment,
-    velocity_ratio: f32,
+pub struct Adsr<T: Sample = f32> {
+    config: AdsrConfig,
+    sample_rate: u32,
+    segment: AdsrSegment<T>,
+    velocity_ratio: T,
+    /// How far through the attack, decay and release each sample moves, for this velocity.
+    attack_step: T,
+    decay_step: T,
+    release_step: T,
+    sustain: T,
+    /// Overall level, for this velocity.
+    velocity_gain: T,
 }
 
-impl Adsr {
-    fn new(config: Rc<AdsrConfig>) -> Self {
-        Self {
-            config,
+impl<T: Sample> Adsr<T> {
+    /// Create an envelope running at `sample_rate` (in Hz), with the default shape. It stays at
+    /// zero until `note_on`.
+    pub fn new(sample_rate: u32) -> Self {
+        let mut adsr = Self {
+            config: AdsrConfig::default(),
+            sample_rate,
             segment: AdsrSegment::Off,
-            velocity_ratio: 0.0,
-        }
+            velocity_ratio: T::ZERO,
+            attack_step: T::ZERO,
+            decay_step: T::ZERO,
+            release_step: T::ZERO,
+            sustain: T::ZERO,
+            velocity_gain: T::ZERO,
+        };
+        adsr.update_steps();
+        adsr
     }
-}
 
-impl Iterator for Adsr {
-    type Item = f32;
+    /// Change the shape, which takes effect straight away, even partway through a note.
+    pub fn set_config(&mut self, config: AdsrConfig) {
+        self.config = config;
+        self.update_steps();
+    }
 
-    fn next(&mut self) -> Option<Self::Item> {
+    pub fn config(&self) -> AdsrConfig {
+        self.config
+    }
+
+    /// Start the attack, from wherever the envelope is now, with a velocity from 0 to 1.
+    pub fn note_on(&mut self, velocity: T) {
+        self.segment = AdsrSegment::Attack(T::ZERO, self.tick());
+        self.velocity_ratio = if velocity < T::ZERO {
+            T::ZERO
+        } else if velocity > T::ONE {
+            T::ONE
+        } else {
+            velocity
+        };
+        self.update_steps();
+    }
+
+    /// Start the release, from wherever the envelope is now.
+    pub fn note_off(&mut self) {
+        let release_point = if let AdsrSegment::Sustain = self.segment {
+            self.sustain
+        } else {
+            self.tick()
+        };
+        self.segment = AdsrSegment::Release(T::ZERO, release_point);
+    }
+
+    /// Whether the envelope is anywhere but at rest, i.e. still making sound.
+    pub fn is_active(&self) -> bool {
+        !matches!(self.segment, AdsrSegment::Off)
+    }
+
+    /// Whether the note is still held, i.e. hasn't been released.
+    pub fn is_held(&self) -> bool {
+        matches!(
+            self.segment,
+            AdsrSegment::Attack(..) | AdsrSegment::Decay(_) | AdsrSegment::Sustain
+        )
+    }
+
+    /// Go straight back to rest, without a release.
+    pub fn reset(&mut self) {
+        self.segment = AdsrSegment::Off;
+    }
+
+    /// Work out everything `tick` needs from the shape and velocity, so that it doesn't have to
+    /// divide or convert anything itself.
+    fn update_steps(&mut self) {
+        let velocity = self.velocity_ratio.to_f64();
+        let sample_rate = self.sample_rate as f64;
+        // velocity scaling - TODO use an actual mod matrix instead of hard coding
+        let step = |time: f32| {
+            let time = time as f64;
+            T::from_f64(1.0 / (time - velocity * time) / sample_rate)
+        };
+        self.attack_step = step(self.config.attack_time);
+        self.decay_step = step(self.config.decay_time);
+        self.release_step = step(self.config.release_time);
+        self.sustain = T::from_f64(self.config.sustain_amount as f64);
+        // velocity scaling - TODO make the depth changeable via CC
+        self.velocity_gain = lerp(self.velocity_ratio, (T::from_f64(0.25), T::ONE));
+    }
+
+    /// The envelope's level at the next sample, from 0 to 1.
+    pub fn tick(&mut self) -> T {
+        let (zero, one) = (T::ZERO, T::ONE);
         let raw_amplitude = match self.segment {
-            AdsrSegment::Off => 0.0,
-            AdsrSegment::Attack(amt, _) if amt >= 1.0 => {
-                self.segment = AdsrSegment::Decay(0.0);
-                1.0
+            AdsrSegment::Off => zero,
+            AdsrSegment::Attack(amt, _) if amt >= one => {
+                self.segment = AdsrSegment::Decay(zero);
+                one
             }
             AdsrSegment::Attack(current_amt, start_point) => {
-                // velocity scaling - TODO use an actual mod matrix instead of hard coding
-                let vel_scaled_attack_slope = 1.0
-                    / map_range(
-                        self.velocity_ratio,
-                        (0.0, 1.0),
-                        (self.config.attack_time, 0.0),
-                    );
-                self.segment = AdsrSegment::Attack(
-                    current_amt + vel_scaled_attack_slope / oversample_rate() as f32,
-                    start_point,
-                );
-                map_range(current_amt, (0.0, 1.0), (start_point, 1.0))
+                self.segment = AdsrSegment::Attack(current_amt + self.attack_step, start_point);
+                lerp(current_amt, (start_point, one))
             }
-            AdsrSegment::Decay(amt) if amt >= 1.0 => {
+            AdsrSegment::Decay(amt) if amt >= one => {
                 self.segment = AdsrSegment::Sustain;
-                self.config.sustain_amount
+                self.sustain
             }
             AdsrSegment::Decay(current_amt) => {
-                // velocity scaling - TODO use an actual mod matrix instead of hard coding
-                let vel_scaled_decay_slope = 1.0
-                    / map_range(
-                        self.velocity_ratio,
-                        (0.0, 1.0),
-                        (self.config.decay_time, 0.0),
-                    );
-                self.segment = AdsrSegment::Decay(
-                    current_amt + vel_scaled_decay_slope / oversample_rate() as f32,
-                );
-                map_range(current_amt, (0.0, 1.0), (1.0, self.config.sustain_amount))
+                self.segment = AdsrSegment::Decay(current_amt + self.decay_step);
+                lerp(current_amt, (one, self.sustain))
             }
-            AdsrSegment::Sustain => self.config.sustain_amount,
-            AdsrSegment::Release(amt, _) if amt >= 1.0 => {
+            AdsrSegment::Sustain => self.sustain,
+            AdsrSegment::Release(amt, _) if amt >= one => {
                 self.segment = AdsrSegment::Off;
-                0.0
+                zero
             }
             AdsrSegment::Release(current_amt, release_point) => {
-                // velocity scaling - TODO use an actual mod matrix instead of hard coding
-                let vel_scaled_release_slope = 1.0
-                    / map_range(
-                        self.velocity_ratio,
-                        (0.0, 1.0),
-                        (self.config.release_time, 0.0),
-                    );
-                self.segment = AdsrSegment::Release(
-                    current_amt + vel_scaled_release_slope / oversample_rate() as f32,
-                    release_point,
-                );
-                map_range(current_amt, (0.0, 1.0), (release_point, 0.0))
+                self.segment = AdsrSegment::Release(current_amt + self.release_step, release_point);
+                lerp(current_amt, (release_point, zero))
             }
         };
 
-        // velocity scaling - TODO make the depth changeable via CC
-        Some(raw_amplitude * map_range(self.velocity_ratio, (0.0, 1.0), (0.25, 1.0)))
+        raw_amplitude * self.velocity_gain
     }
 }
 
-#[derive(Debug)]
-struct AdsrConfig {
-    attack_time: f32,
-    decay_time: f32,
-    sustain_amount: f32,
-    release_time: f32,
+/// The shape of an envelope: times in seconds (at full velocity, notes take less time), and the
+/// sustain level from 0 to 1.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct AdsrConfig {
+    pub attack_time: f32,
+    pub decay_time: f32,
+    pub sustain_amount: f32,
+    pub release_time: f32,
 }
 
 impl Default for AdsrConfig {
@@ -366,12 +3109,17 @@ impl Default for AdsrConfig {
 }
 
 #[derive(Debug)]
-enum AdsrSegment {
+enum AdsrSegment<T> {
     Off,
-    Attack(f32, f32),
-    Decay(f32),
+    Attack(T, T),
+    Decay(T),
     Sustain,
-    Release(f32, f32),
+    Release(T, T),
+}
+
+/// The point `amount` of the way from one value to another, where `amount` is from 0 to 1.
+fn lerp<T: Sample>(amount: T, (from, to): (T, T)) -> T {
+    amount * (to - from) + from
 }
 
 /// Transform a value from one range into another, relative to those ranges' limits.
@@ -388,3 +3136,73 @@ where
     let rel_qty = (quantity - bottom_old) / (top_old - bottom_old);
     rel_qty * (top_new - bottom_new) + bottom_new
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn synth() -> Synth {
+        Synth::new(4, 48000, Oversampling::default())
+    }
+
+    #[test]
+    fn set_param_clamps_to_range() {
+        let mut synth = synth();
+        for param in ParamId::fixed() {
+            let info = synth.param_info(param).unwrap();
+            synth.set_param(param, info.max + 1000.0).unwrap();
+            assert_eq!(synth.get_param(param), Some(info.max), "{:?}", param);
+            synth.set_param(param, info.min - 1000.0).unwrap();
+            assert_eq!(synth.get_param(param), Some(info.min), "{:?}", param);
+        }
+    }
+
+    /// Plays a note for a while, returning whether every sample was a number.
+    fn renders_finite(synth: &mut Synth) -> bool {
+        synth
+            .apply(SynthCommand::NoteOn {
+                note: 60,
+                velocity: 100,
+            })
+            .unwrap();
+        let mut out = [0.0; 4096];
+        synth.process(&mut out);
+        out.iter().all(|sample| sample.is_finite())
+    }
+
+    #[test]
+    fn cutoff_stays_in_range() {
+        let mut synth = synth();
+        for cutoff in [0.0, -5.0, f32::INFINITY, 1e9] {
+            synth.set_cutoff(cutoff);
+            assert!(synth.cutoff() >= 20.0 && synth.cutoff() < 24000.0);
+            assert!(renders_finite(&mut synth), "{}", cutoff);
+        }
+        synth.set_cutoff(f32::NAN);
+        assert!(synth.cutoff().is_finite());
+        synth.set_param(ParamId::Cutoff, 0.0).unwrap();
+        synth.set_cutoff(1000.0);
+        assert!(renders_finite(&mut synth));
+    }
+
+    #[test]
+    fn smoothing_ignores_targets_which_are_not_finite() {
+        let mut smoothed = Smoothed::new(1.0);
+        assert_eq!(smoothed.follow(f32::NEG_INFINITY, 0.5), None);
+        assert_eq!(smoothed.follow(f32::NAN, 0.5), None);
+        assert_eq!(smoothed.follow(3.0, 0.5), Some(2.0));
+    }
+
+    #[test]
+    fn set_param_rejects_nan_and_missing_params() {
+        let mut synth = synth();
+        assert!(synth.set_param(ParamId::Cutoff, f32::NAN).is_err());
+        let missing = ParamId::Effect {
+            placement: Placement::Master,
+            slot: 0,
+            parameter: 0,
+        };
+        assert!(synth.set_param(missing, 0.5).is_err());
+        assert!(synth.set_param(ParamId::SendLevel(SENDS), 0.5).is_err());
+    }
+}
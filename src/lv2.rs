@@ -0,0 +1,422 @@
+//! The synth as an LV2 plugin, for Linux hosts such as Ardour and Carla.
+//!
+//! An LV2 plugin is a bundle: a directory holding the library along with Turtle files describing
+//! its ports, which `write_bundle` writes from the parameters' metadata. To install it, build the
+//! library with the `lv2` feature, write the bundle, and copy the library in, e.g.
+//! `cargo build --release --features lv2`, `basic-synth-cli --lv2-bundle ~/.lv2/basic-synth.lv2`
+//! and `cp target/release/libbasic_synth.so ~/.lv2/basic-synth.lv2/`.
+//!
+//! The plugin takes MIDI through an atom sequence port, plays through a pair of audio output
+//! ports, and has a control port for each of the fixed parameters (see `ParamId::fixed`) apart
+//! from the tempo, which follows the host's transport.
+
+mod sys;
+
+use {
+    core::ffi::{c_char, c_void},
+    midi_msg::MidiMsg,
+    std::{
+        env::consts::{DLL_PREFIX, DLL_SUFFIX},
+        ffi::CStr,
+        fmt::Write as _,
+        fs, io, mem,
+        path::Path,
+        ptr, slice,
+    },
+};
+
+use crate::{
+    param::{Curve, ParamId, Unit},
+    Synth, SynthCommand, Waveform, CHANNELS,
+};
+
+/// Voices in each instance of the plugin.
+const VOICES: usize = 16;
+
+const PLUGIN_URI: &[u8] = b"https://github.com/g-s-k/basic-synth\0";
+
+/// The file describing the plugin, which the manifest points to.
+const PLUGIN_TTL: &str = "basic-synth.ttl";
+
+/// The ports, in order: the events, each output channel, then a control for each of `params`.
+const EVENTS_PORT: u32 = 0;
+const FIRST_OUTPUT_PORT: u32 = 1;
+const FIRST_CONTROL_PORT: u32 = FIRST_OUTPUT_PORT + CHANNELS as u32;
+
+/// Something the host is handed pointers into, which never changes, so it can be shared between
+/// threads despite the raw pointers in it.
+struct Constant<T>(T);
+
+unsafe impl<T> Sync for Constant<T> {}
+
+static DESCRIPTOR: Constant<sys::Descriptor> = Constant(sys::Descriptor {
+    uri: PLUGIN_URI.as_ptr() as *const c_char,
+    instantiate,
+    connect_port,
+    activate,
+    run,
+    deactivate,
+    cleanup,
+    extension_data,
+});
+
+/// What the host looks up when it loads the library.
+#[no_mangle]
+pub extern "C" fn lv2_descriptor(index: u32) -> *const c_void {
+    if index == 0 {
+        &DESCRIPTOR.0 as *const sys::Descriptor as *const c_void
+    } else {
+        ptr::null()
+    }
+}
+
+/// The parameters with control ports, in order.
+fn params() -> impl Iterator<Item = ParamId> {
+    ParamId::fixed().filter(|param| *param != ParamId::Tempo)
+}
+
+/// Write the bundle's `manifest.ttl`, and the file describing the plugin, into `dir`, making it
+/// if need be. The library itself has to be copied in alongside them.
+pub fn write_bundle(dir: &Path) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+    fs::write(dir.join("manifest.ttl"), manifest())?;
+    fs::write(dir.join(PLUGIN_TTL), plugin_description())
+}
+
+fn plugin_uri() -> &'static str {
+    let uri = &PLUGIN_URI[..PLUGIN_URI.len() - 1];
+    core::str::from_utf8(uri).unwrap_or_default()
+}
+
+fn manifest() -> String {
+    format!(
+        "\
+@prefix lv2: <http://lv2plug.in/ns/lv2core#> .
+@prefix rdfs: <http://www.w3.org/2000/01/rdf-schema#> .
+
+<{}>
+    a lv2:Plugin, lv2:InstrumentPlugin ;
+    lv2:binary <{}basic_synth{}> ;
+    rdfs:seeAlso <{}> .
+",
+        plugin_uri(),
+        DLL_PREFIX,
+        DLL_SUFFIX,
+        PLUGIN_TTL
+    )
+}
+
+/// The plugin's ports, with a control port for each parameter as its metadata describes it.
+fn plugin_description() -> String {
+    let mut ttl = format!(
+        "\
+@prefix atom: <http://lv2plug.in/ns/ext/atom#> .
+@prefix doap: <http://usefulinc.com/ns/doap#> .
+@prefix lv2: <http://lv2plug.in/ns/lv2core#> .
+@prefix midi: <http://lv2plug.in/ns/ext/midi#> .
+@prefix pprops: <http://lv2plug.in/ns/ext/port-props#> .
+@prefix rdf: <http://www.w3.org/1999/02/22-rdf-syntax-ns#> .
+@prefix rdfs: <http://www.w3.org/2000/01/rdf-schema#> .
+@prefix time: <http://lv2plug.in/ns/ext/time#> .
+@prefix units: <http://lv2plug.in/ns/extensions/units#> .
+@prefix urid: <http://lv2plug.in/ns/ext/urid#> .
+
+<{}>
+    a lv2:Plugin, lv2:InstrumentPlugin ;
+    doap:name \"basic-synth\" ;
+    doap:maintainer [ doap:name \"George Kaplan\" ] ;
+    lv2:requiredFeature urid:map ;
+    lv2:optionalFeature lv2:hardRTCapable ;
+    lv2:port [
+        a lv2:InputPort, atom:AtomPort ;
+        atom:bufferType atom:Sequence ;
+        atom:supports midi:MidiEvent, time:Position ;
+        lv2:designation lv2:control ;
+        lv2:index {} ;
+        lv2:symbol \"events\" ;
+        lv2:name \"Events\"
+    ]",
+        plugin_uri(),
+        EVENTS_PORT
+    );
+    for (channel, name) in ["left", "right"].iter().enumerate() {
+        let _ = write!(
+            ttl,
+            " , [
+        a lv2:OutputPort, lv2:AudioPort ;
+        lv2:index {} ;
+        lv2:symbol \"out_{}\" ;
+        lv2:name \"Output {}\"
+    ]",
+            FIRST_OUTPUT_PORT + channel as u32,
+            name,
+            name
+        );
+    }
+    for (index, param) in params().enumerate() {
+        let info = match param.info() {
+            Some(info) => info,
+            None => continue,
+        };
+        let key = param.to_string();
+        let _ = write!(
+            ttl,
+            " , [
+        a lv2:InputPort, lv2:ControlPort ;
+        lv2:index {} ;
+        lv2:symbol \"{}\" ;
+        lv2:name \"{}\" ;
+        lv2:default {:?} ;
+        lv2:minimum {:?} ;
+        lv2:maximum {:?}",
+            FIRST_CONTROL_PORT + index as u32,
+            key.replace(' ', "_"),
+            key,
+            info.default,
+            info.min,
+            info.max
+        );
+        if let Some(unit) = unit_uri(info.unit) {
+            let _ = write!(ttl, " ;\n        units:unit {}", unit);
+        }
+        match info.curve {
+            Curve::Linear => {}
+            Curve::Logarithmic => ttl.push_str(" ;\n        lv2:portProperty pprops:logarithmic"),
+            Curve::Stepped if param == ParamId::Waveform => {
+                ttl.push_str(" ;\n        lv2:portProperty lv2:integer, lv2:enumeration");
+                for (value, waveform) in Waveform::ALL.iter().enumerate() {
+                    let _ = write!(
+                        ttl,
+                        " ;\n        lv2:scalePoint [ rdfs:label \"{}\" ; rdf:value {} ]",
+                        waveform.name(),
+                        value
+                    );
+                }
+            }
+            Curve::Stepped => ttl.push_str(" ;\n        lv2:portProperty lv2:integer"),
+        }
+        ttl.push_str("\n    ]");
+    }
+    ttl.push_str(" .\n");
+    ttl
+}
+
+/// The LV2 unit for a parameter's, if there is one.
+fn unit_uri(unit: Unit) -> Option<&'static str> {
+    Some(match unit {
+        Unit::None => return None,
+        Unit::Hz => "units:hz",
+        Unit::Seconds => "units:s",
+        Unit::Decibels => "units:db",
+        Unit::Cents => "units:cent",
+        Unit::Semitones => "units:semitone12TET",
+        Unit::Bpm => "units:bpm",
+    })
+}
+
+/// The ids the host has given the URIs the plugin needs to recognize.
+struct Urids {
+    midi_event: u32,
+    atom_object: u32,
+    atom_blank: u32,
+    atom_float: u32,
+    time_position: u32,
+    time_beats_per_minute: u32,
+}
+
+impl Urids {
+    unsafe fn new(map: &sys::UridMap) -> Self {
+        let map = |uri: &[u8]| (map.map)(map.handle, uri.as_ptr() as *const c_char);
+        Self {
+            midi_event: map(sys::MIDI_EVENT),
+            atom_object: map(sys::ATOM_OBJECT),
+            atom_blank: map(sys::ATOM_BLANK),
+            atom_float: map(sys::ATOM_FLOAT),
+            time_position: map(sys::TIME_POSITION),
+            time_beats_per_minute: map(sys::TIME_BEATS_PER_MINUTE),
+        }
+    }
+}
+
+/// One instance of the plugin, which the host's handle points to.
+struct Instance {
+    synth: Synth,
+    urids: Urids,
+    /// Where the host has connected each port, or null until it has.
+    events: *const sys::AtomSequence,
+    outputs: [*mut f32; CHANNELS],
+    controls: Vec<*const f32>,
+    /// The value of each control last passed on to the synth, in the same order.
+    applied: Vec<f32>,
+}
+
+impl Instance {
+    /// Pass on each control the host has changed since the last block.
+    unsafe fn apply_controls(&mut self) {
+        for ((param, control), applied) in params().zip(&self.controls).zip(&mut self.applied) {
+            if let Some(value) = control.as_ref() {
+                if *value != *applied {
+                    let _ = self.synth.set_param(param, *value);
+                    *applied = *value;
+                }
+            }
+        }
+    }
+
+    /// Schedule the commands for the events in this block, at the frames they're for.
+    unsafe fn read_events(&mut self) {
+        let sequence = match self.events.as_ref() {
+            Some(sequence) => sequence,
+            None => return,
+        };
+        let start = self.synth.clock();
+        // the events follow the header, which the size counts from the end of
+        let body =
+            (sequence as *const sys::AtomSequence as *const u8).add(mem::size_of::<sys::Atom>());
+        let end = body.add(sequence.atom.size as usize);
+        let mut next = (sequence as *const sys::AtomSequence).add(1) as *const u8;
+        while next < end {
+            let event = &*(next as *const sys::AtomEvent);
+            let data = next.add(mem::size_of::<sys::AtomEvent>());
+            let data = slice::from_raw_parts(data, event.body.size as usize);
+            if let Some(command) = self.translate_event(event.body.type_, data) {
+                let frame = start + event.frames.max(0) as u64;
+                // there's nobody to tell about dropped commands in here
+                let _ = self.synth.schedule(frame, command);
+            }
+            next = next.add(pad(mem::size_of::<sys::AtomEvent>() + data.len()));
+        }
+    }
+
+    /// The command for an event's body, if it's one the synth understands: MIDI, or a change of
+    /// tempo in the host's transport.
+    unsafe fn translate_event(&self, type_: u32, data: &[u8]) -> Option<SynthCommand> {
+        if type_ == self.urids.midi_event {
+            let (msg, _) = MidiMsg::from_midi(data).ok()?;
+            return SynthCommand::from_midi(&msg);
+        }
+        if type_ != self.urids.atom_object && type_ != self.urids.atom_blank {
+            return None;
+        }
+        let header_size = mem::size_of::<sys::AtomObjectBody>();
+        if data.len() < header_size {
+            return None;
+        }
+        let object = &*(data.as_ptr() as *const sys::AtomObjectBody);
+        if object.otype != self.urids.time_position {
+            return None;
+        }
+        let mut offset = header_size;
+        let property_size = mem::size_of::<sys::AtomPropertyBody>();
+        while offset + property_size <= data.len() {
+            let property = &*(data.as_ptr().add(offset) as *const sys::AtomPropertyBody);
+            if property.key == self.urids.time_beats_per_minute
+                && property.value.type_ == self.urids.atom_float
+            {
+                let bpm = *(data.as_ptr().add(offset + property_size) as *const f32);
+                return Some(SynthCommand::Tempo(bpm));
+            }
+            offset += pad(property_size + property.value.size as usize);
+        }
+        None
+    }
+}
+
+/// Atoms in sequences and objects start on 8-byte boundaries.
+fn pad(size: usize) -> usize {
+    (size + 7) & !7
+}
+
+unsafe extern "C" fn instantiate(
+    _descriptor: *const sys::Descriptor,
+    sample_rate: f64,
+    _bundle_path: *const c_char,
+    features: *const *const sys::Feature,
+) -> sys::Handle {
+    let mut map = None;
+    let mut feature = features;
+    while let Some(f) = feature.as_ref().and_then(|f| f.as_ref()) {
+        if CStr::from_ptr(f.uri).to_bytes_with_nul() == sys::URID_MAP {
+            map = (f.data as *const sys::UridMap).as_ref();
+        }
+        feature = feature.add(1);
+    }
+    let map = match map {
+        Some(map) => map,
+        None => {
+            log::error!("The host can't map URIDs, which the plugin needs");
+            return ptr::null_mut();
+        }
+    };
+
+    let mut synth = match Synth::builder()
+        .voices(VOICES)
+        .sample_rate(sample_rate.round() as u32)
+        .build()
+    {
+        Ok(synth) => synth,
+        Err(e) => {
+            log::error!("Could not create the synth: {}", e);
+            return ptr::null_mut();
+        }
+    };
+    // the host saves the controls, and saving settings for undo would allocate while rendering
+    synth.set_undo_enabled(false);
+    let controls = params().count();
+    let applied = params()
+        .map(|param| synth.get_param(param).unwrap_or_default())
+        .collect();
+    Box::into_raw(Box::new(Instance {
+        synth,
+        urids: Urids::new(map),
+        events: ptr::null(),
+        outputs: [ptr::null_mut(); CHANNELS],
+        controls: vec![ptr::null(); controls],
+        applied,
+    })) as sys::Handle
+}
+
+unsafe extern "C" fn connect_port(instance: sys::Handle, port: u32, data: *mut c_void) {
+    let instance = &mut *(instance as *mut Instance);
+    match port {
+        EVENTS_PORT => instance.events = data as *const sys::AtomSequence,
+        port if port < FIRST_CONTROL_PORT => {
+            instance.outputs[(port - FIRST_OUTPUT_PORT) as usize] = data as *mut f32
+        }
+        port => {
+            if let Some(control) = instance
+                .controls
+                .get_mut((port - FIRST_CONTROL_PORT) as usize)
+            {
+                *control = data as *const f32;
+            }
+        }
+    }
+}
+
+unsafe extern "C" fn activate(instance: sys::Handle) {
+    (*(instance as *mut Instance)).synth.reset();
+}
+
+unsafe extern "C" fn run(instance: sys::Handle, sample_count: u32) {
+    let instance = &mut *(instance as *mut Instance);
+    instance.apply_controls();
+    instance.read_events();
+    let [left, right] = instance.outputs;
+    if left.is_null() || right.is_null() {
+        return;
+    }
+    let left = slice::from_raw_parts_mut(left, sample_count as usize);
+    let right = slice::from_raw_parts_mut(right, sample_count as usize);
+    instance.synth.process_stereo(left, right);
+}
+
+unsafe extern "C" fn deactivate(_instance: sys::Handle) {}
+
+unsafe extern "C" fn cleanup(instance: sys::Handle) {
+    drop(Box::from_raw(instance as *mut Instance));
+}
+
+unsafe extern "C" fn extension_data(_uri: *const c_char) -> *const c_void {
+    ptr::null()
+}
@@ -0,0 +1,81 @@
+//! The parts of the LV2 C API the plugin uses, declared by hand after the LV2 1.18 headers. Names
+//! follow the headers' without their `LV2_` prefix, e.g. `AtomSequence` for `LV2_Atom_Sequence`.
+
+use core::ffi::{c_char, c_void};
+
+pub const URID_MAP: &[u8] = b"http://lv2plug.in/ns/ext/urid#map\0";
+pub const MIDI_EVENT: &[u8] = b"http://lv2plug.in/ns/ext/midi#MidiEvent\0";
+pub const ATOM_OBJECT: &[u8] = b"http://lv2plug.in/ns/ext/atom#Object\0";
+pub const ATOM_BLANK: &[u8] = b"http://lv2plug.in/ns/ext/atom#Blank\0";
+pub const ATOM_FLOAT: &[u8] = b"http://lv2plug.in/ns/ext/atom#Float\0";
+pub const TIME_POSITION: &[u8] = b"http://lv2plug.in/ns/ext/time#Position\0";
+pub const TIME_BEATS_PER_MINUTE: &[u8] = b"http://lv2plug.in/ns/ext/time#beatsPerMinute\0";
+
+pub type Handle = *mut c_void;
+
+#[repr(C)]
+pub struct Descriptor {
+    pub uri: *const c_char,
+    pub instantiate: unsafe extern "C" fn(
+        descriptor: *const Descriptor,
+        sample_rate: f64,
+        bundle_path: *const c_char,
+        features: *const *const Feature,
+    ) -> Handle,
+    pub connect_port: unsafe extern "C" fn(instance: Handle, port: u32, data: *mut c_void),
+    pub activate: unsafe extern "C" fn(instance: Handle),
+    pub run: unsafe extern "C" fn(instance: Handle, sample_count: u32),
+    pub deactivate: unsafe extern "C" fn(instance: Handle),
+    pub cleanup: unsafe extern "C" fn(instance: Handle),
+    pub extension_data: unsafe extern "C" fn(uri: *const c_char) -> *const c_void,
+}
+
+#[repr(C)]
+pub struct Feature {
+    pub uri: *const c_char,
+    pub data: *mut c_void,
+}
+
+#[repr(C)]
+pub struct UridMap {
+    pub handle: *mut c_void,
+    pub map: unsafe extern "C" fn(handle: *mut c_void, uri: *const c_char) -> u32,
+}
+
+/// The header of every atom, followed by `size` bytes of body.
+#[repr(C)]
+pub struct Atom {
+    pub size: u32,
+    pub type_: u32,
+}
+
+/// Followed by events, each padded to 8 bytes.
+#[repr(C)]
+pub struct AtomSequence {
+    pub atom: Atom,
+    pub unit: u32,
+    pub pad: u32,
+}
+
+/// Followed by `body.size` bytes of the event's body.
+#[repr(C)]
+pub struct AtomEvent {
+    /// The frame of the block the event is for, in sequences timed in frames.
+    pub frames: i64,
+    pub body: Atom,
+}
+
+/// An object's body, followed by its properties, each padded to 8 bytes.
+#[repr(C)]
+pub struct AtomObjectBody {
+    pub id: u32,
+    pub otype: u32,
+}
+
+/// Followed by `value.size` bytes of the value's body.
+#[repr(C)]
+pub struct AtomPropertyBody {
+    pub key: u32,
+    pub context: u32,
+    pub value: Atom,
+}
@@ -1,37 +1,352 @@
 use std::{
-    io::{stdin, stdout, Write},
+    fs::{self, File},
+    io::{self, stdin, stdout, Write},
+    path::{Path, PathBuf},
     process,
-    sync::mpsc::{self, Sender, TryRecvError},
+    sync::{
+        mpsc::{self, RecvTimeoutError},
+        Arc, Mutex,
+    },
     thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 
 use {
     midi_msg::*,
     midir::{Ignore, MidiInput},
-    rodio::{buffer::SamplesBuffer, OutputStream, Sink},
 };
 
-use basic_synth::{Synth, SAMPLE_RATE};
+use basic_synth::{
+    backend,
+    effects::Placement,
+    patch::{EffectPatch, Patch},
+    preset::{Preset, PresetBank},
+    random::Randomizer,
+    sequencer::{Pattern, SequencerClock, SequencerCommand},
+    smf::{self, Playback},
+    Synth, SynthBuilder, SynthCommand,
+};
+
+mod cli;
+
+use cli::{
+    audio::{self, CommandSender},
+    monitor::Monitor,
+    record::Recorder,
+    watch::PatchWatcher,
+    Options,
+};
 
 const BLOCKS_PER_SECOND: u32 = 100;
-const BLOCKS_BUFFER: usize = 4;
 
-fn block_size() -> u32 {
-    unsafe { SAMPLE_RATE / BLOCKS_PER_SECOND }
-}
+/// Sample rate to render at offline, unless told otherwise.
+const DEFAULT_RENDER_SAMPLE_RATE: u32 = 48000;
+
+/// How much of every voice goes to the send buses set up with `--send`.
+const SEND_LEVEL: f32 = 0.3;
+
+/// How long to keep running after non-interactive input ends, so release tails aren't cut off.
+const INPUT_TAIL: Duration = Duration::from_secs(2);
+
+/// How long to fade out for when shutting down, to avoid ending on a click.
+const FADE_OUT_TIME: Duration = Duration::from_millis(100);
+
+/// How long to wait for the fade out to be heard before giving up on it.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// How long to wait for the synth thread to hand over its settings, when saving them.
+const SAVE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// How often to check that the MIDI port is still there, when the watchdog is enabled.
+const PORT_POLL_INTERVAL: Duration = Duration::from_millis(500);
 
 fn main() {
+    let mut opts = Options::from_env();
+    cli::logger::init(opts.log_level);
+
+    #[cfg(feature = "lv2")]
+    if let Some(dir) = &opts.lv2_bundle {
+        if let Err(e) = basic_synth::lv2::write_bundle(dir) {
+            log::error!("Failed to write the LV2 bundle to {}: {}", dir.display(), e);
+            process::exit(1);
+        }
+        log::info!("Wrote the LV2 bundle to {}", dir.display());
+        return;
+    }
+
+    let (presets, sound) = load_presets(&opts);
+    if opts.list_presets {
+        for (index, preset) in presets.presets().iter().enumerate() {
+            match &preset.category {
+                Some(category) => println!("{:3}: {}/{}", index, category, preset.name),
+                None => println!("{:3}: {}", index, preset.name),
+            }
+        }
+        return;
+    }
+
+    if let Some(path) = &opts.render {
+        render(path, &opts, &sound);
+        return;
+    }
+
+    if opts.benchmark {
+        let sample_rate = opts.sample_rate.unwrap_or(DEFAULT_RENDER_SAMPLE_RATE);
+        cli::bench::run(|voices| {
+            build_synth(&opts, sample_rate, &sound, Synth::builder().voices(voices))
+        });
+        return;
+    }
+
+    if let Some(name) = &opts.audio_device {
+        opts.audio_device = Some(select_audio_device(name));
+    }
+
+    let watchdog = opts.note_timeout.is_some();
+    let path = sound.path.clone();
+    let commands = run_synth_bg(&opts, sound);
+    let watcher = opts.watch.then(|| {
+        let watcher = PatchWatcher::spawn(commands.clone());
+        watcher.watch(path);
+        watcher
+    });
+    let current = opts.preset.as_ref().and_then(|name| presets.find(name));
+    let mut midi_state = MidiState {
+        presets: Presets {
+            current: Arc::new(Mutex::new(current)),
+            bank: Arc::new(Mutex::new(presets)),
+            dir: opts.preset_dir.clone(),
+            commands: commands.clone(),
+            watcher,
+        },
+        commands,
+        monitor: if opts.monitor {
+            Some(Monitor::default())
+        } else {
+            None
+        },
+        recorder: opts.record.map(Recorder::new),
+    };
+
+    if opts.latch {
+        midi_state.commands.send(SynthCommand::Latch(true));
+    }
+    if opts.generate.is_some() {
+        midi_state
+            .commands
+            .send(SynthCommand::Generate(opts.generate));
+    }
+    if let Some(path) = &opts.pattern {
+        start_pattern(path, opts.pattern_clock, &midi_state.commands);
+    }
+
+    if let Some(addr) = &opts.remote {
+        let presets = Arc::new(midi_state.presets.clone());
+        let commands = midi_state.commands.clone();
+        let allowed_origins = opts.allowed_origins.clone();
+        if let Err(e) = cli::remote::spawn(addr, commands, presets, allowed_origins) {
+            log::error!("Failed to listen for remote control at {}: {}", addr, e);
+            process::exit(1);
+        }
+    }
+
+    let commands = midi_state.commands.clone();
+    ctrlc::set_handler(move || {
+        commands.shutdown(FADE_OUT_TIME, SHUTDOWN_TIMEOUT);
+        process::exit(130);
+    })
+    .expect("Could not set Ctrl-C handler");
+
+    if opts.replay.is_some() || opts.stdin.is_some() {
+        if let Some(path) = &opts.replay {
+            replay(path, &mut midi_state);
+        } else if let Some(format) = opts.stdin {
+            let start = Instant::now();
+            if let Err(e) = cli::stdin::read(format, |msg| {
+                midi_state.dispatch(start.elapsed().as_micros() as u64, &msg)
+            }) {
+                log::error!("Failed to read from stdin: {}", e);
+            }
+        }
+
+        // input is over, so treat it like a dropped connection
+        if watchdog {
+            midi_state.commands.send_midi(&all_notes_off());
+        }
+        thread::sleep(INPUT_TAIL);
+    } else {
+        #[cfg(feature = "rtp-midi")]
+        if let Some(addr) = &opts.rtp_midi {
+            midi_state = listen_network(addr, midi_state, watchdog, opts.tui);
+        } else {
+            midi_state = listen(midi_state, watchdog, opts.tui);
+        }
+        #[cfg(not(feature = "rtp-midi"))]
+        {
+            midi_state = listen(midi_state, watchdog, opts.tui);
+        }
+    }
+    midi_state
+        .commands
+        .shutdown(FADE_OUT_TIME, SHUTDOWN_TIMEOUT);
+
+    if let Some(recorder) = midi_state.recorder {
+        match recorder.save() {
+            Ok(()) => log::info!("Saved recording to {}", recorder.path().display()),
+            Err(e) => log::error!("Failed to save recording: {}", e),
+        }
+    }
+}
+
+/// Have the step sequencer play the pattern in the file given with `--pattern`, straight away
+/// unless it waits for MIDI Start.
+fn start_pattern(path: &Path, clock: SequencerClock, commands: &CommandSender) {
+    let pattern: Pattern = fs::read_to_string(path)
+        .map_err(|e| e.to_string())
+        .and_then(|text| text.parse())
+        .unwrap_or_else(|e| {
+            log::error!("Failed to read the pattern in {}: {}", path.display(), e);
+            process::exit(1);
+        });
+    commands.send(SynthCommand::Sequencer(SequencerCommand::LoadPattern(
+        Box::new(pattern),
+    )));
+    commands.send(SynthCommand::Sequencer(SequencerCommand::SetClock(clock)));
+    if clock == SequencerClock::Internal {
+        commands.send(SynthCommand::Sequencer(SequencerCommand::Start));
+    }
+}
+
+/// Bounce the file given with `--replay` to a WAV file, without touching any devices.
+fn render(path: &Path, opts: &Options, sound: &Sound) {
+    let midi_path = opts.replay.as_ref().unwrap_or_else(|| {
+        log::error!("Rendering needs a MIDI file to play, given with --replay");
+        process::exit(2);
+    });
+    let events = File::open(midi_path)
+        .and_then(smf::read)
+        .unwrap_or_else(|e| {
+            log::error!("Failed to read {}: {}", midi_path.display(), e);
+            process::exit(1);
+        });
+    let duration = events.last().map_or(Duration::ZERO, |e| e.time) + INPUT_TAIL;
+
+    let sample_rate = opts.sample_rate.unwrap_or(DEFAULT_RENDER_SAMPLE_RATE);
+    let mut synth = new_synth(opts, sample_rate, sound);
+    let start = Instant::now();
+    if let Err(e) = synth.render_to_wav(path, &events, duration, opts.bit_depth) {
+        log::error!("Failed to render {}: {}", path.display(), e);
+        process::exit(1);
+    }
+    log::info!(
+        "Rendered {:.1} s of audio to {} in {:.1} s",
+        duration.as_secs_f32(),
+        path.display(),
+        start.elapsed().as_secs_f32()
+    );
+    #[cfg(feature = "profile")]
+    if let Some(profile) = synth.profile() {
+        log::info!("{}", profile);
+    }
+}
+
+/// The patches to start with, from `--preset` and `--morph`.
+#[derive(Clone, Default)]
+struct Sound {
+    patch: Option<Patch>,
+    /// Where `patch` came from, unless it's built in.
+    path: Option<PathBuf>,
+    morph: Option<Patch>,
+}
+
+/// Gather the factory presets and any in the directory given with `--preset-dir`, and load the
+/// ones given with `--preset` and `--morph`, if any.
+fn load_presets(opts: &Options) -> (PresetBank, Sound) {
+    let mut presets = PresetBank::factory();
+    // more presets are optional, so it's fine if the default directory isn't there
+    if let Ok(more) = PresetBank::scan(&opts.preset_dir) {
+        presets.extend(more);
+    }
+
+    let mut load = |name: &String| {
+        presets.load_by_name(name).unwrap_or_else(|e| {
+            log::error!("Failed to load preset {}: {}", name, e);
+            process::exit(1);
+        })
+    };
+    let sound = Sound {
+        patch: opts.preset.as_ref().map(&mut load).or_else(|| {
+            let category = opts.random?;
+            let seed = opts.seed.unwrap_or_else(|| {
+                SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0, |t| t.subsec_nanos())
+            });
+            let patch = Randomizer::new(seed).patch(category);
+            // on stdout, so it can be redirected to a file and kept
+            patch.write(stdout()).expect("Failed to print patch");
+            Some(patch)
+        }),
+        morph: opts.morph.as_ref().map(&mut load),
+        path: opts
+            .preset
+            .as_ref()
+            .and_then(|name| presets.find(name))
+            .and_then(|index| presets.presets()[index].path())
+            .map(Path::to_path_buf),
+    };
+    (presets, sound)
+}
+
+/// Check that the device given with `--audio-device` exists, or let the user pick one.
+fn select_audio_device(requested: &str) -> String {
+    let names = backend::output_device_names();
+    if names.iter().any(|name| name == requested) {
+        return requested.to_string();
+    }
+
+    match names.as_slice() {
+        [] => {
+            log::error!("No audio output devices available");
+            process::exit(101);
+        }
+        otherwise => {
+            if requested != "ask" {
+                println!("There is no audio output device called {}", requested);
+            }
+            println!("Available audio output devices:");
+            for (i, name) in otherwise.iter().enumerate() {
+                println!("\t{}: {}", i, name);
+            }
+            print!("Please select output device: ");
+            stdout().flush().unwrap();
+            let mut input = String::new();
+            stdin().read_line(&mut input).unwrap();
+            otherwise
+                .get(
+                    input
+                        .trim()
+                        .parse::<usize>()
+                        .expect("Input was not an integer"),
+                )
+                .expect("Selected index is out of range")
+                .clone()
+        }
+    }
+}
+
+fn listen(midi_state: MidiState, watchdog: bool, tui: bool) -> MidiState {
     let mut midi_in = MidiInput::new("basic-synth").expect("Could not create MIDI Input object");
     midi_in.ignore(Ignore::None);
 
     let in_ports = midi_in.ports();
     let in_port = match in_ports.as_slice() {
         [] => {
-            eprintln!("No MIDI ports available");
+            log::error!("No MIDI ports available");
             process::exit(101);
         }
         [only_one] => {
-            eprintln!(
+            log::info!(
                 "Connecting to MIDI port: {}",
                 midi_in.port_name(only_one).unwrap()
             );
@@ -57,71 +372,345 @@ fn main() {
         }
     };
 
-    let _conn_in = midi_in
-        .connect(in_port, "basic-synth-midi-in", process_midi, run_synth_bg())
+    let port_name = midi_in.port_name(in_port).unwrap();
+    let commands = midi_state.commands.clone();
+    let presets = midi_state.presets.clone();
+    let conn_in = midi_in
+        .connect(in_port, "basic-synth-midi-in", process_midi, midi_state)
         .expect("Failed to connect to MIDI source");
 
-    let mut input = String::new();
-    stdin().read_line(&mut input).unwrap();
+    if watchdog {
+        wait_watching_port(&port_name, &commands, presets, tui);
+    } else {
+        interact(&presets, tui);
+    }
+
+    conn_in.close().1
+}
+
+/// Play what's sent to a network MIDI session at `addr`, until the user quits as for `interact`,
+/// releasing all notes whenever a sender leaves if the watchdog is enabled.
+#[cfg(feature = "rtp-midi")]
+fn listen_network(addr: &str, mut midi_state: MidiState, watchdog: bool, tui: bool) -> MidiState {
+    use {
+        cli::rtp_midi::{Event, Session},
+        std::sync::atomic::{AtomicBool, Ordering},
+    };
+
+    let mut session = Session::bind(addr).unwrap_or_else(|e| {
+        log::error!("Failed to listen for network MIDI at {}: {}", addr, e);
+        process::exit(1);
+    });
+    let presets = midi_state.presets.clone();
+    let stop = Arc::new(AtomicBool::new(false));
+    let receiver = {
+        let stop = stop.clone();
+        thread::spawn(move || {
+            let start = Instant::now();
+            let result = session.run(&stop, |event| match event {
+                Event::Midi(msg) => midi_state.dispatch(start.elapsed().as_micros() as u64, &msg),
+                Event::Left if watchdog => midi_state.commands.send_midi(&all_notes_off()),
+                Event::Left => (),
+            });
+            if let Err(e) = result {
+                log::error!("Network MIDI stopped working: {}", e);
+            }
+            midi_state
+        })
+    };
+
+    interact(&presets, tui);
+    stop.store(true, Ordering::Relaxed);
+    receiver.join().expect("The network MIDI thread panicked")
 }
 
-fn process_midi(_stamp: u64, message: &[u8], tx: &mut Sender<MidiMsg>) {
-    let (msg, _len) = MidiMsg::from_midi(message).expect("Bad MIDI data");
-    tx.send(msg)
-        .expect("Failed to send message to synth thread");
+/// Wait for the user to quit, in the terminal UI if asked for and there's a terminal for it, or
+/// else as for `read_keyboard`.
+#[cfg_attr(not(feature = "tui"), allow(unused_variables))]
+fn interact(presets: &Presets, tui: bool) {
+    #[cfg(feature = "tui")]
+    if tui {
+        match cli::tui::run(&presets.commands, presets) {
+            Ok(()) => return,
+            Err(e) => log::error!("Failed to start the terminal UI: {}", e),
+        }
+    }
+    read_keyboard(presets);
 }
 
-fn run_synth_bg() -> Sender<MidiMsg> {
-    let (tx, rx) = mpsc::channel::<MidiMsg>();
+/// Save presets as the user types their names, until they just press Enter.
+fn read_keyboard(presets: &Presets) {
+    eprintln!("Type a name and press Enter to save the sound as a preset, or just Enter to quit");
+    for line in stdin().lines() {
+        match line.as_deref().map(str::trim) {
+            Ok("") | Err(_) => break,
+            Ok(name) => presets.save(Some(name)),
+        }
+    }
+}
 
+/// Wait for the user to quit as for `interact`, releasing all notes if the MIDI port disappears
+/// meanwhile.
+fn wait_watching_port(port_name: &str, commands: &CommandSender, presets: Presets, tui: bool) {
+    let (done_tx, done_rx) = mpsc::channel();
     thread::spawn(move || {
-        let mut synth = Synth::new(8);
-        let (_stream, stream_handle) = OutputStream::try_default().unwrap();
-        let sink = Sink::try_new(&stream_handle).unwrap();
-
-        loop {
-            match rx.try_recv() {
-                Err(TryRecvError::Empty) => {
-                    // don't get ahead of ourselves
-                    if sink.len() < BLOCKS_BUFFER {
-                        let buffer: Vec<f32> =
-                            (0..block_size()).flat_map(|_| synth.next()).collect();
-                        sink.append(SamplesBuffer::new(1, unsafe { SAMPLE_RATE }, buffer));
-                    }
-                }
-                Err(TryRecvError::Disconnected) => {
-                    panic!(
-                        "Synth thread disconnected from main thread unexpectedly. Shutting down."
-                    );
-                }
-                Ok(MidiMsg::ChannelVoice {
-                    msg: ChannelVoiceMsg::NoteOn { note, velocity },
-                    ..
-                }) => {
-                    if let Err(_) = synth.try_begin_note(note, velocity) {
-                        eprintln!(
-                            "Out of voices. Note requested was {} with velocity {}",
-                            note, velocity
-                        );
-                    }
-                }
-                Ok(MidiMsg::ChannelVoice {
-                    msg: ChannelVoiceMsg::NoteOff { note, .. },
-                    ..
-                }) => {
-                    if let Err(_) = synth.try_end_note(note) {
-                        eprintln!(
-                            "Expected a voice playing note {} but could not find one",
-                            note
-                        );
-                    }
-                }
-                Ok(other_msg) => {
-                    println!("{:?}", other_msg);
+        interact(&presets, tui);
+        done_tx.send(()).unwrap();
+    });
+
+    let watcher =
+        MidiInput::new("basic-synth-watchdog").expect("Could not create MIDI Input object");
+    let mut connected = true;
+    while let Err(RecvTimeoutError::Timeout) = done_rx.recv_timeout(PORT_POLL_INTERVAL) {
+        let present = watcher
+            .ports()
+            .iter()
+            .any(|p| watcher.port_name(p).is_ok_and(|name| name == port_name));
+        if connected && !present {
+            log::warn!("MIDI port {} went away, releasing all notes", port_name);
+            commands.send_midi(&all_notes_off());
+        }
+        connected = present;
+    }
+}
+
+fn all_notes_off() -> MidiMsg {
+    MidiMsg::ChannelMode {
+        channel: Channel::Ch1,
+        msg: ChannelModeMsg::AllNotesOff,
+    }
+}
+
+fn replay(path: &Path, midi_state: &mut MidiState) {
+    let events = File::open(path).and_then(smf::read).unwrap_or_else(|e| {
+        log::error!("Failed to read {}: {}", path.display(), e);
+        process::exit(1);
+    });
+    let mut playback = Playback::new(events);
+    log::info!(
+        "Replaying {} ({:.1} s)",
+        path.display(),
+        playback.duration().as_secs_f32()
+    );
+
+    let start = Instant::now();
+    while let Some(next) = playback.next_time() {
+        thread::sleep(next.saturating_sub(start.elapsed()));
+        for event in playback.advance(start.elapsed()) {
+            midi_state.dispatch(event.time.as_micros() as u64, &event.msg);
+        }
+    }
+}
+
+struct MidiState {
+    commands: CommandSender,
+    presets: Presets,
+    monitor: Option<Monitor>,
+    recorder: Option<Recorder>,
+}
+
+impl MidiState {
+    fn dispatch(&mut self, stamp: u64, msg: &MidiMsg) {
+        if let Some(monitor) = &mut self.monitor {
+            monitor.log(stamp, msg);
+        }
+        if let Some(recorder) = &mut self.recorder {
+            recorder.record(stamp, msg);
+        }
+        if let MidiMsg::ChannelVoice { msg, .. } = msg {
+            match msg {
+                ChannelVoiceMsg::ProgramChange { program } => {
+                    self.presets.change(*program as usize)
                 }
+                ChannelVoiceMsg::ControlChange {
+                    control: ControlChange::GeneralPurpose5(value),
+                } if *value >= 64 => self.presets.save(None),
+                _ => (),
             }
         }
+        self.commands.send_midi(msg);
+    }
+}
+
+/// The preset bank, shared between the MIDI and keyboard threads.
+#[derive(Clone)]
+struct Presets {
+    bank: Arc<Mutex<PresetBank>>,
+    /// The index of the preset playing, unless the sound didn't come from one.
+    current: Arc<Mutex<Option<usize>>>,
+    /// Where presets are saved.
+    dir: PathBuf,
+    commands: CommandSender,
+    watcher: Option<PatchWatcher>,
+}
+
+impl Presets {
+    /// Switch to the preset at `index`, as for Program Change.
+    fn change(&self, index: usize) {
+        let mut bank = self.bank.lock().unwrap();
+        if index >= bank.len() {
+            return;
+        }
+        if let Err(e) = self.switch(&mut bank, index) {
+            log::error!("Failed to load preset {}: {}", index, e);
+        }
+    }
+
+    fn switch(&self, bank: &mut PresetBank, index: usize) -> io::Result<()> {
+        let patch = bank.load(index)?;
+        log::info!("Switching to preset {}", bank.presets()[index].name);
+        self.commands.send_patch(patch);
+        *self.current.lock().unwrap() = Some(index);
+        self.watch(bank, index);
+        Ok(())
+    }
+
+    /// Save the sound playing now as a preset, called `name` or else the first free `saved-N`.
+    fn save(&self, name: Option<&str>) {
+        let patch = match self.commands.request_patch(SAVE_TIMEOUT) {
+            Some(patch) => patch,
+            None => {
+                log::error!("Failed to save preset: the synth did not respond");
+                return;
+            }
+        };
+        let mut bank = self.bank.lock().unwrap();
+        let name = name.map_or_else(
+            || {
+                (1..)
+                    .map(|n| format!("saved-{}", n))
+                    .find(|name| bank.find(name).is_none())
+                    .unwrap()
+            },
+            str::to_string,
+        );
+        match bank.save(&self.dir, &name, &patch) {
+            Ok(index) => {
+                log::info!("Saved preset {} as number {}", name, index);
+                *self.current.lock().unwrap() = Some(index);
+                self.watch(&bank, index);
+            }
+            Err(e) => log::error!("Failed to save preset {}: {}", name, e),
+        }
+    }
+
+    fn watch(&self, bank: &PresetBank, index: usize) {
+        if let Some(watcher) = &self.watcher {
+            watcher.watch(bank.presets()[index].path().map(Path::to_path_buf));
+        }
+    }
+}
+
+impl cli::remote::Presets for Presets {
+    fn names(&self) -> Vec<String> {
+        let bank = self.bank.lock().unwrap();
+        bank.presets().iter().map(full_name).collect()
+    }
+
+    fn load(&self, name: &str) -> Result<(), String> {
+        let mut bank = self.bank.lock().unwrap();
+        let index = bank
+            .find(name)
+            .ok_or_else(|| format!("No preset called {}", name))?;
+        self.switch(&mut bank, index)
+            .map_err(|e| format!("Failed to load preset {}: {}", name, e))
+    }
+}
+
+#[cfg(feature = "tui")]
+impl cli::tui::Presets for Presets {
+    fn current(&self) -> Option<String> {
+        let index = (*self.current.lock().unwrap())?;
+        Some(full_name(&self.bank.lock().unwrap().presets()[index]))
+    }
+
+    fn step(&self, offset: isize) {
+        let len = self.bank.lock().unwrap().len() as isize;
+        if len == 0 {
+            return;
+        }
+        let index = match *self.current.lock().unwrap() {
+            Some(current) => (current as isize + offset).rem_euclid(len),
+            None if offset > 0 => offset - 1,
+            None => len + offset,
+        };
+        self.change(index.rem_euclid(len) as usize);
+    }
+
+    fn save(&self) {
+        Presets::save(self, None);
+    }
+}
+
+/// A preset's name, as `category/name` if it's in a category.
+fn full_name(preset: &Preset) -> String {
+    match &preset.category {
+        Some(category) => format!("{}/{}", category, preset.name),
+        None => preset.name.clone(),
+    }
+}
+
+fn process_midi(stamp: u64, message: &[u8], state: &mut MidiState) {
+    // a garbled message isn't worth stopping for
+    if let Ok((msg, _len)) = MidiMsg::from_midi(message) {
+        state.dispatch(stamp, &msg);
+    }
+}
+
+fn new_synth(opts: &Options, sample_rate: u32, sound: &Sound) -> Synth {
+    build_synth(opts, sample_rate, sound, Synth::builder())
+}
+
+/// Finish setting up a synth from `builder`, with the options and sound given.
+fn build_synth(opts: &Options, sample_rate: u32, sound: &Sound, builder: SynthBuilder) -> Synth {
+    let mut builder = builder
+        .sample_rate(sample_rate)
+        .oversampling(opts.oversampling)
+        .block_size(
+            opts.block_size
+                .unwrap_or((sample_rate / BLOCKS_PER_SECOND) as usize),
+        )
+        .note_timeout(opts.note_timeout);
+    if let Some(seed) = opts.seed {
+        builder = builder.seed(seed);
+    }
+    let mut synth = builder.build().expect("Could not create synth");
+    #[cfg(feature = "profile")]
+    synth.set_profiling(opts.profile);
+    let patch = with_effects(opts, sound.patch.clone().unwrap_or_default());
+    match &sound.morph {
+        Some(morph) => synth.set_morph_patches(patch, with_effects(opts, morph.clone())),
+        None => synth.load_patch(&patch),
+    }
+    synth
+}
+
+/// Add the effects given with `--effect`, `--voice-effect` and `--send` to a patch, so that they
+/// stay put when morphing.
+fn with_effects(opts: &Options, mut patch: Patch) -> Patch {
+    for (name, placement) in &opts.effects {
+        let mut effect = EffectPatch::new(name);
+        effect.placement = *placement;
+        patch.effects.push(effect);
+    }
+    for (index, name) in opts.sends.iter().enumerate() {
+        let mut effect = EffectPatch::new(name);
+        effect.placement = Placement::Send(index);
+        effect.parameters.push(("mix".to_string(), 1.0));
+        patch.effects.push(effect);
+        patch.send_levels[index] = SEND_LEVEL;
+    }
+    patch
+}
+
+fn run_synth_bg(opts: &Options, sound: Sound) -> CommandSender {
+    let (sender, commands) = audio::command_queue();
+    let opts = opts.clone();
+
+    thread::spawn(move || {
+        let make_synth = |sample_rate| new_synth(&opts, sample_rate, &sound);
+        audio::run(&opts, make_synth, commands);
     });
 
-    tx
+    sender
 }
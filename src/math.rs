@@ -0,0 +1,393 @@
+//! The floating point functions which `std` would otherwise provide, for `no_std` builds, where
+//! only basic arithmetic is built in.
+//!
+//! They're worked out in `f64` with plain series and bit twiddling, accurate to about 1e-12,
+//! which is more than enough for audio even if not correctly rounded like `std`'s.
+
+use core::f64::consts::{FRAC_PI_2, LN_10, LN_2, PI, TAU};
+
+/// The methods `std` adds to `f32` and `f64`, under the same names, so that code calling them
+/// reads the same either way. (`abs` is built in.) Where `Sample` is in scope as well, `sin` has
+/// to be called as `Sample::sin`, as it's then ambiguous.
+// some are only needed by the built-in effects
+#[cfg_attr(not(feature = "effects"), allow(dead_code))]
+pub(crate) trait Float: Copy {
+    fn floor(self) -> Self;
+    fn ceil(self) -> Self;
+    fn round(self) -> Self;
+    fn trunc(self) -> Self;
+    fn fract(self) -> Self;
+    fn rem_euclid(self, rhs: Self) -> Self;
+    fn sqrt(self) -> Self;
+    fn exp(self) -> Self;
+    fn ln(self) -> Self;
+    fn log2(self) -> Self;
+    fn log10(self) -> Self;
+    fn powf(self, n: Self) -> Self;
+    fn powi(self, n: i32) -> Self;
+    fn sin(self) -> Self;
+    fn cos(self) -> Self;
+    fn tan(self) -> Self;
+    fn tanh(self) -> Self;
+}
+
+impl Float for f64 {
+    fn floor(self) -> Self {
+        let truncated = Float::trunc(self);
+        if truncated > self {
+            truncated - 1.0
+        } else {
+            truncated
+        }
+    }
+
+    fn ceil(self) -> Self {
+        -Float::floor(-self)
+    }
+
+    /// Halfway cases round away from zero.
+    fn round(self) -> Self {
+        let truncated = Float::trunc(self);
+        if (self - truncated).abs() >= 0.5 {
+            truncated + if self < 0.0 { -1.0 } else { 1.0 }
+        } else {
+            truncated
+        }
+    }
+
+    fn trunc(self) -> Self {
+        // anything this big is a whole number already (and so are infinities), and NaN fails
+        // the comparison
+        if self.abs() < 4503599627370496.0 {
+            self as i64 as f64
+        } else {
+            self
+        }
+    }
+
+    fn fract(self) -> Self {
+        self - Float::trunc(self)
+    }
+
+    fn rem_euclid(self, rhs: Self) -> Self {
+        let remainder = self % rhs;
+        if remainder < 0.0 {
+            remainder + rhs.abs()
+        } else {
+            remainder
+        }
+    }
+
+    fn sqrt(self) -> Self {
+        if self < 0.0 {
+            return f64::NAN;
+        }
+        if self == 0.0 || self == f64::INFINITY || self.is_nan() {
+            return self;
+        }
+        // halving the exponent gets within a few percent, and each Newton step doubles the
+        // number of correct digits from there
+        let mut root = f64::from_bits((self.to_bits() >> 1) + (1023 << 51));
+        for _ in 0..6 {
+            root = 0.5 * (root + self / root);
+        }
+        root
+    }
+
+    fn exp(self) -> Self {
+        if self.is_nan() {
+            return self;
+        }
+        if self > 709.0 {
+            return f64::INFINITY;
+        }
+        if self < -745.0 {
+            return 0.0;
+        }
+        // e^x = 2^k * e^r, with r small enough for the series to converge quickly
+        let k = Float::round(self / LN_2);
+        let r = self - k * LN_2;
+        let mut term = 1.0;
+        let mut sum = 1.0;
+        for n in 1..=14 {
+            term *= r / n as f64;
+            sum += term;
+        }
+        // 2^k in two halves, as the most negative powers can't be written directly
+        let half = k as i64 / 2;
+        let power = |k: i64| f64::from_bits(((k + 1023) as u64) << 52);
+        sum * power(half) * power(k as i64 - half)
+    }
+
+    fn ln(self) -> Self {
+        if self < 0.0 || self.is_nan() {
+            return f64::NAN;
+        }
+        if self == 0.0 {
+            return f64::NEG_INFINITY;
+        }
+        if self == f64::INFINITY {
+            return self;
+        }
+        // split into a mantissa from √½ to √2 and a power of two, bringing denormals up first
+        let (x, offset) = if self < f64::MIN_POSITIVE {
+            (self * 18014398509481984.0, -54)
+        } else {
+            (self, 0)
+        };
+        let bits = x.to_bits();
+        let mut exponent = ((bits >> 52) & 0x7ff) as i64 - 1023 + offset;
+        let mut mantissa = f64::from_bits((bits & ((1 << 52) - 1)) | (1023 << 52));
+        if mantissa > core::f64::consts::SQRT_2 {
+            mantissa /= 2.0;
+            exponent += 1;
+        }
+        // ln(m) = 2 atanh(s), whose series converges fast for small s
+        let s = (mantissa - 1.0) / (mantissa + 1.0);
+        let s2 = s * s;
+        let mut term = s;
+        let mut sum = 0.0;
+        for n in (1..=23).step_by(2) {
+            sum += term / n as f64;
+            term *= s2;
+        }
+        2.0 * sum + exponent as f64 * LN_2
+    }
+
+    fn log2(self) -> Self {
+        Float::ln(self) / LN_2
+    }
+
+    fn log10(self) -> Self {
+        Float::ln(self) / LN_10
+    }
+
+    fn powf(self, n: Self) -> Self {
+        if n == 0.0 {
+            return 1.0;
+        }
+        if self < 0.0 {
+            // only whole powers of negative numbers are real
+            return if Float::trunc(n) != n {
+                f64::NAN
+            } else if Float::rem_euclid(n, 2.0) == 1.0 {
+                -Float::powf(-self, n)
+            } else {
+                Float::powf(-self, n)
+            };
+        }
+        if self == 0.0 {
+            return if n > 0.0 { 0.0 } else { f64::INFINITY };
+        }
+        Float::exp(n * Float::ln(self))
+    }
+
+    fn powi(self, n: i32) -> Self {
+        let mut result = 1.0;
+        let mut base = self;
+        let mut power = n.unsigned_abs();
+        while power > 0 {
+            if power & 1 == 1 {
+                result *= base;
+            }
+            base *= base;
+            power >>= 1;
+        }
+        if n < 0 {
+            1.0 / result
+        } else {
+            result
+        }
+    }
+
+    fn sin(self) -> Self {
+        if !self.is_finite() {
+            return f64::NAN;
+        }
+        // bring into -π to π, then -π/2 to π/2, where the series converges quickly
+        let mut x = self - TAU * Float::round(self / TAU);
+        if x > FRAC_PI_2 {
+            x = PI - x;
+        } else if x < -FRAC_PI_2 {
+            x = -PI - x;
+        }
+        let x2 = x * x;
+        let mut term = x;
+        let mut sum = x;
+        for n in (3..=21).step_by(2) {
+            term *= -x2 / ((n - 1) * n) as f64;
+            sum += term;
+        }
+        sum
+    }
+
+    fn cos(self) -> Self {
+        Float::sin(self + FRAC_PI_2)
+    }
+
+    fn tan(self) -> Self {
+        Float::sin(self) / Float::cos(self)
+    }
+
+    fn tanh(self) -> Self {
+        if self.abs() > 20.0 {
+            return if self < 0.0 { -1.0 } else { 1.0 };
+        }
+        if self.abs() < 1e-4 {
+            return self - self * self * self / 3.0;
+        }
+        let e = Float::exp(2.0 * self);
+        (e - 1.0) / (e + 1.0)
+    }
+}
+
+/// Worked out in `f64`, then rounded.
+impl Float for f32 {
+    fn floor(self) -> Self {
+        Float::floor(self as f64) as f32
+    }
+
+    fn ceil(self) -> Self {
+        Float::ceil(self as f64) as f32
+    }
+
+    fn round(self) -> Self {
+        Float::round(self as f64) as f32
+    }
+
+    fn trunc(self) -> Self {
+        Float::trunc(self as f64) as f32
+    }
+
+    fn fract(self) -> Self {
+        Float::fract(self as f64) as f32
+    }
+
+    fn rem_euclid(self, rhs: Self) -> Self {
+        Float::rem_euclid(self as f64, rhs as f64) as f32
+    }
+
+    fn sqrt(self) -> Self {
+        Float::sqrt(self as f64) as f32
+    }
+
+    fn exp(self) -> Self {
+        Float::exp(self as f64) as f32
+    }
+
+    fn ln(self) -> Self {
+        Float::ln(self as f64) as f32
+    }
+
+    fn log2(self) -> Self {
+        Float::log2(self as f64) as f32
+    }
+
+    fn log10(self) -> Self {
+        Float::log10(self as f64) as f32
+    }
+
+    fn powf(self, n: Self) -> Self {
+        Float::powf(self as f64, n as f64) as f32
+    }
+
+    fn powi(self, n: i32) -> Self {
+        Float::powi(self as f64, n) as f32
+    }
+
+    fn sin(self) -> Self {
+        Float::sin(self as f64) as f32
+    }
+
+    fn cos(self) -> Self {
+        Float::cos(self as f64) as f32
+    }
+
+    fn tan(self) -> Self {
+        Float::tan(self as f64) as f32
+    }
+
+    fn tanh(self) -> Self {
+        Float::tanh(self as f64) as f32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Float;
+
+    /// Values across the ranges the synth uses, and either side of the awkward ones.
+    const VALUES: &[f64] = &[
+        -1000.5, -10.0, -3.7, -2.5, -1.0, -0.5, -1e-3, 0.0, 1e-6, 0.25, 0.5, 0.75, 1.0, 1.5, 2.5,
+        3.2, 6.5, 10.0, 440.0, 20000.0, 1e12,
+    ];
+
+    fn assert_close(name: &str, x: f64, ours: f64, theirs: f64) {
+        let tolerance = 1e-9 * theirs.abs().max(1.0);
+        assert!(
+            (ours - theirs).abs() <= tolerance,
+            "{}({}): {} rather than {}",
+            name,
+            x,
+            ours,
+            theirs
+        );
+    }
+
+    #[test]
+    fn rounding_matches_std() {
+        for &x in VALUES {
+            assert_eq!(Float::floor(x), x.floor(), "floor({})", x);
+            assert_eq!(Float::ceil(x), x.ceil(), "ceil({})", x);
+            assert_eq!(Float::round(x), x.round(), "round({})", x);
+            assert_eq!(Float::trunc(x), x.trunc(), "trunc({})", x);
+            assert_eq!(Float::fract(x), x.fract(), "fract({})", x);
+            assert_eq!(
+                Float::rem_euclid(x, 3.0),
+                x.rem_euclid(3.0),
+                "rem_euclid({})",
+                x
+            );
+        }
+        assert!(Float::floor(f64::NAN).is_nan());
+        assert_eq!(Float::floor(f64::INFINITY), f64::INFINITY);
+    }
+
+    #[test]
+    fn functions_match_std() {
+        for &x in VALUES {
+            if x > 0.0 {
+                assert_close("sqrt", x, Float::sqrt(x), x.sqrt());
+                assert_close("ln", x, Float::ln(x), x.ln());
+                assert_close("log2", x, Float::log2(x), x.log2());
+                assert_close("log10", x, Float::log10(x), x.log10());
+                assert_close("powf", x, Float::powf(x, 0.3), x.powf(0.3));
+            }
+            if x.abs() <= 100.0 {
+                assert_close("exp", x, Float::exp(x), x.exp());
+                assert_close("sin", x, Float::sin(x), x.sin());
+                assert_close("cos", x, Float::cos(x), x.cos());
+                assert_close("tanh", x, Float::tanh(x), x.tanh());
+                assert_close("powi", x, Float::powi(x, 3), x.powi(3));
+            }
+            if x.abs() <= 10.0 && x.cos().abs() > 0.01 {
+                assert_close("tan", x, Float::tan(x), x.tan());
+            }
+        }
+    }
+
+    #[test]
+    fn f32_matches_std() {
+        for &x in VALUES {
+            let x = x as f32;
+            assert_eq!(Float::floor(x), x.floor(), "floor({})", x);
+            assert_eq!(Float::round(x), x.round(), "round({})", x);
+            if x > 0.0 {
+                assert!((Float::ln(x) - x.ln()).abs() <= 1e-5 * x.ln().abs().max(1.0));
+            }
+            if x.abs() <= 100.0 {
+                assert!((Float::sin(x) - x.sin()).abs() <= 1e-6, "sin({})", x);
+            }
+        }
+    }
+}
@@ -0,0 +1,212 @@
+//! Morphing between two patches with a single control, as set up with `Synth::set_morph_patches`
+//! and moved with `Synth::set_morph`.
+//!
+//! Settings which vary smoothly are blended. Anything else (the waveform, which effects there are,
+//! and discrete effect parameters) comes from whichever patch is nearer, switching over halfway.
+//! Effects are blended when both patches have them in the same place: the first delay on a send
+//! bus in one patch with the first delay on that bus in the other, and so on.
+
+use {
+    alloc::{string::String, vec::Vec},
+    core::{array, mem},
+};
+
+use crate::{
+    effects::{self, EffectsChain, Placement},
+    holds,
+    param::ParamId,
+    patch::{EffectPatch, Patch},
+    placed,
+    prepare::PatchPreparer,
+    send::SENDS,
+    AdsrConfig, Synth,
+};
+
+#[cfg(not(any(feature = "std", test)))]
+use crate::math::Float;
+
+/// One effect parameter which differs between the patches.
+struct Blend {
+    placement: Placement,
+    /// Where the effect is in its chain.
+    position: usize,
+    name: String,
+    index: usize,
+    from: f32,
+    to: f32,
+}
+
+/// An effects chain for each place the synth has one.
+struct Chains {
+    master: EffectsChain,
+    voices: Vec<EffectsChain>,
+    sends: [EffectsChain; SENDS],
+}
+
+pub(crate) struct Morph {
+    patches: [Patch; 2],
+    /// The effect parameters to blend, going from each patch towards the other.
+    blends: [Vec<Blend>; 2],
+    /// Which of the patches was last loaded in full.
+    loaded: Option<usize>,
+    /// Chains holding the effects of the patch which isn't loaded, made up front and swapped in
+    /// when switching over, so that no effects need making then (often on the audio thread).
+    spare: Chains,
+}
+
+impl Morph {
+    /// Set up a morph for the synth `preparer` came from. Making the spare chains allocates, so
+    /// this can be done on another thread.
+    pub(crate) fn new(preparer: &PatchPreparer, a: Patch, b: Patch) -> Self {
+        let spare = Chains {
+            master: preparer.chain(&b, Placement::Master),
+            voices: (0..preparer.voices)
+                .map(|_| preparer.chain(&b, Placement::Voice))
+                .collect(),
+            sends: array::from_fn(|send| preparer.chain(&b, Placement::Send(send))),
+        };
+        Self {
+            blends: [blends(&a, &b), blends(&b, &a)],
+            patches: [a, b],
+            loaded: None,
+            spare,
+        }
+    }
+
+    pub(crate) fn patches(&self) -> (&Patch, &Patch) {
+        (&self.patches[0], &self.patches[1])
+    }
+
+    /// Whether the synth's effects are patch A's, so that starting from there needs none made.
+    pub(crate) fn starts_from(&self, synth: &Synth) -> bool {
+        let a = &self.patches[0];
+        holds(&synth.effects, placed(a, Placement::Master))
+            && (synth.voices.iter()).all(|v| holds(&v.effects, placed(a, Placement::Voice)))
+            && (synth.sends.iter().enumerate())
+                .all(|(index, send)| holds(send.effects(), placed(a, Placement::Send(index))))
+    }
+
+    /// Set the synth up somewhere between the two patches, from 0 (A) to 1 (B).
+    pub(crate) fn apply(&mut self, synth: &mut Synth, amount: f32) {
+        // work from the nearer patch towards the other, so that it's the one switched to
+        let (nearer, t) = if amount < 0.5 {
+            (0, amount)
+        } else {
+            (1, 1.0 - amount)
+        };
+        let (near, far) = (&self.patches[nearer], &self.patches[1 - nearer]);
+        if self.loaded != Some(nearer) {
+            swap_in(synth, &mut self.spare, near);
+            synth.set_patch(near);
+            self.loaded = Some(nearer);
+        }
+
+        let lerp = |from: f32, to: f32| from + (to - from) * t;
+        synth.set_detune(lerp(near.detune, far.detune));
+        // geometrically, so that the sweep sounds even
+        synth.set_cutoff(near.cutoff * (far.cutoff / near.cutoff).powf(t));
+        synth.set_amp_envelope(AdsrConfig {
+            attack_time: lerp(near.amp_envelope.attack_time, far.amp_envelope.attack_time),
+            decay_time: lerp(near.amp_envelope.decay_time, far.amp_envelope.decay_time),
+            sustain_amount: lerp(
+                near.amp_envelope.sustain_amount,
+                far.amp_envelope.sustain_amount,
+            ),
+            release_time: lerp(
+                near.amp_envelope.release_time,
+                far.amp_envelope.release_time,
+            ),
+        });
+        synth.set_stereo_spread(lerp(near.stereo_spread, far.stereo_spread));
+        synth.set_volume(lerp(near.volume, far.volume));
+        for send in 0..SENDS {
+            synth.set_send_level(send, lerp(near.send_levels[send], far.send_levels[send]));
+            let _ = synth.set_param(
+                ParamId::ReturnLevel(send),
+                lerp(near.return_levels[send], far.return_levels[send]),
+            );
+        }
+
+        for blend in &self.blends[nearer] {
+            let live = synth
+                .effects_at(blend.placement)
+                .and_then(|c| c.get(blend.position));
+            if live.is_some_and(|e| e.name() == blend.name) {
+                let param = ParamId::Effect {
+                    placement: blend.placement,
+                    slot: blend.position,
+                    parameter: blend.index,
+                };
+                let _ = synth.set_param(param, lerp(blend.from, blend.to));
+            }
+        }
+    }
+}
+
+/// Swap in whichever spare chains hold `patch`'s effects where the synth's don't, so that loading
+/// it can keep them rather than making new ones.
+fn swap_in(synth: &mut Synth, spare: &mut Chains, patch: &Patch) {
+    let tempo = synth.tempo();
+    let swap = |live: &mut EffectsChain, spare: &mut EffectsChain, placement| {
+        if !holds(live, placed(patch, placement)) && holds(spare, placed(patch, placement)) {
+            mem::swap(live, spare);
+            live.set_tempo(tempo);
+            // so that an old tail doesn't play out if it's swapped back in
+            spare.reset();
+        }
+    };
+    swap(&mut synth.effects, &mut spare.master, Placement::Master);
+    for (voice, spare) in synth.voices.iter_mut().zip(&mut spare.voices) {
+        swap(&mut voice.effects, spare, Placement::Voice);
+    }
+    let sends = synth.sends.iter_mut().zip(&mut spare.sends).enumerate();
+    for (index, (send, spare)) in sends {
+        swap(send.effects_mut(), spare, Placement::Send(index));
+    }
+}
+
+/// Find the effect parameters to blend from one patch to another, and where the effects will be
+/// once `near` is loaded.
+fn blends(near: &Patch, far: &Patch) -> Vec<Blend> {
+    let mut blends = Vec::new();
+    for (index, effect) in near.effects.iter().enumerate() {
+        let same = |e: &&EffectPatch| e.name == effect.name && e.placement == effect.placement;
+        let nth = near.effects[..index].iter().filter(same).count();
+        let other = match far.effects.iter().filter(same).nth(nth) {
+            Some(other) => other,
+            None => continue,
+        };
+        let position = near.effects[..index]
+            .iter()
+            .filter(|e| e.placement == effect.placement)
+            .count();
+
+        // parameters left out of either patch are at their defaults
+        let defaults = match effects::by_name(&effect.name) {
+            Some(defaults) => defaults,
+            None => continue,
+        };
+        for (index, name) in defaults.parameter_names().iter().enumerate() {
+            let default = defaults.parameter(index).unwrap_or(0.0);
+            let value = |patch: &EffectPatch| {
+                patch
+                    .parameters
+                    .iter()
+                    .find(|(n, _)| n == name)
+                    .map_or(default, |(_, v)| *v)
+            };
+            let (from, to) = (value(effect), value(other));
+            if !defaults.is_discrete(index) && from != to {
+                blends.push(Blend {
+                    placement: effect.placement,
+                    position,
+                    name: effect.name.clone(),
+                    index,
+                    from,
+                    to,
+                });
+            }
+        }
+    }
+    blends
+}
@@ -0,0 +1,300 @@
+//! Every value which can be controlled, addressed the same way, for presets, controller mappings
+//! and user interfaces: `Synth::set_param` and `Synth::get_param` with a `ParamId`, and
+//! `Synth::param_info` to find out what the values mean.
+
+use alloc::boxed::Box;
+
+use crate::{
+    effects::{Placement, DEFAULT_TEMPO},
+    send::SENDS,
+    AdsrConfig, Waveform, DEFAULT_CUTOFF, DEFAULT_DETUNE, DEFAULT_STEREO_SPREAD, DEFAULT_VOLUME,
+    MAX_TEMPO, MIN_TEMPO, PITCH_BEND_RANGE,
+};
+
+#[cfg(not(any(feature = "std", test)))]
+use crate::math::Float;
+#[cfg(feature = "presets")]
+use {
+    crate::patch::{parse_placement, parse_send_key, placement_name},
+    alloc::{format, string::String, vec::Vec},
+    core::{fmt, str::FromStr},
+};
+
+/// Called with each parameter that changes, and its new value, as set with
+/// `Synth::set_param_observer`.
+pub type ParamObserver = Box<dyn FnMut(ParamId, f32) + Send + Sync>;
+
+/// Something that can be controlled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case"))]
+pub enum ParamId {
+    /// The index of the waveform in `Waveform::ALL`.
+    Waveform,
+    Detune,
+    Cutoff,
+    Attack,
+    Decay,
+    Sustain,
+    Release,
+    StereoSpread,
+    Volume,
+    PitchBend,
+    Tempo,
+    Morph,
+    /// How much of every voice goes to a send bus.
+    SendLevel(usize),
+    /// The level a send bus is mixed back in at.
+    ReturnLevel(usize),
+    /// A parameter of the effect in `slot` of a chain. For voice effects, every voice's copy is
+    /// changed together.
+    Effect {
+        placement: Placement,
+        slot: usize,
+        parameter: usize,
+    },
+}
+
+impl ParamId {
+    /// The parameters every synth has, whatever its effects.
+    pub fn fixed() -> impl Iterator<Item = ParamId> {
+        [
+            ParamId::Waveform,
+            ParamId::Detune,
+            ParamId::Cutoff,
+            ParamId::Attack,
+            ParamId::Decay,
+            ParamId::Sustain,
+            ParamId::Release,
+            ParamId::StereoSpread,
+            ParamId::Volume,
+            ParamId::PitchBend,
+            ParamId::Tempo,
+            ParamId::Morph,
+        ]
+        .iter()
+        .copied()
+        .chain((0..SENDS).map(ParamId::SendLevel))
+        .chain((0..SENDS).map(ParamId::ReturnLevel))
+    }
+
+    /// What the parameter means, unless it's an effect's, which depends on the effect (see
+    /// `Synth::param_info`) or a send bus which doesn't exist.
+    pub fn info(self) -> Option<ParamInfo> {
+        let envelope = AdsrConfig::default();
+        Some(match self {
+            ParamId::Waveform => ParamInfo::stepped(
+                "waveform",
+                0.0,
+                (Waveform::ALL.len() - 1) as f32,
+                Waveform::ALL
+                    .iter()
+                    .position(|w| *w == Waveform::default())
+                    .unwrap_or(0) as f32,
+            ),
+            ParamId::Detune => ParamInfo::linear("detune", 0.0, 100.0, DEFAULT_DETUNE, Unit::Cents),
+            ParamId::Cutoff => ParamInfo::log("cutoff", 20.0, 20000.0, DEFAULT_CUTOFF, Unit::Hz),
+            ParamId::Attack => {
+                ParamInfo::log("attack", 0.001, 10.0, envelope.attack_time, Unit::Seconds)
+            }
+            ParamId::Decay => {
+                ParamInfo::log("decay", 0.001, 10.0, envelope.decay_time, Unit::Seconds)
+            }
+            ParamId::Sustain => {
+                ParamInfo::linear("sustain", 0.0, 1.0, envelope.sustain_amount, Unit::None)
+            }
+            ParamId::Release => {
+                ParamInfo::log("release", 0.001, 10.0, envelope.release_time, Unit::Seconds)
+            }
+            ParamId::StereoSpread => {
+                ParamInfo::linear("stereo spread", 0.0, 1.0, DEFAULT_STEREO_SPREAD, Unit::None)
+            }
+            ParamId::Volume => ParamInfo::linear("volume", 0.0, 2.0, DEFAULT_VOLUME, Unit::None),
+            ParamId::PitchBend => ParamInfo::linear(
+                "pitch bend",
+                -PITCH_BEND_RANGE,
+                PITCH_BEND_RANGE,
+                0.0,
+                Unit::Semitones,
+            ),
+            ParamId::Tempo => {
+                ParamInfo::linear("tempo", MIN_TEMPO, MAX_TEMPO, DEFAULT_TEMPO, Unit::Bpm)
+            }
+            ParamId::Morph => ParamInfo::linear("morph", 0.0, 1.0, 0.0, Unit::None),
+            ParamId::SendLevel(send) if send < SENDS => {
+                ParamInfo::linear("send level", 0.0, 1.0, 0.0, Unit::None)
+            }
+            ParamId::ReturnLevel(send) if send < SENDS => {
+                ParamInfo::linear("send return", 0.0, 1.0, 1.0, Unit::None)
+            }
+            ParamId::SendLevel(_) | ParamId::ReturnLevel(_) | ParamId::Effect { .. } => {
+                return None
+            }
+        })
+    }
+
+    /// `value` kept within the parameter's range, or as it is if `info` doesn't know the range.
+    pub(crate) fn clamp(self, value: f32) -> f32 {
+        self.info()
+            .map_or(value, |info| value.clamp(info.min, info.max))
+    }
+}
+
+/// How a parameter is named in text, such as the text form of commands: the fixed ones by their
+/// names, and the rest much as patches name them, e.g. `send 1 level` or `effect send 2 0 1` for
+/// the second parameter of the first effect on the second send bus.
+#[cfg(feature = "presets")]
+impl fmt::Display for ParamId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ParamId::SendLevel(send) => write!(f, "send {} level", send + 1),
+            ParamId::ReturnLevel(send) => write!(f, "send {} return", send + 1),
+            ParamId::Effect {
+                placement,
+                slot,
+                parameter,
+            } => write!(
+                f,
+                "effect {} {} {}",
+                placement_name(placement),
+                slot,
+                parameter
+            ),
+            _ => f.write_str(self.info().map_or("", |info| info.name)),
+        }
+    }
+}
+
+#[cfg(feature = "presets")]
+impl FromStr for ParamId {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let unknown = || format!("Unknown parameter: {}", s);
+        if let Some(rest) = s.strip_prefix("effect ") {
+            let words: Vec<&str> = rest.split_whitespace().collect();
+            if words.len() < 3 {
+                return Err(unknown());
+            }
+            let (placement, numbers) = words.split_at(words.len() - 2);
+            return Ok(ParamId::Effect {
+                placement: parse_placement(&placement.join(" ")).ok_or_else(unknown)?,
+                slot: numbers[0].parse().map_err(|_| unknown())?,
+                parameter: numbers[1].parse().map_err(|_| unknown())?,
+            });
+        }
+        if let Some((send, setting)) = parse_send_key(s) {
+            return Ok(match setting {
+                "level" => ParamId::SendLevel(send),
+                _ => ParamId::ReturnLevel(send),
+            });
+        }
+        ParamId::fixed()
+            .filter(|param| !matches!(param, ParamId::SendLevel(_) | ParamId::ReturnLevel(_)))
+            .find(|param| param.info().map(|info| info.name) == Some(s))
+            .ok_or_else(unknown)
+    }
+}
+
+/// What a parameter's values mean.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ParamInfo {
+    pub name: &'static str,
+    pub min: f32,
+    pub max: f32,
+    pub default: f32,
+    pub unit: Unit,
+    pub curve: Curve,
+}
+
+impl ParamInfo {
+    pub const fn linear(name: &'static str, min: f32, max: f32, default: f32, unit: Unit) -> Self {
+        Self {
+            name,
+            min,
+            max,
+            default,
+            unit,
+            curve: Curve::Linear,
+        }
+    }
+
+    /// A parameter best controlled on a log scale, such as a frequency. `min` must be above zero.
+    pub const fn log(name: &'static str, min: f32, max: f32, default: f32, unit: Unit) -> Self {
+        Self {
+            curve: Curve::Logarithmic,
+            ..Self::linear(name, min, max, default, unit)
+        }
+    }
+
+    /// A parameter which picks one of several settings, numbered from `min` to `max`.
+    pub const fn stepped(name: &'static str, min: f32, max: f32, default: f32) -> Self {
+        Self {
+            curve: Curve::Stepped,
+            ..Self::linear(name, min, max, default, Unit::None)
+        }
+    }
+
+    /// Where a value falls in the range, from 0 to 1 along the curve, e.g. for the position of a
+    /// knob.
+    pub fn to_normalized(&self, value: f32) -> f32 {
+        let value = value.clamp(self.min, self.max);
+        let position = match self.curve {
+            Curve::Linear | Curve::Stepped => (value - self.min) / (self.max - self.min),
+            Curve::Logarithmic => (value / self.min).ln() / (self.max / self.min).ln(),
+        };
+        if position.is_finite() {
+            position
+        } else {
+            0.0
+        }
+    }
+
+    /// The value at a position from 0 to 1 along the curve, the opposite of `to_normalized`.
+    pub fn from_normalized(&self, position: f32) -> f32 {
+        let position = position.clamp(0.0, 1.0);
+        match self.curve {
+            Curve::Linear => self.min + (self.max - self.min) * position,
+            Curve::Logarithmic => self.min * (self.max / self.min).powf(position),
+            Curve::Stepped => (self.min + (self.max - self.min) * position).round(),
+        }
+    }
+}
+
+/// What a parameter is measured in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Unit {
+    /// A plain number, such as a 0-1 amount or a ratio.
+    None,
+    Hz,
+    Seconds,
+    Decibels,
+    Cents,
+    Semitones,
+    Bpm,
+}
+
+impl Unit {
+    /// A short label to show after values, empty for `Unit::None`.
+    pub fn symbol(self) -> &'static str {
+        match self {
+            Unit::None => "",
+            Unit::Hz => "Hz",
+            Unit::Seconds => "s",
+            Unit::Decibels => "dB",
+            Unit::Cents => "cents",
+            Unit::Semitones => "st",
+            Unit::Bpm => "BPM",
+        }
+    }
+}
+
+/// How a parameter's values are best spread along a control.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Curve {
+    Linear,
+    /// Evenly in ratio rather than difference, as suits frequencies and times.
+    Logarithmic,
+    /// Whole numbers only, each picking a different setting.
+    Stepped,
+}
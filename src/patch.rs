@@ -0,0 +1,457 @@
+//! Patches: everything that makes up a sound (as opposed to what's being played with it), which
+//! can be taken from a synth with `Synth::save_patch` and put back with `Synth::load_patch`.
+//!
+//! Patches are stored as plain text, one `name = value` setting per line, with a `[name]` section
+//! for each effect holding its placement and parameters. Blank lines and `#` comments are ignored.
+//! For example:
+//!
+//! ```text
+//! version = 1
+//! waveform = pulse
+//! cutoff = 1200
+//! release = 0.3
+//!
+//! [delay]
+//! placement = send 1
+//! time = 0.375
+//! ```
+//!
+//! Settings which are left out keep their defaults, so patches saved before a setting existed
+//! still load. Values outside a setting's range (see `ParamId::info`) are errors. Each patch
+//! starts with the version of the format it was written in (those without one are from version
+//! 1), so that should a setting ever change meaning, older patches can still be told apart.
+//! Patches in a newer format than this version of the crate understands are rejected.
+//!
+//! Reading and writing this form needs the `presets` feature. With the `serde` feature, patches
+//! also serialize field by field, for any format serde supports, with settings left out keeping
+//! their defaults as here.
+
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+
+#[cfg(feature = "presets")]
+use {
+    alloc::format,
+    std::io::{self, BufRead, BufReader, Read, Write},
+};
+
+#[cfg(feature = "presets")]
+use crate::{effects, param::ParamId};
+use crate::{effects::Placement, send::SENDS, AdsrConfig, Waveform};
+
+/// Version of the patch format written by `Patch::write`.
+pub const FORMAT_VERSION: u32 = 1;
+
+/// One effect in a patch.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EffectPatch {
+    /// As accepted by `effects::by_name`.
+    pub name: String,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub placement: Placement,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub bypassed: bool,
+    /// Values for the effect's parameters, by name. Any left out keep their defaults.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub parameters: Vec<(String, f32)>,
+}
+
+impl EffectPatch {
+    /// The effect with its default settings, on the master bus.
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            placement: Placement::Master,
+            bypassed: false,
+            parameters: Vec::new(),
+        }
+    }
+}
+
+/// The settings for a sound.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct Patch {
+    pub waveform: Waveform,
+    /// How far apart each voice's oscillators are tuned, in cents.
+    pub detune: f32,
+    /// Cutoff frequency of the voices' filters, in Hz.
+    pub cutoff: f32,
+    pub amp_envelope: AdsrConfig,
+    pub stereo_spread: f32,
+    pub volume: f32,
+    /// How much of every voice goes to each send bus.
+    pub send_levels: [f32; SENDS],
+    /// The level each send bus is mixed back in at.
+    pub return_levels: [f32; SENDS],
+    /// In order, whatever their placement.
+    pub effects: Vec<EffectPatch>,
+}
+
+impl Default for Patch {
+    /// The sound a new synth starts with.
+    fn default() -> Self {
+        Self {
+            waveform: Waveform::default(),
+            detune: crate::DEFAULT_DETUNE,
+            cutoff: crate::DEFAULT_CUTOFF,
+            amp_envelope: AdsrConfig::default(),
+            stereo_spread: crate::DEFAULT_STEREO_SPREAD,
+            volume: crate::DEFAULT_VOLUME,
+            send_levels: [0.0; SENDS],
+            return_levels: [1.0; SENDS],
+            effects: Vec::new(),
+        }
+    }
+}
+
+impl Patch {
+    /// Write the patch out as text.
+    #[cfg(feature = "presets")]
+    pub fn write<W: Write>(&self, mut w: W) -> io::Result<()> {
+        writeln!(w, "version = {}", FORMAT_VERSION)?;
+        writeln!(w, "waveform = {}", self.waveform.name())?;
+        writeln!(w, "detune = {}", self.detune)?;
+        writeln!(w, "cutoff = {}", self.cutoff)?;
+        writeln!(w, "attack = {}", self.amp_envelope.attack_time)?;
+        writeln!(w, "decay = {}", self.amp_envelope.decay_time)?;
+        writeln!(w, "sustain = {}", self.amp_envelope.sustain_amount)?;
+        writeln!(w, "release = {}", self.amp_envelope.release_time)?;
+        writeln!(w, "stereo spread = {}", self.stereo_spread)?;
+        writeln!(w, "volume = {}", self.volume)?;
+        for send in 0..SENDS {
+            writeln!(w, "send {} level = {}", send + 1, self.send_levels[send])?;
+            writeln!(w, "send {} return = {}", send + 1, self.return_levels[send])?;
+        }
+
+        for effect in &self.effects {
+            writeln!(w)?;
+            writeln!(w, "[{}]", effect.name)?;
+            writeln!(w, "placement = {}", placement_name(effect.placement))?;
+            if effect.bypassed {
+                writeln!(w, "bypassed = true")?;
+            }
+            for (name, value) in &effect.parameters {
+                writeln!(w, "{} = {}", name, value)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Read a patch written by `write` (or by hand). Unknown settings, effects and parameters are
+    /// errors, rather than being silently dropped, as are settings out of their ranges.
+    #[cfg(feature = "presets")]
+    pub fn read<R: Read>(r: R) -> io::Result<Self> {
+        let mut patch = Self::default();
+        let mut version = None;
+        for (index, line) in BufReader::new(r).lines().enumerate() {
+            let line = line?;
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            patch
+                .read_versioned_line(line, &mut version)
+                .map_err(|msg| invalid(&format!("Line {}: {}", index + 1, msg)))?;
+        }
+        Ok(patch)
+    }
+
+    /// Read a line, which may give the format `version` if nothing else has been read yet.
+    #[cfg(feature = "presets")]
+    fn read_versioned_line(&mut self, line: &str, version: &mut Option<u32>) -> Result<(), String> {
+        if let Some(("version", value)) = line.split_once('=').map(|(k, v)| (k.trim(), v.trim())) {
+            if version.is_some() {
+                return Err("The format version must come first, and only once".to_string());
+            }
+            let number = value
+                .parse::<u32>()
+                .ok()
+                .filter(|v| *v >= 1)
+                .ok_or_else(|| format!("Invalid format version: {}", value))?;
+            if number > FORMAT_VERSION {
+                return Err(format!(
+                    "Format version {} is newer than this version of the synth reads ({})",
+                    number, FORMAT_VERSION
+                ));
+            }
+            *version = Some(number);
+            return Ok(());
+        }
+
+        version.get_or_insert(1);
+        self.read_line(line)
+    }
+
+    #[cfg(feature = "presets")]
+    fn read_line(&mut self, line: &str) -> Result<(), String> {
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            let name = name.trim();
+            if !effects::NAMES.contains(&name) {
+                return Err(format!("Unknown effect: {}", name));
+            }
+            self.effects.push(EffectPatch::new(name));
+            return Ok(());
+        }
+
+        let (key, value) = line
+            .split_once('=')
+            .map(|(k, v)| (k.trim(), v.trim()))
+            .ok_or_else(|| format!("Expected `name = value`, found: {}", line))?;
+        let number = || {
+            value
+                .parse::<f32>()
+                .ok()
+                .filter(|v| v.is_finite())
+                .ok_or_else(|| format!("Invalid value for {}: {}", key, value))
+        };
+
+        if let Some(effect) = self.effects.last_mut() {
+            match key {
+                "placement" => {
+                    effect.placement = parse_placement(value)
+                        .ok_or_else(|| format!("Invalid placement: {}", value))?;
+                }
+                "bypassed" => {
+                    effect.bypassed = value
+                        .parse()
+                        .map_err(|_| format!("Invalid value for bypassed: {}", value))?;
+                }
+                _ => {
+                    let known = effects::by_name(&effect.name)
+                        .is_some_and(|e| e.parameter_names().contains(&key));
+                    if !known {
+                        return Err(format!("Unknown parameter for {}: {}", effect.name, key));
+                    }
+                    effect.parameters.push((key.to_string(), number()?));
+                }
+            }
+            return Ok(());
+        }
+
+        // a number in the range of the parameter the setting is
+        let in_range = |param: ParamId| {
+            let value = number()?;
+            match param.info() {
+                Some(info) if value < info.min || value > info.max => Err(format!(
+                    "{} is out of range for {} ({} to {})",
+                    value, key, info.min, info.max
+                )),
+                _ => Ok(value),
+            }
+        };
+        match key {
+            "waveform" => {
+                self.waveform = Waveform::from_name(value)
+                    .ok_or_else(|| format!("Unknown waveform: {}", value))?;
+            }
+            "detune" => self.detune = in_range(ParamId::Detune)?,
+            "cutoff" => self.cutoff = in_range(ParamId::Cutoff)?,
+            "attack" => self.amp_envelope.attack_time = in_range(ParamId::Attack)?,
+            "decay" => self.amp_envelope.decay_time = in_range(ParamId::Decay)?,
+            "sustain" => self.amp_envelope.sustain_amount = in_range(ParamId::Sustain)?,
+            "release" => self.amp_envelope.release_time = in_range(ParamId::Release)?,
+            "stereo spread" => self.stereo_spread = in_range(ParamId::StereoSpread)?,
+            "volume" => self.volume = in_range(ParamId::Volume)?,
+            _ => {
+                let (send, setting) =
+                    parse_send_key(key).ok_or_else(|| format!("Unknown setting: {}", key))?;
+                match setting {
+                    "level" => self.send_levels[send] = in_range(ParamId::SendLevel(send))?,
+                    _ => self.return_levels[send] = in_range(ParamId::ReturnLevel(send))?,
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Split e.g. `send 2 level` into the send's index and the setting.
+#[cfg(feature = "presets")]
+pub(crate) fn parse_send_key(key: &str) -> Option<(usize, &str)> {
+    let mut words = key.split_whitespace();
+    if words.next()? != "send" {
+        return None;
+    }
+    let send = words.next()?.parse::<usize>().ok()?.checked_sub(1)?;
+    let setting = words.next().filter(|s| ["level", "return"].contains(s))?;
+    if send >= SENDS || words.next().is_some() {
+        return None;
+    }
+    Some((send, setting))
+}
+
+#[cfg(feature = "presets")]
+pub(crate) fn placement_name(placement: Placement) -> String {
+    match placement {
+        Placement::Master => "master".to_string(),
+        Placement::Voice => "voice".to_string(),
+        Placement::Send(send) => format!("send {}", send + 1),
+    }
+}
+
+#[cfg(feature = "presets")]
+pub(crate) fn parse_placement(name: &str) -> Option<Placement> {
+    match name {
+        "master" => Some(Placement::Master),
+        "voice" => Some(Placement::Voice),
+        _ => {
+            let send = name.strip_prefix("send")?.trim().parse::<usize>().ok()?;
+            (1..=SENDS)
+                .contains(&send)
+                .then(|| Placement::Send(send - 1))
+        }
+    }
+}
+
+#[cfg(feature = "presets")]
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+#[cfg(all(test, feature = "presets"))]
+mod tests {
+    use super::*;
+
+    fn read(text: &str) -> io::Result<Patch> {
+        Patch::read(text.as_bytes())
+    }
+
+    fn round_trip(patch: &Patch) -> Patch {
+        let mut text = Vec::new();
+        patch.write(&mut text).unwrap();
+        Patch::read(&text[..]).unwrap()
+    }
+
+    #[test]
+    fn default_round_trips() {
+        assert_eq!(round_trip(&Patch::default()), Patch::default());
+    }
+
+    #[test]
+    fn settings_round_trip() {
+        let patch = Patch {
+            waveform: Waveform::Pulse,
+            detune: 12.5,
+            cutoff: 1234.567,
+            amp_envelope: AdsrConfig {
+                attack_time: 0.003,
+                decay_time: 0.25,
+                sustain_amount: 0.6,
+                release_time: 1.75,
+            },
+            stereo_spread: 0.3,
+            volume: 0.45,
+            send_levels: [0.2, 0.7],
+            return_levels: [0.9, 0.1],
+            effects: Vec::new(),
+        };
+        assert_eq!(round_trip(&patch), patch);
+    }
+
+    #[cfg(feature = "effects")]
+    #[test]
+    fn effects_round_trip() {
+        let mut delay = EffectPatch::new("delay");
+        delay.placement = Placement::Send(1);
+        delay.parameters = vec![("time".to_string(), 0.375), ("feedback".to_string(), 0.4)];
+        let mut reverb = EffectPatch::new("reverb");
+        reverb.bypassed = true;
+        let patch = Patch {
+            effects: vec![delay, EffectPatch::new("chorus"), reverb],
+            ..Patch::default()
+        };
+        assert_eq!(round_trip(&patch), patch);
+    }
+
+    #[test]
+    fn synth_saves_what_it_loads() {
+        let patch = Patch {
+            waveform: Waveform::Sine,
+            cutoff: 2500.0,
+            volume: 0.4,
+            send_levels: [0.5, 0.0],
+            ..Patch::default()
+        };
+        let mut synth = crate::Synth::new(4, 48000, Default::default());
+        synth.load_patch(&patch);
+        assert_eq!(round_trip(&synth.save_patch()), patch);
+    }
+
+    #[cfg(feature = "effects")]
+    #[test]
+    fn reads_by_hand() {
+        let patch = read(
+            "# a comment\n\
+             waveform = sine\n\
+             \n\
+             cutoff = 800   # after a setting\n\
+             [delay]\n\
+             placement = voice\n\
+             time = 0.5\n",
+        )
+        .unwrap();
+        assert_eq!(patch.waveform, Waveform::Sine);
+        assert_eq!(patch.cutoff, 800.0);
+        assert_eq!(patch.volume, Patch::default().volume);
+        assert_eq!(patch.effects.len(), 1);
+        assert_eq!(patch.effects[0].placement, Placement::Voice);
+        assert_eq!(patch.effects[0].parameters, [("time".to_string(), 0.5)]);
+    }
+
+    #[test]
+    fn rejects_unknown_settings() {
+        assert!(read("wobble = 1\n").is_err());
+        assert!(read("waveform = kazoo\n").is_err());
+        assert!(read("send 3 level = 0.5\n").is_err());
+        assert!(read("[kazoo]\n").is_err());
+        assert!(read("cutoff = loud\n").is_err());
+        assert!(read("cutoff = inf\n").is_err());
+    }
+
+    #[test]
+    fn reads_versions() {
+        assert_eq!(read("cutoff = 900\n").unwrap().cutoff, 900.0);
+        assert_eq!(read("version = 1\ncutoff = 900\n").unwrap().cutoff, 900.0);
+        assert!(read("version = 0\n").is_err());
+        assert!(read(&format!("version = {}\n", FORMAT_VERSION + 1)).is_err());
+        assert!(read("cutoff = 900\nversion = 1\n").is_err());
+        assert!(read("version = 1\nversion = 1\n").is_err());
+    }
+
+    #[test]
+    fn rejects_out_of_range_settings() {
+        assert!(read("cutoff = 0\n").is_err());
+        assert!(read("cutoff = -5\n").is_err());
+        assert!(read("attack = -1\n").is_err());
+        assert!(read("volume = 50\n").is_err());
+        assert!(read("send 1 level = 2\n").is_err());
+        assert!(read("cutoff = 20\nattack = 10\nsustain = 0\n").is_ok());
+    }
+
+    #[test]
+    fn synth_saves_settings_in_range() {
+        let mut synth = crate::Synth::new(4, 48000, Default::default());
+        synth.set_volume(50.0);
+        synth.set_amp_envelope(AdsrConfig {
+            attack_time: 0.0,
+            decay_time: -1.0,
+            sustain_amount: 2.0,
+            release_time: 100.0,
+        });
+        let mut text = Vec::new();
+        synth.save_patch().write(&mut text).unwrap();
+        assert!(Patch::read(&text[..]).is_ok());
+    }
+
+    #[cfg(feature = "effects")]
+    #[test]
+    fn rejects_unknown_effect_settings() {
+        assert!(read("[delay]\nwobble = 1\n").is_err());
+        assert!(read("[delay]\nplacement = send 3\n").is_err());
+    }
+}
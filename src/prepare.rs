@@ -0,0 +1,109 @@
+//! Getting patches ready to switch to on another thread, for a synth on an audio thread which
+//! mustn't allocate.
+//!
+//! Loading a patch with different effects from the synth's means making them, and gliding to a
+//! patch means saving the current settings to blend from, both of which allocate. A
+//! `PatchPreparer`, taken from the synth with `Synth::patch_preparer` and sent wherever it's
+//! needed, does all of that up front, leaving `Synth::switch_patch` only swapping things over.
+//! Whatever the synth lets go of comes back in the `PreparedPatch`, to be dropped back where it
+//! was made.
+//!
+//! A glide starts from the synth's current settings, which `Synth::save_patch_into` saves
+//! without allocating, into a patch with the same effects, such as one from `PatchPreparer::saved`.
+
+use {
+    alloc::vec::Vec,
+    core::{iter, time::Duration},
+};
+
+use crate::{
+    chain_patches,
+    effects::{EffectsChain, Placement},
+    load_chain,
+    morph::Morph,
+    patch::Patch,
+    placed,
+    send::SENDS,
+    Synth,
+};
+
+/// Makes patches ready for one synth to switch to. It only needs the synth's sample rate, voices
+/// and so on, so it can be sent anywhere, and used there.
+#[derive(Debug, Clone, Copy)]
+pub struct PatchPreparer {
+    pub(crate) sample_rate: u32,
+    /// The rate the voices run at, oversampling included.
+    pub(crate) internal_rate: u32,
+    pub(crate) voices: usize,
+    pub(crate) tempo: f32,
+}
+
+/// A patch with its effects made, ready for `Synth::switch_patch`.
+pub struct PreparedPatch {
+    /// From the synth's current settings to the patch, with chains holding the patch's effects.
+    pub(crate) morph: Option<Morph>,
+    /// How many frames the glide lasts, or less than one to switch straight over.
+    pub(crate) frames: f32,
+    /// What the synth let go of when it switched, to be freed along with this.
+    pub(crate) released: [Option<Morph>; 3],
+}
+
+impl PatchPreparer {
+    pub(crate) fn new(synth: &Synth) -> Self {
+        Self {
+            sample_rate: synth.sample_rate,
+            internal_rate: synth.sample_rate * synth.oversampling.ratio(),
+            voices: synth.voices.len(),
+            tempo: synth.tempo(),
+        }
+    }
+
+    /// Get `patch` ready to switch straight to, as `Synth::load_patch` would.
+    pub fn load(&self, patch: Patch) -> PreparedPatch {
+        self.prepare(patch.clone(), patch, Duration::ZERO)
+    }
+
+    /// Get `patch` ready to glide to over `time`, as `Synth::glide_to_patch` would, from `from`:
+    /// the settings the synth will have when it switches, e.g. from `Synth::save_patch_into`. If
+    /// the synth's effects have changed by then, it switches straight to the patch instead.
+    pub fn glide(&self, from: Patch, patch: Patch, time: Duration) -> PreparedPatch {
+        self.prepare(from, patch, time)
+    }
+
+    fn prepare(&self, from: Patch, patch: Patch, time: Duration) -> PreparedPatch {
+        PreparedPatch {
+            morph: Some(Morph::new(self, from, patch)),
+            frames: time.as_secs_f32() * self.sample_rate as f32,
+            released: [None, None, None],
+        }
+    }
+
+    /// `patch` as `Synth::save_patch` would give it once loaded: without any effects whose names
+    /// are unknown, and with every effect parameter filled in. That's what
+    /// `Synth::save_patch_into` needs to save into without allocating.
+    pub fn saved(&self, patch: &Patch) -> Patch {
+        let placements = iter::once(Placement::Master)
+            .chain(iter::once(Placement::Voice))
+            .chain((0..SENDS).map(Placement::Send));
+        let mut effects = Vec::new();
+        for placement in placements {
+            effects.append(&mut chain_patches(&self.chain(patch, placement), placement));
+        }
+        Patch {
+            effects,
+            ..patch.clone()
+        }
+    }
+
+    /// A chain holding `patch`'s effects at `placement`.
+    pub(crate) fn chain(&self, patch: &Patch, placement: Placement) -> EffectsChain {
+        let sample_rate = match placement {
+            Placement::Voice => self.internal_rate,
+            _ => self.sample_rate,
+        };
+        let mut chain = EffectsChain::new(sample_rate);
+        chain.set_tempo(self.tempo);
+        load_chain(&mut chain, placed(patch, placement));
+        chain
+    }
+}
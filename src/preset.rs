@@ -0,0 +1,282 @@
+//! Banks of presets: patches with names, found by scanning a directory for patch files, which can
+//! be picked by name or number (e.g. from MIDI Program Change) and stepped through in order.
+//!
+//! Patch files at the top of the directory have no category; those in a subdirectory are filed
+//! under its name, so that e.g. `presets/bass/sub.patch` is the preset `sub` in the category
+//! `bass`.
+//!
+//! A bank of factory presets is also built in, from `PresetBank::factory`.
+
+use std::{
+    fs::{self, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::patch::Patch;
+
+/// Extension of the patch files picked up by `PresetBank::scan`.
+pub const PATCH_EXTENSION: &str = "patch";
+
+/// The factory presets, built into the library: category, name and patch.
+const FACTORY: &[(&str, &str, &str)] = &[
+    ("bass", "round", include_str!("../factory/bass/round.patch")),
+    ("bass", "sub", include_str!("../factory/bass/sub.patch")),
+    ("fx", "lofi", include_str!("../factory/fx/lofi.patch")),
+    ("fx", "sweep", include_str!("../factory/fx/sweep.patch")),
+    (
+        "keys",
+        "electric",
+        include_str!("../factory/keys/electric.patch"),
+    ),
+    ("keys", "organ", include_str!("../factory/keys/organ.patch")),
+    (
+        "lead",
+        "screamer",
+        include_str!("../factory/lead/screamer.patch"),
+    ),
+    (
+        "lead",
+        "square",
+        include_str!("../factory/lead/square.patch"),
+    ),
+    ("pad", "glass", include_str!("../factory/pad/glass.patch")),
+    ("pad", "warm", include_str!("../factory/pad/warm.patch")),
+];
+
+#[derive(Debug, Clone, PartialEq)]
+enum Source {
+    File(PathBuf),
+    Factory(&'static str),
+}
+
+/// A patch in a bank, which is only read when it's loaded.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Preset {
+    pub name: String,
+    pub category: Option<String>,
+    source: Source,
+}
+
+impl Preset {
+    /// The file the preset is read from, unless it's built in.
+    pub fn path(&self) -> Option<&Path> {
+        match &self.source {
+            Source::File(path) => Some(path),
+            Source::Factory(_) => None,
+        }
+    }
+
+    /// Whether this is one of the factory presets.
+    pub fn is_factory(&self) -> bool {
+        matches!(self.source, Source::Factory(_))
+    }
+
+    fn read(&self) -> io::Result<Patch> {
+        match &self.source {
+            Source::File(path) => File::open(path).and_then(Patch::read),
+            Source::Factory(text) => Patch::read(text.as_bytes()),
+        }
+    }
+}
+
+/// A list of presets, sorted by category and then by name, and a note of which was last loaded.
+#[derive(Debug, Clone, Default)]
+pub struct PresetBank {
+    presets: Vec<Preset>,
+    current: Option<usize>,
+}
+
+impl PresetBank {
+    /// An empty bank.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The factory presets: a few basses, leads, pads, keys and effects to start from.
+    pub fn factory() -> Self {
+        let mut bank = Self::new();
+        bank.presets = FACTORY
+            .iter()
+            .map(|(category, name, text)| Preset {
+                name: name.to_string(),
+                category: Some(category.to_string()),
+                source: Source::Factory(text),
+            })
+            .collect();
+        bank.sort();
+        bank
+    }
+
+    /// Add the presets from another bank to this one. Where two have the same name and category,
+    /// `find` picks the one which was here first.
+    pub fn extend(&mut self, other: PresetBank) {
+        self.presets.extend(other.presets);
+        self.sort();
+        self.current = None;
+    }
+
+    /// Find all the patch files in `dir`, and in its immediate subdirectories. Nothing is read
+    /// until it's loaded, so a broken patch only causes an error then.
+    pub fn scan(dir: impl AsRef<Path>) -> io::Result<Self> {
+        let dir = dir.as_ref();
+        let mut bank = Self::new();
+        bank.scan_dir(dir, None)?;
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            if entry.file_type()?.is_dir() {
+                let category = entry.file_name().to_string_lossy().into_owned();
+                bank.scan_dir(&entry.path(), Some(category))?;
+            }
+        }
+        bank.sort();
+        log::debug!("Found {} presets in {}", bank.len(), dir.display());
+        Ok(bank)
+    }
+
+    fn scan_dir(&mut self, dir: &Path, category: Option<String>) -> io::Result<()> {
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if !path.is_file() || path.extension().is_none_or(|e| e != PATCH_EXTENSION) {
+                continue;
+            }
+            if let Some(name) = path.file_stem() {
+                self.presets.push(Preset {
+                    name: name.to_string_lossy().into_owned(),
+                    category: category.clone(),
+                    source: Source::File(path.clone()),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    fn sort(&mut self) {
+        self.presets
+            .sort_by(|a, b| (&a.category, &a.name).cmp(&(&b.category, &b.name)));
+    }
+
+    pub fn len(&self) -> usize {
+        self.presets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.presets.is_empty()
+    }
+
+    /// Every preset, in order.
+    pub fn presets(&self) -> &[Preset] {
+        &self.presets
+    }
+
+    /// The names of the categories presets are filed under, in order.
+    pub fn categories(&self) -> Vec<&str> {
+        let mut categories: Vec<&str> = self
+            .presets
+            .iter()
+            .filter_map(|p| p.category.as_deref())
+            .collect();
+        categories.dedup();
+        categories
+    }
+
+    /// The position of the preset called `name`, or `category/name` to tell apart presets with
+    /// the same name in different categories.
+    pub fn find(&self, name: &str) -> Option<usize> {
+        let (category, name) = match name.split_once('/') {
+            Some((category, name)) => (Some(category), name),
+            None => (None, name),
+        };
+        self.presets.iter().position(|p| {
+            p.name == name && category.is_none_or(|c| p.category.as_deref() == Some(c))
+        })
+    }
+
+    /// Read the preset at `index`, which becomes the current one. Reading it afresh every time
+    /// picks up any edits made to the file.
+    pub fn load(&mut self, index: usize) -> io::Result<Patch> {
+        let preset = self
+            .presets
+            .get(index)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "No preset at that position"))?;
+        let patch = preset.read()?;
+        log::debug!("Loaded preset {}", preset.name);
+        self.current = Some(index);
+        Ok(patch)
+    }
+
+    /// Read the preset with the given name, as for `find`.
+    pub fn load_by_name(&mut self, name: &str) -> io::Result<Patch> {
+        let index = self.find(name).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("No preset called {}", name),
+            )
+        })?;
+        self.load(index)
+    }
+
+    /// Save `patch` as a preset in `dir`, called `name` or `category/name` (filed in a
+    /// subdirectory, as `scan` expects), replacing any preset of the same name there. The new
+    /// preset becomes the current one, and its position is returned.
+    pub fn save(&mut self, dir: impl AsRef<Path>, name: &str, patch: &Patch) -> io::Result<usize> {
+        let invalid = || {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Invalid preset name: {}", name),
+            )
+        };
+        let (category, name) = match name.split_once('/') {
+            Some((category, name)) => (Some(category), name),
+            None => (None, name),
+        };
+        let valid =
+            |part: &str| !part.is_empty() && !part.starts_with('.') && !part.contains(['/', '\\']);
+        if !valid(name) || !category.is_none_or(valid) {
+            return Err(invalid());
+        }
+
+        let mut path = dir.as_ref().to_path_buf();
+        if let Some(category) = category {
+            path.push(category);
+        }
+        fs::create_dir_all(&path)?;
+        path.push(name);
+        path.set_extension(PATCH_EXTENSION);
+        let mut file = io::BufWriter::new(File::create(&path)?);
+        patch.write(&mut file)?;
+        file.flush()?;
+        log::debug!("Saved preset {} to {}", name, path.display());
+
+        let preset = Preset {
+            name: name.to_string(),
+            category: category.map(str::to_string),
+            source: Source::File(path),
+        };
+        if !self.presets.contains(&preset) {
+            self.presets.push(preset.clone());
+            self.sort();
+        }
+        let index = self.presets.iter().position(|p| *p == preset).unwrap_or(0);
+        self.current = Some(index);
+        Ok(index)
+    }
+
+    /// The position of the preset last loaded, if any.
+    pub fn current(&self) -> Option<usize> {
+        self.current
+    }
+
+    /// Load the preset after the current one, going back to the first after the last.
+    pub fn load_next(&mut self) -> io::Result<Patch> {
+        let index = self.current.map_or(0, |i| (i + 1) % self.len().max(1));
+        self.load(index)
+    }
+
+    /// Load the preset before the current one, going round to the last before the first.
+    pub fn load_previous(&mut self) -> io::Result<Patch> {
+        let len = self.len().max(1);
+        let index = self.current.map_or(len - 1, |i| (i + len - 1) % len);
+        self.load(index)
+    }
+}
@@ -0,0 +1,72 @@
+//! Measuring where the time goes while rendering, to see what to turn off when the audio drops
+//! out (see `Synth::set_profiling`).
+
+use std::{fmt, time::Duration, time::Instant};
+
+/// Time spent rendering, in total and on each part of the synth, added up since profiling was
+/// turned on or last reset.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Profile {
+    /// The voices' oscillators.
+    pub oscillators: Duration,
+    /// The voices' filters.
+    pub filters: Duration,
+    /// The voices' envelopes, and their DC blockers if they have any.
+    pub envelopes: Duration,
+    /// The voices' effects, the send buses and the master chain.
+    pub effects: Duration,
+    /// Everything, including mixing, decimating and limiting, which aren't counted above.
+    pub total: Duration,
+    /// How much audio was rendered in that time.
+    pub audio: Duration,
+    /// How many blocks were rendered.
+    pub blocks: u64,
+}
+
+impl Profile {
+    /// How much of the time available for rendering was spent on `time` (one of the fields), where
+    /// 1 would leave none for anything else. Rendering as a whole has to stay below 1 to keep up,
+    /// and in practice well below, as the rest of the system needs time too.
+    pub fn load(&self, time: Duration) -> f32 {
+        if self.audio.is_zero() {
+            0.0
+        } else {
+            time.as_secs_f32() / self.audio.as_secs_f32()
+        }
+    }
+}
+
+/// One line, with each part's share of the available time.
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "CPU load {:.1}%: oscillators {:.1}%, filters {:.1}%, envelopes {:.1}%, effects {:.1}%",
+            self.load(self.total) * 100.0,
+            self.load(self.oscillators) * 100.0,
+            self.load(self.filters) * 100.0,
+            self.load(self.envelopes) * 100.0,
+            self.load(self.effects) * 100.0
+        )
+    }
+}
+
+/// Times one part of rendering, if profiling is on.
+pub(crate) struct Stopwatch(Option<Instant>);
+
+impl Stopwatch {
+    pub(crate) fn start(profile: &Option<Profile>) -> Self {
+        Self(profile.as_ref().map(|_| Instant::now()))
+    }
+
+    /// Add the time since starting to the part of `profile` given by `part`.
+    pub(crate) fn stop(
+        self,
+        profile: &mut Option<Profile>,
+        part: fn(&mut Profile) -> &mut Duration,
+    ) {
+        if let (Some(started), Some(profile)) = (self.0, profile) {
+            *part(profile) += started.elapsed();
+        }
+    }
+}
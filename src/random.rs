@@ -0,0 +1,380 @@
+//! Random patches, for exploring: every setting is picked from a range which sounds reasonable for
+//! the kind of sound asked for, and a few effects are thrown in with their parameters shuffled
+//! too.
+//!
+//! The same seed always gives the same sequence of patches, so a happy accident can be found
+//! again.
+
+use alloc::{string::ToString, vec};
+
+use crate::{
+    effects::{self, Placement},
+    patch::{EffectPatch, Patch},
+    send::SENDS,
+    AdsrConfig, Waveform,
+};
+
+#[cfg(not(any(feature = "std", test)))]
+use crate::math::Float;
+
+/// The kinds of sound a random patch can be made to fit, named as the factory preset categories.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Category {
+    Bass,
+    Fx,
+    Keys,
+    Lead,
+    Pad,
+}
+
+impl Category {
+    pub const ALL: [Category; 5] = [
+        Category::Bass,
+        Category::Fx,
+        Category::Keys,
+        Category::Lead,
+        Category::Pad,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Category::Bass => "bass",
+            Category::Fx => "fx",
+            Category::Keys => "keys",
+            Category::Lead => "lead",
+            Category::Pad => "pad",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|c| c.name() == name)
+    }
+
+    /// Where each setting is picked from, for sounds of this kind.
+    fn ranges(self) -> &'static Ranges {
+        match self {
+            Category::Bass => &BASS,
+            Category::Fx => &FX,
+            Category::Keys => &KEYS,
+            Category::Lead => &LEAD,
+            Category::Pad => &PAD,
+        }
+    }
+}
+
+/// A range to pick from, lowest and highest.
+type Range = (f32, f32);
+
+/// Those ending in `_log` are picked evenly on a log scale, as suits frequencies and times.
+struct Ranges {
+    waveforms: &'static [Waveform],
+    detune: Range,
+    cutoff_log: Range,
+    attack_log: Range,
+    decay_log: Range,
+    sustain: Range,
+    release_log: Range,
+    stereo_spread: Range,
+    volume: Range,
+    /// Effects to choose from, and where to put them.
+    effects: &'static [(&'static str, Placement)],
+    /// Most effects to add.
+    max_effects: usize,
+}
+
+const BASS: Ranges = Ranges {
+    waveforms: &Waveform::ALL,
+    detune: (0.0, 8.0),
+    cutoff_log: (300.0, 3000.0),
+    attack_log: (0.001, 0.02),
+    decay_log: (0.1, 0.8),
+    sustain: (0.4, 1.0),
+    release_log: (0.05, 0.3),
+    stereo_spread: (0.0, 0.2),
+    volume: (0.4, 0.5),
+    effects: &[
+        ("distortion", Placement::Voice),
+        ("compressor", Placement::Master),
+        ("tape", Placement::Master),
+    ],
+    max_effects: 1,
+};
+
+const FX: Ranges = Ranges {
+    waveforms: &Waveform::ALL,
+    detune: (0.0, 50.0),
+    cutoff_log: (200.0, 10000.0),
+    attack_log: (0.001, 2.0),
+    decay_log: (0.05, 2.0),
+    sustain: (0.0, 1.0),
+    release_log: (0.05, 4.0),
+    stereo_spread: (0.0, 1.0),
+    volume: (0.3, 0.5),
+    effects: &[
+        ("bitcrusher", Placement::Voice),
+        ("wavefolder", Placement::Voice),
+        ("pitch", Placement::Send(0)),
+        ("phaser", Placement::Master),
+        ("autopan", Placement::Master),
+        ("delay", Placement::Send(1)),
+        ("reverb", Placement::Send(1)),
+    ],
+    max_effects: 4,
+};
+
+const KEYS: Ranges = Ranges {
+    waveforms: &[Waveform::Sine, Waveform::Pulse],
+    detune: (0.0, 10.0),
+    cutoff_log: (1500.0, 6000.0),
+    attack_log: (0.001, 0.02),
+    decay_log: (0.3, 2.0),
+    sustain: (0.2, 0.8),
+    release_log: (0.2, 1.0),
+    stereo_spread: (0.2, 0.7),
+    volume: (0.4, 0.55),
+    effects: &[
+        ("chorus", Placement::Master),
+        ("phaser", Placement::Master),
+        ("tremolo", Placement::Master),
+        ("rotary", Placement::Master),
+        ("reverb", Placement::Send(0)),
+    ],
+    max_effects: 2,
+};
+
+const LEAD: Ranges = Ranges {
+    waveforms: &[Waveform::Pulse, Waveform::Saw],
+    detune: (3.0, 20.0),
+    cutoff_log: (2000.0, 8000.0),
+    attack_log: (0.005, 0.1),
+    decay_log: (0.2, 1.0),
+    sustain: (0.5, 1.0),
+    release_log: (0.1, 0.5),
+    stereo_spread: (0.0, 0.5),
+    volume: (0.35, 0.5),
+    effects: &[
+        ("distortion", Placement::Voice),
+        ("delay", Placement::Send(0)),
+        ("reverb", Placement::Send(1)),
+        ("chorus", Placement::Master),
+    ],
+    max_effects: 3,
+};
+
+const PAD: Ranges = Ranges {
+    waveforms: &Waveform::ALL,
+    detune: (8.0, 25.0),
+    cutoff_log: (800.0, 4000.0),
+    attack_log: (0.5, 3.0),
+    decay_log: (0.5, 2.0),
+    sustain: (0.6, 1.0),
+    release_log: (1.0, 4.0),
+    stereo_spread: (0.6, 1.0),
+    volume: (0.4, 0.55),
+    effects: &[
+        ("chorus", Placement::Master),
+        ("widener", Placement::Master),
+        ("delay", Placement::Send(0)),
+        ("reverb", Placement::Send(1)),
+    ],
+    max_effects: 3,
+};
+
+/// Ranges for effect parameters which shouldn't be picked from anywhere they can go, by effect and
+/// parameter name. The rest are left at their defaults, except for `mix`, which is picked from 0.2
+/// to 0.6 (or set to 1 on a send bus).
+const PARAMETERS: &[(&str, &str, Range)] = &[
+    ("autopan", "rate", (0.1, 4.0)),
+    ("autopan", "depth", (0.3, 1.0)),
+    ("bitcrusher", "bits", (4.0, 12.0)),
+    ("bitcrusher", "rate", (4000.0, 24000.0)),
+    ("chorus", "rate", (0.1, 2.0)),
+    ("chorus", "depth", (0.2, 0.8)),
+    ("compressor", "threshold", (-30.0, -10.0)),
+    ("compressor", "ratio", (2.0, 6.0)),
+    ("delay", "time", (0.1, 0.6)),
+    ("delay", "feedback", (0.2, 0.6)),
+    ("delay", "high cut", (2000.0, 8000.0)),
+    ("distortion", "drive", (3.0, 24.0)),
+    ("distortion", "output", (-12.0, -3.0)),
+    ("phaser", "rate", (0.05, 2.0)),
+    ("phaser", "depth", (0.3, 1.0)),
+    ("phaser", "feedback", (-0.7, 0.7)),
+    ("pitch", "pitch", (-12.0, 12.0)),
+    ("pitch", "spread", (0.0, 20.0)),
+    ("reverb", "size", (0.4, 0.95)),
+    ("reverb", "damping", (0.2, 0.8)),
+    ("rotary", "mic angle", (0.0, 0.25)),
+    ("tape", "drive", (0.0, 9.0)),
+    ("tremolo", "rate", (2.0, 8.0)),
+    ("tremolo", "depth", (0.2, 0.7)),
+    ("wavefolder", "fold", (0.1, 0.7)),
+    ("wavefolder", "symmetry", (-0.5, 0.5)),
+    ("widener", "width", (1.0, 1.8)),
+];
+
+/// How much of the voices goes to a send bus with effects on it.
+const SEND_LEVEL: Range = (0.2, 0.5);
+
+/// A small xorshift generator, for whatever in the synth picks things at random: quick, and the
+/// same every time for the same seed.
+#[derive(Debug, Clone)]
+pub(crate) struct Rng {
+    state: u32,
+}
+
+impl Rng {
+    pub(crate) fn new(seed: u32) -> Self {
+        // xorshift gets stuck on zero
+        Self {
+            state: seed.wrapping_mul(0x9E37_79B9) | 1,
+        }
+    }
+
+    /// Uniformly distributed from 0 to 1.
+    pub(crate) fn uniform(&mut self) -> f32 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 17;
+        self.state ^= self.state << 5;
+        self.state as f32 / u32::MAX as f32
+    }
+
+    /// A position in a list of `len` things.
+    pub(crate) fn index(&mut self, len: usize) -> usize {
+        ((self.uniform() * len as f32) as usize).min(len.saturating_sub(1))
+    }
+}
+
+/// Makes random patches.
+#[derive(Debug, Clone)]
+pub struct Randomizer {
+    rng: Rng,
+}
+
+impl Randomizer {
+    /// A randomizer which gives the same patches for the same seed.
+    pub fn new(seed: u32) -> Self {
+        Self {
+            rng: Rng::new(seed),
+        }
+    }
+
+    /// A new patch, of the given kind or any.
+    pub fn patch(&mut self, category: Option<Category>) -> Patch {
+        let category =
+            category.unwrap_or_else(|| Category::ALL[self.rng.index(Category::ALL.len())]);
+        let ranges = category.ranges();
+
+        let mut patch = Patch {
+            waveform: ranges.waveforms[self.rng.index(ranges.waveforms.len())],
+            detune: self.linear(ranges.detune),
+            cutoff: self.log(ranges.cutoff_log),
+            amp_envelope: AdsrConfig {
+                attack_time: self.log(ranges.attack_log),
+                decay_time: self.log(ranges.decay_log),
+                sustain_amount: self.linear(ranges.sustain),
+                release_time: self.log(ranges.release_log),
+            },
+            stereo_spread: self.linear(ranges.stereo_spread),
+            volume: self.linear(ranges.volume),
+            ..Patch::default()
+        };
+
+        // pick a few of the effects, keeping them in the order they're listed
+        let count = self.rng.index(ranges.max_effects + 1);
+        let mut chosen = vec![false; ranges.effects.len()];
+        for _ in 0..count {
+            let index = self.rng.index(chosen.len());
+            chosen[index] = true;
+        }
+        for (&(name, placement), _) in ranges.effects.iter().zip(chosen).filter(|(_, c)| *c) {
+            patch.effects.push(self.effect(name, placement));
+            if let Placement::Send(send) = placement {
+                if send < SENDS {
+                    patch.send_levels[send] = self.linear(SEND_LEVEL);
+                }
+            }
+        }
+        patch
+    }
+
+    fn effect(&mut self, name: &str, placement: Placement) -> EffectPatch {
+        let mut patch = EffectPatch::new(name);
+        patch.placement = placement;
+        let names = effects::by_name(name).map_or(&[][..], |e| e.parameter_names());
+        for &parameter in names {
+            let range = PARAMETERS
+                .iter()
+                .find(|(e, p, _)| *e == name && *p == parameter)
+                .map(|(_, _, range)| *range);
+            let value = match (range, parameter, placement) {
+                (Some(range), _, _) => self.linear(range),
+                (None, "mix", Placement::Send(_)) => 1.0,
+                (None, "mix", _) => self.linear((0.2, 0.6)),
+                (None, _, _) => continue,
+            };
+            patch.parameters.push((parameter.to_string(), value));
+        }
+        patch
+    }
+
+    fn linear(&mut self, (low, high): Range) -> f32 {
+        low + (high - low) * self.rng.uniform()
+    }
+
+    fn log(&mut self, (low, high): Range) -> f32 {
+        low * (high / low).powf(self.rng.uniform())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rng_is_the_same_for_the_same_seed() {
+        for seed in [0, 1, 42, u32::MAX] {
+            let (mut a, mut b) = (Rng::new(seed), Rng::new(seed));
+            for _ in 0..100 {
+                assert_eq!(a.uniform(), b.uniform());
+            }
+        }
+        assert_ne!(Rng::new(1).uniform(), Rng::new(2).uniform());
+    }
+
+    #[test]
+    fn rng_stays_in_range() {
+        let mut rng = Rng::new(0);
+        let mut seen = [false; 7];
+        for _ in 0..1000 {
+            let value = rng.uniform();
+            assert!((0.0..=1.0).contains(&value), "{}", value);
+            seen[rng.index(seen.len())] = true;
+        }
+        assert!(seen.iter().all(|seen| *seen));
+        assert_eq!(rng.index(1), 0);
+        assert_eq!(rng.index(0), 0);
+    }
+
+    #[test]
+    fn patches_are_the_same_for_the_same_seed() {
+        for category in [None, Some(Category::Bass), Some(Category::Pad)] {
+            let (mut a, mut b) = (Randomizer::new(7), Randomizer::new(7));
+            for _ in 0..10 {
+                assert_eq!(a.patch(category), b.patch(category));
+            }
+        }
+    }
+
+    #[cfg(all(feature = "presets", feature = "effects"))]
+    #[test]
+    fn patches_can_be_read_back() {
+        let mut randomizer = Randomizer::new(3);
+        for _ in 0..100 {
+            let patch = randomizer.patch(None);
+            let mut text = alloc::vec::Vec::new();
+            patch.write(&mut text).unwrap();
+            assert!(Patch::read(&text[..]).is_ok(), "{:?}", patch);
+        }
+    }
+}
@@ -0,0 +1,107 @@
+//! Conversion of interleaved audio between sample rates, for playing through devices which don't
+//! run at the rate the synth was created with.
+
+use alloc::{vec, vec::Vec};
+use core::f64::consts::PI;
+
+#[cfg(not(any(feature = "std", test)))]
+use crate::math::Float;
+
+/// How many input frames either side of each output frame contribute to it.
+const HALF_WIDTH: usize = 16;
+/// How finely the interpolation kernel is tabulated between input frames.
+const PHASES: usize = 256;
+/// Passband edge, relative to the lower of the two Nyquist frequencies.
+const CUTOFF: f64 = 0.95;
+
+/// A streaming windowed-sinc resampler.
+///
+/// Each output frame is interpolated from the nearest `2 * HALF_WIDTH` input frames, with the
+/// kernel narrowed when downsampling so that nothing above the new Nyquist frequency folds back.
+/// Each output frame can only be produced once `HALF_WIDTH` input frames past it have arrived.
+pub struct Resampler {
+    channels: usize,
+    /// Input frames advanced per output frame.
+    step: f64,
+    /// One side of the (symmetric) kernel, from its center outwards.
+    kernel: Vec<f32>,
+    /// Interleaved input which is still needed.
+    pending: Vec<f32>,
+    /// Where the next output frame falls in `pending`, in frames.
+    position: f64,
+}
+
+impl Resampler {
+    pub fn new(from_rate: u32, to_rate: u32, channels: usize) -> Self {
+        assert!(
+            from_rate > 0 && to_rate > 0,
+            "Sample rates must be positive"
+        );
+        let cutoff = CUTOFF * (to_rate as f64 / from_rate as f64).min(1.0);
+        let kernel = (0..=HALF_WIDTH * PHASES + 1)
+            .map(|i| {
+                let x = i as f64 / PHASES as f64;
+                let sinc = if x == 0.0 {
+                    cutoff
+                } else {
+                    (PI * cutoff * x).sin() / (PI * x)
+                };
+                // Blackman window, reaching zero at the edges
+                let phase = PI * (x / HALF_WIDTH as f64).min(1.0);
+                let window = 0.42 + 0.5 * phase.cos() + 0.08 * (2.0 * phase).cos();
+                (sinc * window) as f32
+            })
+            .collect();
+
+        Self {
+            channels,
+            step: from_rate as f64 / to_rate as f64,
+            kernel,
+            // start off with silence before the first frame
+            pending: vec![0.0; (HALF_WIDTH - 1) * channels],
+            position: (HALF_WIDTH - 1) as f64,
+        }
+    }
+
+    /// Take in some interleaved input, and append as much output as it allows to `output`.
+    pub fn process(&mut self, input: &[f32], output: &mut Vec<f32>) {
+        self.pending.extend_from_slice(input);
+        let frames = self.pending.len() / self.channels;
+
+        while self.position as usize + HALF_WIDTH < frames {
+            let base = self.position as usize;
+            let fraction = self.position - base as f64;
+            let start = output.len();
+            output.resize(start + self.channels, 0.0);
+
+            for tap in (base + 1 - HALF_WIDTH)..=(base + HALF_WIDTH) {
+                let weight = self.weight((tap as f64 - base as f64 - fraction).abs());
+                let frame = &self.pending[tap * self.channels..(tap + 1) * self.channels];
+                for (out, sample) in output[start..].iter_mut().zip(frame) {
+                    *out += sample * weight;
+                }
+            }
+
+            self.position += self.step;
+        }
+
+        // forget frames which are too far back to be needed again
+        let done = (self.position as usize + 1).saturating_sub(HALF_WIDTH);
+        self.pending.drain(..done * self.channels);
+        self.position -= done as f64;
+    }
+
+    /// The most output samples one call to `process` can produce, for `input_len` samples in.
+    pub fn max_output_len(&self, input_len: usize) -> usize {
+        ((input_len / self.channels) as f64 / self.step).ceil() as usize * self.channels
+            + self.channels
+    }
+
+    /// Look up the kernel at `distance` input frames from its center.
+    fn weight(&self, distance: f64) -> f32 {
+        let index = distance * PHASES as f64;
+        let lower = index as usize;
+        let fraction = (index - lower as f64) as f32;
+        self.kernel[lower] + (self.kernel[lower + 1] - self.kernel[lower]) * fraction
+    }
+}
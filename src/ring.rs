@@ -0,0 +1,153 @@
+//! A lock-free, single-producer single-consumer ring buffer, for passing samples or messages
+//! between threads without either side ever blocking.
+
+use {
+    alloc::{boxed::Box, sync::Arc},
+    core::{
+        cell::UnsafeCell,
+        mem::MaybeUninit,
+        sync::atomic::{AtomicUsize, Ordering},
+    },
+};
+
+/// Create a ring buffer which can hold up to `capacity` items, split into its two ends.
+pub fn ring_buffer<T>(capacity: usize) -> (Producer<T>, Consumer<T>) {
+    assert!(capacity > 0, "Ring buffer capacity must be at least one");
+    let shared = Arc::new(Shared {
+        slots: (0..capacity)
+            .map(|_| UnsafeCell::new(MaybeUninit::uninit()))
+            .collect(),
+        read: AtomicUsize::new(0),
+        write: AtomicUsize::new(0),
+    });
+    (
+        Producer {
+            shared: shared.clone(),
+        },
+        Consumer { shared },
+    )
+}
+
+struct Shared<T> {
+    /// Only the slots between `read` and `write` have been initialized.
+    slots: Box<[UnsafeCell<MaybeUninit<T>>]>,
+    /// Total number of items ever read. Only written by the consumer.
+    read: AtomicUsize,
+    /// Total number of items ever written. Only written by the producer.
+    write: AtomicUsize,
+}
+
+// Each slot is only ever accessed by one side at a time, as arbitrated by `read` and `write`.
+unsafe impl<T: Send> Sync for Shared<T> {}
+
+impl<T> Shared<T> {
+    fn len(&self) -> usize {
+        self.write
+            .load(Ordering::Acquire)
+            .wrapping_sub(self.read.load(Ordering::Acquire))
+    }
+
+    fn slot(&self, counter: usize) -> *mut MaybeUninit<T> {
+        self.slots[counter % self.slots.len()].get()
+    }
+}
+
+impl<T> Drop for Shared<T> {
+    fn drop(&mut self) {
+        // whatever was pushed but never popped is still owned by the buffer
+        let read = *self.read.get_mut();
+        let unread = self.write.get_mut().wrapping_sub(read);
+        for offset in 0..unread {
+            unsafe { (*self.slot(read.wrapping_add(offset))).assume_init_drop() };
+        }
+    }
+}
+
+/// The writing end of a ring buffer.
+pub struct Producer<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> Producer<T> {
+    /// How many items could be pushed right now.
+    pub fn free_len(&self) -> usize {
+        self.shared.slots.len() - self.shared.len()
+    }
+
+    /// Add an item, or hand it back if the buffer is full.
+    pub fn push(&mut self, item: T) -> Result<(), T> {
+        if self.free_len() == 0 {
+            return Err(item);
+        }
+        let write = self.shared.write.load(Ordering::Relaxed);
+        unsafe { (*self.shared.slot(write)).write(item) };
+        self.shared
+            .write
+            .store(write.wrapping_add(1), Ordering::Release);
+        Ok(())
+    }
+
+    /// Whether the consumer has been dropped, so that nothing pushed will ever be read.
+    pub fn is_abandoned(&self) -> bool {
+        Arc::strong_count(&self.shared) == 1
+    }
+}
+
+impl<T: Copy> Producer<T> {
+    /// Add as many of `items` as will fit, returning how many that was.
+    pub fn push_slice(&mut self, items: &[T]) -> usize {
+        let count = items.len().min(self.free_len());
+        let write = self.shared.write.load(Ordering::Relaxed);
+        for (offset, item) in items[..count].iter().enumerate() {
+            unsafe { (*self.shared.slot(write.wrapping_add(offset))).write(*item) };
+        }
+        self.shared
+            .write
+            .store(write.wrapping_add(count), Ordering::Release);
+        count
+    }
+}
+
+/// The reading end of a ring buffer.
+pub struct Consumer<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> Consumer<T> {
+    /// How many items are waiting to be read.
+    pub fn len(&self) -> usize {
+        self.shared.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Take the oldest item, if there is one.
+    pub fn pop(&mut self) -> Option<T> {
+        if self.is_empty() {
+            return None;
+        }
+        let read = self.shared.read.load(Ordering::Relaxed);
+        let item = unsafe { (*self.shared.slot(read)).assume_init_read() };
+        self.shared
+            .read
+            .store(read.wrapping_add(1), Ordering::Release);
+        Some(item)
+    }
+}
+
+impl<T: Copy> Consumer<T> {
+    /// Fill as much of `out` as possible with the oldest items, returning how many were taken.
+    pub fn pop_slice(&mut self, out: &mut [T]) -> usize {
+        let count = out.len().min(self.len());
+        let read = self.shared.read.load(Ordering::Relaxed);
+        for (offset, item) in out[..count].iter_mut().enumerate() {
+            *item = unsafe { (*self.shared.slot(read.wrapping_add(offset))).assume_init() };
+        }
+        self.shared
+            .read
+            .store(read.wrapping_add(count), Ordering::Release);
+        count
+    }
+}
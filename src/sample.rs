@@ -0,0 +1,240 @@
+//! The number types the DSP building blocks (`Oscillator`, `Lfo`, `Filter` and `Adsr`) can run
+//! on. `f32` is the default, and what `Synth` itself uses; `f64` is there for when the extra
+//! precision is worth the cost, e.g. for long chains of filters. With the `fixed` feature,
+//! `Fixed` runs them on integer arithmetic alone, for microcontrollers without an FPU.
+
+use core::{
+    fmt::Debug,
+    ops::{Add, Div, Mul, Neg, Rem, Sub},
+};
+
+#[cfg(not(any(feature = "std", test)))]
+use crate::math::Float;
+
+/// A number type for samples, and for everything worked out alongside them each sample.
+///
+/// Settings (such as a filter's cutoff) are converted from `f64` when they change, so only the
+/// arithmetic here happens every sample.
+pub trait Sample:
+    Copy
+    + Debug
+    + Default
+    + PartialOrd
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + Mul<Output = Self>
+    + Div<Output = Self>
+    + Rem<Output = Self>
+    + Neg<Output = Self>
+    + Send
+    + Sync
+    + 'static
+{
+    const ZERO: Self;
+    const ONE: Self;
+    const PI: Self;
+    const TAU: Self;
+
+    fn from_f64(value: f64) -> Self;
+
+    fn to_f64(self) -> f64;
+
+    /// The sine of an angle in radians.
+    fn sin(self) -> Self;
+
+    fn abs(self) -> Self;
+}
+
+macro_rules! impl_float_sample {
+    ($float:ident) => {
+        impl Sample for $float {
+            const ZERO: Self = 0.0;
+            const ONE: Self = 1.0;
+            const PI: Self = core::$float::consts::PI;
+            const TAU: Self = core::$float::consts::TAU;
+
+            fn from_f64(value: f64) -> Self {
+                value as $float
+            }
+
+            fn to_f64(self) -> f64 {
+                self as f64
+            }
+
+            fn sin(self) -> Self {
+                #[cfg(any(feature = "std", test))]
+                return $float::sin(self);
+                #[cfg(not(any(feature = "std", test)))]
+                return Float::sin(self);
+            }
+
+            fn abs(self) -> Self {
+                $float::abs(self)
+            }
+        }
+    };
+}
+
+impl_float_sample!(f32);
+impl_float_sample!(f64);
+
+/// A fixed point number, with 27 bits after the binary point in an `i32`: enough range (from -16
+/// to 16) for samples, phases and envelope levels, at a resolution of about 7.5e-9, or 160 dB.
+///
+/// Arithmetic saturates rather than overflowing. Multiplying and dividing go through an `i64`, so
+/// multiplying is a single instruction on 32 bit CPUs such as the Cortex-M3 and M4, though
+/// dividing is slower; none of the building blocks divide each sample except `Waveform::Saw`.
+#[cfg(feature = "fixed")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Fixed(i32);
+
+#[cfg(feature = "fixed")]
+impl Fixed {
+    /// Number of bits after the binary point.
+    pub const FRAC_BITS: u32 = 27;
+
+    pub const MIN: Self = Self(i32::MIN);
+    pub const MAX: Self = Self(i32::MAX);
+
+    const SCALE: f64 = (1 << Self::FRAC_BITS) as f64;
+
+    /// The number whose underlying integer is `bits`, i.e. `bits` / 2^27.
+    pub const fn from_bits(bits: i32) -> Self {
+        Self(bits)
+    }
+
+    pub const fn to_bits(self) -> i32 {
+        self.0
+    }
+
+    /// `value`, rounded to the nearest representable number, or saturating.
+    const fn from_f64_const(value: f64) -> Self {
+        let scaled = value * Self::SCALE;
+        // `as` saturates, and turns NaN into zero
+        Self(if scaled < 0.0 {
+            (scaled - 0.5) as i32
+        } else {
+            (scaled + 0.5) as i32
+        })
+    }
+
+    fn saturate(wide: i64) -> Self {
+        Self(wide.clamp(i32::MIN as i64, i32::MAX as i64) as i32)
+    }
+}
+
+#[cfg(feature = "fixed")]
+impl Add for Fixed {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Self(self.0.saturating_add(rhs.0))
+    }
+}
+
+#[cfg(feature = "fixed")]
+impl Sub for Fixed {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        Self(self.0.saturating_sub(rhs.0))
+    }
+}
+
+#[cfg(feature = "fixed")]
+impl Mul for Fixed {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self {
+        Self::saturate((self.0 as i64 * rhs.0 as i64) >> Self::FRAC_BITS)
+    }
+}
+
+/// Dividing by zero saturates, towards the sign of the dividend.
+#[cfg(feature = "fixed")]
+impl Div for Fixed {
+    type Output = Self;
+
+    fn div(self, rhs: Self) -> Self {
+        if rhs.0 == 0 {
+            return match self.0 {
+                0 => Self(0),
+                n if n < 0 => Self::MIN,
+                _ => Self::MAX,
+            };
+        }
+        Self::saturate(((self.0 as i64) << Self::FRAC_BITS) / rhs.0 as i64)
+    }
+}
+
+/// The remainder after dividing by zero is zero.
+#[cfg(feature = "fixed")]
+impl Rem for Fixed {
+    type Output = Self;
+
+    fn rem(self, rhs: Self) -> Self {
+        // dividing `MIN` by -1 would overflow, but leaves no remainder either
+        Self(self.0.checked_rem(rhs.0).unwrap_or(0))
+    }
+}
+
+#[cfg(feature = "fixed")]
+impl Neg for Fixed {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self(self.0.saturating_neg())
+    }
+}
+
+#[cfg(feature = "fixed")]
+impl Sample for Fixed {
+    const ZERO: Self = Self(0);
+    const ONE: Self = Self(1 << Self::FRAC_BITS);
+    const PI: Self = Self::from_f64_const(core::f64::consts::PI);
+    const TAU: Self = Self::from_f64_const(core::f64::consts::TAU);
+
+    fn from_f64(value: f64) -> Self {
+        Self::from_f64_const(value)
+    }
+
+    fn to_f64(self) -> f64 {
+        self.0 as f64 / Self::SCALE
+    }
+
+    /// From the Taylor series, after folding the angle into -π/2 to π/2, where it converges
+    /// quickly; accurate to a few parts in 10^8.
+    fn sin(self) -> Self {
+        const HALF_PI: Fixed = Fixed::from_f64_const(core::f64::consts::FRAC_PI_2);
+        /// 1 / (n (n + 1)) for each term of the series, last first.
+        const FACTORS: [Fixed; 5] = [
+            Fixed::from_f64_const(1.0 / 110.0),
+            Fixed::from_f64_const(1.0 / 72.0),
+            Fixed::from_f64_const(1.0 / 42.0),
+            Fixed::from_f64_const(1.0 / 20.0),
+            Fixed::from_f64_const(1.0 / 6.0),
+        ];
+        let mut x = self % Self::TAU;
+        if x > Self::PI {
+            x = x - Self::TAU;
+        } else if x < -Self::PI {
+            x = x + Self::TAU;
+        }
+        if x > HALF_PI {
+            x = Self::PI - x;
+        } else if x < -HALF_PI {
+            x = -Self::PI - x;
+        }
+        // x (1 - x²/(2·3) (1 - x²/(4·5) (1 - ...)))
+        let x2 = x * x;
+        let mut sum = Self::ONE;
+        for factor in FACTORS {
+            sum = Self::ONE - x2 * factor * sum;
+        }
+        x * sum
+    }
+
+    fn abs(self) -> Self {
+        Self(self.0.saturating_abs())
+    }
+}
@@ -0,0 +1,56 @@
+//! Commands waiting for the frame they're meant to happen at, so that events can land in the
+//! middle of a block rather than all at its start.
+
+use alloc::collections::VecDeque;
+
+use crate::SynthCommand;
+
+/// Most commands which can be waiting at once.
+const SCHEDULE_LEN: usize = 1024;
+
+/// Commands in order of the frame they're due at, with room for them all made up front so that
+/// neither adding nor taking them allocates.
+pub(crate) struct Schedule {
+    events: VecDeque<(u64, SynthCommand)>,
+}
+
+impl Schedule {
+    pub(crate) fn new() -> Self {
+        Self {
+            events: VecDeque::with_capacity(SCHEDULE_LEN),
+        }
+    }
+
+    /// Add a command due at `frame`, after any already due then. Gives the command back if there
+    /// are too many waiting.
+    pub(crate) fn push(&mut self, frame: u64, command: SynthCommand) -> Result<(), SynthCommand> {
+        if self.is_full() {
+            return Err(command);
+        }
+        // usually at the end, as commands tend to be scheduled in order
+        let index = self.events.partition_point(|(due, _)| *due <= frame);
+        self.events.insert(index, (frame, command));
+        Ok(())
+    }
+
+    pub(crate) fn is_full(&self) -> bool {
+        self.events.len() == SCHEDULE_LEN
+    }
+
+    /// Take the next command due at or before `now`, if any.
+    pub(crate) fn pop_due(&mut self, now: u64) -> Option<SynthCommand> {
+        match self.events.front() {
+            Some((due, _)) if *due <= now => self.events.pop_front().map(|(_, command)| command),
+            _ => None,
+        }
+    }
+
+    /// When the next command is due, if there is one.
+    pub(crate) fn next_time(&self) -> Option<u64> {
+        self.events.front().map(|(due, _)| *due)
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.events.clear();
+    }
+}
@@ -0,0 +1,108 @@
+//! Send buses: effects which are shared by all the voices, each voice sending as much of itself
+//! as it likes into them, with the results returned to the main mix. One reverb can then serve
+//! every voice, rather than each needing its own.
+
+use alloc::{vec, vec::Vec};
+
+use crate::{effects::EffectsChain, Decimator, Frame, Oversampling, CHANNELS};
+
+/// Number of send buses every synth has.
+pub const SENDS: usize = 2;
+
+/// One send bus, with its effects and the level they're returned to the mix at.
+pub struct SendBus {
+    effects: EffectsChain,
+    return_level: f32,
+    /// What the voices have sent in, at the internal sample rate.
+    bus: Vec<Frame>,
+    decimators: [Decimator; CHANNELS],
+    /// The bus at the output sample rate, on its way through the effects.
+    block: Vec<Frame>,
+}
+
+impl SendBus {
+    pub(crate) fn new(sample_rate: u32, oversampling: Oversampling) -> Self {
+        Self {
+            effects: EffectsChain::new(sample_rate),
+            return_level: 1.0,
+            bus: Vec::new(),
+            decimators: [(); CHANNELS].map(|_| Decimator::new(oversampling)),
+            block: Vec::new(),
+        }
+    }
+
+    /// The effects on the bus, which should usually be set fully wet.
+    pub fn effects(&self) -> &EffectsChain {
+        &self.effects
+    }
+
+    pub fn effects_mut(&mut self) -> &mut EffectsChain {
+        &mut self.effects
+    }
+
+    /// Gain the bus is mixed back in with, from 0 to 1.
+    pub fn set_return_level(&mut self, level: f32) {
+        self.return_level = level.clamp(0.0, 1.0);
+    }
+
+    pub fn return_level(&self) -> f32 {
+        self.return_level
+    }
+
+    /// Whether anything needs doing with the bus. With no effects there's nothing to hear.
+    pub(crate) fn is_active(&self) -> bool {
+        !self.effects.is_empty()
+    }
+
+    pub(crate) fn set_block_size(&mut self, block_size: usize, oversampled_len: usize) {
+        self.bus = vec![Frame::ZERO; oversampled_len];
+        self.block = vec![Frame::ZERO; block_size];
+    }
+
+    /// Forget any audio still ringing out in the effects or the decimators.
+    pub(crate) fn reset(&mut self) {
+        self.effects.reset();
+        for decimator in &mut self.decimators {
+            decimator.reset();
+        }
+    }
+
+    /// Clear the bus for the voices to send `len` samples into.
+    pub(crate) fn clear(&mut self, len: usize) {
+        self.bus[..len].fill(Frame::ZERO);
+    }
+
+    /// Add a voice's signal into the bus, at the given gain for each channel.
+    pub(crate) fn send_mono(&mut self, samples: &[f32], gains: Frame) {
+        for (out, sample) in self.bus.iter_mut().zip(samples) {
+            *out += Frame::mono(*sample) * gains;
+        }
+    }
+
+    /// Add a voice's stereo signal into the bus, at the given gain for each channel.
+    pub(crate) fn send_stereo(&mut self, frames: &[Frame], gains: Frame) {
+        for (out, frame) in self.bus.iter_mut().zip(frames) {
+            *out += *frame * gains;
+        }
+    }
+
+    /// Run what's been sent through the effects, and mix it into `block` (at the output rate).
+    pub(crate) fn mix_into(&mut self, block: &mut [Frame], ratio: usize) {
+        let returned = &mut self.block[..block.len()];
+        for (out, oversampled) in returned.iter_mut().zip(self.bus.chunks_exact(ratio)) {
+            for frame in oversampled {
+                for (decimator, sample) in self.decimators.iter_mut().zip(*frame) {
+                    decimator.push(sample);
+                }
+            }
+            for (sample, decimator) in out.iter_mut().zip(&mut self.decimators) {
+                *sample = decimator.output();
+            }
+        }
+
+        self.effects.process(returned);
+        for (out, frame) in block.iter_mut().zip(returned.iter()) {
+            *out += *frame * self.return_level;
+        }
+    }
+}
@@ -0,0 +1,704 @@
+//! A step sequencer, so that the synth can play patterns by itself: up to 32 steps, each a note
+//! or a rest, with its own velocity and gate length, tied into the next step or not, and
+//! optionally locking the filter cutoff while it plays. Steps are sixteenth notes, timed by the
+//! synth's tempo or by MIDI clock (see `SequencerClock`).
+//!
+//! The sequencer runs inside the synth, so its notes land on exactly the right frame. It is
+//! controlled through `SequencerCommand`s, with `Synth::control_sequencer` or as
+//! `SynthCommand::Sequencer` from other threads.
+//!
+//! With the `presets` feature, patterns have a text form, one step per line: a note and velocity
+//! as in MIDI and a gate length as a fraction of the step, e.g. `60 100 0.5`, followed by `tie`
+//! to hold the note into the next step, and `cutoff=800` to lock the cutoff, in Hz; or `rest`.
+//! Blank lines and `#` comments are ignored.
+
+use alloc::boxed::Box;
+
+use crate::param::ParamId;
+
+#[cfg(not(any(feature = "std", test)))]
+use crate::math::Float;
+
+#[cfg(feature = "presets")]
+use {
+    alloc::{format, string::String, vec::Vec},
+    core::{fmt, str::FromStr},
+};
+
+/// Most steps a pattern can have.
+pub const MAX_STEPS: usize = 32;
+
+/// Steps a new pattern has, one bar of sixteenth notes.
+pub const DEFAULT_STEPS: usize = 16;
+
+/// Steps to each beat: sixteenth notes.
+const STEPS_PER_BEAT: f64 = 4.0;
+
+/// MIDI clock ticks to each step, at 24 to a beat.
+const TICKS_PER_STEP: u32 = 6;
+
+/// One step of a pattern.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct Step {
+    /// The MIDI note number played, or `None` for a rest.
+    pub note: Option<u8>,
+    pub velocity: u8,
+    /// How much of the step the note is held for, from 0 to 1.
+    pub gate: f32,
+    /// Hold the note into the next step, which carries it on rather than starting it again if it
+    /// plays the same note, or slides into its own note without a gap if not.
+    pub tie: bool,
+    /// A cutoff, in Hz, to hold the filter at while the step plays, in place of the synth's own:
+    /// from 20 Hz to 20 kHz, as for `ParamId::Cutoff`.
+    pub cutoff: Option<f32>,
+}
+
+impl Step {
+    /// A rest.
+    pub const REST: Self = Self {
+        note: None,
+        velocity: 100,
+        gate: 0.5,
+        tie: false,
+        cutoff: None,
+    };
+
+    /// A step playing `note`, for half of the step.
+    pub fn note(note: u8, velocity: u8) -> Self {
+        Self {
+            note: Some(note),
+            velocity,
+            ..Self::REST
+        }
+    }
+}
+
+impl Default for Step {
+    fn default() -> Self {
+        Self::REST
+    }
+}
+
+/// A sequence of steps, played in a loop.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(into = "alloc::vec::Vec<Step>", try_from = "alloc::vec::Vec<Step>")
+)]
+pub struct Pattern {
+    steps: [Step; MAX_STEPS],
+    len: usize,
+}
+
+impl Pattern {
+    /// A pattern of `len` rests, from 1 to `MAX_STEPS`.
+    pub fn new(len: usize) -> Self {
+        Self {
+            steps: [Step::REST; MAX_STEPS],
+            len: len.clamp(1, MAX_STEPS),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Patterns always have at least one step.
+    pub fn is_empty(&self) -> bool {
+        false
+    }
+
+    /// Change how many steps are played, from 1 to `MAX_STEPS`. Steps beyond the end are kept,
+    /// to come back if the pattern is lengthened again.
+    pub fn set_len(&mut self, len: usize) {
+        self.len = len.clamp(1, MAX_STEPS);
+    }
+
+    pub fn steps(&self) -> &[Step] {
+        &self.steps[..self.len]
+    }
+
+    /// A pattern of the steps given, from 1 to `MAX_STEPS` of them.
+    #[cfg(any(feature = "presets", feature = "serde"))]
+    fn from_steps(steps: alloc::vec::Vec<Step>) -> Result<Self, alloc::string::String> {
+        if steps.is_empty() || steps.len() > MAX_STEPS {
+            return Err(alloc::format!(
+                "A pattern has from 1 to {} steps, not {}",
+                MAX_STEPS,
+                steps.len()
+            ));
+        }
+        let mut pattern = Pattern::new(steps.len());
+        for (index, step) in steps.into_iter().enumerate() {
+            pattern.set_step(index, step);
+        }
+        Ok(pattern)
+    }
+
+    /// Change a step, anywhere up to `MAX_STEPS`, even beyond the pattern's length. Out of range
+    /// values are clamped, a cutoff which isn't a number is dropped, and indices beyond
+    /// `MAX_STEPS` are ignored.
+    pub fn set_step(&mut self, index: usize, step: Step) {
+        if let Some(slot) = self.steps.get_mut(index) {
+            *slot = Step {
+                note: step.note.map(|note| note.min(127)),
+                velocity: step.velocity.min(127),
+                gate: step.gate.clamp(0.0, 1.0),
+                cutoff: step
+                    .cutoff
+                    .filter(|cutoff| !cutoff.is_nan())
+                    .map(|cutoff| ParamId::Cutoff.clamp(cutoff)),
+                ..step
+            };
+        }
+    }
+}
+
+impl Default for Pattern {
+    fn default() -> Self {
+        Self::new(DEFAULT_STEPS)
+    }
+}
+
+/// Patterns serialize as their steps.
+#[cfg(feature = "serde")]
+impl From<Pattern> for alloc::vec::Vec<Step> {
+    fn from(pattern: Pattern) -> Self {
+        pattern.steps().to_vec()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl core::convert::TryFrom<alloc::vec::Vec<Step>> for Pattern {
+    type Error = alloc::string::String;
+
+    fn try_from(steps: alloc::vec::Vec<Step>) -> Result<Self, Self::Error> {
+        Self::from_steps(steps)
+    }
+}
+
+/// What moves the sequencer from one step to the next.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case"))]
+pub enum SequencerClock {
+    /// The synth's own tempo (see `Synth::set_tempo`).
+    #[default]
+    Internal,
+    /// MIDI clock messages, six to a step. Steps are as long as the ticks are far apart, and gate
+    /// lengths follow the tempo measured from them.
+    Midi,
+}
+
+/// Something for the sequencer to do.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case"))]
+pub enum SequencerCommand {
+    /// Play from the first step, as does MIDI Start.
+    Start,
+    /// Play on from where it stopped, as does MIDI Continue.
+    Continue,
+    /// Stop, releasing the note playing, as does MIDI Stop.
+    Stop,
+    SetClock(SequencerClock),
+    /// Change one step (see `Pattern::set_step`).
+    SetStep {
+        index: usize,
+        step: Step,
+    },
+    /// Change how many steps the pattern has (see `Pattern::set_len`).
+    SetLength(usize),
+    /// Replace the whole pattern. Boxed, as for patches, so dropping it frees it.
+    LoadPattern(Box<Pattern>),
+}
+
+/// How many frames each step lasts at `tempo`, in beats per minute.
+pub(crate) fn step_len(sample_rate: u32, tempo: f32) -> f64 {
+    sample_rate as f64 * 60.0 / (tempo as f64 * STEPS_PER_BEAT)
+}
+
+/// Notes for the synth to start and end as the sequencer moves.
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct Notes {
+    pub(crate) play: Option<(u8, u8)>,
+    pub(crate) release: Option<u8>,
+}
+
+/// Plays a pattern, telling the synth which notes to start and end, and when.
+#[derive(Debug)]
+pub struct Sequencer {
+    pattern: Pattern,
+    clock: SequencerClock,
+    running: bool,
+    /// The step playing, or the one to play next when stopped.
+    position: usize,
+    /// When the next step is due, in frames, with the fraction kept so that steps don't drift.
+    next_step_at: f64,
+    /// When the note playing is due to be released, unless it's tied.
+    gate_end_at: Option<u64>,
+    /// MIDI clock ticks since the step started.
+    ticks: u32,
+    /// The note the sequencer is playing, and whether it's tied to the next step.
+    sounding: Option<(u8, bool)>,
+    cutoff_lock: Option<f32>,
+}
+
+impl Sequencer {
+    pub(crate) fn new() -> Self {
+        Self {
+            pattern: Pattern::default(),
+            clock: SequencerClock::default(),
+            running: false,
+            position: 0,
+            next_step_at: 0.0,
+            gate_end_at: None,
+            ticks: 0,
+            sounding: None,
+            cutoff_lock: None,
+        }
+    }
+
+    pub fn pattern(&self) -> &Pattern {
+        &self.pattern
+    }
+
+    pub fn clock(&self) -> SequencerClock {
+        self.clock
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running
+    }
+
+    /// The step playing, counting from zero, or the one which will play next if stopped.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// The cutoff the step playing holds the filter at, if any.
+    pub(crate) fn cutoff_lock(&self) -> Option<f32> {
+        self.cutoff_lock
+    }
+
+    /// Carry out a command, at frame `now`, with steps `step_len` frames long.
+    pub(crate) fn control(&mut self, command: SequencerCommand, now: u64, step_len: f64) -> Notes {
+        match command {
+            SequencerCommand::Start => {
+                let notes = self.stop();
+                self.position = self.pattern.len() - 1;
+                self.running = true;
+                Notes {
+                    release: notes.release,
+                    ..self.advance(now, step_len)
+                }
+            }
+            SequencerCommand::Continue if !self.running => {
+                self.running = true;
+                self.position = (self.position + self.pattern.len() - 1) % self.pattern.len();
+                self.advance(now, step_len)
+            }
+            SequencerCommand::Continue => Notes::default(),
+            SequencerCommand::Stop => self.stop(),
+            SequencerCommand::SetClock(clock) => {
+                self.clock = clock;
+                self.ticks = 0;
+                self.next_step_at = now as f64 + step_len;
+                Notes::default()
+            }
+            SequencerCommand::SetStep { index, step } => {
+                self.pattern.set_step(index, step);
+                Notes::default()
+            }
+            SequencerCommand::SetLength(len) => {
+                self.pattern.set_len(len);
+                self.position %= self.pattern.len();
+                Notes::default()
+            }
+            SequencerCommand::LoadPattern(pattern) => {
+                self.load(&pattern);
+                Notes::default()
+            }
+        }
+    }
+
+    /// Replace the whole pattern with a copy of `pattern`.
+    pub(crate) fn load(&mut self, pattern: &Pattern) {
+        self.pattern.clone_from(pattern);
+        self.position %= self.pattern.len();
+    }
+
+    /// Stop, giving the note to release.
+    fn stop(&mut self) -> Notes {
+        if self.running {
+            // Continue picks up after the step which was playing
+            self.position = (self.position + 1) % self.pattern.len();
+        }
+        self.running = false;
+        self.gate_end_at = None;
+        self.cutoff_lock = None;
+        Notes {
+            release: self.sounding.take().map(|(note, _)| note),
+            ..Notes::default()
+        }
+    }
+
+    /// When something next happens, following the synth's own clock: the note playing ending,
+    /// or the next step.
+    pub(crate) fn next_event(&self) -> Option<u64> {
+        if !self.running {
+            return None;
+        }
+        let next_step = match self.clock {
+            SequencerClock::Internal => Some(self.next_step_at.ceil() as u64),
+            SequencerClock::Midi => None,
+        };
+        match (self.gate_end_at, next_step) {
+            (Some(gate), Some(step)) => Some(gate.min(step)),
+            (gate, step) => gate.or(step),
+        }
+    }
+
+    /// Do whatever is due at frame `now`, with steps `step_len` frames long: first ending the
+    /// note playing, then moving to the next step.
+    pub(crate) fn run(&mut self, now: u64, step_len: f64) -> Notes {
+        if !self.running {
+            return Notes::default();
+        }
+        let mut notes = Notes::default();
+        if self.gate_end_at.is_some_and(|end| end <= now) {
+            self.gate_end_at = None;
+            notes.release = self.sounding.take().map(|(note, _)| note);
+        }
+        if self.clock == SequencerClock::Internal && self.next_step_at.ceil() as u64 <= now {
+            let step = self.advance(self.next_step_at.ceil() as u64, step_len);
+            notes = Self::merge(notes, step);
+        }
+        notes
+    }
+
+    /// Count a MIDI clock tick at frame `now`, moving to the next step every sixth one when
+    /// following MIDI clock.
+    pub(crate) fn tick(&mut self, now: u64, step_len: f64) -> Notes {
+        if !self.running || self.clock != SequencerClock::Midi {
+            return Notes::default();
+        }
+        self.ticks += 1;
+        if self.ticks < TICKS_PER_STEP {
+            return Notes::default();
+        }
+        self.ticks = 0;
+        self.advance(now, step_len)
+    }
+
+    /// Move on to the next step, starting at `now`.
+    fn advance(&mut self, now: u64, step_len: f64) -> Notes {
+        self.position = (self.position + 1) % self.pattern.len();
+        self.next_step_at = match self.clock {
+            // keep the fraction, unless starting afresh
+            SequencerClock::Internal if self.next_step_at.ceil() as u64 == now => {
+                self.next_step_at + step_len
+            }
+            _ => now as f64 + step_len,
+        };
+
+        let step = self.pattern.steps[self.position];
+        self.cutoff_lock = step.cutoff;
+        let previous = self.sounding.take();
+        let mut notes = Notes::default();
+        match step.note {
+            Some(note) => {
+                match previous {
+                    // carried on from a tie
+                    Some((held, true)) if held == note => (),
+                    // starting a voice which is already playing the note starts it again
+                    Some((held, _)) if held == note => notes.play = Some((note, step.velocity)),
+                    Some((held, _)) => {
+                        notes.play = Some((note, step.velocity));
+                        notes.release = Some(held);
+                    }
+                    None => notes.play = Some((note, step.velocity)),
+                }
+                self.sounding = Some((note, step.tie));
+                // a gate of zero still plays the note, for a frame
+                self.gate_end_at = if step.tie {
+                    None
+                } else {
+                    Some(now + ((step_len * step.gate as f64) as u64).max(1))
+                };
+            }
+            None => {
+                notes.release = previous.map(|(note, _)| note);
+                self.gate_end_at = None;
+            }
+        }
+        notes
+    }
+
+    /// Both a gate ending and the next step, which can only release one note between them: the
+    /// gate's, as the step's own release is of a tied note, which has no gate.
+    fn merge(gate: Notes, step: Notes) -> Notes {
+        Notes {
+            play: step.play,
+            release: gate.release.or(step.release),
+        }
+    }
+}
+
+#[cfg(feature = "presets")]
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.note {
+            Some(note) => write!(f, "{} {} {}", note, self.velocity, self.gate)?,
+            None => write!(f, "rest")?,
+        }
+        if self.tie {
+            write!(f, " tie")?;
+        }
+        if let Some(cutoff) = self.cutoff {
+            write!(f, " cutoff={}", cutoff)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "presets")]
+impl FromStr for Step {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut words = s.split_whitespace().peekable();
+        let mut step = Step::REST;
+        match words.next() {
+            Some("rest") => (),
+            Some(note) => {
+                let data_byte = |arg: &str, what: &str| -> Result<u8, String> {
+                    arg.parse::<u8>()
+                        .ok()
+                        .filter(|n| *n < 128)
+                        .ok_or_else(|| format!("Invalid {}: {}", what, arg))
+                };
+                step.note = Some(data_byte(note, "note")?);
+                if let Some(velocity) = words.next_if(|word| !word.contains('=') && *word != "tie")
+                {
+                    step.velocity = data_byte(velocity, "velocity")?;
+                }
+                if let Some(gate) = words.next_if(|word| !word.contains('=') && *word != "tie") {
+                    step.gate = gate
+                        .parse::<f32>()
+                        .ok()
+                        .filter(|gate| (0.0..=1.0).contains(gate))
+                        .ok_or_else(|| format!("Invalid gate: {}", gate))?;
+                }
+            }
+            None => return Err("Expected a note or `rest`".into()),
+        }
+        for word in words {
+            match word.split_once('=') {
+                None if word == "tie" => step.tie = true,
+                Some(("cutoff", cutoff)) => {
+                    step.cutoff = Some(
+                        cutoff
+                            .parse::<f32>()
+                            .ok()
+                            // in range, which NaN never is
+                            .filter(|cutoff| ParamId::Cutoff.clamp(*cutoff) == *cutoff)
+                            .ok_or_else(|| format!("Invalid cutoff: {}", cutoff))?,
+                    )
+                }
+                _ => return Err(format!("Unexpected {:?} in step: {}", word, s)),
+            }
+        }
+        Ok(step)
+    }
+}
+
+/// The pattern's text form, one step per line.
+#[cfg(feature = "presets")]
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for step in self.steps() {
+            writeln!(f, "{}", step)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "presets")]
+impl FromStr for Pattern {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let steps = s
+            .lines()
+            .map(|line| line.split('#').next().unwrap_or_default().trim())
+            .filter(|line| !line.is_empty())
+            .enumerate()
+            .map(|(index, line)| {
+                line.parse()
+                    .map_err(|e| format!("Step {}: {}", index + 1, e))
+            })
+            .collect::<Result<Vec<Step>, _>>()?;
+        Self::from_steps(steps)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Oversampling, Synth, SynthCommand};
+
+    #[test]
+    fn set_step_clamps_to_range() {
+        let mut pattern = Pattern::new(4);
+        let step = Step {
+            note: Some(200),
+            velocity: 255,
+            gate: 2.0,
+            tie: true,
+            cutoff: Some(0.0),
+        };
+        pattern.set_step(0, step);
+        pattern.set_step(
+            1,
+            Step {
+                gate: -1.0,
+                cutoff: Some(f32::INFINITY),
+                ..step
+            },
+        );
+        pattern.set_step(
+            2,
+            Step {
+                cutoff: Some(f32::NAN),
+                ..step
+            },
+        );
+        pattern.set_step(MAX_STEPS, step);
+        let steps = pattern.steps();
+        assert_eq!(
+            steps[0],
+            Step {
+                note: Some(127),
+                velocity: 127,
+                gate: 1.0,
+                tie: true,
+                cutoff: Some(20.0),
+            }
+        );
+        assert_eq!((steps[1].gate, steps[1].cutoff), (0.0, Some(20000.0)));
+        assert_eq!(steps[2].cutoff, None);
+    }
+
+    #[test]
+    fn cutoff_locks_out_of_range_play() {
+        let mut synth = Synth::new(4, 48000, Oversampling::default());
+        for (index, &cutoff) in [0.0, -100.0, f32::NAN, 1e9].iter().enumerate() {
+            synth.control_sequencer(SequencerCommand::SetStep {
+                index,
+                step: Step {
+                    cutoff: Some(cutoff),
+                    ..Step::note(60, 100)
+                },
+            });
+        }
+        synth.control_sequencer(SequencerCommand::SetLength(4));
+        synth.control_sequencer(SequencerCommand::Start);
+        let mut out = [0.0; 48000];
+        synth.process(&mut out);
+        synth.control_sequencer(SequencerCommand::Stop);
+        synth
+            .apply(SynthCommand::NoteOn {
+                note: 64,
+                velocity: 100,
+            })
+            .unwrap();
+        synth.process(&mut out);
+        assert!(out.iter().all(|sample| sample.is_finite()));
+        assert!(synth.cutoff() > 0.0);
+    }
+
+    #[cfg(feature = "presets")]
+    #[test]
+    fn patterns_round_trip() {
+        let mut pattern = Pattern::new(5);
+        pattern.set_step(0, Step::note(60, 100));
+        pattern.set_step(
+            1,
+            Step {
+                gate: 0.125,
+                tie: true,
+                ..Step::note(0, 1)
+            },
+        );
+        pattern.set_step(
+            2,
+            Step {
+                cutoff: Some(1234.5),
+                ..Step::note(127, 127)
+            },
+        );
+        pattern.set_step(
+            3,
+            Step {
+                tie: true,
+                cutoff: Some(20.0),
+                ..Step::REST
+            },
+        );
+        let text = pattern.to_string();
+        assert_eq!(text.parse(), Ok(pattern), "{}", text);
+        assert_eq!(
+            Pattern::default().to_string().parse(),
+            Ok(Pattern::default())
+        );
+    }
+
+    #[cfg(feature = "presets")]
+    #[test]
+    fn reads_by_hand() {
+        let pattern: Pattern = "# a comment\n60 100 0.5 tie\n\n rest cutoff=800 \n62  # D"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            pattern.steps(),
+            [
+                Step {
+                    tie: true,
+                    ..Step::note(60, 100)
+                },
+                Step {
+                    cutoff: Some(800.0),
+                    ..Step::REST
+                },
+                Step::note(62, Step::REST.velocity),
+            ]
+        );
+    }
+
+    #[cfg(feature = "presets")]
+    #[test]
+    fn rejects_bad_steps() {
+        for text in [
+            "",
+            "128",
+            "60 128",
+            "60 100 1.5",
+            "60 100 0.5 0.5",
+            "60 cutoff=0",
+            "60 cutoff=-800",
+            "60 cutoff=NaN",
+            "60 cutoff=30000",
+            "60 slide",
+            "rest 100",
+        ] {
+            assert!(text.parse::<Step>().is_err(), "{:?} parsed", text);
+        }
+        assert!("".parse::<Pattern>().is_err());
+        assert!("# only a comment".parse::<Pattern>().is_err());
+        assert!("rest\n".repeat(MAX_STEPS + 1).parse::<Pattern>().is_err());
+    }
+}
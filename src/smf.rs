@@ -0,0 +1,304 @@
+//! Minimal reading and writing of Standard MIDI Files.
+//!
+//! Only channel messages are preserved; meta events other than tempo changes, as well as system
+//! exclusive messages, are skipped when reading and never written.
+
+use std::{
+    io::{self, Read, Write},
+    time::Duration,
+};
+
+use midi_msg::MidiMsg;
+
+/// Ticks per quarter note used when writing files.
+const DIVISION: u16 = 960;
+
+/// Microseconds per quarter note used when writing files, and assumed when reading until a tempo
+/// event says otherwise (i.e. 120 BPM).
+const DEFAULT_TEMPO: u32 = 500_000;
+
+/// A MIDI message, along with the time it occurred relative to the start of a session.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimedMsg {
+    pub time: Duration,
+    pub msg: MidiMsg,
+}
+
+/// Steps through a sequence of timed messages as time advances, e.g. to replay a recording.
+#[derive(Debug, Clone)]
+pub struct Playback {
+    events: Vec<TimedMsg>,
+    position: usize,
+}
+
+impl Playback {
+    /// Prepare to play the given messages, which are expected to be sorted by time.
+    pub fn new(events: Vec<TimedMsg>) -> Self {
+        Self {
+            events,
+            position: 0,
+        }
+    }
+
+    /// Return all messages which became due since the last call, up to and including `now`.
+    pub fn advance(&mut self, now: Duration) -> &[TimedMsg] {
+        let start = self.position;
+        while self
+            .events
+            .get(self.position)
+            .is_some_and(|e| e.time <= now)
+        {
+            self.position += 1;
+        }
+        &self.events[start..self.position]
+    }
+
+    /// The time of the next message that has yet to be played, if there is one.
+    pub fn next_time(&self) -> Option<Duration> {
+        self.events.get(self.position).map(|e| e.time)
+    }
+
+    /// The time of the last message in the sequence.
+    pub fn duration(&self) -> Duration {
+        self.events.last().map_or(Duration::ZERO, |e| e.time)
+    }
+
+    /// Whether every message has been played.
+    pub fn is_finished(&self) -> bool {
+        self.position >= self.events.len()
+    }
+}
+
+/// Write a sequence of timed messages as a format 0 Standard MIDI File.
+///
+/// Messages are expected to be sorted by time. Anything other than channel voice and channel mode
+/// messages is left out, since it has no meaningful representation in a file.
+pub fn write<W: Write>(mut w: W, events: &[TimedMsg]) -> io::Result<()> {
+    let mut track = Vec::new();
+
+    // tempo, so that readers agree with us about how long a tick is
+    write_var_len(&mut track, 0);
+    track.extend_from_slice(&[0xFF, 0x51, 0x03]);
+    track.extend_from_slice(&DEFAULT_TEMPO.to_be_bytes()[1..]);
+
+    let mut last_tick = 0;
+    for TimedMsg { time, msg } in events {
+        let msg = match msg {
+            MidiMsg::RunningChannelVoice { channel, msg } => MidiMsg::ChannelVoice {
+                channel: *channel,
+                msg: *msg,
+            },
+            MidiMsg::RunningChannelMode { channel, msg } => MidiMsg::ChannelMode {
+                channel: *channel,
+                msg: *msg,
+            },
+            MidiMsg::ChannelVoice { .. } | MidiMsg::ChannelMode { .. } => msg.clone(),
+            _ => continue,
+        };
+
+        let tick = (time.as_micros() * DIVISION as u128 / DEFAULT_TEMPO as u128) as u32;
+        let bytes = msg.to_midi();
+        let data_len = data_len(bytes[0]);
+
+        // some messages (e.g. 14-bit controllers) encode to several messages using running
+        // status, each of which needs its own delta time
+        write_var_len(&mut track, tick.saturating_sub(last_tick));
+        track.extend_from_slice(&bytes[..=data_len]);
+        for chunk in bytes[data_len + 1..].chunks(data_len) {
+            write_var_len(&mut track, 0);
+            track.extend_from_slice(chunk);
+        }
+        last_tick = last_tick.max(tick);
+    }
+
+    // end of track
+    write_var_len(&mut track, 0);
+    track.extend_from_slice(&[0xFF, 0x2F, 0x00]);
+
+    w.write_all(b"MThd")?;
+    w.write_all(&6_u32.to_be_bytes())?;
+    w.write_all(&0_u16.to_be_bytes())?;
+    w.write_all(&1_u16.to_be_bytes())?;
+    w.write_all(&DIVISION.to_be_bytes())?;
+    w.write_all(b"MTrk")?;
+    w.write_all(&(track.len() as u32).to_be_bytes())?;
+    w.write_all(&track)
+}
+
+/// Read the channel messages out of a Standard MIDI File (format 0 or 1), merged into one sequence
+/// sorted by time.
+pub fn read<R: Read>(mut r: R) -> io::Result<Vec<TimedMsg>> {
+    let mut bytes = Vec::new();
+    r.read_to_end(&mut bytes)?;
+    let mut cursor = Cursor(&bytes);
+
+    let header = cursor.chunk(b"MThd")?;
+    if header.len() < 6 {
+        return Err(invalid("MIDI file header is too short"));
+    }
+    let format = u16::from_be_bytes([header[0], header[1]]);
+    let num_tracks = u16::from_be_bytes([header[2], header[3]]);
+    let division = u16::from_be_bytes([header[4], header[5]]);
+    if format > 1 {
+        return Err(invalid("Only format 0 and 1 MIDI files are supported"));
+    }
+
+    // (tick, order within file, contents) - the order keeps simultaneous events stable
+    let mut tempo_map = Vec::new();
+    let mut messages = Vec::new();
+    for _ in 0..num_tracks {
+        read_track(cursor.chunk(b"MTrk")?, &mut tempo_map, &mut messages)?;
+    }
+    tempo_map.sort_by_key(|&(tick, _)| tick);
+    messages.sort_by_key(|(tick, order, _)| (*tick, *order));
+
+    let ticks_to_micros = |tick: u64| -> u64 {
+        if division & 0x8000 != 0 {
+            // SMPTE timing: frames per second and ticks per frame
+            let fps = (-((division >> 8) as i8)) as u64;
+            let ticks_per_frame = (division & 0xFF) as u64;
+            return tick * 1_000_000 / (fps * ticks_per_frame).max(1);
+        }
+
+        let mut micros = 0;
+        let mut last_tick = 0;
+        let mut tempo = DEFAULT_TEMPO as u64;
+        for &(change_tick, new_tempo) in &tempo_map {
+            if change_tick >= tick {
+                break;
+            }
+            micros += (change_tick - last_tick) * tempo / division as u64;
+            last_tick = change_tick;
+            tempo = new_tempo as u64;
+        }
+        micros + (tick - last_tick) * tempo / division as u64
+    };
+
+    Ok(messages
+        .into_iter()
+        .map(|(tick, _, msg)| TimedMsg {
+            time: Duration::from_micros(ticks_to_micros(tick)),
+            msg,
+        })
+        .collect())
+}
+
+fn read_track(
+    track: &[u8],
+    tempo_map: &mut Vec<(u64, u32)>,
+    messages: &mut Vec<(u64, usize, MidiMsg)>,
+) -> io::Result<()> {
+    let mut cursor = Cursor(track);
+    let mut tick = 0;
+    let mut running_status = None;
+
+    while !cursor.0.is_empty() {
+        tick += cursor.var_len()? as u64;
+        let status = match cursor.peek()? {
+            b if b & 0x80 != 0 => {
+                cursor.take(1)?;
+                b
+            }
+            _ => running_status.ok_or_else(|| invalid("Running status with no prior status"))?,
+        };
+
+        match status {
+            0xFF => {
+                let kind = cursor.take(1)?[0];
+                let len = cursor.var_len()? as usize;
+                let data = cursor.take(len)?;
+                if kind == 0x51 && len == 3 {
+                    tempo_map.push((tick, u32::from_be_bytes([0, data[0], data[1], data[2]])));
+                }
+            }
+            0xF0 | 0xF7 => {
+                let len = cursor.var_len()? as usize;
+                cursor.take(len)?;
+            }
+            0x80..=0xEF => {
+                running_status = Some(status);
+                let mut msg_bytes = vec![status];
+                msg_bytes.extend_from_slice(cursor.take(data_len(status))?);
+                if let Ok((msg, _)) = MidiMsg::from_midi(&msg_bytes) {
+                    messages.push((tick, messages.len(), msg));
+                }
+            }
+            _ => return Err(invalid("Unexpected status byte in MIDI track")),
+        }
+    }
+
+    Ok(())
+}
+
+/// Number of data bytes following a channel message's status byte.
+fn data_len(status: u8) -> usize {
+    match status & 0xF0 {
+        0xC0 | 0xD0 => 1,
+        _ => 2,
+    }
+}
+
+fn write_var_len(buf: &mut Vec<u8>, mut value: u32) {
+    let mut stack = [0_u8; 5];
+    let mut len = 0;
+    loop {
+        stack[len] = (value & 0x7F) as u8;
+        len += 1;
+        value >>= 7;
+        if value == 0 {
+            break;
+        }
+    }
+    for i in (0..len).rev() {
+        buf.push(if i > 0 { stack[i] | 0x80 } else { stack[i] });
+    }
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+struct Cursor<'a>(&'a [u8]);
+
+impl<'a> Cursor<'a> {
+    fn peek(&self) -> io::Result<u8> {
+        self.0
+            .first()
+            .copied()
+            .ok_or_else(|| invalid("Unexpected end of MIDI file"))
+    }
+
+    fn take(&mut self, len: usize) -> io::Result<&'a [u8]> {
+        if self.0.len() < len {
+            return Err(invalid("Unexpected end of MIDI file"));
+        }
+        let (taken, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(taken)
+    }
+
+    fn var_len(&mut self) -> io::Result<u32> {
+        let mut value = 0;
+        for _ in 0..4 {
+            let byte = self.take(1)?[0];
+            value = (value << 7) | (byte & 0x7F) as u32;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(invalid("Variable-length quantity is too long"))
+    }
+
+    fn chunk(&mut self, id: &[u8; 4]) -> io::Result<&'a [u8]> {
+        loop {
+            let chunk_id = self.take(4)?;
+            let len = self.take(4)?;
+            let len = u32::from_be_bytes([len[0], len[1], len[2], len[3]]) as usize;
+            let data = self.take(len)?;
+            // unknown chunk types are to be skipped, per the spec
+            if chunk_id == id {
+                return Ok(data);
+            }
+        }
+    }
+}
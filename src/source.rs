@@ -0,0 +1,83 @@
+//! Sharing a synth between threads, so that one can feed it MIDI while another streams its
+//! output through `rodio`.
+
+use std::{
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    time::Duration,
+};
+
+use crate::{Synth, CHANNELS};
+
+/// A handle to a synth which can be cloned and sent between threads.
+#[derive(Clone)]
+pub struct SharedSynth {
+    inner: Arc<Mutex<Synth>>,
+}
+
+impl SharedSynth {
+    pub fn new(synth: Synth) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(synth)),
+        }
+    }
+
+    /// Get exclusive access to the synth, e.g. to send it a MIDI message. Audio stops flowing
+    /// from any `SynthSource` until the guard is dropped, so don't hold onto it for long.
+    pub fn lock(&self) -> MutexGuard<'_, Synth> {
+        // a panic elsewhere can't leave the synth in a state that's unsafe to keep playing
+        self.inner.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Stream the synth's output. The source never ends, so it only needs to be appended to a
+    /// `rodio::Sink` once.
+    pub fn source(&self) -> SynthSource {
+        let synth = self.lock();
+        SynthSource {
+            synth: self.clone(),
+            sample_rate: synth.sample_rate(),
+            buffer: vec![0.0; synth.block_size() * CHANNELS],
+            position: synth.block_size() * CHANNELS,
+        }
+    }
+}
+
+/// Interleaved audio pulled from a `SharedSynth` a block at a time, so that the lock is only taken
+/// once per block.
+pub struct SynthSource {
+    synth: SharedSynth,
+    sample_rate: u32,
+    buffer: Vec<f32>,
+    position: usize,
+}
+
+impl Iterator for SynthSource {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.position == self.buffer.len() {
+            self.synth.lock().process(&mut self.buffer);
+            self.position = 0;
+        }
+
+        self.position += 1;
+        Some(self.buffer[self.position - 1])
+    }
+}
+
+impl rodio::Source for SynthSource {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        CHANNELS as u16
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
@@ -0,0 +1,71 @@
+//! Taps: copies of the audio at some point on its way through the synth, downsampled and passed
+//! through a ring buffer to another thread, e.g. for drawing an oscilloscope in a user interface.
+//! Tapping never changes what's heard, and never waits on or allocates for the reader. With the
+//! `analysis` feature, `analysis::SpectrumAnalyzer` shows which frequencies a tap holds.
+
+use crate::{
+    ring::{ring_buffer, Consumer, Producer},
+    Frame,
+};
+
+/// Where in the synth a tap takes its copy from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TapPoint {
+    /// The finished output, as `process` writes it.
+    Output,
+    /// The voices mixed together, before the send buses, the effects and the master volume.
+    Dry,
+    /// One voice of the pool, counting from zero, after its own effects and panning. A voice
+    /// which isn't playing is tapped as silence, so that the copy keeps time with the others.
+    Voice(usize),
+}
+
+/// The writing end of a tap, kept by the synth.
+pub(crate) struct Tap {
+    point: TapPoint,
+    decimation: usize,
+    /// How many frames of the next block to skip before the next one is copied.
+    offset: usize,
+    producer: Producer<Frame>,
+}
+
+impl Tap {
+    pub(crate) fn new(
+        point: TapPoint,
+        decimation: usize,
+        capacity: usize,
+    ) -> (Self, Consumer<Frame>) {
+        let (producer, consumer) = ring_buffer(capacity);
+        let tap = Self {
+            point,
+            decimation: decimation.max(1),
+            offset: 0,
+            producer,
+        };
+        (tap, consumer)
+    }
+
+    pub(crate) fn point(&self) -> TapPoint {
+        self.point
+    }
+
+    /// Whether the reading end has been dropped, so that there's no point copying anything more.
+    pub(crate) fn is_abandoned(&self) -> bool {
+        self.producer.is_abandoned()
+    }
+
+    /// Copy every `decimation`th of `len` frames, each found by `frame`, with `ratio` frames to
+    /// each output frame (the oversampling, for voices). Frames which don't fit are dropped.
+    pub(crate) fn feed(&mut self, len: usize, ratio: usize, frame: impl Fn(usize) -> Frame) {
+        if self.is_abandoned() {
+            return;
+        }
+        let step = self.decimation * ratio;
+        let mut index = self.offset;
+        while index < len {
+            let _ = self.producer.push(frame(index));
+            index += step;
+        }
+        self.offset = index - len;
+    }
+}
@@ -0,0 +1,89 @@
+//! Universal MIDI Packets, the form MIDI 2.0 travels in: one to four 32-bit words each, the first
+//! saying what kind of message follows. See `SynthCommand::from_ump` for what the synth makes of
+//! them.
+//!
+//! MIDI 2.0 channel voice messages carry 16-bit velocities and 32-bit controllers, and can
+//! address single notes. MIDI 1.0 messages wrapped in packets are understood as well, as
+//! `SynthCommand::from_midi` understands them unwrapped.
+
+/// Message type of system real time and common messages.
+pub const SYSTEM: u8 = 0x1;
+/// Message type of MIDI 1.0 channel voice messages.
+pub const MIDI1_CHANNEL_VOICE: u8 = 0x2;
+/// Message type of MIDI 2.0 channel voice messages.
+pub const MIDI2_CHANNEL_VOICE: u8 = 0x4;
+
+/// Statuses of MIDI 2.0 channel voice messages, in the top half of their second byte.
+pub(crate) mod status {
+    pub const REGISTERED_PER_NOTE_CONTROLLER: u8 = 0x0;
+    pub const PER_NOTE_PITCH_BEND: u8 = 0x6;
+    pub const NOTE_OFF: u8 = 0x8;
+    pub const NOTE_ON: u8 = 0x9;
+    pub const CONTROL_CHANGE: u8 = 0xb;
+    pub const PITCH_BEND: u8 = 0xe;
+}
+
+/// Registered per-note controllers which change a note's expression.
+pub(crate) mod per_note {
+    /// The note's absolute pitch, in semitones with 25 bits of fraction.
+    pub const PITCH: u8 = 3;
+    pub const VOLUME: u8 = 7;
+    pub const PAN: u8 = 10;
+}
+
+/// The value at the middle of the range of 32-bit controllers and pitch bends, such as no bend.
+pub const CENTER: u32 = 0x8000_0000;
+
+/// The message type of a packet, from its first word.
+pub fn message_type(first: u32) -> u8 {
+    (first >> 28) as u8
+}
+
+/// How many words long a packet is, from its first word.
+pub fn packet_len(first: u32) -> usize {
+    match message_type(first) {
+        0x0 | 0x1 | 0x2 | 0x6 | 0x7 => 1,
+        0x3 | 0x4 | 0x8 | 0x9 | 0xa => 2,
+        0xb | 0xc => 3,
+        _ => 4,
+    }
+}
+
+/// Split a stream of words into packets. A packet cut short at the end is left out.
+pub fn packets(words: &[u32]) -> Packets<'_> {
+    Packets { words }
+}
+
+/// The packets in a stream of words, made by `packets`.
+pub struct Packets<'a> {
+    words: &'a [u32],
+}
+
+impl<'a> Iterator for Packets<'a> {
+    type Item = &'a [u32];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let len = packet_len(*self.words.first()?);
+        if len > self.words.len() {
+            self.words = &[];
+            return None;
+        }
+        let (packet, rest) = self.words.split_at(len);
+        self.words = rest;
+        Some(packet)
+    }
+}
+
+/// The bytes of a MIDI 1.0 message wrapped in a one word packet, for `MidiMsg::from_midi`.
+pub(crate) fn midi1_bytes(word: u32) -> [u8; 3] {
+    [
+        (word >> 16) as u8,
+        (word >> 8) as u8 & 0x7f,
+        word as u8 & 0x7f,
+    ]
+}
+
+/// A 32-bit controller's value, from 0 to 1.
+pub(crate) fn unit(value: u32) -> f32 {
+    (value as f64 / u32::MAX as f64) as f32
+}
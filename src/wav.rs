@@ -0,0 +1,139 @@
+//! Writing of WAV files.
+
+use std::io::{self, Seek, SeekFrom, Write};
+
+use crate::dither::Dither;
+
+/// Offset of the RIFF chunk's length field.
+const RIFF_LEN_OFFSET: u64 = 4;
+/// Offset of the frame count in the `fact` chunk.
+const FACT_FRAMES_OFFSET: u64 = 46;
+/// Offset of the data chunk's length field.
+const DATA_LEN_OFFSET: u64 = 54;
+/// Total size of everything before the sample data.
+const HEADER_LEN: u32 = 58;
+
+/// How samples are stored in a WAV file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SampleFormat {
+    /// 16-bit integers, dithered.
+    I16,
+    /// 24-bit integers, dithered.
+    I24,
+    /// 32-bit floats, stored exactly as rendered.
+    #[default]
+    F32,
+}
+
+impl SampleFormat {
+    /// Look up the format with a given number of bits per sample: 16, 24 or 32.
+    pub fn from_bits(bits: u32) -> Option<Self> {
+        match bits {
+            16 => Some(Self::I16),
+            24 => Some(Self::I24),
+            32 => Some(Self::F32),
+            _ => None,
+        }
+    }
+
+    fn bytes(self) -> u16 {
+        match self {
+            Self::I16 => 2,
+            Self::I24 => 3,
+            Self::F32 => 4,
+        }
+    }
+}
+
+/// Writes interleaved float samples to a WAV file, converting them to the requested format.
+///
+/// The header is filled in with placeholder lengths at first, which are corrected by `finish`.
+pub struct WavWriter<W: Write + Seek> {
+    inner: W,
+    channels: u16,
+    format: SampleFormat,
+    dither: Dither,
+    data_len: u32,
+}
+
+impl<W: Write + Seek> WavWriter<W> {
+    /// Start a file of 32-bit float samples.
+    pub fn new(inner: W, channels: u16, sample_rate: u32) -> io::Result<Self> {
+        Self::with_format(inner, channels, sample_rate, SampleFormat::F32)
+    }
+
+    pub fn with_format(
+        mut inner: W,
+        channels: u16,
+        sample_rate: u32,
+        format: SampleFormat,
+    ) -> io::Result<Self> {
+        let bytes_per_sample = format.bytes();
+        let block_align = channels * bytes_per_sample;
+
+        inner.write_all(b"RIFF")?;
+        inner.write_all(&(HEADER_LEN - 8).to_le_bytes())?;
+        inner.write_all(b"WAVE")?;
+
+        inner.write_all(b"fmt ")?;
+        inner.write_all(&18_u32.to_le_bytes())?;
+        // integer PCM or IEEE float
+        let format_tag: u16 = if format == SampleFormat::F32 { 3 } else { 1 };
+        inner.write_all(&format_tag.to_le_bytes())?;
+        inner.write_all(&channels.to_le_bytes())?;
+        inner.write_all(&sample_rate.to_le_bytes())?;
+        inner.write_all(&(sample_rate * block_align as u32).to_le_bytes())?;
+        inner.write_all(&block_align.to_le_bytes())?;
+        inner.write_all(&(bytes_per_sample * 8).to_le_bytes())?;
+        // no extension
+        inner.write_all(&0_u16.to_le_bytes())?;
+
+        // required for anything other than integer PCM, and harmless otherwise
+        inner.write_all(b"fact")?;
+        inner.write_all(&4_u32.to_le_bytes())?;
+        inner.write_all(&0_u32.to_le_bytes())?;
+
+        inner.write_all(b"data")?;
+        inner.write_all(&0_u32.to_le_bytes())?;
+
+        Ok(Self {
+            inner,
+            channels,
+            format,
+            dither: Dither::new(),
+            data_len: 0,
+        })
+    }
+
+    /// Append interleaved samples. Should be a whole number of frames.
+    pub fn write_samples(&mut self, samples: &[f32]) -> io::Result<()> {
+        for sample in samples {
+            match self.format {
+                SampleFormat::I16 => self
+                    .inner
+                    .write_all(&self.dither.to_i16(*sample).to_le_bytes())?,
+                SampleFormat::I24 => self
+                    .inner
+                    .write_all(&self.dither.to_i24(*sample).to_le_bytes()[..3])?,
+                SampleFormat::F32 => self.inner.write_all(&sample.to_le_bytes())?,
+            }
+        }
+        self.data_len += (samples.len() * self.format.bytes() as usize) as u32;
+        Ok(())
+    }
+
+    /// Fill in the lengths in the header, and hand back the underlying writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        let frames = self.data_len / (self.channels as u32 * self.format.bytes() as u32);
+        self.inner.seek(SeekFrom::Start(RIFF_LEN_OFFSET))?;
+        self.inner
+            .write_all(&(HEADER_LEN - 8 + self.data_len).to_le_bytes())?;
+        self.inner.seek(SeekFrom::Start(FACT_FRAMES_OFFSET))?;
+        self.inner.write_all(&frames.to_le_bytes())?;
+        self.inner.seek(SeekFrom::Start(DATA_LEN_OFFSET))?;
+        self.inner.write_all(&self.data_len.to_le_bytes())?;
+        self.inner.seek(SeekFrom::End(0))?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
@@ -0,0 +1,93 @@
+//! Bindings for running the synth in a browser, from an `AudioWorkletProcessor` fed by WebMIDI.
+//!
+//! Build with `wasm-pack build --target web`. Audio worklets deal in planar buffers of 128
+//! frames, which is what `WebSynth::process` expects; `processInterleaved` suits anything which
+//! wants a single interleaved buffer instead.
+//!
+//! From JavaScript, notes can come in as raw MIDI (e.g. from WebMIDI) through `midi`, or be played
+//! with `noteOn` and `noteOff`, and parameters are named as in the text form of commands, e.g.
+//! `synth.setParam("cutoff", 800)`.
+
+use {midi_msg::MidiMsg, wasm_bindgen::prelude::*};
+
+#[cfg(feature = "presets")]
+use crate::param::ParamId;
+use crate::{Oversampling, Synth, SynthCommand};
+
+/// Number of voices in a synth created from JavaScript.
+const VOICES: usize = 8;
+
+/// Frames in each render quantum of an audio worklet.
+const RENDER_QUANTUM: usize = 128;
+
+#[wasm_bindgen]
+pub struct WebSynth {
+    synth: Synth,
+}
+
+#[wasm_bindgen]
+impl WebSynth {
+    /// Create a synth running at `sample_rate` (the `AudioContext`'s), with 2x oversampling to go
+    /// easy on the audio thread.
+    #[wasm_bindgen(constructor)]
+    pub fn new(sample_rate: u32) -> Self {
+        let mut synth = Synth::new(VOICES, sample_rate, Oversampling::X2);
+        synth.set_block_size(RENDER_QUANTUM);
+        Self { synth }
+    }
+
+    /// Apply a raw MIDI message, e.g. `MIDIMessageEvent.data`. Returns false if it could not be
+    /// parsed or played.
+    pub fn midi(&mut self, data: &[u8]) -> bool {
+        match MidiMsg::from_midi(data) {
+            Ok((msg, _)) => self.synth.handle_midi(&msg).is_ok(),
+            Err(_) => false,
+        }
+    }
+
+    /// Start playing a note. Returns false if it could not be played, e.g. with every voice busy
+    /// and voice stealing off.
+    #[wasm_bindgen(js_name = noteOn)]
+    pub fn note_on(&mut self, note: u8, velocity: u8) -> bool {
+        self.synth
+            .apply(SynthCommand::NoteOn { note, velocity })
+            .is_ok()
+    }
+
+    /// Release a note. Returns false if it wasn't playing.
+    #[wasm_bindgen(js_name = noteOff)]
+    pub fn note_off(&mut self, note: u8) -> bool {
+        self.synth.apply(SynthCommand::NoteOff { note }).is_ok()
+    }
+
+    /// Fill the two channels of a worklet output with the next frames of audio.
+    pub fn process(&mut self, left: &mut [f32], right: &mut [f32]) {
+        self.synth.process_stereo(left, right);
+    }
+
+    /// Fill a buffer of interleaved stereo frames with the next of the audio.
+    #[wasm_bindgen(js_name = processInterleaved)]
+    pub fn process_interleaved(&mut self, out: &mut [f32]) {
+        self.synth.process(out);
+    }
+}
+
+#[cfg(feature = "presets")]
+#[wasm_bindgen]
+impl WebSynth {
+    /// Set a parameter by name, e.g. `cutoff` or `send 1 level`. Returns false if there's no such
+    /// parameter, or it can't take the value.
+    #[wasm_bindgen(js_name = setParam)]
+    pub fn set_param(&mut self, name: &str, value: f32) -> bool {
+        match name.parse::<ParamId>() {
+            Ok(param) => self.synth.set_param(param, value).is_ok(),
+            Err(_) => false,
+        }
+    }
+
+    /// A parameter's value by name, or `undefined` if there's no such parameter.
+    #[wasm_bindgen(js_name = getParam)]
+    pub fn get_param(&self, name: &str) -> Option<f32> {
+        self.synth.get_param(name.parse().ok()?)
+    }
+}